@@ -0,0 +1,190 @@
+use crate::error::AppError;
+use crate::presets::CustomPreset;
+use crate::StartConversionArgs;
+use std::path::{Path, PathBuf};
+
+/// Command-line flag that starts the app in quick-convert mode.
+pub const CONVERT_FLAG: &str = "--convert-with";
+/// Optional flag naming the folder outputs go to; next to each input if unset.
+pub const OUTPUT_DIR_FLAG: &str = "--output-dir";
+
+/// Files dropped on a launcher, to run through a custom preset without the UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickConvert {
+    pub preset: String,
+    pub output_dir: Option<String>,
+    pub files: Vec<String>,
+}
+
+/// Quick-convert arguments (`--convert-with <preset> [--output-dir <dir>]
+/// <files>...`), or `None` for a normal start. `args` excludes the program.
+pub fn parse_args(args: &[String]) -> Option<QuickConvert> {
+    let mut preset = None;
+    let mut output_dir = None;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            CONVERT_FLAG => preset = args.next().cloned(),
+            OUTPUT_DIR_FLAG => output_dir = args.next().cloned(),
+            _ => files.push(arg.clone()),
+        }
+    }
+    Some(QuickConvert {
+        preset: preset?,
+        output_dir,
+        files,
+    })
+}
+
+/// The conversion `preset` applies to `input_file`: same name with the
+/// preset's container, in `output_dir` or next to the input. A name that
+/// would overwrite the input gets a `.converted` suffix.
+pub fn request_for(preset: &CustomPreset, input_file: &str, output_dir: Option<&str>) -> StartConversionArgs {
+    let input = Path::new(input_file);
+    let dir = output_dir.map(PathBuf::from).unwrap_or_else(|| input.parent().unwrap_or(Path::new("")).to_path_buf());
+    let stem = input.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let mut output = dir.join(format!("{}.{}", stem, preset.container));
+    if output == input {
+        output = dir.join(format!("{}.converted.{}", stem, preset.container));
+    }
+    StartConversionArgs {
+        input_file: input_file.to_string(),
+        output_file: output.to_string_lossy().to_string(),
+        encoder: preset.encoder.clone(),
+        gpu_index: None,
+        cpu_threads: preset.cpu_threads,
+        preset: preset.preset.clone(),
+        is_adobe_preset: None,
+        options: preset.options.clone(),
+    }
+}
+
+/// Launcher formats: a batch file on Windows (files dropped on it arrive as
+/// `%*`), a `.desktop` entry on Linux and an AppleScript droplet on macOS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LauncherKind {
+    WindowsBatch,
+    DesktopEntry,
+    AppleScript,
+}
+
+impl LauncherKind {
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            LauncherKind::WindowsBatch
+        } else if cfg!(target_os = "macos") {
+            LauncherKind::AppleScript
+        } else {
+            LauncherKind::DesktopEntry
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            LauncherKind::WindowsBatch => "cmd",
+            LauncherKind::DesktopEntry => "desktop",
+            LauncherKind::AppleScript => "applescript",
+        }
+    }
+}
+
+/// Launcher file name for `label`, without characters file systems reject.
+pub fn file_name(label: &str, kind: LauncherKind) -> String {
+    let name: String = label
+        .chars()
+        .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') && !c.is_control())
+        .collect();
+    format!("{}.{}", name.trim(), kind.extension())
+}
+
+fn desktop_quote(value: &str) -> String {
+    let escaped: String = value
+        .chars()
+        .flat_map(|c| match c {
+            '"' | '`' | '$' | '\\' => vec!['\\', c],
+            '%' => vec!['%', '%'],
+            c => vec![c],
+        })
+        .collect();
+    format!("\"{}\"", escaped)
+}
+
+fn applescript_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Contents of a launcher running `exe` in quick-convert mode on the files
+/// dropped onto it.
+pub fn launcher(
+    kind: LauncherKind,
+    exe: &Path,
+    label: &str,
+    preset: &str,
+    output_dir: Option<&str>,
+) -> Result<String, AppError> {
+    let exe = exe.to_string_lossy();
+    let mut values = vec![exe.as_ref(), label, preset];
+    values.extend(output_dir);
+    if values.iter().any(|v| v.contains(['\n', '\r']) || (kind == LauncherKind::WindowsBatch && v.contains('"'))) {
+        return Err(AppError::Internal("Launcher names, presets and paths can't contain quotes or line breaks".to_string()));
+    }
+
+    Ok(match kind {
+        LauncherKind::WindowsBatch => {
+            let mut command = format!("start \"\" \"{}\" {} \"{}\"", exe, CONVERT_FLAG, preset.replace('%', "%%"));
+            if let Some(dir) = output_dir {
+                command.push_str(&format!(" {} \"{}\"", OUTPUT_DIR_FLAG, dir.replace('%', "%%")));
+            }
+            format!("@echo off\r\nrem {}\r\n{} %*\r\n", label.replace('%', "%%"), command)
+        }
+        LauncherKind::DesktopEntry => {
+            let mut exec = format!("{} {} {}", desktop_quote(&exe), CONVERT_FLAG, desktop_quote(preset));
+            if let Some(dir) = output_dir {
+                exec.push_str(&format!(" {} {}", OUTPUT_DIR_FLAG, desktop_quote(dir)));
+            }
+            format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec={} %F\nTerminal=false\nCategories=AudioVideo;\n",
+                label, exec
+            )
+        }
+        LauncherKind::AppleScript => {
+            let mut command = format!(
+                "quoted form of {} & \" {} \" & quoted form of {}",
+                applescript_string(&exe),
+                CONVERT_FLAG,
+                applescript_string(preset)
+            );
+            if let Some(dir) = output_dir {
+                command.push_str(&format!(" & \" {} \" & quoted form of {}", OUTPUT_DIR_FLAG, applescript_string(dir)));
+            }
+            format!(
+                "on open droppedItems\n\tset fileArgs to \"\"\n\trepeat with droppedItem in droppedItems\n\
+                 \t\tset fileArgs to fileArgs & \" \" & quoted form of POSIX path of droppedItem\n\tend repeat\n\
+                 \tdo shell script {} & fileArgs & \" > /dev/null 2>&1 &\"\nend open\n",
+                command
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn launchers_round_trip_through_the_cli() {
+        let args: Vec<String> = ["--convert-with", "ProRes HQ", "/in/a.mov", "/in/b.mp4"].iter().map(|s| s.to_string()).collect();
+        let quick = parse_args(&args).unwrap();
+        assert_eq!(quick.preset, "ProRes HQ");
+        assert_eq!(quick.files, ["/in/a.mov", "/in/b.mp4"]);
+        assert!(parse_args(&args[2..]).is_none());
+
+        let entry = launcher(LauncherKind::DesktopEntry, Path::new("/opt/dreamcodec"), "Convert to ProRes", "ProRes 100%", None).unwrap();
+        assert!(entry.contains("Exec=\"/opt/dreamcodec\" --convert-with \"ProRes 100%%\" %F"));
+        let batch = launcher(LauncherKind::WindowsBatch, Path::new("C:\\App\\dreamcodec.exe"), "Convert", "ProRes", Some("D:\\Out")).unwrap();
+        assert!(batch.contains("start \"\" \"C:\\App\\dreamcodec.exe\" --convert-with \"ProRes\" --output-dir \"D:\\Out\" %*"));
+        assert!(launcher(LauncherKind::WindowsBatch, Path::new("x"), "Convert", "Pro\"Res", None).is_err());
+        assert_eq!(file_name("Drop on: Convert to ProRes", LauncherKind::AppleScript), "Drop on Convert to ProRes.applescript");
+    }
+}
@@ -10,6 +10,10 @@ pub enum AppError {
     #[error("FFmpeg Error: {0}")]
     Ffmpeg(String),
 
+    /// An auxiliary FFmpeg run (probe, validation, thumbnail) hit its time limit.
+    #[error("Timeout Error: {0}")]
+    Timeout(String),
+
     #[error("Tauri Error: {0}")]
     Tauri(String),
 
@@ -0,0 +1,242 @@
+use crate::ffmpeg::{ConversionPhase, ConversionProgress, ConversionStatus};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Tauri event every job event is emitted on.
+pub const JOB_EVENT: &str = "job-event";
+/// Bumped on any breaking change to `JobEvent`; new optional fields don't
+/// count as breaking.
+pub const SCHEMA_VERSION: u32 = 1;
+/// Percentage change that triggers a new `progress` event.
+const PROGRESS_STEP: f64 = 1.0;
+
+/// A job lifecycle event for automations. Unlike `ConversionProgress`, this
+/// layout is a contract:
+///
+/// ```json
+/// {"schema_version": 1, "type": "progress", "timestamp_ms": 1700000000000,
+///  "task_id": "...", "queue_job_id": null, "external_id": null,
+///  "percentage": 42.0, "current_time": 12.5, "duration": 30.0}
+/// ```
+///
+/// `type` is one of `job-queued`, `job-started`, `progress`, `phase-change`
+/// and `job-finished`, each adding the fields of its `JobEventKind` variant.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobEvent {
+    pub schema_version: u32,
+    pub timestamp_ms: u64,
+    /// Conversion task; absent for jobs still waiting in the queue.
+    pub task_id: Option<String>,
+    /// Queue job the task runs, when it came from the queue.
+    pub queue_job_id: Option<String>,
+    /// Caller-supplied ID passed through from the job options.
+    pub external_id: Option<String>,
+    #[serde(flatten)]
+    pub kind: JobEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum JobEventKind {
+    JobQueued,
+    JobStarted,
+    Progress {
+        percentage: f64,
+        current_time: f64,
+        duration: f64,
+    },
+    PhaseChange {
+        phase: &'static str,
+    },
+    JobFinished {
+        /// `completed`, `failed` or `cancelled`.
+        status: &'static str,
+        error: Option<String>,
+        stats: JobStats,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobStats {
+    /// Wall-clock seconds from start to finish, as observed by the app.
+    pub elapsed_secs: f64,
+    /// Media duration of the output in seconds (0 when unknown).
+    pub media_duration: f64,
+    /// FFmpeg runs, including fallback retries.
+    pub attempts: u32,
+    pub output_file: String,
+    pub output_bytes: Option<u64>,
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn phase_name(phase: ConversionPhase) -> &'static str {
+    match phase {
+        ConversionPhase::Probing => "probing",
+        ConversionPhase::SubtitleOcr => "subtitle_ocr",
+        ConversionPhase::LoudnessAnalysis => "loudness_analysis",
+        ConversionPhase::Encoding => "encoding",
+        ConversionPhase::Validating => "validating",
+        ConversionPhase::Finalizing => "finalizing",
+    }
+}
+
+impl JobEvent {
+    pub fn queued(queue_job_id: String, external_id: Option<String>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            timestamp_ms: now_ms(),
+            task_id: None,
+            queue_job_id: Some(queue_job_id),
+            external_id,
+            kind: JobEventKind::JobQueued,
+        }
+    }
+}
+
+/// What was last reported for a task.
+struct Seen {
+    started: Instant,
+    phase: Option<ConversionPhase>,
+    percentage: f64,
+    finished: bool,
+}
+
+/// Turns periodic progress snapshots into lifecycle events, reporting each
+/// transition once.
+#[derive(Default)]
+pub struct JobEventTracker {
+    seen: HashMap<String, Seen>,
+}
+
+impl JobEventTracker {
+    /// Events for the changes since the last call. `jobs` holds each task's
+    /// progress, output file and queue job ID.
+    pub fn update(&mut self, jobs: Vec<(ConversionProgress, String, Option<String>)>) -> Vec<JobEvent> {
+        let mut events = Vec::new();
+        for (progress, output_file, queue_job_id) in jobs {
+            let kinds = self.changes(&progress, &output_file);
+            events.extend(kinds.into_iter().map(|kind| JobEvent {
+                schema_version: SCHEMA_VERSION,
+                timestamp_ms: now_ms(),
+                task_id: Some(progress.task_id.clone()),
+                queue_job_id: queue_job_id.clone(),
+                external_id: progress.external_id.clone(),
+                kind,
+            }));
+        }
+        events
+    }
+
+    fn changes(&mut self, progress: &ConversionProgress, output_file: &str) -> Vec<JobEventKind> {
+        let mut kinds = Vec::new();
+        let seen = self.seen.entry(progress.task_id.clone()).or_insert_with(|| {
+            kinds.push(JobEventKind::JobStarted);
+            Seen {
+                started: Instant::now(),
+                phase: None,
+                percentage: 0.0,
+                finished: false,
+            }
+        });
+        if seen.finished {
+            return kinds;
+        }
+
+        if let Some(phase) = progress.phase.filter(|phase| seen.phase != Some(*phase)) {
+            seen.phase = Some(phase);
+            kinds.push(JobEventKind::PhaseChange {
+                phase: phase_name(phase),
+            });
+        }
+        if (progress.percentage - seen.percentage).abs() >= PROGRESS_STEP {
+            seen.percentage = progress.percentage;
+            kinds.push(JobEventKind::Progress {
+                percentage: progress.percentage,
+                current_time: progress.current_time,
+                duration: progress.duration,
+            });
+        }
+
+        let (status, error) = match progress.status {
+            ConversionStatus::Completed => ("completed", None),
+            ConversionStatus::Failed(ref message) => ("failed", Some(message.clone())),
+            ConversionStatus::Cancelled => ("cancelled", None),
+            ConversionStatus::Pending | ConversionStatus::Running => return kinds,
+        };
+        seen.finished = true;
+        kinds.push(JobEventKind::JobFinished {
+            status,
+            error,
+            stats: JobStats {
+                elapsed_secs: seen.started.elapsed().as_secs_f64(),
+                media_duration: progress.duration,
+                attempts: progress.attempts,
+                output_file: output_file.to_string(),
+                output_bytes: std::fs::metadata(output_file).ok().map(|m| m.len()),
+            },
+        });
+        kinds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(status: ConversionStatus, phase: Option<ConversionPhase>, percentage: f64) -> ConversionProgress {
+        ConversionProgress {
+            task_id: "t1".to_string(),
+            status,
+            phase,
+            percentage,
+            current_time: 0.0,
+            duration: 10.0,
+            log: Vec::new(),
+            error_message: None,
+            attempts: 1,
+            external_id: None,
+            queue_position: None,
+            estimated_start_secs: None,
+            eta_seconds: None,
+            speed: None,
+            fps: None,
+            bitrate_kbps: None,
+            frames_encoded: None,
+        }
+    }
+
+    fn types(events: &[JobEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| serde_json::to_value(e).unwrap()["type"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn reports_each_transition_once() {
+        let mut tracker = JobEventTracker::default();
+        let running = |phase, pct| vec![(progress(ConversionStatus::Running, Some(phase), pct), "out.mp4".to_string(), None)];
+
+        let first = tracker.update(running(ConversionPhase::Probing, 0.0));
+        assert_eq!(types(&first), ["job-started", "phase-change"]);
+        let second = tracker.update(running(ConversionPhase::Encoding, 40.0));
+        assert_eq!(types(&second), ["phase-change", "progress"]);
+        assert!(tracker.update(running(ConversionPhase::Encoding, 40.5)).is_empty());
+
+        let done = vec![(
+            progress(ConversionStatus::Failed("boom".to_string()), Some(ConversionPhase::Encoding), 40.5),
+            "out.mp4".to_string(),
+            None,
+        )];
+        let finished = tracker.update(done.clone());
+        assert_eq!(types(&finished), ["job-finished"]);
+        assert_eq!(serde_json::to_value(&finished[0]).unwrap()["error"], "boom");
+        assert!(tracker.update(done).is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use super::{passthrough, AdobePreset, FfmpegDownloader};
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Presets shipped with the app.
+const BUNDLED: &str = include_str!("../../resources/adobe_presets.json");
+/// Newest preset file layout this build understands.
+const SCHEMA_VERSION: u32 = 1;
+/// File inside the app data directory adding or overriding presets; same
+/// layout as the bundled file.
+const USER_FILE: &str = "adobe_presets.json";
+
+#[derive(Debug, Deserialize)]
+struct PresetFile {
+    version: u32,
+    presets: Vec<AdobePreset>,
+}
+
+static PRESETS: Mutex<Option<Vec<AdobePreset>>> = Mutex::new(None);
+
+fn check(preset: &AdobePreset) -> Result<(), String> {
+    let valid_name = !preset.name.is_empty()
+        && preset
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        return Err(format!("invalid preset name {:?} (use a-z, 0-9 and _)", preset.name));
+    }
+    if preset.encoder.trim().is_empty() || preset.pixel_format.trim().is_empty() {
+        return Err(format!("{} needs an encoder and a pixel format", preset.name));
+    }
+    if !preset.encoder_options.len().is_multiple_of(2) {
+        return Err(format!("{} has an encoder option without a value", preset.name));
+    }
+    passthrough::validate(&preset.encoder_options, "output").map_err(|e| format!("{}: {}", preset.name, e))
+}
+
+/// Read and check a preset file.
+pub fn parse(content: &str) -> Result<Vec<AdobePreset>, String> {
+    let file: PresetFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    if file.version == 0 || file.version > SCHEMA_VERSION {
+        return Err(format!(
+            "unsupported preset file version {} (this build reads up to {})",
+            file.version, SCHEMA_VERSION
+        ));
+    }
+    let mut names = HashSet::new();
+    for preset in &file.presets {
+        check(preset)?;
+        if !names.insert(preset.name.as_str()) {
+            return Err(format!("preset {} is listed twice", preset.name));
+        }
+    }
+    Ok(file.presets)
+}
+
+/// Bundled presets with the user's added; a user preset with a bundled
+/// preset's name replaces it in place.
+pub fn merge(mut presets: Vec<AdobePreset>, user: Vec<AdobePreset>) -> Vec<AdobePreset> {
+    for preset in user {
+        match presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => presets.push(preset),
+        }
+    }
+    presets
+}
+
+fn read_all() -> Vec<AdobePreset> {
+    let bundled = parse(BUNDLED).expect("Bundled Adobe presets are invalid");
+    let Some(path) = FfmpegDownloader::get_ffmpeg_app_dir().ok().map(|dir| dir.join(USER_FILE)) else {
+        return bundled;
+    };
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return bundled;
+    };
+    match parse(&content) {
+        Ok(user) => {
+            info!("Loaded {} Adobe preset(s) from {}", user.len(), path.display());
+            merge(bundled, user)
+        }
+        Err(e) => {
+            warn!("Ignoring {}: {}", path.display(), e);
+            bundled
+        }
+    }
+}
+
+/// Read the bundled and user presets again; called at startup and after the
+/// user file changed.
+pub fn reload() -> Vec<AdobePreset> {
+    let presets = read_all();
+    *PRESETS.lock().expect("Failed to lock Adobe presets") = Some(presets.clone());
+    presets
+}
+
+/// The loaded presets, reading them on first use.
+pub fn all() -> Vec<AdobePreset> {
+    let cached = PRESETS.lock().expect("Failed to lock Adobe presets").clone();
+    cached.unwrap_or_else(reload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_file_loads_and_user_presets_override() {
+        let bundled = parse(BUNDLED).unwrap();
+        assert_eq!(bundled.len(), 15);
+
+        let user = parse(
+            r#"{"version": 1, "presets": [
+                {"name": "prores_proxy", "description": "Proxy 10-bit", "encoder": "prores_ks",
+                 "encoder_options": ["-profile:v", "0"], "pixel_format": "yuv422p10le"},
+                {"name": "xavc_intra_100", "description": "XAVC Intra", "encoder": "libx264",
+                 "encoder_options": ["-x264-params", "keyint=1"], "pixel_format": "yuv422p10le"}
+            ]}"#,
+        )
+        .unwrap();
+        let merged = merge(bundled, user);
+        assert_eq!(merged.len(), 16);
+        assert_eq!(merged.iter().find(|p| p.name == "prores_proxy").unwrap().pixel_format, "yuv422p10le");
+
+        assert!(parse(r#"{"version": 2, "presets": []}"#).is_err());
+        assert!(parse(r#"{"version": 1, "presets": [{"name": "Bad Name", "description": "",
+            "encoder": "dnxhd", "encoder_options": [], "pixel_format": "yuv422p"}]}"#)
+        .is_err());
+    }
+}
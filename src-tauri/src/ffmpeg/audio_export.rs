@@ -0,0 +1,87 @@
+use super::get_format_info;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A separate audio file written by the same FFmpeg run as the video, e.g. an
+/// MP3 of a podcast episode next to its MP4.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioExportOptions {
+    /// Audio container extension (`mp3`, `m4a`, `wav`, ...).
+    pub format: String,
+    /// Output path; the video output with the audio extension when unset.
+    pub file: Option<String>,
+    /// Bitrate in kbit/s; the encoder default when unset.
+    pub bitrate: Option<u32>,
+}
+
+impl Default for AudioExportOptions {
+    fn default() -> Self {
+        Self {
+            format: "mp3".to_string(),
+            file: None,
+            bitrate: None,
+        }
+    }
+}
+
+impl AudioExportOptions {
+    /// Where the audio file is written for a video written to `output_file`.
+    pub fn path_for(&self, output_file: &str) -> String {
+        match self.file {
+            Some(ref file) => file.clone(),
+            None => Path::new(output_file)
+                .with_extension(self.format.to_lowercase())
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+}
+
+/// Arguments for the audio output, appended after the video output so they
+/// apply to this second output only. `audio_filters` and `duration` repeat
+/// what the video output got.
+pub fn output_args(
+    export: &AudioExportOptions,
+    output_file: &str,
+    track: usize,
+    audio_filters: &[String],
+    duration: Option<f64>,
+) -> Vec<String> {
+    let mut args = vec!["-map".to_string(), format!("0:a:{}", track), "-vn".to_string(), "-sn".to_string()];
+    if let Some(duration) = duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+    let codec = get_format_info(&export.format).default_audio_codec;
+    args.push("-c:a".to_string());
+    args.push(if codec.is_empty() { "copy" } else { codec }.to_string());
+    if let Some(bitrate) = export.bitrate {
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", bitrate));
+    }
+    if !audio_filters.is_empty() {
+        args.push("-af".to_string());
+        args.push(audio_filters.join(","));
+    }
+    args.push(export.path_for(output_file));
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_second_output_next_to_the_video() {
+        let export = AudioExportOptions {
+            bitrate: Some(192),
+            ..Default::default()
+        };
+        let args = output_args(&export, "/out/episode.mp4", 0, &["volume=2".to_string()], Some(60.0));
+        assert_eq!(
+            args,
+            ["-map", "0:a:0", "-vn", "-sn", "-t", "60.000", "-c:a", "libmp3lame", "-b:a", "192k", "-af", "volume=2", "/out/episode.mp3"]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use super::{get_format_info, StreamInfo, PROGRESS_PIPE};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Raw or simple containers that hold a codec unchanged; anything else is
+/// copied into Matroska audio.
+const NATIVE_EXTENSIONS: &[(&str, &str)] = &[
+    ("aac", "aac"),
+    ("ac3", "ac3"),
+    ("eac3", "eac3"),
+    ("dts", "dts"),
+    ("truehd", "thd"),
+    ("mp3", "mp3"),
+    ("mp2", "mp2"),
+    ("flac", "flac"),
+    ("alac", "m4a"),
+    ("opus", "opus"),
+    ("vorbis", "ogg"),
+    ("pcm_s16le", "wav"),
+    ("pcm_s24le", "wav"),
+    ("pcm_s32le", "wav"),
+    ("pcm_f32le", "wav"),
+];
+
+/// Which audio tracks to pull out of a file and how.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioExtractOptions {
+    /// Tracks as in `0:a:N`; every track when empty.
+    pub tracks: Vec<usize>,
+    /// Audio format to transcode to (`mp3`, `flac`, ...); each track keeps
+    /// its codec in a matching container when unset.
+    pub format: Option<String>,
+    /// Bitrate in kbit/s when transcoding; the encoder default when unset.
+    pub bitrate: Option<u32>,
+}
+
+/// Extension a track is written with: the requested format, or the codec's
+/// own container when copying.
+pub fn extension(codec: &str, format: Option<&str>) -> String {
+    match format {
+        Some(format) => format.to_lowercase(),
+        None => NATIVE_EXTENSIONS
+            .iter()
+            .find(|(name, _)| *name == codec)
+            .map_or("mka", |(_, ext)| *ext)
+            .to_string(),
+    }
+}
+
+/// `<name>.track<N>[.<language>].<ext>` in `output_dir`, numbering tracks
+/// from 1 as players list them.
+pub fn track_path(input_file: &str, output_dir: &Path, position: usize, stream: &StreamInfo, ext: &str) -> String {
+    let stem = Path::new(input_file)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "audio".to_string());
+    let language = stream
+        .language
+        .as_deref()
+        .filter(|l| !l.is_empty() && *l != "und")
+        .map(|l| format!(".{}", l))
+        .unwrap_or_default();
+    output_dir
+        .join(format!("{}.track{}{}.{}", stem, position + 1, language, ext))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// One FFmpeg run writing each `(track, path)` to its own file.
+pub fn extract_args(input_file: &str, outputs: &[(usize, String)], options: &AudioExtractOptions) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-progress", PROGRESS_PIPE, "-nostats", "-i", input_file]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let codec = options.format.as_deref().map(|f| get_format_info(f).default_audio_codec);
+    for (track, path) in outputs {
+        args.extend(["-map".to_string(), format!("0:a:{}", track), "-vn".to_string(), "-sn".to_string(), "-dn".to_string()]);
+        args.push("-c:a".to_string());
+        args.push(codec.unwrap_or("copy").to_string());
+        if let Some(bitrate) = options.bitrate.filter(|_| codec.is_some()) {
+            args.push("-b:a".to_string());
+            args.push(format!("{}k", bitrate));
+        }
+        args.push(path.clone());
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_each_track_into_its_own_container() {
+        assert_eq!(extension("ac3", None), "ac3");
+        assert_eq!(extension("pcm_bluray", None), "mka");
+        assert_eq!(extension("ac3", Some("MP3")), "mp3");
+
+        let stream = StreamInfo {
+            index: 2,
+            codec: "eac3".to_string(),
+            language: Some("deu".to_string()),
+            title: None,
+            default: false,
+            forced: false,
+            closed_captions: false,
+            sample_rate: None,
+            channels: None,
+            width: None,
+            height: None,
+        };
+        let path = track_path("/in/Film.mkv", Path::new("/out"), 1, &stream, "eac3");
+        assert_eq!(Path::new(&path), Path::new("/out/Film.track2.deu.eac3"));
+
+        let outputs = [(1, path.clone())];
+        let copy = extract_args("/in/Film.mkv", &outputs, &AudioExtractOptions::default());
+        assert_eq!(copy[7..], ["-map", "0:a:1", "-vn", "-sn", "-dn", "-c:a", "copy", path.as_str()]);
+
+        let options = AudioExtractOptions {
+            format: Some("mp3".to_string()),
+            bitrate: Some(192),
+            ..Default::default()
+        };
+        let mp3 = extract_args("/in/Film.mkv", &outputs, &options);
+        assert!(mp3.windows(4).any(|w| w == ["-c:a", "libmp3lame", "-b:a", "192k"]));
+    }
+}
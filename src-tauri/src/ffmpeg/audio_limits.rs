@@ -0,0 +1,153 @@
+use super::StreamInfo;
+
+/// What an audio encoder accepts. An empty `sample_rates` list means any rate.
+struct EncoderLimits {
+    sample_rates: &'static [u32],
+    max_channels: u32,
+}
+
+const AAC_RATES: &[u32] = &[96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000];
+const MP3_RATES: &[u32] = &[48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000];
+const OPUS_RATES: &[u32] = &[48000, 24000, 16000, 12000, 8000];
+const WMA_RATES: &[u32] = &[48000, 44100, 32000, 22050, 16000, 11025, 8000];
+const AC3_RATES: &[u32] = &[48000, 44100, 32000];
+
+fn encoder_limits(codec: &str) -> Option<EncoderLimits> {
+    let (sample_rates, max_channels) = match codec {
+        "aac" => (AAC_RATES, 8),
+        "libmp3lame" | "mp3" => (MP3_RATES, 2),
+        "libopus" => (OPUS_RATES, 8),
+        "libvorbis" | "flac" => (&[][..], 8),
+        "wmav2" => (WMA_RATES, 2),
+        "ac3" => (AC3_RATES, 6),
+        _ => return None,
+    };
+    Some(EncoderLimits {
+        sample_rates,
+        max_channels,
+    })
+}
+
+/// `-ar`/`-ac` overrides needed so the source audio fits the encoder.
+#[derive(Debug, Default, PartialEq)]
+pub struct AudioAdjustment {
+    /// `(source, target)` sample rate in Hz.
+    pub sample_rate: Option<(u32, u32)>,
+    /// `(source, target)` channel count.
+    pub channels: Option<(u32, u32)>,
+}
+
+impl AudioAdjustment {
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some((_, rate)) = self.sample_rate {
+            args.push("-ar".to_string());
+            args.push(rate.to_string());
+        }
+        if let Some((_, channels)) = self.channels {
+            args.push("-ac".to_string());
+            args.push(channels.to_string());
+        }
+        args
+    }
+
+    /// Log line explaining the change, `None` when nothing changes.
+    pub fn describe(&self, codec: &str) -> Option<String> {
+        let mut changes = Vec::new();
+        if let Some((source, target)) = self.sample_rate {
+            changes.push(format!("resampling {} Hz to {} Hz", source, target));
+        }
+        if let Some((source, target)) = self.channels {
+            changes.push(format!("downmixing {} channels to {}", source, target));
+        }
+        if changes.is_empty() {
+            return None;
+        }
+        Some(format!("Audio exceeds what {} supports: {}.", codec, changes.join(", ")))
+    }
+}
+
+/// Overrides that keep the highest sample rate and channel count among the
+/// source audio streams within what `codec` accepts.
+pub fn adjust(codec: &str, streams: &[StreamInfo]) -> AudioAdjustment {
+    let Some(limits) = encoder_limits(codec) else {
+        return AudioAdjustment::default();
+    };
+    let max_rate = streams.iter().filter_map(|s| s.sample_rate).max();
+    let max_channels = streams.iter().filter_map(|s| s.channels).max();
+
+    let sample_rate = max_rate
+        .filter(|rate| !limits.sample_rates.is_empty() && !limits.sample_rates.contains(rate))
+        .map(|rate| {
+            // Closest supported rate above the source (no quality lost),
+            // else the highest one.
+            let target = limits
+                .sample_rates
+                .iter()
+                .copied()
+                .filter(|&r| r >= rate)
+                .min()
+                .or_else(|| limits.sample_rates.iter().copied().max())
+                .unwrap_or(rate);
+            (rate, target)
+        });
+    let channels = max_channels
+        .filter(|&channels| channels > limits.max_channels)
+        .map(|channels| (channels, limits.max_channels));
+    AudioAdjustment { sample_rate, channels }
+}
+
+/// Channel count of an ffmpeg channel layout name (`stereo`, `5.1(side)`,
+/// `6 channels`).
+pub fn layout_channels(layout: &str) -> Option<u32> {
+    let layout = layout.trim();
+    if let Some(count) = layout.strip_suffix(" channels") {
+        return count.trim().parse().ok();
+    }
+    let base = layout.split('(').next().unwrap_or(layout);
+    let channels = match base {
+        "mono" => 1,
+        "stereo" | "downmix" => 2,
+        "2.1" | "3.0" => 3,
+        "quad" | "4.0" | "3.1" => 4,
+        "4.1" | "5.0" => 5,
+        "5.1" | "6.0" | "hexagonal" => 6,
+        "6.1" | "7.0" => 7,
+        "7.1" | "octagonal" | "cube" => 8,
+        _ => return None,
+    };
+    Some(channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(sample_rate: u32, channels: u32) -> StreamInfo {
+        StreamInfo {
+            index: 0,
+            codec: "pcm_s24le".to_string(),
+            language: None,
+            title: None,
+            default: false,
+            forced: false,
+            closed_captions: false,
+            sample_rate: Some(sample_rate),
+            channels: Some(channels),
+            width: None,
+            height: None,
+        }
+    }
+
+    #[test]
+    fn fits_source_audio_to_encoder_limits() {
+        let hi_res = [stream(96000, 6)];
+        let mp3 = adjust("libmp3lame", &hi_res);
+        assert_eq!(mp3.to_args(), vec!["-ar", "48000", "-ac", "2"]);
+        assert_eq!(adjust("aac", &hi_res), AudioAdjustment::default());
+        assert_eq!(adjust("libopus", &[stream(44100, 2)]).sample_rate, Some((44100, 48000)));
+        assert_eq!(adjust("pcm_s16le", &hi_res), AudioAdjustment::default());
+        assert_eq!(layout_channels("5.1(side)"), Some(6));
+        assert_eq!(layout_channels("10 channels"), Some(10));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::gpu::{GpuDetector, GpuType};
+use super::watchdog;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Capabilities per FFmpeg path; probing runs several processes, so preset
+/// lists reuse the last result.
+static CACHE: Mutex<Option<(String, Capabilities)>> = Mutex::new(None);
+
+/// Closest encoders, best first, for one a build may lack: CineForm is
+/// missing from "essentials" builds and hardware encoders from CPU-only ones.
+const SUBSTITUTES: &[(&str, &[&str])] = &[
+    ("cfhd", &["prores_ks", "dnxhd"]),
+    ("prores_ks", &["prores", "dnxhd"]),
+    ("dnxhd", &["prores_ks"]),
+    ("h264_nvenc", &["libx264"]),
+    ("h264_amf", &["libx264"]),
+    ("h264_qsv", &["libx264"]),
+    ("hevc_nvenc", &["libx265"]),
+    ("hevc_amf", &["libx265"]),
+    ("hevc_qsv", &["libx265"]),
+    ("av1_nvenc", &["libsvtav1", "libaom-av1"]),
+    ("av1_amf", &["libsvtav1", "libaom-av1"]),
+    ("av1_qsv", &["libsvtav1", "libaom-av1"]),
+    ("libsvtav1", &["libaom-av1"]),
+    ("libaom-av1", &["libsvtav1"]),
+];
+
+/// What this machine's FFmpeg build and hardware offer.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    /// False when FFmpeg couldn't be run; encoder and filter checks are
+    /// skipped then rather than flagging everything.
+    probed: bool,
+    encoders: HashSet<String>,
+    filters: HashSet<String>,
+    gpu_types: Vec<GpuType>,
+}
+
+/// Whether a preset can run here, and why not.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Availability {
+    pub available: bool,
+    pub issues: Vec<String>,
+    /// Encoder this build has that can stand in for a missing one.
+    pub substitute: Option<String>,
+}
+
+/// A preset with its availability, serialized as the preset's own fields
+/// plus `availability`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Annotated<T> {
+    #[serde(flatten)]
+    pub preset: T,
+    pub availability: Availability,
+}
+
+async fn list(ffmpeg_path: &str, flag: &str, pattern: &Regex) -> Option<HashSet<String>> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", flag]);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    let output = watchdog::output(&mut cmd).await.ok().filter(|o| o.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(
+        stdout
+            .lines()
+            .filter_map(|line| pattern.captures(line).map(|c| c[1].to_string()))
+            .collect(),
+    )
+}
+
+/// Probe (or reuse) the capabilities of the FFmpeg at `ffmpeg_path`.
+pub async fn capabilities(ffmpeg_path: Option<&str>) -> Capabilities {
+    let Some(ffmpeg_path) = ffmpeg_path else {
+        return Capabilities::default();
+    };
+    if let Some((ref path, ref caps)) = *CACHE.lock().unwrap_or_else(|e| e.into_inner()) {
+        if path == ffmpeg_path {
+            return caps.clone();
+        }
+    }
+
+    let encoder_pattern = Regex::new(r"^\s*[VASFXDB.]{6}\s+(\S+)").expect("Invalid regex");
+    let filter_pattern = Regex::new(r"^\s*[TSC.]{2,3}\s+(\S+)\s+\S+->\S+").expect("Invalid regex");
+    let encoders = list(ffmpeg_path, "-encoders", &encoder_pattern).await;
+    let filters = list(ffmpeg_path, "-filters", &filter_pattern).await;
+    let gpu_types = GpuDetector::detect_with_ffmpeg(Some(ffmpeg_path))
+        .await
+        .map(|info| info.adapters.iter().filter(|a| !a.is_virtual).map(|a| a.gpu_type).collect())
+        .unwrap_or_default();
+
+    let caps = Capabilities {
+        probed: encoders.is_some(),
+        encoders: encoders.unwrap_or_default(),
+        filters: filters.unwrap_or_default(),
+        gpu_types,
+    };
+    *CACHE.lock().unwrap_or_else(|e| e.into_inner()) = Some((ffmpeg_path.to_string(), caps.clone()));
+    caps
+}
+
+fn required_gpu(encoder: &str) -> Option<(GpuType, &'static str)> {
+    if encoder.contains("nvenc") {
+        Some((GpuType::Nvidia, "an NVIDIA GPU"))
+    } else if encoder.contains("amf") {
+        Some((GpuType::Amd, "an AMD GPU"))
+    } else if encoder.contains("qsv") {
+        Some((GpuType::Intel, "an Intel GPU"))
+    } else {
+        None
+    }
+}
+
+/// Whether the build was probed and has no `encoder`.
+pub fn missing_encoder(caps: &Capabilities, encoder: &str) -> bool {
+    caps.probed && !caps.encoders.contains(encoder)
+}
+
+/// The closest encoder to `encoder` that can run here.
+pub fn substitute(caps: &Capabilities, encoder: &str) -> Option<&'static str> {
+    SUBSTITUTES
+        .iter()
+        .find(|(name, _)| *name == encoder)
+        .and_then(|(_, candidates)| candidates.iter().find(|c| issues(caps, c, None, &[]).is_empty()))
+        .copied()
+}
+
+fn issues(caps: &Capabilities, encoder: &str, pixel_format: Option<&str>, filters: &[&str]) -> Vec<String> {
+    let mut issues = Vec::new();
+    if caps.probed {
+        if !caps.encoders.contains(encoder) {
+            issues.push(format!("This FFmpeg build has no {} encoder", encoder));
+        }
+        for filter in filters {
+            if !caps.filters.contains(*filter) {
+                issues.push(format!("This FFmpeg build has no {} filter", filter));
+            }
+        }
+    }
+    if let Some((gpu_type, label)) = required_gpu(encoder) {
+        // Adapters that couldn't be classified might be the right vendor.
+        let maybe_present = caps.gpu_types.iter().any(|t| *t == gpu_type || *t == GpuType::Unknown);
+        if !maybe_present {
+            issues.push(format!("{} needs {}", encoder, label));
+        }
+        if encoder.starts_with("h264") && pixel_format.is_some_and(|f| f.contains("10")) {
+            issues.push(format!("{} can't encode 10-bit video", encoder));
+        }
+    }
+    issues
+}
+
+/// Check an encoder, optional output pixel format and the filters a preset
+/// uses against `caps`, suggesting a substitute for a missing encoder.
+pub fn check(caps: &Capabilities, encoder: &str, pixel_format: Option<&str>, filters: &[&str]) -> Availability {
+    let issues = issues(caps, encoder, pixel_format, filters);
+    let substitute = missing_encoder(caps, encoder)
+        .then(|| substitute(caps, encoder))
+        .flatten()
+        .map(str::to_string);
+    Availability {
+        available: issues.is_empty(),
+        issues,
+        substitute,
+    }
+}
+
+/// Filter names used by a filter chain (`fade=t=in:...` -> `fade`).
+pub fn filter_names(chain: &[String]) -> Vec<String> {
+    chain
+        .iter()
+        .filter_map(|entry| entry.split(['=', '@', ',']).next())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_missing_encoders_filters_and_hardware() {
+        let caps = Capabilities {
+            probed: true,
+            encoders: ["libx264", "h264_nvenc"].iter().map(|s| s.to_string()).collect(),
+            filters: ["fade"].iter().map(|s| s.to_string()).collect(),
+            gpu_types: vec![GpuType::Intel],
+        };
+        assert!(check(&caps, "libx264", Some("yuv420p"), &["fade"]).available);
+
+        let nvenc = check(&caps, "h264_nvenc", Some("yuv420p10le"), &["afade"]);
+        assert_eq!(nvenc.issues.len(), 3);
+        assert!(!nvenc.available);
+        assert_eq!(nvenc.substitute, None);
+
+        assert_eq!(check(&caps, "hevc_nvenc", None, &[]).substitute, None);
+        assert_eq!(substitute(&caps, "h264_qsv"), Some("libx264"));
+        let caps = Capabilities {
+            encoders: ["libx265", "dnxhd"].iter().map(|s| s.to_string()).collect(),
+            ..caps
+        };
+        assert_eq!(check(&caps, "cfhd", None, &[]).substitute.as_deref(), Some("dnxhd"));
+        assert_eq!(check(&caps, "hevc_nvenc", None, &[]).substitute.as_deref(), Some("libx265"));
+
+        let unprobed = Capabilities::default();
+        assert_eq!(check(&unprobed, "prores_ks", None, &[]).issues, Vec::<String>::new());
+        assert_eq!(
+            filter_names(&["fade=t=in:st=0:d=1".to_string(), "drawtext@hud=text=x".to_string()]),
+            ["fade", "drawtext"]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use super::{captions, get_format_info, probe, probe_info, tracks, ConversionOptions, VideoInfo};
+use futures::StreamExt;
+use serde::Serialize;
+use std::path::Path;
+
+/// Files probed at the same time.
+const PROBE_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FindingKind {
+    Missing,
+    Unreadable,
+    NoVideo,
+    NoAudio,
+    UnsupportedCodec,
+    NeedsTonemap,
+    LosesSubtitles,
+    LosesCaptions,
+    LosesAudioTracks,
+}
+
+impl FindingKind {
+    /// Whether the job would fail rather than just produce a worse output.
+    fn is_error(self) -> bool {
+        matches!(
+            self,
+            FindingKind::Missing | FindingKind::Unreadable | FindingKind::NoVideo | FindingKind::UnsupportedCodec
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub input_file: String,
+    pub status: FileStatus,
+    pub findings: Vec<Finding>,
+}
+
+impl FileReport {
+    fn new(input_file: &str, findings: Vec<Finding>) -> Self {
+        let status = if findings.iter().any(|f| f.kind.is_error()) {
+            FileStatus::Error
+        } else if findings.is_empty() {
+            FileStatus::Ok
+        } else {
+            FileStatus::Warning
+        };
+        Self {
+            input_file: input_file.to_string(),
+            status,
+            findings,
+        }
+    }
+}
+
+/// What every file of the batch is converted to.
+pub struct BatchTarget<'a> {
+    pub output_format: &'a str,
+    pub encoder: &'a str,
+    pub options: &'a ConversionOptions,
+}
+
+fn finding(kind: FindingKind, message: String) -> Finding {
+    Finding { kind, message }
+}
+
+/// Encoders that can keep HDR video as HDR (given a 10-bit pixel format).
+fn keeps_hdr(encoder: &str) -> bool {
+    ["hevc", "265", "av1", "vp9"].iter().any(|codec| encoder.contains(codec))
+}
+
+/// Problems converting a file with streams `info` (and HDR format `hdr`,
+/// when known) to `target` would run into.
+pub fn check(info: &VideoInfo, hdr: Option<&str>, target: &BatchTarget) -> Vec<Finding> {
+    let format_info = get_format_info(target.output_format);
+    let options = target.options;
+    let mut findings = Vec::new();
+
+    let streams = info.video_streams.iter().chain(&info.audio_streams).chain(&info.subtitle_streams);
+    for stream in streams.filter(|s| s.codec.is_empty() || s.codec == "none") {
+        findings.push(finding(
+            FindingKind::UnsupportedCodec,
+            format!("Stream {} has a codec FFmpeg can't decode", stream.index),
+        ));
+    }
+
+    if format_info.supports_video {
+        if info.video_streams.is_empty() {
+            findings.push(finding(FindingKind::NoVideo, "The file has no video stream".to_string()));
+        }
+        if let Some(format) = hdr.filter(|_| !keeps_hdr(target.encoder)) {
+            findings.push(finding(
+                FindingKind::NeedsTonemap,
+                format!(
+                    "{} source encoded with {} will look washed out without tone mapping",
+                    format, target.encoder
+                ),
+            ));
+        }
+        let kept_subtitles = if options.copy_subtitles {
+            info.subtitle_streams
+                .iter()
+                .filter(|s| tracks::subtitle_codec(target.output_format, &s.codec).is_some())
+                .count()
+        } else {
+            0
+        };
+        // A burned-in track still shows, so it doesn't count as lost.
+        let burned = usize::from(options.subtitle_stream.is_some());
+        let lost = info.subtitle_streams.len().saturating_sub(kept_subtitles + burned);
+        if lost > 0 {
+            findings.push(finding(
+                FindingKind::LosesSubtitles,
+                format!("{} of {} subtitle track(s) won't be kept", lost, info.subtitle_streams.len()),
+            ));
+        }
+        let embedded_captions = captions::detect(info).contains(&captions::CaptionSource::Embedded);
+        if embedded_captions && !options.extract_captions && !captions::carries_embedded_captions(target.encoder) {
+            findings.push(finding(
+                FindingKind::LosesCaptions,
+                "Embedded closed captions will be dropped; enable caption extraction to keep them".to_string(),
+            ));
+        }
+    } else if info.audio_streams.len() > 1 && options.audio_streams.len() <= 1 {
+        findings.push(finding(
+            FindingKind::LosesAudioTracks,
+            format!("Only one of {} audio tracks goes into the audio file", info.audio_streams.len()),
+        ));
+    }
+
+    if format_info.supports_audio && !format_info.supports_video && info.audio_streams.is_empty() {
+        findings.push(finding(FindingKind::NoAudio, "The file has no audio stream".to_string()));
+    }
+    findings
+}
+
+async fn check_file(ffmpeg_path: &str, input_file: &str, target: &BatchTarget<'_>) -> FileReport {
+    if !Path::new(input_file).exists() {
+        return FileReport::new(input_file, vec![finding(FindingKind::Missing, "File not found".to_string())]);
+    }
+    // ffprobe also reports HDR; plain FFmpeg output doesn't.
+    let probed = match probe::ffprobe_path_for(ffmpeg_path) {
+        Some(ffprobe) => probe::probe_media(&ffprobe, input_file).await.ok().map(|media| {
+            let hdr = media
+                .streams
+                .iter()
+                .find(|s| s.kind == "video" && !s.attached_pic)
+                .and_then(|s| s.hdr.as_ref().map(|h| h.format.clone()));
+            (media.to_video_info(), hdr)
+        }),
+        None => None,
+    };
+    let probed = match probed {
+        Some(probed) => Some(probed),
+        None => probe_info(ffmpeg_path, input_file).await.map(|info| (info, None)),
+    };
+    match probed {
+        Some((info, hdr)) => FileReport::new(input_file, check(&info, hdr.as_deref(), target)),
+        None => FileReport::new(
+            input_file,
+            vec![finding(FindingKind::Unreadable, "FFmpeg couldn't read the file".to_string())],
+        ),
+    }
+}
+
+/// Probe every file and report what converting it to `target` would run
+/// into, in input order.
+pub async fn check_batch(ffmpeg_path: &str, input_files: &[String], target: &BatchTarget<'_>) -> Vec<FileReport> {
+    futures::stream::iter(input_files.iter().cloned())
+        .map(|file| async move { check_file(ffmpeg_path, &file, target).await })
+        .buffered(PROBE_CONCURRENCY)
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffmpeg::StreamInfo;
+
+    fn stream(index: u32, codec: &str) -> StreamInfo {
+        StreamInfo {
+            index,
+            codec: codec.to_string(),
+            language: None,
+            title: None,
+            default: false,
+            forced: false,
+            closed_captions: false,
+            sample_rate: None,
+            channels: None,
+            width: None,
+            height: None,
+        }
+    }
+
+    #[test]
+    fn reports_tonemapping_and_lost_subtitles() {
+        let info = VideoInfo {
+            duration: Some(60.0),
+            width: Some(3840),
+            height: Some(2160),
+            video_streams: vec![stream(0, "hevc")],
+            audio_streams: vec![stream(1, "eac3")],
+            subtitle_streams: vec![stream(2, "hdmv_pgs_subtitle"), stream(3, "subrip")],
+            sample_aspect: None,
+            display_aspect: None,
+        };
+        let options = ConversionOptions {
+            copy_subtitles: true,
+            ..Default::default()
+        };
+        let target = BatchTarget {
+            output_format: "mp4",
+            encoder: "libx264",
+            options: &options,
+        };
+        let kinds: Vec<FindingKind> = check(&info, Some("HDR10"), &target).iter().map(|f| f.kind).collect();
+        assert_eq!(kinds, [FindingKind::NeedsTonemap, FindingKind::LosesSubtitles]);
+
+        let hevc = BatchTarget { encoder: "hevc_nvenc", output_format: "mkv", ..target };
+        assert!(check(&info, Some("HDR10"), &hevc).is_empty());
+        assert_eq!(FileReport::new("a.mkv", Vec::new()).status, FileStatus::Ok);
+    }
+}
@@ -0,0 +1,160 @@
+use super::filters::escape_filter_path;
+use super::VideoInfo;
+use crate::error::AppError;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Subtitle codecs carrying broadcast teletext pages.
+const TELETEXT_CODECS: &[&str] = &["dvb_teletext"];
+
+/// Where a caption track lives in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptionSource {
+    /// CEA-608/708 captions embedded in the video stream's A53 side data.
+    Embedded,
+    /// A teletext subtitle stream, by position as in `0:s:N`.
+    Teletext { position: usize, language: Option<String> },
+}
+
+/// Encoders that pass A53 closed captions through from decoded frames.
+pub fn carries_embedded_captions(encoder: &str) -> bool {
+    matches!(encoder, "copy" | "libx264" | "libx265" | "h264_nvenc" | "hevc_nvenc")
+}
+
+pub fn is_teletext(codec: &str) -> bool {
+    TELETEXT_CODECS.contains(&codec)
+}
+
+/// Caption tracks found in a probed source.
+pub fn detect(info: &VideoInfo) -> Vec<CaptionSource> {
+    let mut sources = Vec::new();
+    if info.video_streams.iter().any(|s| s.closed_captions) {
+        sources.push(CaptionSource::Embedded);
+    }
+    for (position, stream) in info.subtitle_streams.iter().enumerate() {
+        if is_teletext(&stream.codec) {
+            sources.push(CaptionSource::Teletext {
+                position,
+                language: stream.language.clone(),
+            });
+        }
+    }
+    sources
+}
+
+/// SRT path next to `output` for a caption track, e.g. `clip.cc.srt` or
+/// `clip.deu.teletext.srt`.
+pub fn srt_path(output: &Path, source: &CaptionSource) -> PathBuf {
+    let stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name = match source {
+        CaptionSource::Embedded => format!("{}.cc.srt", stem),
+        CaptionSource::Teletext { position, language } => match language {
+            Some(language) => format!("{}.{}.teletext.srt", stem, language),
+            None => format!("{}.{}.teletext.srt", stem, position),
+        },
+    };
+    output.with_file_name(name)
+}
+
+/// FFmpeg arguments writing one caption track of `input_file` to SRT.
+/// Embedded captions are only reachable through the `movie` source's
+/// `subcc` output; teletext is decoded as plain text.
+pub fn extract_args(input_file: &str, source: &CaptionSource, srt_file: &str) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-nostats"].iter().map(|s| s.to_string()).collect();
+    match source {
+        CaptionSource::Embedded => {
+            args.extend([
+                "-f".to_string(),
+                "lavfi".to_string(),
+                "-i".to_string(),
+                format!("movie={}[out0+subcc]", escape_filter_path(input_file)),
+                "-map".to_string(),
+                "0:s".to_string(),
+            ]);
+        }
+        CaptionSource::Teletext { position, .. } => {
+            args.extend([
+                "-txt_format".to_string(),
+                "text".to_string(),
+                "-i".to_string(),
+                input_file.to_string(),
+                "-map".to_string(),
+                format!("0:s:{}", position),
+            ]);
+        }
+    }
+    args.extend(["-c:s".to_string(), "srt".to_string(), srt_file.to_string()]);
+    args
+}
+
+/// Extract every caption track of `input_file` to SRT files next to
+/// `output`. Returns the files written; tracks that yield no cues are
+/// skipped.
+pub async fn extract_all(
+    ffmpeg_path: &str,
+    input_file: &str,
+    info: &VideoInfo,
+    output: &Path,
+) -> Result<Vec<PathBuf>, AppError> {
+    let mut written = Vec::new();
+    for source in detect(info) {
+        let srt = srt_path(output, &source);
+        let srt_file = srt.to_string_lossy().to_string();
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.args(extract_args(input_file, &source, &srt_file))
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        let result = cmd
+            .output()
+            .await
+            .map_err(|e| AppError::Ffmpeg(format!("Failed to run caption extraction: {}", e)))?;
+        if !result.status.success() {
+            let _ = std::fs::remove_file(&srt);
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(AppError::Ffmpeg(format!(
+                "Caption extraction failed: {}",
+                stderr.lines().last().unwrap_or("unknown error")
+            )));
+        }
+        if std::fs::metadata(&srt).map(|m| m.len() > 0).unwrap_or(false) {
+            written.push(srt);
+        } else {
+            let _ = std::fs::remove_file(&srt);
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_embedded_and_teletext_captions() {
+        let output = "Duration: 00:00:10.00, start: 0.000000, bitrate: 5000 kb/s\n\
+            Stream #0:0[0x100]: Video: h264 (High) ([27][0][0][0] / 0x001B), yuv420p(tv, top first), 1920x1080 [SAR 1:1 DAR 16:9], Closed Captions, 25 fps\n\
+            Stream #0:1[0x101](deu): Audio: mp2, 48000 Hz, stereo, fltp, 192 kb/s\n\
+            Stream #0:2[0x102](deu): Subtitle: dvb_teletext ([6][0][0][0] / 0x0006)\n";
+        let info = VideoInfo::parse(output).unwrap();
+        let sources = detect(&info);
+        assert_eq!(
+            sources,
+            vec![
+                CaptionSource::Embedded,
+                CaptionSource::Teletext { position: 0, language: Some("deu".to_string()) },
+            ]
+        );
+        assert_eq!(srt_path(Path::new("/out/news.mp4"), &sources[1]), Path::new("/out/news.deu.teletext.srt"));
+        assert_eq!(
+            extract_args("/in/news.ts", &sources[0], "/out/news.cc.srt")[6],
+            "movie=/in/news.ts[out0+subcc]"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::error::AppError;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Scene change score (0-1) above which a cut is considered a new scene.
+const SCENE_THRESHOLD: f64 = 0.4;
+/// Scene cuts closer together than this are merged into one chapter.
+const MIN_CHAPTER_LENGTH: f64 = 60.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start: f64,
+    pub title: String,
+}
+
+/// Parse a `[hh:]mm:ss[.ms] Title` timestamp list (one chapter per line, as
+/// used in video descriptions). Blank lines and `#` comments are skipped.
+pub fn parse_chapter_list(content: &str) -> Result<Vec<Chapter>, AppError> {
+    let line_regex = Regex::new(r"^(?:(\d+):)?(\d{1,2}):(\d{2}(?:\.\d+)?)\s*[-–:|]?\s*(.*)$")
+        .expect("Invalid regex");
+
+    let mut chapters = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let caps = line_regex.captures(line).ok_or_else(|| {
+            AppError::Internal(format!("Invalid chapter line {}: {}", number + 1, line))
+        })?;
+        let hours: f64 = caps.get(1).map_or(0.0, |m| m.as_str().parse().unwrap_or(0.0));
+        let minutes: f64 = caps[2].parse().unwrap_or(0.0);
+        let seconds: f64 = caps[3].parse().unwrap_or(0.0);
+        let title = caps[4].trim();
+
+        chapters.push(Chapter {
+            start: hours * 3600.0 + minutes * 60.0 + seconds,
+            title: if title.is_empty() {
+                format!("Chapter {}", chapters.len() + 1)
+            } else {
+                title.to_string()
+            },
+        });
+    }
+
+    chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(chapters)
+}
+
+/// Find scene cuts with FFmpeg's scene score and turn them into chapters.
+/// This decodes the whole input, so it takes roughly as long as a fast encode.
+pub async fn detect_scene_chapters(ffmpeg_path: &str, input_file: &str) -> Result<Vec<Chapter>, AppError> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-nostats", "-i", input_file, "-an", "-sn"])
+        .args(["-vf", &format!("select='gt(scene,{})',showinfo", SCENE_THRESHOLD)])
+        .args(["-f", "null", "-"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| AppError::Ffmpeg(format!("Failed to run scene detection: {}", e)))?;
+    if !output.status.success() {
+        return Err(AppError::Ffmpeg("Scene detection failed".to_string()));
+    }
+
+    let pts_regex = Regex::new(r"pts_time:(\d+(?:\.\d+)?)").expect("Invalid regex");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut chapters = vec![Chapter {
+        start: 0.0,
+        title: "Chapter 1".to_string(),
+    }];
+    for caps in stderr
+        .lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| pts_regex.captures(line))
+    {
+        let start: f64 = caps[1].parse().unwrap_or(0.0);
+        if start - chapters.last().map_or(0.0, |c| c.start) >= MIN_CHAPTER_LENGTH {
+            chapters.push(Chapter {
+                start,
+                title: format!("Chapter {}", chapters.len() + 1),
+            });
+        }
+    }
+
+    Ok(chapters)
+}
+
+/// Shift chapters onto the trimmed clip's timeline: chapters before the trim
+/// start collapse into one starting at zero, chapters past the end are dropped.
+pub fn trim_chapters(chapters: &[Chapter], trim_start: f64, clip_length: Option<f64>) -> Vec<Chapter> {
+    let mut trimmed: Vec<Chapter> = Vec::new();
+    for chapter in chapters {
+        let start = chapter.start - trim_start;
+        if clip_length.is_some_and(|length| start >= length) {
+            break;
+        }
+        if start <= 0.0 {
+            trimmed.clear();
+        }
+        trimmed.push(Chapter {
+            start: start.max(0.0),
+            title: chapter.title.clone(),
+        });
+    }
+    trimmed
+}
+
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Render chapters in FFmpeg's FFMETADATA format; the last chapter runs to `end`.
+pub fn render_ffmetadata(chapters: &[Chapter], end: f64) -> String {
+    let mut body = String::from(";FFMETADATA1\n");
+    for (i, chapter) in chapters.iter().enumerate() {
+        let chapter_end = chapters.get(i + 1).map_or(end, |next| next.start);
+        body.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0).round() as u64,
+            (chapter_end.max(chapter.start) * 1000.0).round() as u64,
+            escape_metadata(&chapter.title)
+        ));
+    }
+    body
+}
+
+/// Path of the FFMETADATA chapter file written for a task.
+pub fn metadata_path(task_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("dreamcodec_chapters_{}.txt", task_id))
+}
+
+/// Write chapters as an FFMETADATA file to `path`.
+pub fn write_ffmetadata(chapters: &[Chapter], end: f64, path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, render_ffmetadata(chapters, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_description_style_timestamps() {
+        let chapters = parse_chapter_list("0:00 Intro\n# skipped\n1:02:03.5 - Boss fight\n12:30 Lore").unwrap();
+        assert_eq!(
+            chapters.iter().map(|c| c.start).collect::<Vec<_>>(),
+            vec![0.0, 750.0, 3723.5]
+        );
+        assert_eq!(chapters[2].title, "Boss fight");
+    }
+
+    #[test]
+    fn trimming_keeps_the_chapter_in_progress() {
+        let chapters = parse_chapter_list("0:00 A\n1:00 B\n3:00 C\n5:00 D").unwrap();
+        let trimmed = trim_chapters(&chapters, 90.0, Some(180.0));
+        assert_eq!(
+            trimmed,
+            vec![
+                Chapter { start: 0.0, title: "B".to_string() },
+                Chapter { start: 90.0, title: "C".to_string() },
+            ]
+        );
+    }
+}
@@ -0,0 +1,481 @@
+use super::{
+    filters, read_ffmpeg_output, metadata, tracks, validate_output, ConversionOptions, ConversionPhase, ConversionStatus,
+    ConversionTask, FormatInfo, MediaServerPreset, ProgressParser, PROGRESS_PIPE,
+};
+use super::audio_limits::AudioAdjustment;
+use futures::future::try_join_all;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::{BELOW_NORMAL_PRIORITY_CLASS, CREATE_NO_WINDOW};
+
+/// Chunks shorter than this aren't worth the extra process and keyframe.
+const MIN_CHUNK_SECONDS: f64 = 30.0;
+/// Upper bound for the automatic chunk count.
+const MAX_AUTO_CHUNKS: usize = 8;
+
+/// Why a job can't be split into chunks, or `None` if it can. Anything that
+/// depends on the whole timeline (fades, stitched clips, chapters) or on
+/// multiple inputs keeps the regular single-process path.
+pub fn ineligible_reason(
+    options: &ConversionOptions,
+    encoder: &str,
+    format_info: &FormatInfo,
+    is_adobe_preset: bool,
+) -> Option<&'static str> {
+    if ["nvenc", "amf", "qsv", "videotoolbox", "vaapi"].iter().any(|hw| encoder.contains(hw)) {
+        Some("hardware encoders are not chunked")
+    } else if !format_info.supports_video {
+        Some("audio-only output")
+    } else if is_adobe_preset {
+        Some("editing presets are not chunked")
+    } else if options.screen_preset.is_some() {
+        Some("screen-recording presets are not chunked")
+    } else if options.fade_in.is_some() || options.fade_out.is_some() {
+        Some("fades need the full timeline")
+    } else if options.overlay.is_some() || options.intro_file.is_some() || options.outro_file.is_some() {
+        Some("multi-input jobs are not chunked")
+    } else if options.audio_export.is_some() || !options.extra_outputs.is_empty() {
+        Some("multi-output jobs are not chunked")
+    } else if options.timelapse.is_some() {
+        Some("timelapse jobs are not chunked")
+    } else if !options.input_segments.is_empty() {
+        Some("joined segment inputs are not chunked")
+    } else if options.chapters_file.is_some() || options.scene_chapters {
+        Some("chapters need the full timeline")
+    } else if options.copy_subtitles
+        || options.ocr_subtitles
+        || !options.audio_languages.is_empty()
+        || options.video_stream.is_some()
+        || !options.audio_streams.is_empty()
+    {
+        Some("track selection is not chunked")
+    } else if options.reframe.as_ref().is_some_and(|r| !r.keyframes.is_empty()) {
+        Some("keyframed reframing needs the full timeline")
+    } else if options.telemetry.is_some() {
+        Some("telemetry overlays need the full timeline")
+    } else if options.keep_surround_track && options.media_server_preset.is_some() {
+        Some("the surround copy track is not chunked")
+    } else if options.audio_edit.is_some() {
+        Some("audio edits need the full timeline")
+    } else if options.normalize_audio.is_some() {
+        Some("loudness is measured over the whole file")
+    } else if options.rate_control.as_ref().is_some_and(|rc| rc.two_pass) {
+        Some("two-pass encodes need the full timeline")
+    } else if options.growing_file {
+        Some("growing files can't be split ahead of time")
+    } else if !options.extra_input_args.is_empty() || !options.extra_output_args.is_empty() {
+        Some("custom FFmpeg arguments are not chunked")
+    } else {
+        None
+    }
+}
+
+/// Number of chunks to use: the requested count, or one per four logical
+/// cores (x264/x265 already use several threads per process).
+pub fn chunk_count(requested: Option<u32>) -> usize {
+    match requested.filter(|n| *n >= 2) {
+        Some(n) => n as usize,
+        None => {
+            let cores = std::thread::available_parallelism().map_or(4, |n| n.get());
+            (cores / 4).clamp(2, MAX_AUTO_CHUNKS)
+        }
+    }
+}
+
+/// Split `length` seconds into up to `count` equal chunks of `(start, length)`.
+pub fn plan_chunks(length: f64, count: usize) -> Vec<(f64, f64)> {
+    let count = count.min((length / MIN_CHUNK_SECONDS).floor() as usize).max(1);
+    let chunk = length / count as f64;
+    (0..count)
+        .map(|i| {
+            let start = i as f64 * chunk;
+            let end = if i + 1 == count { length } else { start + chunk };
+            (start, end - start)
+        })
+        .collect()
+}
+
+/// Everything a chunked encode needs, resolved by `run_chunked`.
+pub struct ChunkedJob<'a> {
+    pub task_id: &'a str,
+    pub ffmpeg_path: &'a str,
+    pub input_file: &'a str,
+    pub output_file: &'a str,
+    pub encoder: &'a str,
+    pub preset: &'a str,
+    pub cpu_threads: Option<u32>,
+    pub options: &'a ConversionOptions,
+    pub media_preset: Option<&'a MediaServerPreset>,
+    pub audio_codec: &'a str,
+    /// Resampling/downmix keeping the source audio within `audio_codec`.
+    pub audio_adjustment: AudioAdjustment,
+    /// Length of the exported range, starting at `options.trim_start`.
+    pub clip_length: f64,
+}
+
+fn scratch_dir(task_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("dreamcodec_chunks_{}", task_id))
+}
+
+/// Run one FFmpeg process, feeding its reported output time into `times[slot]`
+/// and the summed time into the task progress.
+async fn run_part(
+    task_arc: &Arc<Mutex<ConversionTask>>,
+    job: &ChunkedJob<'_>,
+    args: Vec<String>,
+    times: Arc<Mutex<Vec<f64>>>,
+    slot: Option<usize>,
+) -> Result<(), String> {
+    let mut cmd = Command::new(job.ffmpeg_path);
+    cmd.args(&args)
+        .envs(&job.options.env_vars)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(ref dir) = job.options.working_dir {
+        cmd.current_dir(dir);
+    }
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    let pid = child.id();
+    let cancel = task_arc.lock().expect("Failed to lock task mutex").cancel.clone();
+    if let Some(pid) = pid {
+        cancel.register(pid);
+    }
+
+    let parser = ProgressParser::new();
+    let mut last_lines = Vec::new();
+    read_ffmpeg_output(
+        &mut child,
+        |line| {
+            if let (Some(slot), Some(time)) = (slot, parser.time(line)) {
+                let total: f64 = {
+                    let mut times = times.lock().expect("Failed to lock chunk times");
+                    times[slot] = time.max(times[slot]);
+                    times.iter().sum()
+                };
+                task_arc.lock().expect("Failed to lock task mutex").advance(total);
+            }
+        },
+        |line| last_lines.push(line),
+    )
+    .await;
+
+    let status = child.wait().await;
+    if let Some(pid) = pid {
+        cancel.unregister(pid);
+    }
+    let status = status.map_err(|e| format!("Failed to wait for FFmpeg: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "FFmpeg exited with code {}: {}",
+            status.code().map_or("None".to_string(), |c| c.to_string()),
+            last_lines.last().map(String::as_str).unwrap_or("no output")
+        ))
+    }
+}
+
+fn base_args(start: f64, length: f64, input_file: &str) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-progress", PROGRESS_PIPE, "-nostats"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    if start > 0.0 {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+    args.push("-t".to_string());
+    args.push(format!("{:.3}", length));
+    args.push("-i".to_string());
+    args.push(input_file.to_string());
+    args
+}
+
+fn video_chunk_args(job: &ChunkedJob<'_>, start: f64, length: f64, threads: usize, out: &Path) -> Vec<String> {
+    let mut args = base_args(start, length, job.input_file);
+    args.extend(["-map", "0:v:0", "-an", "-sn", "-dn", "-c:v"].iter().map(|s| s.to_string()));
+    args.push(job.encoder.to_string());
+    if job.encoder == "libx264" || job.encoder == "libx265" {
+        args.push("-preset".to_string());
+        args.push(job.preset.to_string());
+    }
+    args.push("-threads".to_string());
+    args.push(threads.to_string());
+    if let Some(preset) = job.media_preset {
+        args.extend(preset.encoder_options.iter().cloned());
+        args.push("-pix_fmt".to_string());
+        args.push(preset.pixel_format.clone());
+        args.push("-fps_mode".to_string());
+        args.push("cfr".to_string());
+    }
+    if let Some(ref rate_control) = job.options.rate_control {
+        args.extend(rate_control.args(job.encoder));
+    }
+
+    // Filters see the chunk as if it were a trimmed clip of its own, which
+    // keeps burned-in subtitles in sync.
+    let chunk_options = ConversionOptions {
+        trim_start: Some(start),
+        trim_duration: Some(length),
+        ..job.options.clone()
+    };
+    let video_filters = filters::video_filter_chain(&chunk_options, job.input_file, Some(length));
+    if !video_filters.is_empty() {
+        args.push("-vf".to_string());
+        args.push(video_filters.join(","));
+    }
+
+    args.push(out.to_string_lossy().to_string());
+    args
+}
+
+fn audio_args(job: &ChunkedJob<'_>, out: &Path) -> Vec<String> {
+    let start = job.options.trim_start.unwrap_or(0.0);
+    let mut args = base_args(start, job.clip_length, job.input_file);
+    args.extend(["-map", "0:a:0?", "-vn", "-sn", "-dn"].iter().map(|s| s.to_string()));
+    match job.media_preset {
+        Some(preset) => args.extend(preset.audio_options.iter().cloned()),
+        None => {
+            args.push("-c:a".to_string());
+            if job.audio_codec.is_empty() {
+                args.push("copy".to_string());
+            } else {
+                args.push(job.audio_codec.to_string());
+                args.extend(job.audio_adjustment.to_args());
+                if let Some(bitrate) = job.options.audio_bitrate {
+                    args.push("-b:a".to_string());
+                    args.push(format!("{}k", bitrate));
+                }
+            }
+        }
+    }
+    args.push(out.to_string_lossy().to_string());
+    args
+}
+
+fn fail(task_arc: &Arc<Mutex<ConversionTask>>, message: String) {
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    if task.cancel.is_cancelled() {
+        task.progress.status = ConversionStatus::Cancelled;
+    } else {
+        warn!("{}", message);
+        task.progress.status = ConversionStatus::Failed(message.clone());
+        task.progress.error_message = Some(message);
+    }
+}
+
+/// Encode the video in parallel chunks plus one audio pass, then join the
+/// pieces with the concat demuxer (stream copy). Each chunk seeks to its own
+/// start, so it begins with a fresh keyframe and the pieces join cleanly.
+pub async fn run(task_arc: Arc<Mutex<ConversionTask>>, job: ChunkedJob<'_>, count: usize) {
+    let dir = scratch_dir(job.task_id);
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        fail(&task_arc, format!("Failed to create chunk directory: {}", e));
+        return;
+    }
+
+    let trim_start = job.options.trim_start.unwrap_or(0.0);
+    let chunks = plan_chunks(job.clip_length, count);
+    let cores = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let threads = job
+        .cpu_threads
+        .map_or(cores, |t| t as usize)
+        .div_ceil(chunks.len())
+        .max(1);
+
+    {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.enter_phase(ConversionPhase::Encoding);
+        task.progress.attempts += 1;
+        task.progress.duration = job.clip_length;
+        task.progress.log.push(format!(
+            "Chunked encoding: {} chunks with {} threads each",
+            chunks.len(),
+            threads
+        ));
+    }
+    info!("Chunked encode of {} in {} chunks", job.input_file, chunks.len());
+
+    let times = Arc::new(Mutex::new(vec![0.0; chunks.len()]));
+    let chunk_files: Vec<PathBuf> = (0..chunks.len())
+        .map(|i| dir.join(format!("chunk_{:03}.mkv", i)))
+        .collect();
+    let audio_file = dir.join("audio.mka");
+
+    let mut parts = Vec::new();
+    for (i, (start, length)) in chunks.iter().enumerate() {
+        let args = video_chunk_args(&job, trim_start + start, *length, threads, &chunk_files[i]);
+        parts.push(run_part(&task_arc, &job, args, times.clone(), Some(i)));
+    }
+    parts.push(run_part(&task_arc, &job, audio_args(&job, &audio_file), times.clone(), None));
+
+    if let Err(e) = try_join_all(parts).await {
+        fail(&task_arc, format!("Chunked encoding failed: {}", e));
+        let _ = std::fs::remove_dir_all(&dir);
+        return;
+    }
+
+    let list_path = dir.join("chunks.txt");
+    let list: Vec<String> = chunk_files.iter().map(|f| f.to_string_lossy().to_string()).collect();
+    if let Err(e) = super::segments::write_concat_list(&list, &list_path) {
+        fail(&task_arc, format!("Failed to write chunk list: {}", e));
+        let _ = std::fs::remove_dir_all(&dir);
+        return;
+    }
+
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-f", "concat", "-safe", "0", "-i"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.push(list_path.to_string_lossy().to_string());
+    args.push("-i".to_string());
+    args.push(audio_file.to_string_lossy().to_string());
+    args.extend(["-map", "0:v", "-map", "1:a?", "-c", "copy"].iter().map(|s| s.to_string()));
+    let lower = job.output_file.to_lowercase();
+    if lower.ends_with(".mp4") || lower.ends_with(".mov") {
+        args.push("-movflags".to_string());
+        if job.options.metadata.as_ref().is_some_and(metadata::MetadataFields::has_custom_tags) {
+            args.push("+faststart+use_metadata_tags".to_string());
+        } else {
+            args.push("+faststart".to_string());
+        }
+    }
+    if job.options.strip_metadata {
+        args.extend(["-map_metadata", "-1", "-map_chapters", "-1", "-fflags", "+bitexact"].iter().map(|s| s.to_string()));
+    }
+    if let Some(ref fields) = job.options.metadata {
+        args.extend(fields.to_args());
+    }
+    args.extend(tracks::metadata_args(&job.options.track_metadata));
+    args.push(job.output_file.to_string());
+
+    {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.enter_phase(ConversionPhase::Finalizing);
+        task.progress.log.push("Joining chunks...".to_string());
+    }
+    let joined = run_part(&task_arc, &job, args, times, None).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    if let Err(e) = joined {
+        fail(&task_arc, format!("Failed to join chunks: {}", e));
+        return;
+    }
+
+    task_arc
+        .lock()
+        .expect("Failed to lock task mutex")
+        .progress
+        .enter_phase(ConversionPhase::Validating);
+    if let Some(problem) = validate_output(job.ffmpeg_path, job.output_file).await {
+        fail(&task_arc, format!("Conversion produced corrupt output: {}", problem));
+        return;
+    }
+
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    if task.cancel.is_cancelled() {
+        task.progress.status = ConversionStatus::Cancelled;
+    } else {
+        info!("Chunked conversion completed and validated for {}", job.input_file);
+        task.progress.status = ConversionStatus::Completed;
+        task.progress.percentage = 100.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffmpeg::{get_format_info, AudioEditOptions};
+
+    fn mkv() -> FormatInfo {
+        get_format_info("mkv")
+    }
+
+    #[test]
+    fn surround_copy_track_keeps_the_single_pass() {
+        let options = ConversionOptions {
+            media_server_preset: Some("media_server_h264".to_string()),
+            keep_surround_track: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ineligible_reason(&options, "libx264", &mkv(), false),
+            Some("the surround copy track is not chunked")
+        );
+        let options = ConversionOptions {
+            keep_surround_track: false,
+            ..options
+        };
+        assert_eq!(ineligible_reason(&options, "libx264", &mkv(), false), None);
+    }
+
+    #[test]
+    fn audio_edits_keep_the_single_pass() {
+        let options = ConversionOptions {
+            audio_edit: Some(AudioEditOptions {
+                gain_db: Some(3.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            ineligible_reason(&options, "libx264", &mkv(), false),
+            Some("audio edits need the full timeline")
+        );
+    }
+
+    fn job(options: &ConversionOptions) -> ChunkedJob<'_> {
+        ChunkedJob {
+            task_id: "test",
+            ffmpeg_path: "ffmpeg",
+            input_file: "in.mkv",
+            output_file: "out.mp4",
+            encoder: "libx264",
+            preset: "medium",
+            cpu_threads: None,
+            options,
+            media_preset: None,
+            audio_codec: "aac",
+            audio_adjustment: AudioAdjustment::default(),
+            clip_length: 120.0,
+        }
+    }
+
+    #[test]
+    fn audio_pass_fits_the_source_to_the_encoder() {
+        let options = ConversionOptions::default();
+        let job = ChunkedJob {
+            audio_adjustment: AudioAdjustment {
+                sample_rate: Some((192000, 96000)),
+                channels: None,
+            },
+            ..job(&options)
+        };
+        let args = audio_args(&job, Path::new("audio.mka"));
+        assert!(args.windows(2).any(|w| w == ["-ar", "96000"]));
+    }
+
+    #[test]
+    fn audio_pass_applies_the_audio_bitrate() {
+        let options = ConversionOptions {
+            audio_bitrate: Some(192),
+            ..Default::default()
+        };
+        let args = audio_args(&job(&options), Path::new("audio.mka"));
+        assert!(args.windows(2).any(|w| w == ["-b:a", "192k"]));
+    }
+
+    #[test]
+    fn short_inputs_get_fewer_chunks() {
+        assert_eq!(plan_chunks(50.0, 8), vec![(0.0, 50.0)]);
+        assert_eq!(plan_chunks(120.0, 4), vec![(0.0, 30.0), (30.0, 30.0), (60.0, 30.0), (90.0, 30.0)]);
+    }
+}
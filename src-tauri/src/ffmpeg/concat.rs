@@ -0,0 +1,287 @@
+use super::probe::{MediaInfo, MediaStream};
+use super::ratecontrol::RateControl;
+use super::{run_prepared_job, segments, ConversionTask, VideoInfo, PROGRESS_PIPE};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Sample rate and layout every input's audio is brought to before the
+/// concat filter, which needs them to match.
+const JOINED_SAMPLE_RATE: u32 = 48000;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ConcatMode {
+    /// Copy when the inputs match, re-encode otherwise.
+    #[default]
+    Auto,
+    /// Concat demuxer with stream copy; fails if the inputs differ.
+    Copy,
+    /// Concat filter, re-encoding everything to the first input's frame size.
+    Reencode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConcatOptions {
+    pub mode: ConcatMode,
+    /// Video encoder when re-encoding.
+    pub encoder: String,
+    pub preset: Option<String>,
+    #[serde(alias = "rateControl")]
+    pub rate_control: Option<RateControl>,
+}
+
+impl Default for ConcatOptions {
+    fn default() -> Self {
+        Self {
+            mode: ConcatMode::Auto,
+            encoder: "libx264".to_string(),
+            preset: None,
+            rate_control: None,
+        }
+    }
+}
+
+/// What has to match for a stream copy: the output keeps the first input's
+/// codec setup (e.g. the H.264 SPS/PPS in MP4's avcC), so a later input
+/// with another profile, format, rate or setup data plays back corrupt.
+#[derive(Debug, PartialEq)]
+struct CopyKey<'a> {
+    codec: &'a str,
+    profile: Option<&'a str>,
+    size: (Option<u32>, Option<u32>),
+    pixel_format: Option<&'a str>,
+    frame_rate: Option<String>,
+    time_base: Option<&'a str>,
+    sample_aspect: Option<(u32, u32)>,
+    audio: (Option<u32>, Option<u32>),
+    extradata: Option<&'a str>,
+}
+
+fn copy_keys<'a>(info: &'a MediaInfo, kind: &str) -> Vec<CopyKey<'a>> {
+    info.streams
+        .iter()
+        .filter(|s| s.kind == kind && !s.attached_pic)
+        .map(|s: &'a MediaStream| CopyKey {
+            codec: &s.codec,
+            profile: s.profile.as_deref(),
+            size: (s.width, s.height),
+            pixel_format: s.pixel_format.as_deref(),
+            frame_rate: s.frame_rate.map(|r| format!("{:.3}", r)),
+            time_base: s.time_base.as_deref(),
+            sample_aspect: s.sample_aspect,
+            audio: (s.sample_rate, s.channels),
+            extradata: s.extradata_hash.as_deref(),
+        })
+        .collect()
+}
+
+/// Why the inputs can't be joined by stream copy, or `None` when every
+/// stream parameter ffprobe shows matches the first input's.
+pub fn copy_blocker(inputs: &[MediaInfo]) -> Option<String> {
+    let first = inputs.first()?;
+    inputs.iter().enumerate().skip(1).find_map(|(index, info)| {
+        if copy_keys(info, "video") != copy_keys(first, "video") {
+            Some(format!(
+                "input {} differs in video codec, profile, size, pixel format, frame rate or codec setup",
+                index + 1
+            ))
+        } else if copy_keys(info, "audio") != copy_keys(first, "audio") {
+            Some(format!("input {} has different audio tracks", index + 1))
+        } else {
+            None
+        }
+    })
+}
+
+fn base_args() -> Vec<String> {
+    ["-y", "-hide_banner", "-progress", PROGRESS_PIPE, "-nostats"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn faststart(args: &mut Vec<String>, output_file: &str) {
+    let ext = Path::new(output_file)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if matches!(ext.as_str(), "mp4" | "mov" | "m4v") {
+        args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+    }
+}
+
+/// Lossless join through the concat demuxer list at `list_path`.
+pub fn copy_args(list_path: &Path, output_file: &str) -> Vec<String> {
+    let mut args = base_args();
+    args.extend(["-f", "concat", "-safe", "0", "-i"].iter().map(|s| s.to_string()));
+    args.push(list_path.to_string_lossy().to_string());
+    args.extend(["-map", "0:v?", "-map", "0:a?", "-c", "copy"].iter().map(|s| s.to_string()));
+    faststart(&mut args, output_file);
+    args.push(output_file.to_string());
+    args
+}
+
+/// Re-encoding join: every input is scaled and padded to the first one's
+/// frame size and its first audio track resampled to stereo, filling inputs
+/// without audio with silence, then the concat filter joins them. The
+/// silence needs the input's length, so an unknown one is an error.
+pub fn reencode_args(
+    files: &[String],
+    inputs: &[VideoInfo],
+    options: &ConcatOptions,
+    output_file: &str,
+) -> Result<Vec<String>, String> {
+    let first = inputs.first();
+    let width = first.and_then(|i| i.width).unwrap_or(1920) / 2 * 2;
+    let height = first.and_then(|i| i.height).unwrap_or(1080) / 2 * 2;
+    let has_audio = inputs.iter().any(|i| !i.audio_streams.is_empty());
+
+    let mut args = base_args();
+    for file in files {
+        args.extend(["-i".to_string(), file.clone()]);
+    }
+    let mut graph = Vec::new();
+    let mut pads = String::new();
+    for (index, info) in inputs.iter().enumerate() {
+        graph.push(format!(
+            "[{i}:v:0]scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,format=yuv420p[v{i}]",
+            i = index,
+            w = width,
+            h = height
+        ));
+        pads.push_str(&format!("[v{}]", index));
+        if has_audio {
+            let format = format!("aformat=sample_rates={}:channel_layouts=stereo", JOINED_SAMPLE_RATE);
+            graph.push(if info.audio_streams.is_empty() {
+                let duration = info
+                    .duration
+                    .filter(|d| *d > 0.0)
+                    .ok_or_else(|| format!("the length of input {} (which has no audio) is unknown", index + 1))?;
+                format!("anullsrc=r={}:cl=stereo:d={:.3},{}[a{}]", JOINED_SAMPLE_RATE, duration, format, index)
+            } else {
+                format!("[{}:a:0]aresample={},{}[a{}]", index, JOINED_SAMPLE_RATE, format, index)
+            });
+            pads.push_str(&format!("[a{}]", index));
+        }
+    }
+    graph.push(format!(
+        "{}concat=n={}:v=1:a={}[vout]{}",
+        pads,
+        inputs.len(),
+        u8::from(has_audio),
+        if has_audio { "[aout]" } else { "" }
+    ));
+    args.extend(["-filter_complex".to_string(), graph.join(";"), "-map".to_string(), "[vout]".to_string()]);
+    if has_audio {
+        args.extend(["-map".to_string(), "[aout]".to_string(), "-c:a".to_string(), "aac".to_string()]);
+    }
+    args.extend(["-c:v".to_string(), options.encoder.clone()]);
+    if let Some(ref preset) = options.preset {
+        args.extend(["-preset".to_string(), preset.clone()]);
+    }
+    args.extend(options.rate_control.iter().flat_map(|rc| rc.args(&options.encoder)));
+    faststart(&mut args, output_file);
+    args.push(output_file.to_string());
+    Ok(args)
+}
+
+/// A prepared join.
+pub struct ConcatJob {
+    pub args: Vec<String>,
+    /// Concat demuxer list of a copy join, removed when the job ends.
+    pub list_path: Option<PathBuf>,
+}
+
+pub async fn run(task_arc: Arc<Mutex<ConversionTask>>, job: ConcatJob) {
+    run_prepared_job(task_arc, job.args).await;
+    if let Some(path) = job.list_path {
+        if let Err(e) = std::fs::remove_file(&path) {
+            warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Write the concat demuxer list for a copy join of `files`.
+pub fn write_list(task_id: &str, files: &[String]) -> std::io::Result<PathBuf> {
+    let path = segments::concat_list_path(task_id);
+    segments::write_concat_list(files, &path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffmpeg::StreamInfo;
+
+    fn clip(codec: &str, height: u32, audio: bool) -> VideoInfo {
+        let stream = |codec: &str| StreamInfo {
+            index: 0,
+            codec: codec.to_string(),
+            language: None,
+            title: None,
+            default: false,
+            forced: false,
+            closed_captions: false,
+            sample_rate: None,
+            channels: None,
+            width: None,
+            height: None,
+        };
+        VideoInfo {
+            duration: Some(10.0),
+            width: Some(height * 16 / 9),
+            height: Some(height),
+            video_streams: vec![stream(codec)],
+            audio_streams: if audio { vec![stream("aac")] } else { Vec::new() },
+            subtitle_streams: Vec::new(),
+            sample_aspect: None,
+            display_aspect: None,
+        }
+    }
+
+    fn media(profile: &str, extradata: &str) -> MediaInfo {
+        let stream = |kind: &str, codec: &str| MediaStream {
+            kind: kind.to_string(),
+            codec: codec.to_string(),
+            ..Default::default()
+        };
+        MediaInfo {
+            streams: vec![
+                MediaStream {
+                    profile: Some(profile.to_string()),
+                    width: Some(1920),
+                    height: Some(1080),
+                    pixel_format: Some("yuv420p".to_string()),
+                    extradata_hash: Some(extradata.to_string()),
+                    ..stream("video", "h264")
+                },
+                MediaStream {
+                    sample_rate: Some(48000),
+                    channels: Some(2),
+                    ..stream("audio", "aac")
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn copies_matching_clips_and_reencodes_mixed_ones() {
+        assert_eq!(copy_blocker(&[media("High", "SHA256:a"), media("High", "SHA256:a")]), None);
+        assert!(copy_blocker(&[media("High", "SHA256:a"), media("Main", "SHA256:a")]).is_some());
+        assert!(copy_blocker(&[media("High", "SHA256:a"), media("High", "SHA256:b")]).is_some());
+
+        let mixed = [clip("h264", 1080, true), clip("hevc", 720, false)];
+        let files = ["a.mp4".to_string(), "b.mov".to_string()];
+        let args = reencode_args(&files, &mixed, &ConcatOptions::default(), "out.mp4").unwrap();
+        let graph = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        assert!(graph.starts_with("[0:v:0]scale=1920:1080:"));
+        assert!(graph.contains("anullsrc=r=48000:cl=stereo:d=10.000,"));
+        assert!(graph.ends_with("[v0][a0][v1][a1]concat=n=2:v=1:a=1[vout][aout]"));
+
+        let unknown = [clip("h264", 1080, true), VideoInfo { duration: None, ..clip("hevc", 720, false) }];
+        assert!(reencode_args(&files, &unknown, &ConcatOptions::default(), "out.mp4").is_err());
+    }
+}
@@ -0,0 +1,359 @@
+use serde::{Deserialize, Serialize};
+
+/// What the output has to hold; codecs are FFmpeg encoder or codec names.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ContainerNeeds {
+    #[serde(alias = "videoCodec")]
+    pub video_codec: Option<String>,
+    #[serde(alias = "audioCodec")]
+    pub audio_codec: Option<String>,
+    pub chapters: bool,
+    pub alpha: bool,
+    pub subtitles: bool,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ContainerProblem {
+    pub container: String,
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ContainerAdvice {
+    /// Best fitting extension; `None` when no container holds everything.
+    pub recommended: Option<String>,
+    /// Every fitting extension, best first.
+    pub compatible: Vec<String>,
+    pub incompatible: Vec<ContainerProblem>,
+    /// Problems no container choice fixes, e.g. a codec without alpha.
+    pub warnings: Vec<String>,
+}
+
+struct Container {
+    ext: &'static str,
+    video: &'static [&'static str],
+    audio: &'static [&'static str],
+    chapters: bool,
+    subtitles: bool,
+    alpha: bool,
+}
+
+/// Candidates in order of preference: widely playable first, MOV ahead of
+/// Matroska because editors expect intermediate codecs in it.
+const CONTAINERS: &[Container] = &[
+    Container {
+        ext: "mp4",
+        video: &["h264", "hevc", "av1", "vp9", "mpeg4", "mpeg2video"],
+        audio: &["aac", "mp3", "ac3", "eac3", "opus", "flac", "alac"],
+        chapters: true,
+        subtitles: true,
+        alpha: false,
+    },
+    Container {
+        ext: "mov",
+        video: &["h264", "hevc", "prores", "dnxhd", "mpeg4", "mpeg2video", "qtrle", "png"],
+        audio: &["aac", "mp3", "ac3", "alac", "pcm"],
+        chapters: true,
+        subtitles: true,
+        alpha: true,
+    },
+    Container {
+        ext: "mkv",
+        video: &["h264", "hevc", "av1", "vp9", "vp8", "mpeg4", "mpeg2video", "prores", "dnxhd", "ffv1", "theora"],
+        audio: &["aac", "mp3", "ac3", "eac3", "opus", "vorbis", "flac", "alac", "pcm", "mp2", "dts", "truehd"],
+        chapters: true,
+        subtitles: true,
+        alpha: true,
+    },
+    Container {
+        ext: "webm",
+        video: &["vp9", "vp8", "av1"],
+        audio: &["opus", "vorbis"],
+        chapters: true,
+        subtitles: true,
+        alpha: true,
+    },
+    Container {
+        ext: "m4v",
+        video: &["h264", "hevc", "mpeg4"],
+        audio: &["aac", "ac3", "alac"],
+        chapters: true,
+        subtitles: true,
+        alpha: false,
+    },
+    Container {
+        ext: "avi",
+        video: &["h264", "mpeg4", "mpeg2video", "ffv1"],
+        audio: &["mp3", "ac3", "pcm"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "mts",
+        video: &["h264", "hevc", "mpeg2video"],
+        audio: &["aac", "ac3", "eac3", "mp2"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "mpg",
+        video: &["mpeg2video"],
+        audio: &["mp2", "ac3", "mp3", "pcm"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "vob",
+        video: &["mpeg2video"],
+        audio: &["ac3", "mp2", "pcm"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "flv",
+        video: &["h264"],
+        audio: &["aac", "mp3"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "3gp",
+        video: &["h264", "mpeg4"],
+        audio: &["aac"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "wmv",
+        video: &["wmv2"],
+        audio: &["wmav2", "mp3"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "ogv",
+        video: &["theora", "vp8"],
+        audio: &["vorbis", "opus", "flac"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "m4a",
+        video: &[],
+        audio: &["aac", "alac"],
+        chapters: true,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "mp3",
+        video: &[],
+        audio: &["mp3"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "flac",
+        video: &[],
+        audio: &["flac"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "ogg",
+        video: &[],
+        audio: &["vorbis", "opus", "flac"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "wav",
+        video: &[],
+        audio: &["pcm"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+    Container {
+        ext: "aac",
+        video: &[],
+        audio: &["aac"],
+        chapters: false,
+        subtitles: false,
+        alpha: false,
+    },
+];
+
+/// Codecs with an alpha channel (ProRes only in its 4444 profiles).
+const ALPHA_CODECS: &[&str] = &["prores", "vp8", "vp9", "ffv1", "png", "qtrle"];
+
+/// Codec an encoder produces: `h264_nvenc` -> `h264`, `libopus` -> `opus`.
+fn codec_family(codec: &str) -> String {
+    let codec = codec.trim().to_lowercase();
+    let family = match codec.as_str() {
+        "libx264" | "h264" | "avc" => "h264",
+        "libx265" | "hevc" | "h265" => "hevc",
+        "libaom-av1" | "libsvtav1" | "librav1e" | "av1" => "av1",
+        "libvpx-vp9" | "vp9" => "vp9",
+        "libvpx" | "vp8" => "vp8",
+        "libtheora" | "theora" => "theora",
+        "prores_ks" | "prores_aw" | "prores" => "prores",
+        "libmp3lame" | "mp3" => "mp3",
+        "libopus" | "opus" => "opus",
+        "libvorbis" | "vorbis" => "vorbis",
+        "aac" | "libfdk_aac" => "aac",
+        c if c.starts_with("pcm_") => "pcm",
+        // Hardware encoders are named after their codec.
+        c => return c.split('_').next().unwrap_or(c).to_string(),
+    };
+    family.to_string()
+}
+
+fn is_known(family: &str) -> bool {
+    CONTAINERS
+        .iter()
+        .any(|c| c.video.contains(&family) || c.audio.contains(&family))
+}
+
+/// Rank the containers for `needs`, explaining why the others don't fit.
+pub fn suggest(needs: &ContainerNeeds) -> ContainerAdvice {
+    let video = needs.video_codec.as_deref().filter(|c| !c.trim().is_empty()).map(codec_family);
+    let audio = needs.audio_codec.as_deref().filter(|c| !c.trim().is_empty()).map(codec_family);
+
+    let mut warnings = Vec::new();
+    for family in video.iter().chain(audio.iter()).filter(|f| !is_known(f)) {
+        warnings.push(format!("Unknown codec {}; check the container yourself", family));
+    }
+    if needs.alpha {
+        match video {
+            Some(ref v) if !ALPHA_CODECS.contains(&v.as_str()) => {
+                warnings.push(format!("{} has no alpha channel; use ProRes 4444, VP9 or FFV1", v))
+            }
+            None => warnings.push("Alpha needs a video codec".to_string()),
+            _ => {}
+        }
+    }
+
+    let mut compatible = Vec::new();
+    let mut incompatible = Vec::new();
+    for container in CONTAINERS {
+        let mut reasons = Vec::new();
+        match video {
+            Some(ref v) if container.video.is_empty() => reasons.push(format!("audio-only, can't hold {} video", v)),
+            Some(ref v) if is_known(v) && !container.video.contains(&v.as_str()) => {
+                reasons.push(format!("can't hold {} video", v))
+            }
+            None if !container.video.is_empty() && audio.is_some() => {
+                // Audio-only jobs belong in an audio container.
+                reasons.push("video container for an audio-only output".to_string())
+            }
+            _ => {}
+        }
+        if let Some(ref a) = audio {
+            if is_known(a) && !container.audio.contains(&a.as_str()) {
+                reasons.push(format!("can't hold {} audio", a));
+            }
+        }
+        if needs.chapters && !container.chapters {
+            reasons.push("no chapter support".to_string());
+        }
+        if needs.subtitles && !container.subtitles {
+            reasons.push("no soft subtitle support".to_string());
+        }
+        if needs.alpha && !container.alpha {
+            reasons.push("no alpha channel support".to_string());
+        }
+
+        if reasons.is_empty() {
+            compatible.push(container.ext.to_string());
+        } else {
+            incompatible.push(ContainerProblem {
+                container: container.ext.to_string(),
+                reasons,
+            });
+        }
+    }
+
+    ContainerAdvice {
+        recommended: compatible.first().cloned(),
+        compatible,
+        incompatible,
+        warnings,
+    }
+}
+
+/// Why streams with these codecs (as FFmpeg reports them) can't be copied
+/// into an `ext` file unchanged; `None` when they can.
+pub fn copy_blocker(ext: &str, video_codec: Option<&str>, audio_codecs: &[&str]) -> Option<String> {
+    let ext = match ext {
+        "mpeg" => "mpg",
+        "m2ts" => "mts",
+        other => other,
+    };
+    let Some(container) = CONTAINERS.iter().find(|c| c.ext == ext) else {
+        return Some(format!("{} isn't a known container", ext));
+    };
+    if let Some(codec) = video_codec {
+        if !container.video.contains(&codec_family(codec).as_str()) {
+            return Some(format!("{} video doesn't fit {}", codec, ext));
+        }
+    }
+    audio_codecs
+        .iter()
+        .find(|codec| !container.audio.contains(&codec_family(codec).as_str()))
+        .map(|codec| format!("{} audio doesn't fit {}", codec, ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steers_codecs_to_fitting_containers() {
+        let prores = suggest(&ContainerNeeds {
+            video_codec: Some("prores_ks".to_string()),
+            audio_codec: Some("pcm_s16le".to_string()),
+            alpha: true,
+            ..Default::default()
+        });
+        assert_eq!(prores.recommended.as_deref(), Some("mov"));
+        assert_eq!(prores.compatible, ["mov", "mkv"]);
+        let mp4 = prores.incompatible.iter().find(|p| p.container == "mp4").unwrap();
+        assert!(mp4.reasons.contains(&"can't hold prores video".to_string()));
+
+        let vp9 = suggest(&ContainerNeeds {
+            video_codec: Some("vp9_qsv".to_string()),
+            audio_codec: Some("libopus".to_string()),
+            subtitles: true,
+            ..Default::default()
+        });
+        assert_eq!(vp9.compatible, ["mp4", "mkv", "webm"]);
+        assert!(vp9.warnings.is_empty());
+
+        let audio = suggest(&ContainerNeeds {
+            audio_codec: Some("flac".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(audio.recommended.as_deref(), Some("flac"));
+    }
+
+    #[test]
+    fn copies_only_codecs_the_container_holds() {
+        assert_eq!(copy_blocker("mp4", Some("h264"), &["aac", "ac3"]), None);
+        assert_eq!(copy_blocker("mp4", Some("h264"), &["dts"]).as_deref(), Some("dts audio doesn't fit mp4"));
+        assert_eq!(copy_blocker("m2ts", Some("hevc"), &["eac3"]), None);
+        assert!(copy_blocker("avi", Some("vp9"), &[]).is_some());
+    }
+}
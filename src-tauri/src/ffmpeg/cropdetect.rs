@@ -0,0 +1,122 @@
+use super::watchdog;
+use log::{info, warn};
+use regex::Regex;
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Seconds analysed at each sample point.
+const SAMPLE_SECS: &str = "2";
+/// Bars thinner than this share of the frame are left alone; encoders and
+/// scalers leave a few dark edge lines on many full-frame sources.
+const MIN_BAR_SHARE: f64 = 0.02;
+
+/// Picture area inside the black bars, in source pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropBox {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl CropBox {
+    pub fn filter(&self) -> String {
+        format!("crop={}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Every `crop=W:H:X:Y` suggestion `cropdetect` logged.
+pub fn parse_crops(stderr: &str) -> Vec<CropBox> {
+    let regex = Regex::new(r"crop=(\d+):(\d+):(\d+):(\d+)").expect("Invalid regex");
+    regex
+        .captures_iter(stderr)
+        .filter_map(|caps| {
+            Some(CropBox {
+                width: caps[1].parse().ok()?,
+                height: caps[2].parse().ok()?,
+                x: caps[3].parse().ok()?,
+                y: caps[4].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Smallest box holding every suggestion, so a dark scene at one sample
+/// point can't crop away picture the others show.
+pub fn union(boxes: &[CropBox]) -> Option<CropBox> {
+    let left = boxes.iter().map(|b| b.x).min()?;
+    let top = boxes.iter().map(|b| b.y).min()?;
+    let right = boxes.iter().map(|b| b.x + b.width).max()?;
+    let bottom = boxes.iter().map(|b| b.y + b.height).max()?;
+    Some(CropBox {
+        width: right - left,
+        height: bottom - top,
+        x: left,
+        y: top,
+    })
+}
+
+/// The crop when it removes real bars from a `width`x`height` frame.
+pub fn letterbox(boxes: &[CropBox], width: u32, height: u32) -> Option<CropBox> {
+    let crop = union(boxes)?;
+    let removed = |kept: u32, full: u32| full > 0 && 1.0 - kept as f64 / full as f64 >= MIN_BAR_SHARE;
+    let fits = crop.width > 0 && crop.height > 0 && crop.x + crop.width <= width && crop.y + crop.height <= height;
+    (fits && (removed(crop.width, width) || removed(crop.height, height))).then_some(crop)
+}
+
+/// Sample start times spread over the middle of the input, skipping
+/// openings and credits that are often black or differently framed.
+fn sample_points(duration: Option<f64>) -> Vec<f64> {
+    match duration.filter(|d| *d > 20.0) {
+        Some(duration) => [0.2, 0.4, 0.6, 0.8].iter().map(|share| duration * share).collect(),
+        None => vec![0.0],
+    }
+}
+
+/// Run `cropdetect` at a few points of `input_file`; `None` when the frame
+/// has no bars worth removing or detection failed.
+pub async fn detect(ffmpeg_path: &str, input_file: &str, duration: Option<f64>, width: u32, height: u32) -> Option<CropBox> {
+    let mut boxes = Vec::new();
+    for start in sample_points(duration) {
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.args(["-hide_banner", "-nostats", "-ss", &format!("{:.3}", start), "-i", input_file])
+            .args(["-t", SAMPLE_SECS, "-map", "0:v:0", "-vf", "cropdetect=limit=24:round=2:reset=0", "-an", "-sn"])
+            .args(["-f", "null", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        match watchdog::output(&mut cmd).await {
+            // With `reset=0` the last suggestion covers the whole sample.
+            Ok(output) => boxes.extend(parse_crops(&String::from_utf8_lossy(&output.stderr)).last()),
+            Err(e) => warn!("Black bar detection failed at {:.1}s of {}: {}", start, input_file, e),
+        }
+    }
+    let crop = letterbox(&boxes, width, height);
+    if let Some(crop) = crop {
+        info!("Detected black bars in {}: {}x{} picture at {},{}", input_file, crop.width, crop.height, crop.x, crop.y);
+    }
+    crop
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_picture_seen_at_any_sample() {
+        let stderr = "[Parsed_cropdetect_0 @ 0x1] x1:0 x2:1919 y1:140 y2:939 w:1920 h:800 x:0 y:140 pts:1 t:0.04 crop=1920:800:0:140\n\
+                      [Parsed_cropdetect_0 @ 0x1] x1:0 x2:1919 y1:132 y2:947 w:1920 h:816 x:0 y:132 pts:2 t:0.08 crop=1920:816:0:132";
+        let boxes = parse_crops(stderr);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(letterbox(&boxes, 1920, 1080).unwrap().filter(), "crop=1920:816:0:132");
+
+        let full = CropBox { width: 1920, height: 1072, x: 0, y: 4 };
+        assert_eq!(letterbox(&[full], 1920, 1080), None);
+    }
+}
@@ -0,0 +1,66 @@
+use super::FfmpegDownloader;
+use log::{info, warn};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// File inside the app data directory listing codecs whose hardware decode failed.
+const BLACKLIST_FILE: &str = "decoder_blacklist.json";
+
+static BLACKLIST: Mutex<Option<BTreeSet<String>>> = Mutex::new(None);
+
+fn blacklist_path() -> Option<PathBuf> {
+    FfmpegDownloader::get_ffmpeg_app_dir()
+        .ok()
+        .map(|dir| dir.join(BLACKLIST_FILE))
+}
+
+fn load() -> BTreeSet<String> {
+    blacklist_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn with_blacklist<T>(f: impl FnOnce(&mut BTreeSet<String>) -> T) -> T {
+    let mut guard = BLACKLIST.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(load))
+}
+
+fn save(codecs: &BTreeSet<String>) {
+    let Some(path) = blacklist_path() else {
+        return;
+    };
+    let result = serde_json::to_string_pretty(codecs)
+        .map_err(|e| e.to_string())
+        .and_then(|content| std::fs::write(&path, content).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Failed to save decoder blacklist {:?}: {}", path, e);
+    }
+}
+
+/// Whether hardware decoding of `codec` failed before and should be skipped.
+pub fn is_blacklisted(codec: &str) -> bool {
+    with_blacklist(|codecs| codecs.contains(codec))
+}
+
+/// Remember that hardware decoding of `codec` fails on this machine.
+pub fn blacklist(codec: &str) {
+    with_blacklist(|codecs| {
+        if codecs.insert(codec.to_string()) {
+            info!("Hardware decoding of {} failed; using software decode from now on", codec);
+            save(codecs);
+        }
+    });
+}
+
+pub fn list() -> Vec<String> {
+    with_blacklist(|codecs| codecs.iter().cloned().collect())
+}
+
+pub fn clear() {
+    with_blacklist(|codecs| {
+        codecs.clear();
+        save(codecs);
+    });
+}
@@ -0,0 +1,309 @@
+use log::debug;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// MPLS timestamps are expressed in 45 kHz ticks.
+const MPLS_TICKS_PER_SECOND: f64 = 45_000.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DiscKind {
+    Dvd,
+    Bluray,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscTitle {
+    pub id: u32,
+    pub name: String,
+    pub files: Vec<String>,
+    pub size_bytes: u64,
+    pub duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscInfo {
+    pub kind: DiscKind,
+    pub root: String,
+    pub titles: Vec<DiscTitle>,
+    pub main_title: Option<u32>,
+}
+
+impl DiscInfo {
+    /// Pick the requested title, or the main title when none is given.
+    pub fn title(&self, id: Option<u32>) -> Option<&DiscTitle> {
+        let id = id.or(self.main_title)?;
+        self.titles.iter().find(|t| t.id == id)
+    }
+}
+
+/// Build an FFmpeg `concat:` protocol URL joining the title's segments.
+/// VOB and M2TS are both byte-concatenable transport/program streams.
+pub fn concat_url(title: &DiscTitle) -> String {
+    format!("concat:{}", title.files.join("|"))
+}
+
+/// Find a child entry by name, ignoring case (discs are often mastered in upper case).
+fn find_child(dir: &Path, name: &str) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .find(|e| e.file_name().to_string_lossy().eq_ignore_ascii_case(name))
+        .map(|e| e.path())
+}
+
+fn dir_name_is(path: &Path, name: &str) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy().eq_ignore_ascii_case(name))
+        .unwrap_or(false)
+}
+
+/// Detect a DVD (`VIDEO_TS`) or Blu-ray (`BDMV`) folder structure at `path`.
+/// Accepts either the structure folder itself or the disc root containing it.
+pub fn scan_disc(path: &Path) -> Option<DiscInfo> {
+    if !path.is_dir() {
+        return None;
+    }
+
+    let video_ts = if dir_name_is(path, "VIDEO_TS") {
+        Some(path.to_path_buf())
+    } else {
+        find_child(path, "VIDEO_TS").filter(|p| p.is_dir())
+    };
+    if let Some(video_ts) = video_ts {
+        return Some(scan_dvd(&video_ts));
+    }
+
+    let bdmv = if dir_name_is(path, "BDMV") {
+        Some(path.to_path_buf())
+    } else {
+        find_child(path, "BDMV").filter(|p| p.is_dir())
+    };
+    bdmv.map(|bdmv| scan_bluray(&bdmv))
+}
+
+/// DVD titles are grouped by title set: `VTS_01_1.VOB`, `VTS_01_2.VOB`, ...
+/// (`VTS_xx_0.VOB` holds the menus and is skipped). The main title is the
+/// largest title set.
+fn scan_dvd(video_ts: &Path) -> DiscInfo {
+    let vob_regex = Regex::new(r"(?i)^VTS_(\d{2})_([1-9])\.VOB$").expect("Invalid regex");
+    let mut title_sets: BTreeMap<u32, Vec<(u32, PathBuf, u64)>> = BTreeMap::new();
+
+    if let Ok(entries) = std::fs::read_dir(video_ts) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(caps) = vob_regex.captures(&name) {
+                let set: u32 = caps[1].parse().unwrap_or(0);
+                let part: u32 = caps[2].parse().unwrap_or(0);
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                title_sets.entry(set).or_default().push((part, entry.path(), size));
+            }
+        }
+    }
+
+    let titles: Vec<DiscTitle> = title_sets
+        .into_iter()
+        .map(|(set, mut parts)| {
+            parts.sort_by_key(|(part, _, _)| *part);
+            DiscTitle {
+                id: set,
+                name: format!("VTS_{:02}", set),
+                size_bytes: parts.iter().map(|(_, _, size)| size).sum(),
+                files: parts
+                    .into_iter()
+                    .map(|(_, path, _)| path.to_string_lossy().to_string())
+                    .collect(),
+                duration: None,
+            }
+        })
+        .collect();
+
+    let main_title = titles.iter().max_by_key(|t| t.size_bytes).map(|t| t.id);
+
+    DiscInfo {
+        kind: DiscKind::Dvd,
+        root: video_ts.to_string_lossy().to_string(),
+        titles,
+        main_title,
+    }
+}
+
+/// Blu-ray titles come from `BDMV/PLAYLIST/*.mpls`, each referencing an ordered
+/// list of clips in `BDMV/STREAM`. The main title is the longest playlist.
+fn scan_bluray(bdmv: &Path) -> DiscInfo {
+    let stream_dir = find_child(bdmv, "STREAM").unwrap_or_else(|| bdmv.join("STREAM"));
+    let mut titles = Vec::new();
+
+    if let Some(playlist_dir) = find_child(bdmv, "PLAYLIST") {
+        let mut playlists: Vec<PathBuf> = std::fs::read_dir(&playlist_dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        p.extension()
+                            .map(|e| e.to_string_lossy().eq_ignore_ascii_case("mpls"))
+                            .unwrap_or(false)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        playlists.sort();
+
+        for playlist in playlists {
+            let Ok(data) = std::fs::read(&playlist) else {
+                continue;
+            };
+            let Some(items) = parse_mpls(&data) else {
+                debug!("Skipping unreadable playlist: {:?}", playlist);
+                continue;
+            };
+
+            let mut files = Vec::new();
+            let mut size_bytes = 0;
+            for (clip, _) in &items {
+                let clip_path = find_child(&stream_dir, &format!("{}.m2ts", clip))
+                    .unwrap_or_else(|| stream_dir.join(format!("{}.m2ts", clip)));
+                if let Ok(meta) = std::fs::metadata(&clip_path) {
+                    size_bytes += meta.len();
+                    files.push(clip_path.to_string_lossy().to_string());
+                }
+            }
+            if files.is_empty() {
+                continue;
+            }
+
+            let stem = playlist
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            titles.push(DiscTitle {
+                id: stem.parse().unwrap_or(titles.len() as u32),
+                name: format!("{}.mpls", stem),
+                files,
+                size_bytes,
+                duration: Some(items.iter().map(|(_, d)| d).sum()),
+            });
+        }
+    }
+
+    // Without readable playlists, fall back to one title per stream file.
+    if titles.is_empty() {
+        if let Ok(entries) = std::fs::read_dir(&stream_dir) {
+            let mut clips: Vec<_> = entries
+                .flatten()
+                .filter(|e| {
+                    e.path()
+                        .extension()
+                        .map(|x| x.to_string_lossy().eq_ignore_ascii_case("m2ts"))
+                        .unwrap_or(false)
+                })
+                .collect();
+            clips.sort_by_key(|e| e.file_name());
+            for (index, entry) in clips.into_iter().enumerate() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                titles.push(DiscTitle {
+                    id: name
+                        .split('.')
+                        .next()
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(index as u32),
+                    name,
+                    files: vec![entry.path().to_string_lossy().to_string()],
+                    size_bytes: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                    duration: None,
+                });
+            }
+        }
+    }
+
+    let main_title = titles
+        .iter()
+        .max_by(|a, b| {
+            let left = (a.duration.unwrap_or(0.0), a.size_bytes);
+            let right = (b.duration.unwrap_or(0.0), b.size_bytes);
+            left.partial_cmp(&right).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|t| t.id);
+
+    DiscInfo {
+        kind: DiscKind::Bluray,
+        root: bdmv.to_string_lossy().to_string(),
+        titles,
+        main_title,
+    }
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Parse a Blu-ray `.mpls` playlist into `(clip name, duration seconds)` play items.
+pub fn parse_mpls(data: &[u8]) -> Option<Vec<(String, f64)>> {
+    if data.get(0..4)? != b"MPLS" {
+        return None;
+    }
+
+    let playlist_start = read_u32(data, 8)? as usize;
+    let item_count = read_u16(data, playlist_start + 6)?;
+    let mut pos = playlist_start + 10;
+    let mut items = Vec::with_capacity(item_count as usize);
+
+    for _ in 0..item_count {
+        let length = read_u16(data, pos)? as usize;
+        let clip = std::str::from_utf8(data.get(pos + 2..pos + 7)?).ok()?.to_string();
+        let in_time = read_u32(data, pos + 14)?;
+        let out_time = read_u32(data, pos + 18)?;
+        let duration = out_time.saturating_sub(in_time) as f64 / MPLS_TICKS_PER_SECOND;
+        items.push((clip, duration));
+        pos += 2 + length;
+    }
+
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_item(clip: &str, in_time: u32, out_time: u32) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(clip.as_bytes());
+        body.extend_from_slice(b"M2TS");
+        body.extend_from_slice(&[0, 1, 0]);
+        body.extend_from_slice(&in_time.to_be_bytes());
+        body.extend_from_slice(&out_time.to_be_bytes());
+        let mut item = (body.len() as u16).to_be_bytes().to_vec();
+        item.extend(body);
+        item
+    }
+
+    #[test]
+    fn parses_mpls_play_items() {
+        let mut data = b"MPLS0200".to_vec();
+        data.extend_from_slice(&20u32.to_be_bytes());
+        data.resize(20, 0);
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&2u16.to_be_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend(play_item("00001", 0, 45_000 * 60));
+        data.extend(play_item("00002", 45_000, 45_000 * 31));
+
+        let items = parse_mpls(&data).expect("valid playlist");
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0], ("00001".to_string(), 60.0));
+        assert_eq!(items[1], ("00002".to_string(), 30.0));
+    }
+
+    #[test]
+    fn rejects_non_mpls_data() {
+        assert!(parse_mpls(b"NOPE0200").is_none());
+    }
+}
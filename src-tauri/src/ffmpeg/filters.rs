@@ -0,0 +1,651 @@
+use super::{loudness, safe_mode, telemetry};
+use super::{AspectMode, AudioEditOptions, ConversionOptions, FitOptions, ReframeKeyframe, ReframeOptions, OverlayCorner, OverlayOptions, TimelapseOptions};
+
+/// Format a number of seconds the way FFmpeg filter options expect it.
+fn secs(value: f64) -> String {
+    format!("{:.3}", value.max(0.0))
+}
+
+/// Backslash-escape `special` characters in `value`.
+fn escape_chars(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape a file path for use as a filter option inside `-vf`. Paths are
+/// escaped once for the option parser and once for the filtergraph parser.
+pub fn escape_filter_path(path: &str) -> String {
+    let normalized = path.replace('\\', "/");
+    let option_level = escape_chars(&normalized, &['\'', ':']);
+    escape_chars(&option_level, &['\'', '[', ']', ',', ';'])
+}
+
+/// Subtitle burn-in filter, if a subtitle file or embedded track was chosen.
+fn subtitle_filter(options: &ConversionOptions, input_file: &str) -> Option<String> {
+    let mut filter = if let Some(ref file) = options.subtitle_file {
+        format!("subtitles={}", escape_filter_path(file))
+    } else {
+        let stream = options.subtitle_stream?;
+        format!("subtitles={}:si={}", escape_filter_path(input_file), stream)
+    };
+
+    if let Some(ref fonts_dir) = options.fonts_dir {
+        filter.push_str(&format!(":fontsdir={}", escape_filter_path(fonts_dir)));
+    }
+
+    // With `-ss` before `-i` timestamps restart at zero, so shift them back
+    // while rendering subtitles to keep them in sync with the trimmed clip.
+    if let Some(start) = options.trim_start.filter(|s| *s > 0.0) {
+        filter = format!(
+            "setpts=PTS+{}/TB,{},setpts=PTS-STARTPTS",
+            secs(start),
+            filter
+        );
+    }
+
+    Some(filter)
+}
+
+/// Length of the exported clip once trimming is applied, if it can be known.
+pub fn clip_length(options: &ConversionOptions, source_duration: Option<f64>) -> Option<f64> {
+    if let Some(duration) = options.trim_duration {
+        return Some(duration);
+    }
+    source_duration.map(|d| (d - options.trim_start.unwrap_or(0.0)).max(0.0))
+}
+
+/// Build fade in/out filters for either the video (`fade`) or audio (`afade`) chain.
+/// Fade-out needs the clip length; it is skipped when that is unknown.
+fn fade_filters(name: &str, options: &ConversionOptions, clip_length: Option<f64>) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    if let Some(fade_in) = options.fade_in.filter(|d| *d > 0.0) {
+        filters.push(format!("{}=t=in:st=0:d={}", name, secs(fade_in)));
+    }
+
+    if let (Some(fade_out), Some(length)) = (options.fade_out.filter(|d| *d > 0.0), clip_length) {
+        let fade_out = fade_out.min(length);
+        filters.push(format!(
+            "{}=t=out:st={}:d={}",
+            name,
+            secs(length - fade_out),
+            secs(fade_out)
+        ));
+    }
+
+    filters
+}
+
+/// Pixel aspect handling: an explicit display aspect, or square pixels for
+/// anamorphic sources unless their SAR is to be preserved.
+fn aspect_filters(options: &ConversionOptions) -> Vec<String> {
+    if let Some(ref dar) = options.display_aspect {
+        return vec![format!("setdar={}", dar.replace(':', "/"))];
+    }
+    match options.source_sar {
+        Some((num, den)) if num > 0 && den > 0 && num != den && options.aspect_mode == AspectMode::SquarePixels => {
+            vec!["scale=trunc(iw*sar/2)*2:ih".to_string(), "setsar=1".to_string()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Parse a `W:H` (or `W/H`) aspect ratio.
+pub fn parse_aspect(value: &str) -> Option<(u32, u32)> {
+    let (w, h) = value.trim().split_once([':', '/'])?;
+    let (w, h): (u32, u32) = (w.trim().parse().ok()?, h.trim().parse().ok()?);
+    (w > 0 && h > 0).then_some((w, h))
+}
+
+/// Crop position expression (0-1) interpolating between keyframes over `t`.
+fn keyframe_position(keyframes: &[ReframeKeyframe], position: fn(&ReframeKeyframe) -> f64) -> String {
+    let mut sorted = keyframes.to_vec();
+    sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+    let Some(last) = sorted.last() else {
+        return "0.5".to_string();
+    };
+    let mut expr = format!("{:.4}", position(last).clamp(0.0, 1.0));
+    for pair in sorted.windows(2).rev() {
+        let (from, to) = (&pair[0], &pair[1]);
+        let (p0, p1) = (position(from).clamp(0.0, 1.0), position(to).clamp(0.0, 1.0));
+        let span = (to.time - from.time).max(0.001);
+        expr = format!(
+            "if(lt(t,{}),{:.4}+{:.4}*(t-{})/{},{})",
+            secs(to.time),
+            p0,
+            p1 - p0,
+            secs(from.time),
+            secs(span),
+            expr
+        );
+    }
+    let first = &sorted[0];
+    format!("if(lt(t,{}),{:.4},{})", secs(first.time), position(first).clamp(0.0, 1.0), expr)
+}
+
+/// Crop to the target aspect ratio at a fixed or keyframed position.
+fn reframe_filter(reframe: &ReframeOptions) -> Option<String> {
+    let (w, h) = parse_aspect(&reframe.aspect)?;
+    let (x, y) = if reframe.keyframes.is_empty() {
+        (format!("{:.4}", reframe.x.clamp(0.0, 1.0)), format!("{:.4}", reframe.y.clamp(0.0, 1.0)))
+    } else {
+        (keyframe_position(&reframe.keyframes, |k| k.x), keyframe_position(&reframe.keyframes, |k| k.y))
+    };
+    // Even crop sizes keep 4:2:0 chroma subsampling happy.
+    Some(format!(
+        "crop=w='trunc(min(iw,ih*{w}/{h})/2)*2':h='trunc(min(ih,iw*{h}/{w})/2)*2':x='(iw-ow)*({x})':y='(ih-oh)*({y})'",
+        w = w,
+        h = h,
+        x = x,
+        y = y
+    ))
+}
+
+/// Scale into the target frame keeping the aspect ratio, then pad to it.
+fn fit_filters(fit: &FitOptions) -> Vec<String> {
+    let (width, height) = (fit.width, fit.height);
+    vec![
+        format!("scale={}:{}:force_original_aspect_ratio=decrease", width, height),
+        format!(
+            "pad={}:{}:(ow-iw)/2:(oh-ih)/2:color={}",
+            width,
+            height,
+            fit.color.as_deref().unwrap_or("black")
+        ),
+        "setsar=1".to_string(),
+    ]
+}
+
+/// Frame size `video_filter_chain` turns a `source`-sized input into, so
+/// clips joined to the output can be scaled to match.
+pub fn output_size(options: &ConversionOptions, source: (u32, u32)) -> (u32, u32) {
+    let even = |value: f64| ((value / 2.0).trunc() * 2.0).max(2.0) as u32;
+    let (mut width, mut height) = source;
+    if let Some(crop) = options.letterbox_crop {
+        (width, height) = (crop.width, crop.height);
+    }
+    if options.display_aspect.is_none() && options.aspect_mode == AspectMode::SquarePixels {
+        if let Some((num, den)) = options.source_sar.filter(|(num, den)| *num > 0 && *den > 0 && num != den) {
+            width = even(width as f64 * num as f64 / den as f64);
+        }
+    }
+    if let Some((w, h)) = options.reframe.as_ref().and_then(|r| parse_aspect(&r.aspect)) {
+        // Same sizes as the `reframe_filter` crop.
+        let (w, h) = (w as f64, h as f64);
+        (width, height) = (
+            even((width as f64).min(height as f64 * w / h)),
+            even((height as f64).min(width as f64 * h / w)),
+        );
+    }
+    if let Some(ref fit) = options.fit {
+        (width, height) = (fit.width, fit.height);
+    }
+    if options.safe_mode {
+        let scale = (1920.0 / width as f64).min(1080.0 / height as f64).min(1.0);
+        (width, height) = (even(width as f64 * scale), even(height as f64 * scale));
+    }
+    (width, height)
+}
+
+/// Full `-vf` chain for the job, in the order FFmpeg should apply it.
+pub fn video_filter_chain(
+    options: &ConversionOptions,
+    input_file: &str,
+    clip_length: Option<f64>,
+) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    // Only frames flagged as interlaced are touched, so this is safe to
+    // leave on for mixed progressive/interlaced disc sources.
+    if options.deinterlace == Some(true) {
+        filters.push("yadif=deint=interlaced".to_string());
+    }
+
+    filters.extend(options.letterbox_crop.map(|crop| crop.filter()));
+    filters.extend(aspect_filters(options));
+    filters.extend(options.reframe.as_ref().and_then(reframe_filter));
+    if let Some(ref fit) = options.fit {
+        filters.extend(fit_filters(fit));
+    }
+
+    filters.extend(subtitle_filter(options, input_file));
+    filters.extend(options.telemetry.as_ref().and_then(telemetry::overlay_filter));
+
+    // Fades are applied after the speed-up, so they work on output time.
+    let mut output_length = clip_length;
+    if let Some(ref timelapse) = options.timelapse {
+        filters.extend(timelapse_filters(timelapse));
+        output_length = clip_length.map(|l| l / timelapse.speed_factor());
+    }
+
+    filters.extend(fade_filters("fade", options, output_length));
+    if options.safe_mode {
+        filters.extend(safe_mode::VIDEO_FILTERS.iter().map(|f| f.to_string()));
+    }
+    filters
+}
+
+/// Frame decimation for timelapse jobs, plus optional deflicker.
+fn timelapse_filters(timelapse: &TimelapseOptions) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    if let Some(n) = timelapse.every_nth.filter(|n| *n > 1) {
+        // Keep every Nth frame, then close the gaps at the source frame rate.
+        filters.push(format!("select='not(mod(n,{}))'", n));
+        filters.push(format!("setpts=PTS/{}", n));
+    } else if let Some(speed) = timelapse.speed.filter(|s| *s > 1.0) {
+        // Frames landing between output frames are dropped by `-fps_mode
+        // cfr`, which timelapse encodes always set.
+        filters.push(format!("setpts=PTS/{}", speed));
+    }
+
+    if timelapse.deflicker {
+        filters.push("deflicker=size=10:mode=pm".to_string());
+    }
+
+    filters
+}
+
+/// Full `-af` chain for the job.
+pub fn audio_filter_chain(options: &ConversionOptions, clip_length: Option<f64>) -> Vec<String> {
+    let mut filters = match options.audio_edit {
+        Some(ref edit) => audio_edit_filters(edit, clip_length),
+        None => fade_filters("afade", options, clip_length),
+    };
+    if let (Some(target), Some(stats)) = (&options.normalize_audio, &options.loudnorm_stats) {
+        filters.extend(loudness::normalize_filters(target, stats));
+    }
+    filters
+}
+
+fn ms(value: u64) -> f64 {
+    value as f64 / 1000.0
+}
+
+/// Output length after an audio edit's trim, if it can be known.
+pub fn audio_edit_length(options: &ConversionOptions, clip_length: Option<f64>) -> Option<f64> {
+    let edit = options.audio_edit.as_ref()?;
+    let start = edit.start_ms.map_or(0.0, ms);
+    let end = edit.end_ms.map(ms).filter(|end| *end > start).or(clip_length);
+    end.map(|end| (end - start).max(0.0))
+}
+
+/// Trim, gain and fades of an audio edit, in that order so fades are placed
+/// on the trimmed timeline.
+fn audio_edit_filters(edit: &AudioEditOptions, clip_length: Option<f64>) -> Vec<String> {
+    let mut filters = Vec::new();
+    let start = edit.start_ms.map_or(0.0, ms);
+    let end = edit.end_ms.map(ms).filter(|end| *end > start);
+    if start > 0.0 || end.is_some() {
+        let mut trim = format!("atrim=start={}", secs(start));
+        if let Some(end) = end {
+            trim.push_str(&format!(":end={}", secs(end)));
+        }
+        filters.push(trim);
+        filters.push("asetpts=PTS-STARTPTS".to_string());
+    }
+
+    if let Some(gain) = edit.gain_db.filter(|g| *g != 0.0) {
+        filters.push(format!("volume={:.2}dB", gain));
+    }
+
+    let curve = edit
+        .fade_curve
+        .as_deref()
+        .map(|c| format!(":curve={}", c.trim()))
+        .unwrap_or_default();
+    if let Some(fade_in) = edit.fade_in_ms.filter(|d| *d > 0).map(ms) {
+        filters.push(format!("afade=t=in:st=0:d={}{}", secs(fade_in), curve));
+    }
+    let length = end.or(clip_length).map(|end| (end - start).max(0.0));
+    if let (Some(fade_out), Some(length)) = (edit.fade_out_ms.filter(|d| *d > 0).map(ms), length) {
+        let fade_out = fade_out.min(length);
+        filters.push(format!(
+            "afade=t=out:st={}:d={}{}",
+            secs(length - fade_out),
+            secs(fade_out),
+            curve
+        ));
+    }
+
+    filters
+}
+
+/// Chains compositing `overlay` (FFmpeg input `input`) onto video stream
+/// `video_stream` of input 0 and then applying the job's video filters,
+/// ending in `[{label}]`.
+pub fn main_video_graph(
+    video_filters: &[String],
+    video_stream: usize,
+    overlay: Option<(&OverlayOptions, usize)>,
+    label: &str,
+) -> String {
+    let chain = if video_filters.is_empty() {
+        "null".to_string()
+    } else {
+        video_filters.join(",")
+    };
+
+    let Some((overlay, input)) = overlay else {
+        return format!("[0:v:{}]{}[{}]", video_stream, chain, label);
+    };
+
+    let size = overlay.size.filter(|s| *s > 0.0 && *s <= 1.0).unwrap_or(0.25);
+    let border = overlay.border_width;
+    let scaled = if border > 0 { "pipraw" } else { "pip" };
+    let mut pip = format!(
+        "[{}:v:0][0:v:{}]scale2ref=w='trunc(main_w*{}/2)*2':h='trunc(ow/dar/2)*2'[{}][base]",
+        input, video_stream, size, scaled
+    );
+    if border > 0 {
+        pip.push_str(&format!(
+            ";[pipraw]pad=w=iw+{}:h=ih+{}:x={b}:y={b}:color={}[pip]",
+            border * 2,
+            border * 2,
+            overlay.border_color.as_deref().unwrap_or("white"),
+            b = border
+        ));
+    }
+
+    let margin = overlay.margin.unwrap_or(20);
+    let (x, y) = match (overlay.x, overlay.y) {
+        (Some(x), Some(y)) => (x.to_string(), y.to_string()),
+        _ => match overlay.corner {
+            OverlayCorner::TopLeft => (margin.to_string(), margin.to_string()),
+            OverlayCorner::TopRight => (format!("main_w-overlay_w-{}", margin), margin.to_string()),
+            OverlayCorner::BottomLeft => (margin.to_string(), format!("main_h-overlay_h-{}", margin)),
+            OverlayCorner::BottomRight => (
+                format!("main_w-overlay_w-{}", margin),
+                format!("main_h-overlay_h-{}", margin),
+            ),
+        },
+    };
+
+    let enable = match (overlay.start, overlay.end) {
+        (Some(start), Some(end)) => format!(":enable='between(t,{},{})'", secs(start), secs(end)),
+        (Some(start), None) => format!(":enable='gte(t,{})'", secs(start)),
+        (None, Some(end)) => format!(":enable='lte(t,{})'", secs(end)),
+        (None, None) => String::new(),
+    };
+
+    format!(
+        "{};[base][pip]overlay=x={}:y={}{},{}[{}]",
+        pip, x, y, enable, chain, label
+    )
+}
+
+/// An intro/outro clip stitched around the main input.
+pub struct Bumper {
+    /// FFmpeg input index of the clip.
+    pub input: usize,
+    /// Clip length, used to generate matching silence when it has no audio.
+    pub duration: f64,
+    pub has_audio: bool,
+}
+
+/// `-filter_complex` graph joining `intro`, the main input and `outro` with the
+/// concat filter. `main_video` is the main input's graph ending in `[vbase]`
+/// (see `main_video_graph`). Bumpers are scaled and padded to `size`, the
+/// main chain's output size (`output_size`); the main video is scaled to it
+/// as well, so rounding differences can't break the concat. Produces
+/// `[vout]` and, when the main input has audio, `[aout]`.
+pub fn bumper_graph(
+    main_video: &str,
+    audio_filters: &[String],
+    main_has_audio: bool,
+    size: (u32, u32),
+    intro: Option<&Bumper>,
+    outro: Option<&Bumper>,
+) -> String {
+    let (width, height) = size;
+    let mut chains = Vec::new();
+    let mut pads = String::new();
+
+    let main_audio = if audio_filters.is_empty() {
+        "anull".to_string()
+    } else {
+        audio_filters.join(",")
+    };
+
+    let segments = [intro, None, outro];
+    let mut count = 0;
+    for (position, bumper) in segments.iter().enumerate() {
+        if position != 1 && bumper.is_none() {
+            continue;
+        }
+        count += 1;
+
+        match bumper {
+            Some(bumper) => {
+                chains.push(format!(
+                    "[{i}:v:0]scale={w}:{h}:force_original_aspect_ratio=decrease,\
+                     pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1[v{i}]",
+                    i = bumper.input,
+                    w = width,
+                    h = height
+                ));
+                pads.push_str(&format!("[v{}]", bumper.input));
+                if main_has_audio {
+                    if bumper.has_audio {
+                        pads.push_str(&format!("[{}:a:0]", bumper.input));
+                    } else {
+                        chains.push(format!(
+                            "anullsrc=r=48000:cl=stereo,atrim=duration={}[a{}]",
+                            secs(bumper.duration),
+                            bumper.input
+                        ));
+                        pads.push_str(&format!("[a{}]", bumper.input));
+                    }
+                }
+            }
+            None => {
+                chains.push(format!("{};[vbase]scale={}:{},setsar=1[vmain]", main_video, width, height));
+                pads.push_str("[vmain]");
+                if main_has_audio {
+                    chains.push(format!("[0:a:0]{}[amain]", main_audio));
+                    pads.push_str("[amain]");
+                }
+            }
+        }
+    }
+
+    if main_has_audio {
+        chains.push(format!("{}concat=n={}:v=1:a=1[vout][aout]", pads, count));
+    } else {
+        chains.push(format!("{}concat=n={}:v=1:a=0[vout]", pads, count));
+    }
+    chains.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timelapse_keeps_every_nth_frame_or_squeezes_timestamps() {
+        let interval = TimelapseOptions {
+            every_nth: Some(30),
+            speed: Some(10.0),
+            deflicker: true,
+        };
+        assert_eq!(
+            timelapse_filters(&interval),
+            vec!["select='not(mod(n,30))'", "setpts=PTS/30", "deflicker=size=10:mode=pm"]
+        );
+        let speed = TimelapseOptions {
+            speed: Some(60.0),
+            ..Default::default()
+        };
+        assert_eq!(timelapse_filters(&speed), vec!["setpts=PTS/60"]);
+    }
+
+    #[test]
+    fn escapes_windows_paths_for_filtergraphs() {
+        assert_eq!(
+            escape_filter_path(r"C:\Subs\Episode 01.ass"),
+            r"C\\:/Subs/Episode 01.ass"
+        );
+    }
+
+    #[test]
+    fn anamorphic_sources_get_square_pixels() {
+        let mut options = ConversionOptions {
+            source_sar: Some((64, 45)),
+            ..Default::default()
+        };
+        assert_eq!(aspect_filters(&options), vec!["scale=trunc(iw*sar/2)*2:ih", "setsar=1"]);
+        options.aspect_mode = AspectMode::Preserve;
+        assert!(aspect_filters(&options).is_empty());
+        options.display_aspect = Some("4:3".to_string());
+        assert_eq!(aspect_filters(&options), vec!["setdar=4/3"]);
+    }
+
+    #[test]
+    fn reframe_pans_between_keyframes() {
+        let reframe = ReframeOptions {
+            keyframes: vec![
+                ReframeKeyframe { time: 4.0, x: 1.0, y: 0.5 },
+                ReframeKeyframe { time: 2.0, x: 0.0, y: 0.5 },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            reframe_filter(&reframe).unwrap(),
+            "crop=w='trunc(min(iw,ih*9/16)/2)*2':h='trunc(min(ih,iw*16/9)/2)*2':\
+             x='(iw-ow)*(if(lt(t,2.000),0.0000,if(lt(t,4.000),0.0000+1.0000*(t-2.000)/2.000,1.0000)))':\
+             y='(ih-oh)*(if(lt(t,2.000),0.5000,if(lt(t,4.000),0.5000+0.0000*(t-2.000)/2.000,0.5000)))'"
+        );
+    }
+
+    #[test]
+    fn fit_pads_to_the_exact_frame() {
+        let fit = FitOptions {
+            width: 1920,
+            height: 1080,
+            color: Some("0x202020".to_string()),
+        };
+        assert_eq!(
+            fit_filters(&fit),
+            vec![
+                "scale=1920:1080:force_original_aspect_ratio=decrease",
+                "pad=1920:1080:(ow-iw)/2:(oh-ih)/2:color=0x202020",
+                "setsar=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn fade_out_is_anchored_to_clip_end() {
+        let options = ConversionOptions {
+            fade_in: Some(1.0),
+            fade_out: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            audio_filter_chain(&options, Some(30.0)),
+            vec!["afade=t=in:st=0:d=1.000", "afade=t=out:st=28.000:d=2.000"]
+        );
+    }
+
+    #[test]
+    fn audio_edit_fades_follow_the_trimmed_clip() {
+        let options = ConversionOptions {
+            audio_edit: Some(AudioEditOptions {
+                start_ms: Some(1_250),
+                end_ms: Some(61_250),
+                fade_out_ms: Some(3_000),
+                fade_curve: Some("qsin".to_string()),
+                gain_db: Some(-3.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            audio_filter_chain(&options, None),
+            vec![
+                "atrim=start=1.250:end=61.250",
+                "asetpts=PTS-STARTPTS",
+                "volume=-3.00dB",
+                "afade=t=out:st=57.000:d=3.000:curve=qsin",
+            ]
+        );
+    }
+
+    #[test]
+    fn silent_intro_gets_generated_audio() {
+        let intro = Bumper {
+            input: 1,
+            duration: 3.0,
+            has_audio: false,
+        };
+        assert_eq!(
+            bumper_graph("[0:v:0]null[vbase]", &[], true, (1920, 1080), Some(&intro), None),
+            "[1:v:0]scale=1920:1080:force_original_aspect_ratio=decrease,\
+             pad=1920:1080:(ow-iw)/2:(oh-ih)/2,setsar=1[v1];\
+             anullsrc=r=48000:cl=stereo,atrim=duration=3.000[a1];\
+             [0:v:0]null[vbase];[vbase]scale=1920:1080,setsar=1[vmain];[0:a:0]anull[amain];\
+             [v1][a1][vmain][amain]concat=n=2:v=1:a=1[vout][aout]"
+        );
+
+        let fit = ConversionOptions {
+            fit: Some(FitOptions {
+                width: 1280,
+                height: 720,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(output_size(&fit, (1920, 1080)), (1280, 720));
+        let safe = ConversionOptions {
+            safe_mode: true,
+            ..Default::default()
+        };
+        assert_eq!(output_size(&safe, (3840, 1600)), (1920, 800));
+    }
+
+    #[test]
+    fn bumpers_match_a_reframed_main_video() {
+        let options = ConversionOptions {
+            reframe: Some(ReframeOptions {
+                aspect: "9:16".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let size = output_size(&options, (1920, 1080));
+        assert_eq!(size, (606, 1080));
+        let outro = Bumper {
+            input: 1,
+            duration: 2.0,
+            has_audio: true,
+        };
+        let main = main_video_graph(&video_filter_chain(&options, "in.mp4", None), 0, None, "vbase");
+        let graph = bumper_graph(&main, &[], false, size, None, Some(&outro));
+        assert!(graph.starts_with("[0:v:0]crop=w='trunc(min(iw,ih*9/16)/2)*2'"));
+        assert!(graph.contains("[vbase]scale=606:1080,setsar=1[vmain]"));
+        assert!(graph.contains("[1:v:0]scale=606:1080:force_original_aspect_ratio=decrease,pad=606:1080:"));
+    }
+
+    #[test]
+    fn overlay_is_bordered_and_time_limited() {
+        let overlay = OverlayOptions {
+            file: "cam.mp4".to_string(),
+            corner: OverlayCorner::TopRight,
+            border_width: 4,
+            start: Some(10.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            main_video_graph(&[], 0, Some((&overlay, 1)), "vout"),
+            "[1:v:0][0:v:0]scale2ref=w='trunc(main_w*0.25/2)*2':h='trunc(ow/dar/2)*2'[pipraw][base];\
+             [pipraw]pad=w=iw+8:h=ih+8:x=4:y=4:color=white[pip];\
+             [base][pip]overlay=x=main_w-overlay_w-20:y=20:enable='gte(t,10.000)',null[vout]"
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use super::FfmpegDownloader;
+use crate::error::AppError;
+use std::path::{Path, PathBuf};
+
+/// Folder inside the app data directory holding the generated fontconfig setup.
+const FONTCONFIG_FOLDER: &str = "fontconfig";
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// System font directories fontconfig should index.
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    if let Some(windir) = std::env::var_os("WINDIR") {
+        dirs.push(PathBuf::from(windir).join("Fonts"));
+    }
+    if let Some(local) = dirs::data_local_dir() {
+        // Per-user fonts installed without admin rights (Windows 10 1809+).
+        dirs.push(local.join("Microsoft").join("Windows").join("Fonts"));
+    }
+    if let Some(font_dir) = dirs::font_dir() {
+        dirs.push(font_dir);
+    }
+
+    dirs.dedup();
+    dirs
+}
+
+/// Minimal fonts.conf so libass can resolve fonts with Windows FFmpeg builds,
+/// which ship without any fontconfig configuration.
+fn render_fonts_conf(font_dirs: &[PathBuf], cache_dir: &Path) -> String {
+    let mut conf = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE fontconfig SYSTEM \"fonts.dtd\">\n<fontconfig>\n",
+    );
+    for dir in font_dirs {
+        conf.push_str(&format!("  <dir>{}</dir>\n", xml_escape(&dir.to_string_lossy())));
+    }
+    conf.push_str(&format!(
+        "  <cachedir>{}</cachedir>\n",
+        xml_escape(&cache_dir.to_string_lossy())
+    ));
+    for (generic, preferred) in [
+        ("sans-serif", "Arial"),
+        ("serif", "Times New Roman"),
+        ("monospace", "Consolas"),
+    ] {
+        conf.push_str(&format!(
+            "  <alias><family>{}</family><prefer><family>{}</family></prefer></alias>\n",
+            generic, preferred
+        ));
+    }
+    conf.push_str("</fontconfig>\n");
+    conf
+}
+
+/// Write (or refresh) the bundled fonts.conf and return its path, suitable for
+/// `FONTCONFIG_FILE`. `extra_dir` is the user's font directory setting.
+pub fn ensure_fonts_conf(extra_dir: Option<&str>) -> Result<PathBuf, AppError> {
+    let base = FfmpegDownloader::get_ffmpeg_app_dir()?.join(FONTCONFIG_FOLDER);
+    let cache_dir = base.join("cache");
+    std::fs::create_dir_all(&cache_dir)
+        .map_err(|e| AppError::Io(format!("Failed to create fontconfig directory: {}", e)))?;
+
+    let mut font_dirs = system_font_dirs();
+    if let Some(extra) = extra_dir {
+        font_dirs.push(PathBuf::from(extra));
+    }
+
+    let conf_path = base.join("fonts.conf");
+    std::fs::write(&conf_path, render_fonts_conf(&font_dirs, &cache_dir))
+        .map_err(|e| AppError::Io(format!("Failed to write fonts.conf: {}", e)))?;
+    Ok(conf_path)
+}
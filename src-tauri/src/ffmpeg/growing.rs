@@ -0,0 +1,42 @@
+use super::CancelToken;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wait until `path` stopped changing (same size and modification time) for
+/// `stable_secs` seconds, so a file that is still being copied or recorded
+/// isn't picked up half-written. Returns `false` if the job was cancelled.
+pub async fn wait_until_stable(path: &Path, stable_secs: u64, cancel: &CancelToken) -> bool {
+    let snapshot = |path: &Path| -> Option<(u64, Option<SystemTime>)> {
+        std::fs::metadata(path).ok().map(|m| (m.len(), m.modified().ok()))
+    };
+
+    let mut last = snapshot(path);
+    let mut unchanged = 0;
+    while unchanged < stable_secs {
+        if cancel.is_cancelled() {
+            return false;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let current = snapshot(path);
+        if current.is_some() && current == last {
+            unchanged += 1;
+        } else {
+            unchanged = 0;
+            last = current;
+        }
+    }
+    true
+}
+
+/// Input options that keep reading a file that is still growing. FFmpeg
+/// treats the file as finished once no new data arrived for `stall_secs`.
+pub fn follow_args(stall_secs: u64) -> Vec<String> {
+    vec![
+        "-follow".to_string(),
+        "1".to_string(),
+        "-rw_timeout".to_string(),
+        (stall_secs.max(1) * 1_000_000).to_string(),
+    ]
+}
@@ -0,0 +1,358 @@
+use super::{read_ffmpeg_output, ConversionTask, ProgressParser, PROGRESS_PIPE};
+use crate::error::AppError;
+use log::info;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::{BELOW_NORMAL_PRIORITY_CLASS, CREATE_NO_WINDOW};
+
+/// ReplayGain 2.0 reference level.
+const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+/// Opus R128 gain tags are relative to EBU R128's -23 LUFS.
+const R128_REFERENCE_LUFS: f64 = -23.0;
+
+/// Sample rate `loudnorm` output is resampled to when the source's is
+/// unknown; the filter itself works at 192 kHz.
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
+
+/// Two-pass EBU R128 normalization to a delivery target: -14 LUFS for
+/// streaming, -16 for podcasts, -23 for broadcast.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeOptions {
+    /// Integrated loudness target in LUFS.
+    #[serde(alias = "targetLufs")]
+    pub target_lufs: f64,
+    /// Maximum true peak in dBTP.
+    #[serde(alias = "truePeak")]
+    pub true_peak: f64,
+    /// Loudness range target in LU.
+    #[serde(alias = "loudnessRange")]
+    pub loudness_range: f64,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            target_lufs: -14.0,
+            true_peak: -1.0,
+            loudness_range: 11.0,
+        }
+    }
+}
+
+impl NormalizeOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-70.0..=-5.0).contains(&self.target_lufs) {
+            return Err(format!("Loudness target {} LUFS is outside -70 to -5", self.target_lufs));
+        }
+        if !(-9.0..=0.0).contains(&self.true_peak) {
+            return Err(format!("True peak {} dBTP is outside -9 to 0", self.true_peak));
+        }
+        if !(1.0..=50.0).contains(&self.loudness_range) {
+            return Err(format!("Loudness range {} LU is outside 1 to 50", self.loudness_range));
+        }
+        Ok(())
+    }
+
+    fn targets(&self) -> String {
+        format!("I={:.1}:TP={:.1}:LRA={:.1}", self.target_lufs, self.true_peak, self.loudness_range)
+    }
+}
+
+/// What the first `loudnorm` pass measured, fed back into the second.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoudnormStats {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+    /// Source sample rate to return to after the filter.
+    pub sample_rate: Option<u32>,
+}
+
+/// The JSON block `loudnorm` prints with `print_format=json`; values are
+/// strings, `-inf` for silence.
+#[derive(Deserialize)]
+struct LoudnormJson {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+/// Read the first pass's stats from FFmpeg's stderr.
+pub fn parse_loudnorm_json(stderr: &str) -> Option<LoudnormStats> {
+    let from = stderr.rfind("Parsed_loudnorm")?;
+    let json = &stderr[from..];
+    let json = &json[json.find('{')?..=json.rfind('}')?];
+    let values: LoudnormJson = serde_json::from_str(json).ok()?;
+    let number = |value: &str| value.trim().parse::<f64>().ok().filter(|v| v.is_finite());
+    Some(LoudnormStats {
+        input_i: number(&values.input_i)?,
+        input_tp: number(&values.input_tp)?,
+        input_lra: number(&values.input_lra)?,
+        input_thresh: number(&values.input_thresh)?,
+        target_offset: number(&values.target_offset)?,
+        sample_rate: None,
+    })
+}
+
+/// Second-pass filters: `loudnorm` with the measured values, in linear mode
+/// so the whole file gets one gain, then back to the source sample rate.
+pub fn normalize_filters(options: &NormalizeOptions, stats: &LoudnormStats) -> Vec<String> {
+    vec![
+        format!(
+            "loudnorm={}:measured_I={:.2}:measured_TP={:.2}:measured_LRA={:.2}:measured_thresh={:.2}:offset={:.2}:linear=true",
+            options.targets(),
+            stats.input_i,
+            stats.input_tp,
+            stats.input_lra,
+            stats.input_thresh,
+            stats.target_offset
+        ),
+        format!("aresample={}", stats.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE)),
+    ]
+}
+
+/// First-pass arguments: decode audio track `track` over the job's trim
+/// range through `audio_filters` and measure it.
+pub fn analysis_args(
+    input_file: &str,
+    track: usize,
+    trim: (Option<f64>, Option<f64>),
+    audio_filters: &[String],
+    options: &NormalizeOptions,
+) -> Vec<String> {
+    let mut args: Vec<String> = ["-hide_banner", "-progress", PROGRESS_PIPE, "-nostats"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    if let Some(start) = trim.0.filter(|s| *s > 0.0) {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+    args.push("-i".to_string());
+    args.push(input_file.to_string());
+    if let Some(duration) = trim.1 {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+    let mut chain = audio_filters.to_vec();
+    chain.push(format!("loudnorm={}:print_format=json", options.targets()));
+    args.extend(["-map".to_string(), format!("0:a:{}", track), "-vn".to_string(), "-sn".to_string()]);
+    args.extend(["-af".to_string(), chain.join(",")]);
+    args.extend(["-f", "null", "-"].iter().map(|s| s.to_string()));
+    args
+}
+
+/// Run the first pass, reporting its progress like a regular encode.
+pub async fn analyze(task_arc: &Arc<Mutex<ConversionTask>>, ffmpeg_path: &str, args: &[String]) -> Result<LoudnormStats, String> {
+    info!("Measuring loudness: {:?}", args);
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    let pid = child.id();
+    let cancel = task_arc.lock().expect("Failed to lock task mutex").cancel.clone();
+    if let Some(pid) = pid {
+        cancel.register(pid);
+    }
+
+    let parser = ProgressParser::new();
+    let mut stderr = Vec::new();
+    read_ffmpeg_output(
+        &mut child,
+        |line| {
+            if let Some(time) = parser.time(line) {
+                task_arc.lock().expect("Failed to lock task mutex").advance(time);
+            }
+        },
+        |line| stderr.push(line),
+    )
+    .await;
+
+    let status = child.wait().await;
+    if let Some(pid) = pid {
+        cancel.unregister(pid);
+    }
+    let status = status.map_err(|e| format!("Failed to wait for FFmpeg: {}", e))?;
+    if !status.success() {
+        return Err(format!(
+            "Loudness analysis exited with code {}: {}",
+            status.code().map_or("None".to_string(), |c| c.to_string()),
+            stderr.last().map(String::as_str).unwrap_or("no output")
+        ));
+    }
+    parse_loudnorm_json(&stderr.join("\n"))
+        .ok_or_else(|| "Loudness analysis found no measurable audio (silent track?)".to_string())
+}
+
+/// EBU R128 measurement of a whole file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS.
+    pub integrated: f64,
+    /// True peak in dBFS.
+    pub true_peak: f64,
+}
+
+/// Read the summary `ebur128` prints at the end of a run.
+pub fn parse_ebur128_summary(stderr: &str) -> Option<Loudness> {
+    let summary = &stderr[stderr.rfind("Summary:")?..];
+    let value = |label: &str| -> Option<f64> {
+        let regex = Regex::new(&format!(r"{}:\s+(-?\d+(?:\.\d+)?|-inf)", label)).expect("Invalid regex");
+        let caps = regex.captures(summary)?;
+        match &caps[1] {
+            "-inf" => Some(f64::NEG_INFINITY),
+            number => number.parse().ok(),
+        }
+    };
+    Some(Loudness {
+        integrated: value("I")?,
+        true_peak: value("Peak").unwrap_or(0.0),
+    })
+}
+
+/// Measure integrated loudness and true peak by decoding the whole file.
+pub async fn measure(ffmpeg_path: &str, input_file: &str) -> Result<Loudness, AppError> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-nostats", "-i", input_file, "-vn", "-sn"])
+        .args(["-af", "ebur128=peak=true", "-f", "null", "-"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| AppError::Ffmpeg(format!("Failed to run loudness analysis: {}", e)))?;
+    parse_ebur128_summary(&String::from_utf8_lossy(&output.stderr))
+        .filter(|l| l.integrated.is_finite())
+        .ok_or_else(|| AppError::Ffmpeg("Loudness analysis produced no result".to_string()))
+}
+
+/// Loudness tags in the convention of the output format: R128 gain for Opus,
+/// ReplayGain 2.0 for everything else. `None` for formats without a tag
+/// convention players read.
+pub fn gain_tags(ext: &str, loudness: Loudness) -> Option<Vec<(String, String)>> {
+    match ext {
+        "opus" => {
+            // Q7.8 fixed point, as RFC 7845 specifies.
+            let gain = ((R128_REFERENCE_LUFS - loudness.integrated) * 256.0).round() as i32;
+            Some(vec![("R128_TRACK_GAIN".to_string(), gain.to_string())])
+        }
+        "mp3" | "flac" | "ogg" => {
+            let gain = REPLAYGAIN_REFERENCE_LUFS - loudness.integrated;
+            let peak = 10f64.powf(loudness.true_peak / 20.0);
+            Some(vec![
+                ("REPLAYGAIN_TRACK_GAIN".to_string(), format!("{:.2} dB", gain)),
+                ("REPLAYGAIN_TRACK_PEAK".to_string(), format!("{:.6}", peak)),
+            ])
+        }
+        _ => None,
+    }
+}
+
+/// Measure `file` and write its loudness tags in place (stream copy into a
+/// temp file that replaces the original). Returns the tags written.
+pub async fn tag_file(ffmpeg_path: &str, file: &str) -> Result<Vec<(String, String)>, AppError> {
+    let path = Path::new(file);
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let loudness = measure(ffmpeg_path, file).await?;
+    let Some(tags) = gain_tags(&ext, loudness) else {
+        return Ok(Vec::new());
+    };
+
+    let temp = path.with_extension(format!("gain-tmp.{}", ext));
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-v", "error", "-y", "-i", file, "-map", "0", "-c", "copy", "-map_metadata", "0"]);
+    for (key, value) in &tags {
+        // Ogg-based formats keep tags per stream (Vorbis comments).
+        let flag = if matches!(ext.as_str(), "ogg" | "opus") { "-metadata:s:a:0" } else { "-metadata" };
+        cmd.arg(flag).arg(format!("{}={}", key, value));
+    }
+    cmd.arg(&temp).stdout(Stdio::null()).stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| AppError::Ffmpeg(format!("Failed to write loudness tags: {}", e)))?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&temp);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Ffmpeg(format!(
+            "Failed to write loudness tags: {}",
+            stderr.lines().next().unwrap_or("unknown error")
+        )));
+    }
+    std::fs::rename(&temp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        AppError::Io(format!("Failed to replace {}: {}", file, e))
+    })?;
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_ebur128_summary_to_gain_tags() {
+        let stderr = "[Parsed_ebur128_0 @ 0x1] Summary:\n\n  Integrated loudness:\n    I:         -14.0 LUFS\n    Threshold: -24.5 LUFS\n\n  True peak:\n    Peak:        -1.0 dBFS\n";
+        let loudness = parse_ebur128_summary(stderr).unwrap();
+        assert_eq!(loudness, Loudness { integrated: -14.0, true_peak: -1.0 });
+        assert_eq!(
+            gain_tags("flac", loudness).unwrap(),
+            vec![
+                ("REPLAYGAIN_TRACK_GAIN".to_string(), "-4.00 dB".to_string()),
+                ("REPLAYGAIN_TRACK_PEAK".to_string(), "0.891251".to_string()),
+            ]
+        );
+        assert_eq!(gain_tags("opus", loudness).unwrap()[0].1, "-2304");
+    }
+
+    #[test]
+    fn feeds_first_pass_stats_into_the_second() {
+        let stderr = "[Parsed_loudnorm_1 @ 0x1] \n{\n\t\"input_i\" : \"-27.61\",\n\t\"input_tp\" : \"-4.47\",\n\
+                      \t\"input_lra\" : \"18.06\",\n\t\"input_thresh\" : \"-39.20\",\n\t\"output_i\" : \"-16.58\",\n\
+                      \t\"normalization_type\" : \"dynamic\",\n\t\"target_offset\" : \"0.58\"\n}\n";
+        let stats = parse_loudnorm_json(stderr).unwrap();
+        assert_eq!(stats.input_i, -27.61);
+        assert_eq!(stats.target_offset, 0.58);
+
+        let options = NormalizeOptions {
+            target_lufs: -16.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_filters(&options, &stats),
+            [
+                "loudnorm=I=-16.0:TP=-1.0:LRA=11.0:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:\
+                 measured_thresh=-39.20:offset=0.58:linear=true",
+                "aresample=48000"
+            ]
+        );
+        assert!(parse_loudnorm_json(&stderr.replace("-27.61", "-inf")).is_none());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Global (container-level) metadata to write. `None` leaves a tag as it is;
+/// an empty string removes it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataFields {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub comment: Option<String>,
+    /// ISO 8601 date/time, e.g. `2024-05-01T18:30:00Z`.
+    #[serde(alias = "creationTime")]
+    pub creation_time: Option<String>,
+    /// Any other tags, written under their own key.
+    pub custom: HashMap<String, String>,
+}
+
+impl MetadataFields {
+    /// `-metadata key=value` arguments for every field that was set.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut tags: Vec<(&str, &str)> = [
+            ("title", &self.title),
+            ("artist", &self.artist),
+            ("comment", &self.comment),
+            ("creation_time", &self.creation_time),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.as_deref().map(|v| (key, v)))
+        .collect();
+
+        let mut custom: Vec<(&str, &str)> = self
+            .custom
+            .iter()
+            .filter(|(key, _)| !key.trim().is_empty())
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        custom.sort();
+        tags.extend(custom);
+
+        tags.into_iter()
+            .flat_map(|(key, value)| ["-metadata".to_string(), format!("{}={}", key, value)])
+            .collect()
+    }
+
+    /// MP4/MOV only store non-standard keys with `-movflags use_metadata_tags`.
+    pub fn has_custom_tags(&self) -> bool {
+        self.custom.keys().any(|key| !key.trim().is_empty())
+    }
+}
+
+/// Rewrite `input_file` into `output_file` with new global metadata, copying
+/// every stream as-is so no re-encode happens.
+pub async fn remux_with_metadata(
+    ffmpeg_path: &str,
+    input_file: &str,
+    output_file: &str,
+    fields: &MetadataFields,
+) -> Result<(), AppError> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-v", "error", "-y", "-i", input_file, "-map", "0", "-c", "copy", "-map_metadata", "0"])
+        .args(fields.to_args());
+
+    let lower = output_file.to_lowercase();
+    if fields.has_custom_tags()
+        && [".mp4", ".mov", ".m4a", ".m4v"].iter().any(|ext| lower.ends_with(ext))
+    {
+        cmd.args(["-movflags", "use_metadata_tags"]);
+    }
+    cmd.arg(output_file)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| AppError::Ffmpeg(format!("Failed to run FFmpeg for metadata: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let _ = std::fs::remove_file(output_file);
+        return Err(AppError::Ffmpeg(format!(
+            "Failed to write metadata: {}",
+            stderr.lines().next().unwrap_or("unknown error")
+        )));
+    }
+
+    Ok(())
+}
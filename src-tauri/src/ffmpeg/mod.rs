@@ -1,15 +1,64 @@
 use crate::error::AppError;
+use crate::gpu;
+use crate::metrics;
+use crate::quarantine;
+use crate::sidecars;
+use crate::stats;
 use futures::StreamExt;
+use progress_events::ProgressEmitter;
 use log::{debug, error, info, warn};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::fs;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, Command};
+use tokio::process::{Child, ChildStdin, Command};
+
+pub mod adobe_presets;
+pub mod audio_export;
+pub mod audio_extract;
+pub mod audio_limits;
+pub mod availability;
+pub mod batch_check;
+pub mod captions;
+pub mod chapters;
+pub mod chunked;
+pub mod concat;
+pub mod containers;
+pub mod cropdetect;
+pub mod decoders;
+pub mod disc;
+pub mod filters;
+pub mod fonts;
+pub mod growing;
+pub mod loudness;
+pub mod metadata;
+pub mod multi_output;
+pub mod mux;
+pub mod ocr;
+pub mod passthrough;
+pub mod pipe_input;
+pub mod preview;
+pub mod probe;
+pub mod progress_events;
+pub mod ratecontrol;
+pub mod requirement;
+pub mod safe_mode;
+pub mod scrub;
+pub mod segments;
+pub mod slideshow;
+pub mod snapshot;
+pub mod streaming;
+pub mod telemetry;
+pub mod tracks;
+pub mod transfer;
+pub mod transient;
+pub mod watchdog;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
@@ -20,9 +69,20 @@ const CREATE_NO_WINDOW: u32 = 0x08000000;
 #[cfg(target_os = "windows")]
 const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x00004000;
 
+// Binary names of a downloaded or installed FFmpeg
+#[cfg(target_os = "windows")]
+const FFMPEG_BINARY: &str = "ffmpeg.exe";
+#[cfg(not(target_os = "windows"))]
+const FFMPEG_BINARY: &str = "ffmpeg";
+#[cfg(target_os = "windows")]
+const FFPROBE_BINARY: &str = "ffprobe.exe";
+#[cfg(not(target_os = "windows"))]
+const FFPROBE_BINARY: &str = "ffprobe";
+
 // Supported video formats
 pub const VIDEO_FORMATS: &[&str] = &[
-    "mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "ogv",
+    "mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "ogv", "m4v", "3gp", "mpg", "mpeg", "vob", "mts",
+    "m2ts",
 ];
 
 // Supported audio formats
@@ -292,7 +352,7 @@ impl FfmpegLocator {
     /// Check app's data directory for downloaded FFmpeg
     async fn find_in_app_data() -> Option<PathBuf> {
         if let Ok(app_dir) = FfmpegDownloader::get_ffmpeg_app_dir() {
-            let ffmpeg_path = app_dir.join("ffmpeg.exe");
+            let ffmpeg_path = app_dir.join(FFMPEG_BINARY);
             if ffmpeg_path.exists() {
                 return Some(ffmpeg_path);
             }
@@ -310,7 +370,7 @@ impl FfmpegLocator {
         cmd.arg("-version");
         #[cfg(target_os = "windows")]
         cmd.creation_flags(CREATE_NO_WINDOW);
-        let output = cmd.output().await;
+        let output = watchdog::output(&mut cmd).await;
 
         match output {
             Ok(result) => result.status.success(),
@@ -324,7 +384,7 @@ impl FfmpegLocator {
         cmd.arg("-version");
         #[cfg(target_os = "windows")]
         cmd.creation_flags(CREATE_NO_WINDOW);
-        if let Ok(output) = cmd.output().await {
+        if let Ok(output) = watchdog::output(&mut cmd).await {
             if output.status.success() {
                 let stdout = String::from_utf8_lossy(&output.stdout);
                 return stdout.lines().next().map(|s| s.to_string());
@@ -393,6 +453,45 @@ pub fn get_format_info(ext: &str) -> FormatInfo {
             supports_video: true,
             supports_audio: true,
         },
+        "m4v" => FormatInfo {
+            container: "mp4",
+            default_video_codec: "libx264",
+            default_audio_codec: "aac",
+            supports_video: true,
+            supports_audio: true,
+        },
+        // Modern phones write H.264/AAC into 3GP; H.263/AMR only suits very
+        // old handsets and needs non-default encoders.
+        "3gp" => FormatInfo {
+            container: "3gp",
+            default_video_codec: "libx264",
+            default_audio_codec: "aac",
+            supports_video: true,
+            supports_audio: true,
+        },
+        "mpg" | "mpeg" => FormatInfo {
+            container: "mpeg",
+            default_video_codec: "mpeg2video",
+            default_audio_codec: "mp2",
+            supports_video: true,
+            supports_audio: true,
+        },
+        // DVD-Video: MPEG-2 with AC-3 audio, as authoring tools expect.
+        "vob" => FormatInfo {
+            container: "vob",
+            default_video_codec: "mpeg2video",
+            default_audio_codec: "ac3",
+            supports_video: true,
+            supports_audio: true,
+        },
+        // AVCHD camcorder streams; FFmpeg picks 192-byte packets for .m2ts.
+        "mts" | "m2ts" => FormatInfo {
+            container: "mpegts",
+            default_video_codec: "libx264",
+            default_audio_codec: "ac3",
+            supports_video: true,
+            supports_audio: true,
+        },
         "mp3" => FormatInfo {
             container: "mp3",
             default_video_codec: "",
@@ -462,102 +561,197 @@ pub struct AdobePreset {
     pub encoder: String,
     pub encoder_options: Vec<String>,
     pub pixel_format: String,
+    /// Output containers the format allows; any when empty.
+    #[serde(default)]
+    pub containers: Vec<String>,
+    /// Audio codec the format requires; PCM for ProRes and DNxHD when unset.
+    #[serde(default)]
+    pub audio_codec: Option<String>,
 }
 
+/// Bundled presets from `resources/adobe_presets.json` merged with the
+/// user's `adobe_presets.json`; see `adobe_presets`.
 pub fn get_adobe_presets() -> Vec<AdobePreset> {
+    adobe_presets::all()
+}
+
+// Short clip presets (ringtones, social clips) built on trim + fades
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipPreset {
+    pub name: String,
+    pub description: String,
+    pub extension: String,
+    pub trim_duration: f64,
+    pub fade_in: f64,
+    pub fade_out: f64,
+}
+
+pub fn get_clip_presets() -> Vec<ClipPreset> {
     vec![
-        // ProRes presets
-        AdobePreset {
-            name: "prores_422".to_string(),
-            description: "Apple ProRes 422 (High Quality for Premiere Pro / Final Cut)".to_string(),
-            encoder: "prores_ks".to_string(),
-            encoder_options: vec!["-profile:v".to_string(), "2".to_string()],
-            pixel_format: "yuv422p10le".to_string(),
+        ClipPreset {
+            name: "ringtone_30s".to_string(),
+            description: "Ringtone (30 s, M4A, smooth fade in/out)".to_string(),
+            extension: "m4a".to_string(),
+            trim_duration: 30.0,
+            fade_in: 1.0,
+            fade_out: 2.0,
         },
-        AdobePreset {
-            name: "prores_422_hq".to_string(),
-            description: "Apple ProRes 422 HQ (Highest Quality for Premiere Pro / Final Cut)"
-                .to_string(),
-            encoder: "prores_ks".to_string(),
-            encoder_options: vec!["-profile:v".to_string(), "3".to_string()],
-            pixel_format: "yuv422p10le".to_string(),
+        ClipPreset {
+            name: "ringtone_mp3_30s".to_string(),
+            description: "Ringtone (30 s, MP3, smooth fade in/out)".to_string(),
+            extension: "mp3".to_string(),
+            trim_duration: 30.0,
+            fade_in: 1.0,
+            fade_out: 2.0,
         },
-        AdobePreset {
-            name: "prores_4444".to_string(),
-            description: "Apple ProRes 4444 (With Alpha Channel)".to_string(),
-            encoder: "prores_ks".to_string(),
-            encoder_options: vec![
-                "-profile:v".to_string(),
-                "4".to_string(),
-                "-alpha_bits".to_string(),
-                "16".to_string(),
-            ],
-            pixel_format: "yuva444p10le".to_string(),
+        ClipPreset {
+            name: "notification_5s".to_string(),
+            description: "Notification sound (5 s, MP3, short fades)".to_string(),
+            extension: "mp3".to_string(),
+            trim_duration: 5.0,
+            fade_in: 0.2,
+            fade_out: 0.5,
         },
-        AdobePreset {
-            name: "prores_proxy".to_string(),
-            description: "Apple ProRes Proxy (Lightweight Editing)".to_string(),
-            encoder: "prores_ks".to_string(),
-            encoder_options: vec!["-profile:v".to_string(), "0".to_string()],
-            pixel_format: "yuv422p".to_string(),
+        ClipPreset {
+            name: "short_clip_15s".to_string(),
+            description: "Short video clip (15 s, MP4, fade from/to black)".to_string(),
+            extension: "mp4".to_string(),
+            trim_duration: 15.0,
+            fade_in: 0.5,
+            fade_out: 0.5,
         },
-        // DNxHD/DNxHR presets for Avid/After Effects
-        AdobePreset {
-            name: "dnxhd_1080p_220".to_string(),
-            description: "DNxHD 220 Mbps 1080p (Broadcast Quality)".to_string(),
-            encoder: "dnxhd".to_string(),
-            encoder_options: vec!["-b:v".to_string(), "220M".to_string()],
-            pixel_format: "yuv422p".to_string(),
+        ClipPreset {
+            name: "social_clip_60s".to_string(),
+            description: "Social media clip (60 s, MP4, fade from/to black)".to_string(),
+            extension: "mp4".to_string(),
+            trim_duration: 60.0,
+            fade_in: 1.0,
+            fade_out: 1.0,
         },
-        AdobePreset {
-            name: "dnxhd_1080p_145".to_string(),
-            description: "DNxHD 145 Mbps 1080p (High Quality)".to_string(),
-            encoder: "dnxhd".to_string(),
-            encoder_options: vec!["-b:v".to_string(), "145M".to_string()],
-            pixel_format: "yuv422p".to_string(),
+    ]
+}
+
+// Plex/Jellyfin presets tuned so common clients direct-play without transcoding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaServerPreset {
+    pub name: String,
+    pub description: String,
+    pub encoder: String,
+    pub encoder_options: Vec<String>,
+    pub pixel_format: String,
+    pub audio_options: Vec<String>,
+}
+
+pub fn get_media_server_presets() -> Vec<MediaServerPreset> {
+    let aac_stereo = vec![
+        "-c:a:0".to_string(),
+        "aac".to_string(),
+        "-ac:a:0".to_string(),
+        "2".to_string(),
+        "-b:a:0".to_string(),
+        "192k".to_string(),
+    ];
+
+    vec![
+        MediaServerPreset {
+            name: "media_server_h264".to_string(),
+            description: "Plex/Jellyfin H.264 (High@4.1, AAC stereo, direct play everywhere)"
+                .to_string(),
+            encoder: "libx264".to_string(),
+            encoder_options: vec![
+                "-profile:v".to_string(),
+                "high".to_string(),
+                "-level:v".to_string(),
+                "4.1".to_string(),
+                "-crf".to_string(),
+                "20".to_string(),
+                // Level 4.1 caps High profile at 50 Mbps; stay well inside it.
+                "-maxrate".to_string(),
+                "20M".to_string(),
+                "-bufsize".to_string(),
+                "40M".to_string(),
+            ],
+            pixel_format: "yuv420p".to_string(),
+            audio_options: aac_stereo.clone(),
         },
-        AdobePreset {
-            name: "dnxhr_hq".to_string(),
-            description: "DNxHR HQ (High Quality for 4K/UHD)".to_string(),
-            encoder: "dnxhd".to_string(),
-            encoder_options: vec!["-profile:v".to_string(), "dnxhr_hq".to_string()],
-            pixel_format: "yuv422p".to_string(),
+        MediaServerPreset {
+            name: "media_server_hevc".to_string(),
+            description: "Plex/Jellyfin HEVC (Main, AAC stereo, smaller files for modern clients)"
+                .to_string(),
+            encoder: "libx265".to_string(),
+            encoder_options: vec![
+                "-profile:v".to_string(),
+                "main".to_string(),
+                "-crf".to_string(),
+                "22".to_string(),
+                // Apple clients only direct-play HEVC tagged as hvc1.
+                "-tag:v".to_string(),
+                "hvc1".to_string(),
+            ],
+            pixel_format: "yuv420p".to_string(),
+            audio_options: aac_stereo,
         },
-        AdobePreset {
-            name: "dnxhr_sq".to_string(),
-            description: "DNxHR SQ (Standard Quality)".to_string(),
-            encoder: "dnxhd".to_string(),
-            encoder_options: vec!["-profile:v".to_string(), "dnxhr_sq".to_string()],
-            pixel_format: "yuv422p".to_string(),
+    ]
+}
+
+// Screen-recording presets: OBS and game captures are mostly flat UI and
+// text, often variable frame rate, and need different tuning than camera footage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreenRecordingPreset {
+    pub name: String,
+    pub description: String,
+    pub encoder: String,
+    pub encoder_options: Vec<String>,
+    pub pixel_format: String,
+}
+
+pub fn get_screen_recording_presets() -> Vec<ScreenRecordingPreset> {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    vec![
+        ScreenRecordingPreset {
+            name: "screen_x264_text".to_string(),
+            description: "Tutorials and slides (x264 tuned for still images, sharp text)".to_string(),
+            encoder: "libx264".to_string(),
+            encoder_options: args(&["-tune", "stillimage", "-crf", "20"]),
+            pixel_format: "yuv420p".to_string(),
         },
-        AdobePreset {
-            name: "dnxhr_lb".to_string(),
-            description: "DNxHR LB (Low Bandwidth / Proxy)".to_string(),
-            encoder: "dnxhd".to_string(),
-            encoder_options: vec!["-profile:v".to_string(), "dnxhr_lb".to_string()],
-            pixel_format: "yuv422p".to_string(),
+        ScreenRecordingPreset {
+            name: "screen_x264_text_444".to_string(),
+            description: "Code and small text without chroma bleed (x264 4:4:4, for archiving and editing)"
+                .to_string(),
+            encoder: "libx264".to_string(),
+            // 4:4:4 needs the High 4:4:4 Predictive profile, which many
+            // hardware decoders and browsers can't play.
+            encoder_options: args(&["-tune", "stillimage", "-crf", "16", "-profile:v", "high444"]),
+            pixel_format: "yuv444p".to_string(),
         },
-        // CineForm presets
-        AdobePreset {
-            name: "cineform_high".to_string(),
-            description: "GoPro CineForm High (After Effects Compatible)".to_string(),
-            encoder: "cfhd".to_string(),
-            encoder_options: vec!["-quality".to_string(), "film3+".to_string()],
-            pixel_format: "yuv422p10le".to_string(),
+        ScreenRecordingPreset {
+            name: "screen_x264_animation".to_string(),
+            description: "UI animation and 2D games (x264 tuned for flat colours and sharp edges)".to_string(),
+            encoder: "libx264".to_string(),
+            encoder_options: args(&["-tune", "animation", "-crf", "18"]),
+            pixel_format: "yuv420p".to_string(),
         },
-        AdobePreset {
-            name: "cineform_medium".to_string(),
-            description: "GoPro CineForm Medium".to_string(),
-            encoder: "cfhd".to_string(),
-            encoder_options: vec!["-quality".to_string(), "film3".to_string()],
-            pixel_format: "yuv422p".to_string(),
+        ScreenRecordingPreset {
+            name: "screen_nvenc_gameplay".to_string(),
+            description: "Gameplay captures (NVENC H.264 with lookahead and adaptive quantization)".to_string(),
+            encoder: "h264_nvenc".to_string(),
+            encoder_options: args(&[
+                "-rc", "vbr", "-cq", "21", "-b:v", "0", "-rc-lookahead", "32", "-spatial-aq", "1",
+                "-temporal-aq", "1", "-bf", "3",
+            ]),
+            pixel_format: "yuv420p".to_string(),
         },
-        AdobePreset {
-            name: "cineform_low".to_string(),
-            description: "GoPro CineForm Low (Proxy)".to_string(),
-            encoder: "cfhd".to_string(),
-            encoder_options: vec!["-quality".to_string(), "film2".to_string()],
-            pixel_format: "yuv422p".to_string(),
+        ScreenRecordingPreset {
+            name: "screen_hevc_nvenc_444".to_string(),
+            description: "Desktop captures in full colour (NVENC HEVC 4:4:4, GTX 950 and newer)".to_string(),
+            encoder: "hevc_nvenc".to_string(),
+            encoder_options: args(&[
+                "-profile:v", "rext", "-rc", "vbr", "-cq", "20", "-b:v", "0", "-rc-lookahead", "32",
+                "-tag:v", "hvc1",
+            ]),
+            pixel_format: "yuv444p".to_string(),
         },
     ]
 }
@@ -569,6 +763,14 @@ pub struct VideoInfo {
     pub height: Option<u32>,
     pub video_streams: Vec<StreamInfo>,
     pub audio_streams: Vec<StreamInfo>,
+    #[serde(default)]
+    pub subtitle_streams: Vec<StreamInfo>,
+    /// Sample (pixel) aspect ratio of the main video stream, e.g. `(64, 45)`.
+    #[serde(default)]
+    pub sample_aspect: Option<(u32, u32)>,
+    /// Display aspect ratio of the main video stream, e.g. `(16, 9)`.
+    #[serde(default)]
+    pub display_aspect: Option<(u32, u32)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -577,6 +779,27 @@ pub struct StreamInfo {
     pub codec: String,
     pub language: Option<String>,
     pub title: Option<String>,
+    /// `default` disposition flag.
+    #[serde(default)]
+    pub default: bool,
+    /// `forced` disposition flag.
+    #[serde(default)]
+    pub forced: bool,
+    /// Video stream carries CEA-608/708 closed captions.
+    #[serde(default)]
+    pub closed_captions: bool,
+    /// Audio sample rate in Hz.
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+    /// Audio channel count.
+    #[serde(default)]
+    pub channels: Option<u32>,
+    /// Video frame width.
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// Video frame height.
+    #[serde(default)]
+    pub height: Option<u32>,
 }
 
 impl VideoInfo {
@@ -586,6 +809,9 @@ impl VideoInfo {
         let mut height = None;
         let mut video_streams = Vec::new();
         let mut audio_streams = Vec::new();
+        let mut subtitle_streams = Vec::new();
+        let mut sample_aspect = None;
+        let mut display_aspect = None;
 
         // Parse duration
         let duration_regex = Regex::new(r"Duration: (\d+):(\d+):(\d+\.\d+)")
@@ -597,35 +823,69 @@ impl VideoInfo {
             duration = Some(hours * 3600.0 + minutes * 60.0 + seconds);
         }
 
-        // Parse streams (handles optional [0x..] and (lang) segments)
+        // Parse streams (handles optional [0x..] and (lang) segments). The
+        // stream's indented metadata block follows its line and holds the title.
         let stream_regex =
-            Regex::new(r"Stream #0:(\d+)(?:\[[^\]]+\])?(?:\(([^\)]+)\))?: (Video|Audio): ([^,\s]+)")
+            Regex::new(r"Stream #0:(\d+)(?:\[[^\]]+\])?(?:\(([^\)]+)\))?: (Video|Audio|Subtitle): ([^,\s]+)(.*)")
                 .map_err(|e| AppError::Internal(e.to_string()))?;
-        for caps in stream_regex.captures_iter(ffmpeg_output) {
-            let index: u32 = caps[1].parse().unwrap_or(0);
-            let language = caps.get(2).map(|m| m.as_str().to_string());
-            let stream_type = caps.get(3).map(|m| m.as_str()).unwrap_or("");
-            let codec = caps.get(4).map(|m| m.as_str()).unwrap_or("").to_string();
-
-            let stream_info = StreamInfo {
-                index,
-                codec,
-                language,
-                title: None,
-            };
-
-            match stream_type {
-                "Video" => {
-                    // Parse resolution from the same line
-                    let resolution_regex = Regex::new(r"(\d+)x(\d+)")
-                        .map_err(|e| AppError::Internal(e.to_string()))?;
-                    if let Some(res_caps) = resolution_regex.captures(&caps[0]) {
+        let resolution_regex = Regex::new(r", (\d{2,5})x(\d{2,5})")
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let title_regex = Regex::new(r"^\s{6,}title\s*:\s*(.*)$")
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let aspect_regex = Regex::new(r"\[SAR (\d+):(\d+) DAR (\d+):(\d+)\]")
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let audio_format_regex = Regex::new(r"(\d+) Hz, ([^,]+)")
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut pending: Vec<(&str, StreamInfo)> = Vec::new();
+        for line in ffmpeg_output.lines() {
+            if let Some(caps) = stream_regex.captures(line) {
+                let rest = caps.get(5).map(|m| m.as_str()).unwrap_or("");
+                let stream_type = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+                if stream_type == "Video" && width.is_none() && !rest.contains("(attached pic)") {
+                    if let Some(res_caps) = resolution_regex.captures(rest) {
                         width = Some(res_caps[1].parse().unwrap_or(0));
                         height = Some(res_caps[2].parse().unwrap_or(0));
                     }
-                    video_streams.push(stream_info);
+                    if let Some(aspect_caps) = aspect_regex.captures(rest) {
+                        let ratio = |a: usize, b: usize| -> Option<(u32, u32)> {
+                            Some((aspect_caps[a].parse().ok()?, aspect_caps[b].parse().ok()?))
+                        };
+                        sample_aspect = ratio(1, 2);
+                        display_aspect = ratio(3, 4);
+                    }
+                }
+                let audio_format = audio_format_regex.captures(rest).filter(|_| stream_type == "Audio");
+                let resolution = resolution_regex.captures(rest).filter(|_| stream_type == "Video");
+                pending.push((
+                    stream_type,
+                    StreamInfo {
+                        index: caps[1].parse().unwrap_or(0),
+                        codec: caps[4].to_string(),
+                        language: caps.get(2).map(|m| m.as_str().to_string()),
+                        title: None,
+                        default: rest.contains("(default)"),
+                        forced: rest.contains("(forced)"),
+                        closed_captions: stream_type == "Video" && rest.contains("Closed Captions"),
+                        sample_rate: audio_format.as_ref().and_then(|c| c[1].parse().ok()),
+                        channels: audio_format.as_ref().and_then(|c| audio_limits::layout_channels(&c[2])),
+                        width: resolution.as_ref().and_then(|c| c[1].parse().ok()),
+                        height: resolution.as_ref().and_then(|c| c[2].parse().ok()),
+                    },
+                ));
+            } else if let Some(caps) = title_regex.captures(line) {
+                if let Some((_, stream)) = pending.last_mut() {
+                    if stream.title.is_none() {
+                        stream.title = Some(caps[1].trim().to_string());
+                    }
                 }
+            }
+        }
+
+        for (stream_type, stream_info) in pending {
+            match stream_type {
+                "Video" => video_streams.push(stream_info),
                 "Audio" => audio_streams.push(stream_info),
+                "Subtitle" => subtitle_streams.push(stream_info),
                 _ => {}
             }
         }
@@ -636,10 +896,416 @@ impl VideoInfo {
             height,
             video_streams,
             audio_streams,
+            subtitle_streams,
+            sample_aspect,
+            display_aspect,
         })
     }
 }
 
+/// Optional per-job editing settings layered on top of encoder/preset selection.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionOptions {
+    /// Seconds to skip from the start of the input.
+    #[serde(alias = "trimStart")]
+    pub trim_start: Option<f64>,
+    /// Maximum length of the output in seconds.
+    #[serde(alias = "trimDuration")]
+    pub trim_duration: Option<f64>,
+    /// Fade-in length in seconds (video from black, audio from silence).
+    #[serde(alias = "fadeIn")]
+    pub fade_in: Option<f64>,
+    /// Fade-out length in seconds, ending at the end of the clip.
+    #[serde(alias = "fadeOut")]
+    pub fade_out: Option<f64>,
+    /// Deinterlace frames flagged as interlaced. Defaults on for DVD/Blu-ray sources.
+    pub deinterlace: Option<bool>,
+    /// Handling of sources with non-square pixels (DV, DVB, anamorphic DVD).
+    #[serde(alias = "aspectMode")]
+    pub aspect_mode: AspectMode,
+    /// Display aspect ratio to flag on the output (e.g. `16:9`); takes
+    /// precedence over `aspect_mode`.
+    #[serde(alias = "displayAspect")]
+    pub display_aspect: Option<String>,
+    /// Pixel aspect ratio of the source, filled in from the probe when the
+    /// job starts.
+    #[serde(skip)]
+    pub source_sar: Option<(u32, u32)>,
+    /// Crop a region of another aspect ratio, e.g. a 9:16 slice of a 16:9 master.
+    pub reframe: Option<ReframeOptions>,
+    /// Remove letterboxing found by `cropdetect` before reframing; reframes
+    /// to the default 9:16 when no reframe is set.
+    #[serde(alias = "smartReframe")]
+    pub smart_reframe: bool,
+    /// Black-bar crop detected when the job starts.
+    #[serde(skip)]
+    pub letterbox_crop: Option<cropdetect::CropBox>,
+    /// Scale into an exact frame size, letterboxing or pillarboxing the rest.
+    pub fit: Option<FitOptions>,
+    /// Title to convert when the input is a DVD/Blu-ray folder (main title if unset).
+    #[serde(alias = "discTitle")]
+    pub disc_title: Option<u32>,
+    /// Segment files joined into one logical input (e.g. GoPro chapters).
+    #[serde(alias = "inputSegments")]
+    pub input_segments: Vec<String>,
+    /// Extra environment variables for the FFmpeg process (merged over settings).
+    #[serde(alias = "envVars")]
+    pub env_vars: HashMap<String, String>,
+    /// Working directory for the FFmpeg process.
+    #[serde(alias = "workingDir")]
+    pub working_dir: Option<String>,
+    /// External subtitle file (SRT/ASS/SSA) to burn into the video.
+    #[serde(alias = "subtitleFile")]
+    pub subtitle_file: Option<String>,
+    /// Embedded subtitle track (subtitle-relative index) to burn into the video.
+    #[serde(alias = "subtitleStream")]
+    pub subtitle_stream: Option<u32>,
+    /// Extra font directory used when rendering burned-in subtitles.
+    #[serde(alias = "fontsDir")]
+    pub fonts_dir: Option<String>,
+    /// Output path template relative to the output file's folder, e.g.
+    /// `{title}/Season {season}/{title} - S{season}E{episode}`.
+    #[serde(alias = "outputTemplate")]
+    pub output_template: Option<String>,
+    /// Name of a media server preset (see `get_media_server_presets`).
+    #[serde(alias = "mediaServerPreset")]
+    pub media_server_preset: Option<String>,
+    /// With a media server preset, also copy the original (surround) audio
+    /// track as a secondary stream next to the stereo AAC track.
+    #[serde(alias = "keepSurroundTrack")]
+    pub keep_surround_track: bool,
+    /// Copy the streams instead of re-encoding when their codecs already fit
+    /// the output container and nothing needs the decoded frames (e.g.
+    /// H.264/AAC MKV to MP4). Falls back to a normal encode otherwise.
+    #[serde(alias = "smartCopy")]
+    pub smart_copy: bool,
+    /// Name of a screen-recording preset (see `get_screen_recording_presets`).
+    #[serde(alias = "screenPreset")]
+    pub screen_preset: Option<String>,
+    /// Clip prepended to the output (channel intro), re-encoded to match it.
+    #[serde(alias = "introFile")]
+    pub intro_file: Option<String>,
+    /// Clip appended to the output (channel outro), re-encoded to match it.
+    #[serde(alias = "outroFile")]
+    pub outro_file: Option<String>,
+    /// Second video composited over the main one (facecam / picture-in-picture).
+    pub overlay: Option<OverlayOptions>,
+    /// Turn the input into a sped-up, silent timelapse.
+    pub timelapse: Option<TimelapseOptions>,
+    /// Text file with `hh:mm:ss Title` lines written as chapter markers.
+    #[serde(alias = "chaptersFile")]
+    pub chapters_file: Option<String>,
+    /// Generate chapter markers from detected scene changes.
+    #[serde(alias = "sceneChapters")]
+    pub scene_chapters: bool,
+    /// Global tags (title, artist, ...) written to the output.
+    pub metadata: Option<metadata::MetadataFields>,
+    /// Privacy export: drop all source metadata, chapters, data tracks and
+    /// cover art. Tags and chapters explicitly set on this job still apply.
+    #[serde(alias = "stripMetadata")]
+    pub strip_metadata: bool,
+    /// Split CPU encodes into chunks encoded by parallel FFmpeg processes.
+    #[serde(alias = "chunkedEncoding")]
+    pub chunked_encoding: bool,
+    /// Number of chunks; picked from the core count when unset.
+    #[serde(alias = "chunkCount")]
+    pub chunk_count: Option<u32>,
+    /// Keep frames on the GPU between decode and encode. Chosen automatically
+    /// when possible; `Some(false)` turns it off.
+    #[serde(alias = "fullHardware")]
+    pub full_hardware: Option<bool>,
+    /// FFmpeg decoder for the main input (e.g. `h264_cuvid`, `libdav1d`),
+    /// passed as `-c:v` before `-i`. Disables hardware decode attempts.
+    pub decoder: Option<String>,
+    /// Never try hardware decoding for this job.
+    #[serde(alias = "softwareDecode")]
+    pub software_decode: bool,
+    /// Set when the output path is the input itself: the job writes to a
+    /// staging file that replaces this path once the output validated.
+    #[serde(skip)]
+    pub replaces_input: Option<String>,
+    /// Wait until the input's size stopped changing for this many seconds
+    /// before starting (for files still being copied in).
+    #[serde(alias = "waitUntilStable")]
+    pub wait_until_stable: Option<u64>,
+    /// Convert a file that is still being written (e.g. a running capture),
+    /// reading on until no new data arrived for `growing_stall_secs`.
+    #[serde(alias = "growingFile")]
+    pub growing_file: bool,
+    #[serde(alias = "growingStallSecs")]
+    pub growing_stall_secs: Option<u64>,
+    /// Copy subtitles, .nfo files and artwork that share the input's name to
+    /// the output folder, renamed to match the output.
+    #[serde(alias = "carrySidecars")]
+    pub carry_sidecars: bool,
+    /// Keep only audio tracks tagged with these languages (e.g. `eng`,
+    /// `jpn`), checked against each input's own tags. Empty keeps all.
+    #[serde(alias = "audioLanguages")]
+    pub audio_languages: Vec<String>,
+    /// Source video stream (`0:v:N`) to encode instead of the first one.
+    #[serde(alias = "videoStream")]
+    pub video_stream: Option<usize>,
+    /// Source audio streams (`0:a:N`) to keep, in output order. Takes
+    /// precedence over `audio_languages`; empty keeps the default mapping.
+    #[serde(alias = "audioStreams")]
+    pub audio_streams: Vec<usize>,
+    /// Carry the source's subtitle tracks into the output (converted to the
+    /// container's text format where needed) with their default/forced flags.
+    #[serde(alias = "copySubtitles")]
+    pub copy_subtitles: bool,
+    /// Turn picture-based subtitle tracks (PGS/VobSub) into SRT with the
+    /// configured OCR tool when they are burned in or the output container
+    /// can't carry them.
+    #[serde(alias = "ocrSubtitles")]
+    pub ocr_subtitles: bool,
+    /// OCR tool from the settings; set when the job starts.
+    #[serde(skip)]
+    pub ocr_tool: Option<ocr::OcrTool>,
+    /// Tracks the OCR phase produced, muxed as extra inputs.
+    #[serde(skip)]
+    pub ocr_tracks: Vec<ocr::OcrSubtitle>,
+    /// Source subtitle track (`0:s:N`) to mark as default instead of the
+    /// source's own flags.
+    #[serde(alias = "defaultSubtitle")]
+    pub default_subtitle: Option<usize>,
+    /// Source subtitle track to mark as forced; detected from flags and
+    /// track titles ("Signs", "Forced") when unset.
+    #[serde(alias = "forcedSubtitle")]
+    pub forced_subtitle: Option<usize>,
+    /// Language and title tags for output tracks.
+    #[serde(alias = "trackMetadata")]
+    pub track_metadata: Vec<tracks::TrackMetadata>,
+    /// Sample-accurate trim, fades and gain for audio-only outputs.
+    #[serde(alias = "audioEdit")]
+    pub audio_edit: Option<AudioEditOptions>,
+    /// Measure audio-only outputs and write ReplayGain (R128 for Opus) tags.
+    #[serde(alias = "replayGain")]
+    pub replay_gain: bool,
+    /// Normalize audio to a loudness target with a measuring first pass.
+    #[serde(alias = "normalizeAudio")]
+    pub normalize_audio: Option<loudness::NormalizeOptions>,
+    /// First-pass measurement; set by the analysis phase.
+    #[serde(skip)]
+    pub loudnorm_stats: Option<loudness::LoudnormStats>,
+    /// Write closed captions and teletext found in the source to SRT files
+    /// next to the output.
+    #[serde(alias = "extractCaptions")]
+    pub extract_captions: bool,
+    /// Speed/altitude overlay from a GPX/CSV sidecar or GoPro GPMF.
+    pub telemetry: Option<telemetry::TelemetryOptions>,
+    /// CRF/CQ, bitrate limits and two-pass mode for the video encoder.
+    #[serde(alias = "rateControl")]
+    pub rate_control: Option<ratecontrol::RateControl>,
+    /// Fit the output under this size (MB), deriving the video bitrate from
+    /// the duration and running two passes where the encoder supports it.
+    #[serde(alias = "targetSizeMb")]
+    pub target_size_mb: Option<f64>,
+    /// Audio bitrate in kbit/s for the container's default audio encoder.
+    #[serde(alias = "audioBitrate")]
+    pub audio_bitrate: Option<u32>,
+    /// FFmpeg installation or minimum version the job's preset was
+    /// validated against.
+    #[serde(alias = "ffmpegRequirement")]
+    pub ffmpeg_requirement: Option<requirement::FfmpegRequirement>,
+    /// Also write the audio to its own file from the same decode.
+    #[serde(alias = "audioExport")]
+    pub audio_export: Option<audio_export::AudioExportOptions>,
+    /// More files encoded from the same decode of the input, each with its
+    /// own codecs (e.g. a ProRes master next to the MP4 delivery).
+    #[serde(alias = "extraOutputs")]
+    pub extra_outputs: Vec<multi_output::OutputSpec>,
+    /// Folder that collects inputs whose conversion failed after all retries,
+    /// with a CSV report. Defaults to the `quarantine_dir` setting.
+    #[serde(alias = "quarantineDir")]
+    pub quarantine_dir: Option<String>,
+    /// Move failed inputs into the quarantine folder instead of linking them.
+    #[serde(alias = "quarantineMove")]
+    pub quarantine_move: bool,
+    /// Caller's own ID for the job, echoed in its progress so upstream
+    /// systems can correlate.
+    #[serde(alias = "externalId")]
+    pub external_id: Option<String>,
+    /// Submitting again with the same key returns the existing job instead
+    /// of starting a duplicate (unless that job failed or was cancelled).
+    #[serde(alias = "idempotencyKey")]
+    pub idempotency_key: Option<String>,
+    /// Raw FFmpeg options placed before the main input, checked by
+    /// `passthrough::validate`.
+    #[serde(alias = "extraInputArgs")]
+    pub extra_input_args: Vec<String>,
+    /// Raw FFmpeg options placed before the output file.
+    #[serde(alias = "extraOutputArgs")]
+    pub extra_output_args: Vec<String>,
+    /// "Maximum compatibility" profile for when other settings keep
+    /// failing; see `safe_mode::apply`.
+    #[serde(alias = "safeMode")]
+    pub safe_mode: bool,
+    /// Encode with the closest encoder this FFmpeg build has when it lacks
+    /// the requested one, instead of failing.
+    #[serde(alias = "substituteEncoder")]
+    pub substitute_encoder: bool,
+    /// Substitution made for this job, written to its log when it starts.
+    #[serde(skip)]
+    pub encoder_substitution: Option<String>,
+    /// Demuxer for the main input (`-f`), for piped data FFmpeg can't
+    /// detect on its own, e.g. `matroska` or `rawvideo`.
+    #[serde(alias = "inputFormat")]
+    pub input_format: Option<String>,
+    /// Length of a piped input in seconds, when the sender knows it. Pipes
+    /// can't be probed, so without it progress shows only the elapsed time.
+    #[serde(alias = "inputDuration")]
+    pub input_duration: Option<f64>,
+    /// Free-form labels such as a client or project, kept in the history
+    /// for filtering and reports.
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimelapseOptions {
+    /// Keep every Nth frame; takes precedence over `speed`.
+    #[serde(alias = "everyNth")]
+    pub every_nth: Option<u32>,
+    /// Target speed-up factor (e.g. 60 for one minute per second).
+    pub speed: Option<f64>,
+    /// Smooth out exposure flicker between frames (day/night, auto exposure).
+    pub deflicker: bool,
+}
+
+impl TimelapseOptions {
+    /// How many times faster the output plays than the input.
+    pub fn speed_factor(&self) -> f64 {
+        match self.every_nth.filter(|n| *n > 1) {
+            Some(n) => n as f64,
+            None => self.speed.filter(|s| *s > 1.0).unwrap_or(1.0),
+        }
+    }
+}
+
+/// Precise edits for audio-only outputs. Times are on the input timeline
+/// (after `trim_start`) in milliseconds and are applied with `atrim`, so
+/// cuts land on the exact sample instead of the nearest packet.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioEditOptions {
+    #[serde(alias = "startMs")]
+    pub start_ms: Option<u64>,
+    #[serde(alias = "endMs")]
+    pub end_ms: Option<u64>,
+    #[serde(alias = "fadeInMs")]
+    pub fade_in_ms: Option<u64>,
+    #[serde(alias = "fadeOutMs")]
+    pub fade_out_ms: Option<u64>,
+    /// `afade` curve (`tri`, `qsin`, `exp`, `log`, ...); linear when unset.
+    #[serde(alias = "fadeCurve")]
+    pub fade_curve: Option<String>,
+    /// Gain in dB; negative values attenuate.
+    #[serde(alias = "gainDb")]
+    pub gain_db: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AspectMode {
+    /// Resample to square pixels at the source height, so every player shows
+    /// the right shape.
+    #[default]
+    SquarePixels,
+    /// Keep the source resolution and pixel aspect flag.
+    Preserve,
+}
+
+/// Crop position at a point of the output timeline. Positions run from 0
+/// (left/top edge) to 1 (right/bottom edge).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ReframeKeyframe {
+    pub time: f64,
+    pub x: f64,
+    #[serde(default = "ReframeKeyframe::center")]
+    pub y: f64,
+}
+
+impl ReframeKeyframe {
+    fn center() -> f64 {
+        0.5
+    }
+}
+
+/// Crop-zoom for vertical/square social cuts from horizontal masters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReframeOptions {
+    /// Target aspect ratio as `W:H`.
+    pub aspect: String,
+    /// Static crop position (0-1) when there are no keyframes.
+    pub x: f64,
+    pub y: f64,
+    /// Pan positions over time, interpolated linearly; times are seconds of
+    /// the output clip.
+    pub keyframes: Vec<ReframeKeyframe>,
+}
+
+impl Default for ReframeOptions {
+    fn default() -> Self {
+        Self {
+            aspect: "9:16".to_string(),
+            x: 0.5,
+            y: 0.5,
+            keyframes: Vec::new(),
+        }
+    }
+}
+
+/// Exact output frame size for delivery specs: the picture is scaled to fit
+/// and padded with `color`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FitOptions {
+    pub width: u32,
+    pub height: u32,
+    /// FFmpeg color name or hex value (default black).
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OverlayCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// Picture-in-picture settings. The overlay is assumed to be recorded in sync
+/// with the main input, so it is trimmed together with it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayOptions {
+    pub file: String,
+    pub corner: OverlayCorner,
+    /// Explicit top-left position in pixels; overrides `corner`.
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    /// Overlay width as a fraction of the main video width (default 0.25).
+    pub size: Option<f64>,
+    /// Distance from the chosen corner in pixels (default 20).
+    pub margin: Option<u32>,
+    #[serde(alias = "borderWidth")]
+    pub border_width: u32,
+    /// FFmpeg color name or hex value (default white).
+    #[serde(alias = "borderColor")]
+    pub border_color: Option<String>,
+    /// Output time range (seconds) in which the overlay is shown.
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+impl ConversionOptions {
+    pub fn burns_subtitles(&self) -> bool {
+        self.subtitle_file.is_some() || self.subtitle_stream.is_some()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConversionStatus {
     Pending,
@@ -649,15 +1315,260 @@ pub enum ConversionStatus {
     Cancelled,
 }
 
+/// Stage of a running job, so long probes and validations don't look stuck.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConversionPhase {
+    Probing,
+    SubtitleOcr,
+    LoudnessAnalysis,
+    Encoding,
+    Validating,
+    Finalizing,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversionProgress {
     pub task_id: String,
     pub status: ConversionStatus,
+    pub phase: Option<ConversionPhase>,
     pub percentage: f64,
     pub current_time: f64,
     pub duration: f64,
     pub log: Vec<String>,
     pub error_message: Option<String>,
+    /// FFmpeg runs so far, including fallback retries.
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    /// Place among the queue's waiting jobs, from 1 (next to start).
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+    /// Estimated seconds until a waiting job starts.
+    #[serde(default)]
+    pub estimated_start_secs: Option<f64>,
+    /// Seconds left at the current encode speed; `None` until FFmpeg
+    /// reports a speed or while the duration is unknown.
+    #[serde(default)]
+    pub eta_seconds: Option<f64>,
+    /// Media seconds encoded per second of wall time (FFmpeg's `speed=`).
+    #[serde(default)]
+    pub speed: Option<f64>,
+    #[serde(default)]
+    pub fps: Option<f64>,
+    #[serde(default)]
+    pub bitrate_kbps: Option<f64>,
+    #[serde(default)]
+    pub frames_encoded: Option<u64>,
+}
+
+impl ConversionProgress {
+    /// Progress of a queue job that hasn't started; `task_id` is the queue
+    /// job ID.
+    pub fn queued(
+        job_id: String,
+        queue_position: usize,
+        estimated_start_secs: Option<f64>,
+        external_id: Option<String>,
+    ) -> Self {
+        Self {
+            task_id: job_id,
+            status: ConversionStatus::Pending,
+            phase: None,
+            percentage: 0.0,
+            current_time: 0.0,
+            duration: 0.0,
+            log: Vec::new(),
+            error_message: None,
+            attempts: 0,
+            external_id,
+            queue_position: Some(queue_position),
+            estimated_start_secs,
+            eta_seconds: None,
+            speed: None,
+            fps: None,
+            bitrate_kbps: None,
+            frames_encoded: None,
+        }
+    }
+
+    /// Mark the job as running in `phase`, unless it was cancelled meanwhile.
+    fn enter_phase(&mut self, phase: ConversionPhase) {
+        if !matches!(self.status, ConversionStatus::Cancelled) {
+            self.status = ConversionStatus::Running;
+            self.phase = Some(phase);
+        }
+    }
+
+    /// Record the latest output time reported by FFmpeg.
+    fn advance(&mut self, current_time: f64) {
+        self.current_time = current_time.max(self.current_time);
+        if self.duration > 0.0 {
+            self.percentage = (self.current_time / self.duration * 100.0).min(100.0);
+        }
+        self.eta_seconds = self
+            .speed
+            .filter(|_| self.duration > 0.0)
+            .map(|speed| (self.duration - self.current_time).max(0.0) / speed);
+    }
+}
+
+/// How long FFmpeg gets to finalize the output after `q` before it is killed.
+const GRACEFUL_QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cancellation state kept outside the task mutex. The runner holds that
+/// lock for every line of FFmpeg output, so cancelling must not depend on it.
+#[derive(Debug, Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+    pids: Mutex<Vec<u32>>,
+    /// Stdin of processes that can be asked to quit, by PID.
+    quit_pipes: Mutex<HashMap<u32, ChildStdin>>,
+}
+
+impl CancelToken {
+    /// Flag the job as cancelled and stop its processes. Encodes with a quit
+    /// pipe get `q` so FFmpeg finalizes the container (an MP4 killed
+    /// mid-write has no moov atom) and are killed only if they don't exit in
+    /// time; everything else is killed right away.
+    pub fn cancel(self: &Arc<Self>) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let mut pipes = self.quit_pipes.lock().map(|mut p| std::mem::take(&mut *p)).unwrap_or_default();
+        let pids: Vec<u32> = self.pids.lock().map(|p| p.clone()).unwrap_or_default();
+        for pid in pids {
+            match pipes.remove(&pid) {
+                Some(stdin) => {
+                    let token = self.clone();
+                    tauri::async_runtime::spawn(async move { token.quit(pid, stdin).await });
+                }
+                None => {
+                    self.unregister(pid);
+                    kill_process(pid);
+                }
+            }
+        }
+    }
+
+    /// Flag the job as cancelled and kill every process it is running, e.g.
+    /// when the app closes and can't wait for FFmpeg.
+    pub fn kill(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Ok(mut pipes) = self.quit_pipes.lock() {
+            pipes.clear();
+        }
+        let pids: Vec<u32> = self.pids.lock().map(|mut p| p.drain(..).collect()).unwrap_or_default();
+        for pid in pids {
+            kill_process(pid);
+        }
+    }
+
+    async fn quit(&self, pid: u32, mut stdin: ChildStdin) {
+        info!("Asking FFmpeg (pid {}) to quit", pid);
+        let _ = stdin.write_all(b"q").await;
+        let _ = stdin.flush().await;
+        let deadline = std::time::Instant::now() + GRACEFUL_QUIT_TIMEOUT;
+        // The runner unregisters the process once it has exited.
+        while std::time::Instant::now() < deadline {
+            if !self.pids.lock().is_ok_and(|p| p.contains(&pid)) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        warn!("FFmpeg (pid {}) didn't quit within {:?}; killing it", pid, GRACEFUL_QUIT_TIMEOUT);
+        self.unregister(pid);
+        kill_process(pid);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Let `cancel` stop process `pid` with `q` on its stdin. Register this
+    /// before the PID itself.
+    fn register_quit_pipe(&self, pid: u32, stdin: ChildStdin) {
+        if let Ok(mut pipes) = self.quit_pipes.lock() {
+            pipes.insert(pid, stdin);
+        }
+    }
+
+    /// Track a process of this job. One started after cancellation is killed
+    /// right away.
+    fn register(&self, pid: u32) {
+        if let Ok(mut pids) = self.pids.lock() {
+            pids.push(pid);
+        }
+        if self.is_cancelled() {
+            self.kill();
+        }
+    }
+
+    fn unregister(&self, pid: u32) {
+        if let Ok(mut pids) = self.pids.lock() {
+            pids.retain(|p| *p != pid);
+        }
+        if let Ok(mut pipes) = self.quit_pipes.lock() {
+            pipes.remove(&pid);
+        }
+    }
+}
+
+/// What to convert and how, as handed to `FfmpegManager::start_conversion`.
+#[derive(Debug, Clone)]
+pub struct ConversionRequest {
+    pub input_file: String,
+    pub output_file: String,
+    pub ffmpeg_path: String,
+    pub encoder: String,
+    pub gpu_index: Option<u32>,
+    pub cpu_threads: Option<u32>,
+    pub preset: String,
+    pub is_adobe_preset: bool,
+    pub options: ConversionOptions,
+}
+
+impl ConversionRequest {
+    /// How the job is expected to run: `chunked` (parallel CPU chunks),
+    /// `hardware` (GPU encode, falling back to the CPU on failure) or
+    /// `software`.
+    pub fn pipeline(&self) -> &'static str {
+        let (_, format_info) = output_format(&self.output_file);
+        let (_, _, encoder) = preset_encoder(&self.options, self.encoder.clone());
+        let chunked = self.options.chunked_encoding
+            && chunked::ineligible_reason(&self.options, &encoder, &format_info, self.is_adobe_preset).is_none();
+        if chunked {
+            "chunked"
+        } else if !self.is_adobe_preset && ["nvenc", "amf", "qsv"].iter().any(|hw| encoder.contains(hw)) {
+            "hardware"
+        } else {
+            "software"
+        }
+    }
+
+    /// Expected output length in seconds: the probed input with the trim
+    /// and timelapse speed-up applied. `None` for growing files and inputs
+    /// that can't be probed.
+    pub async fn estimated_duration(&self) -> Option<f64> {
+        if self.options.growing_file {
+            return None;
+        }
+        if pipe_input::is_pipe(&self.input_file) {
+            return self.options.input_duration;
+        }
+        let duration = probe_info(&self.ffmpeg_path, &self.input_file).await?.duration?;
+        let mut length = (duration - self.options.trim_start.unwrap_or(0.0)).max(0.0);
+        if let Some(trim) = self.options.trim_duration.filter(|d| *d > 0.0) {
+            length = length.min(trim);
+        }
+        if let Some(ref timelapse) = self.options.timelapse {
+            length /= timelapse.speed_factor();
+        }
+        Some(length)
+    }
+
+    /// Where the output ends up; in-place jobs write to a staging file first.
+    pub fn final_output(&self) -> &str {
+        self.options.replaces_input.as_deref().unwrap_or(&self.output_file)
+    }
 }
 
 pub struct ConversionTask {
@@ -671,36 +1582,44 @@ pub struct ConversionTask {
     pub preset: String,
     pub is_adobe_preset: bool,
     pub adobe_preset: Option<AdobePreset>,
+    pub options: ConversionOptions,
     pub progress: ConversionProgress,
     pub process: Option<Child>,
     pub pid: Option<u32>,
+    pub cancel: Arc<CancelToken>,
+    /// Progress events for the frontend; `None` before the app is set up.
+    pub emitter: Option<ProgressEmitter>,
+    pub started_at: Instant,
+    /// Input size when the task was created, before a replace-in-place.
+    pub input_bytes: Option<u64>,
+    /// The output came from a hardware encoder, not the CPU fallback.
+    pub hardware_encode: bool,
+    /// Encoder of the attempt that produced the output.
+    pub encoder_used: Option<String>,
 }
 
-pub struct FfmpegManager {
-    tasks: HashMap<String, Arc<Mutex<ConversionTask>>>,
+/// What a task ran, for the history.
+#[derive(Debug, Clone)]
+pub struct TaskSummary {
+    pub input_file: String,
+    /// Encoder that produced the output, or the requested one; `None` for
+    /// prepared jobs.
+    pub encoder: Option<String>,
 }
 
-impl FfmpegManager {
-    pub fn new() -> Self {
-        Self {
-            tasks: HashMap::new(),
-        }
-    }
-
-    pub fn start_conversion(
-        &mut self,
-        task_id: String,
-        input_file: String,
-        output_file: String,
-        ffmpeg_path: String,
-        encoder: String,
-        gpu_index: Option<u32>,
-        cpu_threads: Option<u32>,
-        preset: String,
-        is_adobe_preset: bool,
-    ) -> Result<(), AppError> {
-        let duration = 0.0;
-
+impl ConversionTask {
+    pub fn new(id: String, request: ConversionRequest, emitter: Option<ProgressEmitter>) -> Self {
+        let ConversionRequest {
+            input_file,
+            output_file,
+            ffmpeg_path,
+            encoder,
+            gpu_index,
+            cpu_threads,
+            preset,
+            is_adobe_preset,
+            options,
+        } = request;
         let adobe_preset = if is_adobe_preset {
             get_adobe_presets().into_iter().find(|p| p.name == preset)
         } else {
@@ -708,96 +1627,430 @@ impl FfmpegManager {
         };
 
         let progress = ConversionProgress {
-            task_id: task_id.clone(),
+            task_id: id.clone(),
             status: ConversionStatus::Pending,
+            phase: None,
             percentage: 0.0,
             current_time: 0.0,
-            duration,
+            duration: options.trim_duration.unwrap_or(0.0),
             log: Vec::new(),
             error_message: None,
+            attempts: 0,
+            external_id: options.external_id.clone(),
+            queue_position: None,
+            estimated_start_secs: None,
+            eta_seconds: None,
+            speed: None,
+            fps: None,
+            bitrate_kbps: None,
+            frames_encoded: None,
         };
 
-        let task = ConversionTask {
-            id: task_id.clone(),
-            input_file: input_file.clone(),
-            output_file: output_file.clone(),
-            ffmpeg_path: ffmpeg_path.clone(),
-            encoder: encoder.clone(),
+        let input_bytes = std::fs::metadata(&input_file).ok().map(|m| m.len());
+
+        Self {
+            id,
+            input_file,
+            output_file,
+            ffmpeg_path,
+            encoder,
             gpu_index,
             cpu_threads,
-            preset: preset.clone(),
+            preset,
             is_adobe_preset,
             adobe_preset,
+            options,
             progress,
             process: None,
             pid: None,
-        };
+            cancel: Arc::new(CancelToken::default()),
+            emitter,
+            started_at: Instant::now(),
+            input_bytes,
+            hardware_encode: false,
+            encoder_used: None,
+        }
+    }
+
+    fn request(&self) -> ConversionRequest {
+        ConversionRequest {
+            input_file: self.input_file.clone(),
+            output_file: self.output_file.clone(),
+            ffmpeg_path: self.ffmpeg_path.clone(),
+            encoder: self.encoder.clone(),
+            gpu_index: self.gpu_index,
+            cpu_threads: self.cpu_threads,
+            preset: self.preset.clone(),
+            is_adobe_preset: self.is_adobe_preset,
+            options: self.options.clone(),
+        }
+    }
+
+    /// Record the latest output time and push a throttled progress event.
+    fn advance(&mut self, current_time: f64) {
+        self.progress.advance(current_time);
+        if let Some(ref mut emitter) = self.emitter {
+            emitter.throttled(&self.progress);
+        }
+    }
+}
+
+/// Push a finished task's final progress, which throttling must not drop.
+fn emit_final_progress(task_arc: &Arc<Mutex<ConversionTask>>) {
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    let task = &mut *task;
+    if let Some(ref mut emitter) = task.emitter {
+        emitter.emit(&task.progress);
+    }
+}
+
+/// Outcome of `FfmpegManager::claim_idempotency_key`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyClaim {
+    /// The key is now held by the caller's submission.
+    Claimed,
+    /// A live task was already submitted with the key.
+    Existing(String),
+    /// Another submission holds the key and hasn't started its task yet.
+    InFlight,
+}
+
+pub struct FfmpegManager {
+    tasks: HashMap<String, Arc<Mutex<ConversionTask>>>,
+    cancel_tokens: HashMap<String, Arc<CancelToken>>,
+    /// Idempotency key -> task ID of the job submitted with it.
+    idempotency_keys: HashMap<String, String>,
+    app_handle: Option<tauri::AppHandle>,
+}
+
+impl FfmpegManager {
+    pub fn new() -> Self {
+        Self {
+            tasks: HashMap::new(),
+            cancel_tokens: HashMap::new(),
+            idempotency_keys: HashMap::new(),
+            app_handle: None,
+        }
+    }
+
+    /// Let tasks started from now on emit `conversion-progress` events.
+    pub fn set_app_handle(&mut self, app_handle: tauri::AppHandle) {
+        self.app_handle = Some(app_handle);
+    }
 
+    pub fn start_conversion(&mut self, task_id: String, request: ConversionRequest) -> Result<(), AppError> {
+        if pipe_input::is_stdin(&request.input_file) && self.reading_stdin() {
+            return Err(AppError::Internal("Another job is already reading from stdin".to_string()));
+        }
+        let task = ConversionTask::new(task_id.clone(), request, self.app_handle.clone().map(ProgressEmitter::new));
+
+        if let Some(key) = task.options.idempotency_key.clone() {
+            self.idempotency_keys.insert(key, task_id.clone());
+        }
+        self.cancel_tokens.insert(task_id.clone(), task.cancel.clone());
         let task_arc = Arc::new(Mutex::new(task));
         self.tasks.insert(task_id.clone(), task_arc.clone());
 
         tokio::spawn(async move {
-            run_conversion_task(task_arc).await;
+            run_conversion_task(task_arc.clone()).await;
+            finish_staged_output(&task_arc);
+            carry_sidecars(&task_arc);
+            quarantine_failed_input(&task_arc);
+            record_outcome(&task_arc);
+            emit_final_progress(&task_arc);
+            let _ = std::fs::remove_file(segments::concat_list_path(&task_id));
+            let _ = std::fs::remove_file(chapters::metadata_path(&task_id));
+            let _ = std::fs::remove_dir_all(ocr::work_dir(&task_id));
         });
 
         Ok(())
     }
 
-    pub fn get_progress(&self, task_id: &str) -> Option<ConversionProgress> {
-        self.tasks.get(task_id).map(|t| {
-            let task = t.lock().unwrap();
-            task.progress.clone()
+    /// Whether an unfinished job takes its input from stdin.
+    fn reading_stdin(&self) -> bool {
+        self.tasks.values().any(|task| {
+            let task = task.lock().expect("Failed to lock task mutex");
+            pipe_input::is_stdin(&task.input_file)
+                && matches!(task.progress.status, ConversionStatus::Pending | ConversionStatus::Running)
         })
     }
 
-    pub fn cancel_conversion(&mut self, task_id: &str) -> Result<(), AppError> {
-        if let Some(task_arc) = self.tasks.get(task_id) {
-            // Use try_lock to avoid blocking if task is being processed
-            if let Ok(mut task) = task_arc.try_lock() {
-                // Only cancel if not already in a terminal state
-                if !matches!(
-                    task.progress.status,
-                    ConversionStatus::Completed | ConversionStatus::Failed(_) | ConversionStatus::Cancelled
-                ) {
-                    if let Some(ref mut child) = task.process {
-                        let _ = child.start_kill();
-                    } else if let Some(pid) = task.pid {
-                        kill_process(pid);
-                    }
+    /// Start a job whose FFmpeg arguments were fully built by the caller
+    /// (slideshows and other multi-input jobs). `duration` is the expected
+    /// output length used for progress reporting.
+    pub fn start_job(
+        &mut self,
+        task_id: String,
+        label: String,
+        output_file: String,
+        ffmpeg_path: String,
+        args: Vec<String>,
+        duration: f64,
+    ) -> Result<(), AppError> {
+        let task_arc = self.insert_job_task(task_id, label, output_file, ffmpeg_path, duration);
+        tokio::spawn(async move {
+            run_prepared_job(task_arc.clone(), args).await;
+            record_outcome(&task_arc);
+            emit_final_progress(&task_arc);
+        });
+
+        Ok(())
+    }
+
+    /// Start a verified copy of `input_file` to `output_file`; see
+    /// `transfer::CopyMode` for how each mode is checked.
+    pub fn start_copy(
+        &mut self,
+        task_id: String,
+        input_file: String,
+        output_file: String,
+        ffmpeg_path: String,
+        mode: transfer::CopyMode,
+        move_source: bool,
+    ) -> Result<(), AppError> {
+        let task_arc = self.insert_job_task(task_id, input_file, output_file, ffmpeg_path, 0.0);
+        tokio::spawn(async move {
+            transfer::run_copy(task_arc.clone(), mode, move_source).await;
+            record_outcome(&task_arc);
+            emit_final_progress(&task_arc);
+        });
+
+        Ok(())
+    }
+
+    /// Encode the renditions of an adaptive streaming ladder one after the
+    /// other and package them into `manifest`.
+    pub fn start_packaging(
+        &mut self,
+        task_id: String,
+        input_file: String,
+        manifest: String,
+        ffmpeg_path: String,
+        job: streaming::PackageJob,
+        duration: f64,
+    ) -> Result<(), AppError> {
+        let task_arc = self.insert_job_task(task_id, input_file, manifest, ffmpeg_path, duration);
+        tokio::spawn(async move {
+            streaming::run(task_arc.clone(), job).await;
+            record_outcome(&task_arc);
+            emit_final_progress(&task_arc);
+        });
+
+        Ok(())
+    }
+
+    /// Join several inputs into `output_file`; see `concat::ConcatJob`.
+    pub fn start_concat(
+        &mut self,
+        task_id: String,
+        label: String,
+        output_file: String,
+        ffmpeg_path: String,
+        job: concat::ConcatJob,
+        duration: f64,
+    ) -> Result<(), AppError> {
+        let task_arc = self.insert_job_task(task_id, label, output_file, ffmpeg_path, duration);
+        tokio::spawn(async move {
+            concat::run(task_arc.clone(), job).await;
+            record_outcome(&task_arc);
+            emit_final_progress(&task_arc);
+        });
+
+        Ok(())
+    }
+
+    /// Register a task for a job that doesn't go through `run_conversion_task`.
+    fn insert_job_task(
+        &mut self,
+        task_id: String,
+        label: String,
+        output_file: String,
+        ffmpeg_path: String,
+        duration: f64,
+    ) -> Arc<Mutex<ConversionTask>> {
+        let task = ConversionTask {
+            id: task_id.clone(),
+            input_file: label,
+            output_file,
+            ffmpeg_path,
+            encoder: String::new(),
+            gpu_index: None,
+            cpu_threads: None,
+            preset: String::new(),
+            is_adobe_preset: false,
+            adobe_preset: None,
+            options: ConversionOptions::default(),
+            progress: ConversionProgress {
+                task_id: task_id.clone(),
+                status: ConversionStatus::Pending,
+                phase: None,
+                percentage: 0.0,
+                current_time: 0.0,
+                duration,
+                log: Vec::new(),
+                error_message: None,
+                attempts: 0,
+                external_id: None,
+                queue_position: None,
+                estimated_start_secs: None,
+                eta_seconds: None,
+                speed: None,
+                fps: None,
+                bitrate_kbps: None,
+                frames_encoded: None,
+            },
+            process: None,
+            pid: None,
+            cancel: Arc::new(CancelToken::default()),
+            emitter: self.app_handle.clone().map(ProgressEmitter::new),
+            started_at: Instant::now(),
+            input_bytes: None,
+            hardware_encode: false,
+            encoder_used: None,
+        };
+
+        self.cancel_tokens.insert(task_id.clone(), task.cancel.clone());
+        let task_arc = Arc::new(Mutex::new(task));
+        self.tasks.insert(task_id, task_arc.clone());
+        task_arc
+    }
 
-                    task.progress.status = ConversionStatus::Cancelled;
+    /// Reserve `key` for the submission that will start `task_id`, unless a
+    /// live task or another submission still being resolved holds it.
+    pub fn claim_idempotency_key(&mut self, key: &str, task_id: &str) -> KeyClaim {
+        match self.idempotency_keys.get(key) {
+            Some(holder) if !self.tasks.contains_key(holder) => return KeyClaim::InFlight,
+            Some(_) => {
+                if let Some(existing) = self.task_for_idempotency_key(key) {
+                    return KeyClaim::Existing(existing);
                 }
-                Ok(())
-            } else {
-                // Task is locked, just mark it for cancellation by killing the process
-                // This is safe because we're not accessing the process directly
-                Ok(())
             }
-        } else {
-            Err(AppError::Internal("Task not found".to_string()))
+            None => {}
         }
+        self.idempotency_keys.insert(key.to_string(), task_id.to_string());
+        KeyClaim::Claimed
     }
 
-    pub fn cancel_all(&mut self) {
-        let task_ids: Vec<String> = self.tasks.keys().cloned().collect();
+    /// Drop the claim on `key` of a submission that failed before `task_id`
+    /// started.
+    pub fn release_idempotency_key(&mut self, key: &str, task_id: &str) {
+        if self.idempotency_keys.get(key).is_some_and(|holder| holder == task_id) && !self.tasks.contains_key(task_id) {
+            self.idempotency_keys.remove(key);
+        }
+    }
 
-        // Collect PIDs first to avoid holding locks
-        for task_id in &task_ids {
-            if let Some(task_arc) = self.tasks.get(task_id) {
-                if let Ok(task) = task_arc.try_lock() {
-                    if let Some(pid) = task.pid {
-                        kill_process(pid);
-                    }
+    /// Task already submitted with `key`, unless it failed or was cancelled
+    /// (a retry with the same key then starts a new job).
+    pub fn task_for_idempotency_key(&self, key: &str) -> Option<String> {
+        let task_id = self.idempotency_keys.get(key)?;
+        let task = self.tasks.get(task_id)?;
+        let reusable = match task.try_lock() {
+            Ok(task) => !matches!(
+                task.progress.status,
+                ConversionStatus::Failed(_) | ConversionStatus::Cancelled
+            ),
+            // Locked tasks are running.
+            Err(_) => true,
+        };
+        reusable.then(|| task_id.clone())
+    }
+
+    /// Number of (pending, running) jobs.
+    pub fn job_counts(&self) -> (usize, usize) {
+        let mut pending = 0;
+        let mut running = 0;
+        for task in self.tasks.values() {
+            if let Ok(task) = task.try_lock() {
+                match task.progress.status {
+                    ConversionStatus::Pending => pending += 1,
+                    ConversionStatus::Running => running += 1,
+                    _ => {}
                 }
+            } else {
+                // Locked tasks are being updated by their runner.
+                running += 1;
+            }
+        }
+        (pending, running)
+    }
+
+    /// Progress and output file of every task not locked by its runner right now.
+    pub fn snapshots(&self) -> Vec<(ConversionProgress, String)> {
+        self.tasks
+            .values()
+            .filter_map(|task| task.try_lock().ok().map(|t| (t.progress.clone(), t.output_file.clone())))
+            .collect()
+    }
+
+    /// Seconds running and percentage done of each of `task_ids` still running.
+    pub fn running_progress(&self, task_ids: &[String]) -> Vec<(f64, f64)> {
+        task_ids
+            .iter()
+            .filter_map(|id| self.tasks.get(id)?.lock().ok())
+            .filter(|task| matches!(task.progress.status, ConversionStatus::Pending | ConversionStatus::Running))
+            .map(|task| (task.started_at.elapsed().as_secs_f64(), task.progress.percentage))
+            .collect()
+    }
+
+    pub fn task_request(&self, task_id: &str) -> Option<ConversionRequest> {
+        Some(self.tasks.get(task_id)?.lock().ok()?.request())
+    }
+
+    pub fn task_summary(&self, task_id: &str) -> Option<TaskSummary> {
+        let task = self.tasks.get(task_id)?.lock().ok()?;
+        Some(TaskSummary {
+            input_file: task.input_file.clone(),
+            encoder: task
+                .encoder_used
+                .clone()
+                .or_else(|| Some(task.encoder.clone()).filter(|e| !e.is_empty())),
+        })
+    }
+
+    pub fn get_progress(&self, task_id: &str) -> Option<ConversionProgress> {
+        self.tasks.get(task_id).map(|t| {
+            let task = t.lock().unwrap();
+            task.progress.clone()
+        })
+    }
+
+    pub fn cancel_conversion(&mut self, task_id: &str) -> Result<(), AppError> {
+        self.stop(task_id, true)
+    }
+
+    /// Cancel a task, letting FFmpeg finalize its output when `graceful`.
+    fn stop(&mut self, task_id: &str, graceful: bool) -> Result<(), AppError> {
+        let (Some(task_arc), Some(token)) = (self.tasks.get(task_id), self.cancel_tokens.get(task_id)) else {
+            return Err(AppError::Internal("Task not found".to_string()));
+        };
+
+        if let Ok(task) = task_arc.try_lock() {
+            if matches!(
+                task.progress.status,
+                ConversionStatus::Completed | ConversionStatus::Failed(_) | ConversionStatus::Cancelled
+            ) {
+                return Ok(());
             }
         }
 
-        // Wait a bit for processes to terminate
-        std::thread::sleep(std::time::Duration::from_millis(100));
+        // Stop through the token; it doesn't need the task lock. If the task
+        // is locked right now, its runner sees the token and marks itself
+        // cancelled once FFmpeg exits.
+        if graceful {
+            token.cancel();
+        } else {
+            token.kill();
+        }
+        if let Ok(mut task) = task_arc.try_lock() {
+            task.progress.status = ConversionStatus::Cancelled;
+        }
+        Ok(())
+    }
 
-        // Now cancel the tasks
+    pub fn cancel_all(&mut self) {
+        let task_ids: Vec<String> = self.tasks.keys().cloned().collect();
         for task_id in task_ids {
-            let _ = self.cancel_conversion(&task_id);
+            let _ = self.stop(&task_id, false);
         }
     }
 }
@@ -819,9 +2072,11 @@ fn kill_process(pid: u32) {
 }
 
 /// Translate CPU-oriented preset names to NVENC-compatible presets.
-/// NVENC only supports: default, slow, medium, fast, hp (high performance)
+/// NVENC supports p1 (fastest) to p7 (best quality) and the older default,
+/// slow, medium, fast, hp (high performance) names.
 fn translate_nvenc_preset(cpu_preset: &str) -> String {
     match cpu_preset {
+        "p1" | "p2" | "p3" | "p4" | "p5" | "p6" | "p7" => cpu_preset.to_string(),
         // Fast presets - map to NVENC's fastest
         "ultrafast" | "superfast" | "veryfast" | "faster" => "fast".to_string(),
         // Medium speed - direct mapping
@@ -837,6 +2092,240 @@ fn translate_nvenc_preset(cpu_preset: &str) -> String {
     }
 }
 
+/// Translate NVENC preset names back for x264/x265, e.g. when a GPU job
+/// falls back to the CPU encoder.
+fn translate_cpu_preset(preset: &str) -> String {
+    match preset {
+        "p1" => "veryfast".to_string(),
+        "p2" => "faster".to_string(),
+        "p3" => "fast".to_string(),
+        "p4" | "default" | "medium" | "hq" | "bd" | "ll" | "llhq" => "medium".to_string(),
+        "p5" => "slow".to_string(),
+        "p6" => "slower".to_string(),
+        "p7" | "lossless" => "veryslow".to_string(),
+        "hp" | "llhp" | "losslesshp" => "fast".to_string(),
+        _ => preset.to_string(),
+    }
+}
+
+/// Mark the task cancelled if its token was triggered. Returns `true` when
+/// the runner should stop.
+fn stop_if_cancelled(task_arc: &Arc<Mutex<ConversionTask>>) -> bool {
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    if task.cancel.is_cancelled() {
+        task.pid = None;
+        task.progress.status = ConversionStatus::Cancelled;
+        true
+    } else {
+        false
+    }
+}
+
+/// Move a staged output over the input it replaces once the job completed;
+/// drop the staging file otherwise so the source stays untouched.
+fn finish_staged_output(task_arc: &Arc<Mutex<ConversionTask>>) {
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    let Some(target) = task.options.replaces_input.clone() else {
+        return;
+    };
+    if !matches!(task.progress.status, ConversionStatus::Completed) {
+        let _ = std::fs::remove_file(&task.output_file);
+        return;
+    }
+    match std::fs::rename(&task.output_file, &target) {
+        Ok(()) => {
+            info!("Replaced {} with the converted output", target);
+            task.progress.log.push(format!("Replaced input file {}", target));
+        }
+        Err(e) => {
+            let message = format!(
+                "Converted output is at {} but replacing {} failed: {}",
+                task.output_file, target, e
+            );
+            error!("{}", message);
+            task.progress.status = ConversionStatus::Failed(message.clone());
+            task.progress.error_message = Some(message);
+        }
+    }
+}
+
+/// Copy the input's sidecar files next to a completed output.
+fn carry_sidecars(task_arc: &Arc<Mutex<ConversionTask>>) {
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    if !task.options.carry_sidecars || !matches!(task.progress.status, ConversionStatus::Completed) {
+        return;
+    }
+    let source = task.options.input_segments.first().unwrap_or(&task.input_file).clone();
+    let output = task.options.replaces_input.clone().unwrap_or_else(|| task.output_file.clone());
+    for file in sidecars::copy_sidecars(Path::new(&source), Path::new(&output)) {
+        task.progress.log.push(format!("Copied sidecar {}", file.display()));
+    }
+}
+
+/// Hand the input of a failed job to the quarantine folder, if one is set.
+fn quarantine_failed_input(task_arc: &Arc<Mutex<ConversionTask>>) {
+    let task = task_arc.lock().expect("Failed to lock task mutex");
+    let (Some(dir), ConversionStatus::Failed(error)) = (&task.options.quarantine_dir, &task.progress.status) else {
+        return;
+    };
+    let input = task.options.input_segments.first().unwrap_or(&task.input_file);
+    if pipe_input::is_pipe(input) {
+        return;
+    }
+    quarantine::quarantine(
+        Path::new(dir),
+        Path::new(input),
+        error,
+        task.progress.attempts,
+        task.options.quarantine_move,
+    );
+}
+
+/// Count a finished job in the exported metrics.
+fn record_outcome(task_arc: &Arc<Mutex<ConversionTask>>) {
+    let task = task_arc.lock().expect("Failed to lock task mutex");
+    match task.progress.status {
+        ConversionStatus::Completed => {
+            metrics::job_completed();
+            stats::record(stats::CompletedJob {
+                media_seconds: task.progress.duration,
+                input_bytes: task.input_bytes,
+                output_bytes: std::fs::metadata(&task.output_file).ok().map(|m| m.len()),
+                elapsed: task.started_at.elapsed(),
+                hardware: task.hardware_encode,
+            });
+        }
+        ConversionStatus::Failed(_) => metrics::job_failed(),
+        ConversionStatus::Cancelled => metrics::job_cancelled(),
+        _ => {}
+    }
+}
+
+/// Input codecs (as named by FFmpeg) that NVDEC, QSV and D3D11VA can all decode.
+const HW_DECODABLE_CODECS: &[&str] = &["h264", "hevc", "vp9", "av1", "mpeg2video"];
+
+/// Extracts the current output time and encode speed from FFmpeg's stats and
+/// `-progress` lines.
+struct ProgressParser {
+    fps: Regex,
+    frame: Regex,
+    bitrate: Regex,
+    speed: Regex,
+    time: Regex,
+    out_time: Regex,
+    out_time_us: Regex,
+    out_time_ms: Regex,
+}
+
+impl ProgressParser {
+    fn new() -> Self {
+        Self {
+            fps: Regex::new(r"fps=\s*(\d+(?:\.\d+)?)").expect("Invalid regex"),
+            frame: Regex::new(r"frame=\s*(\d+)").expect("Invalid regex"),
+            bitrate: Regex::new(r"bitrate=\s*(\d+(?:\.\d+)?)kbits/s").expect("Invalid regex"),
+            speed: Regex::new(r"speed=\s*(\d+(?:\.\d+)?)x").expect("Invalid regex"),
+            time: Regex::new(r"time=(\d+):(\d+):(\d+\.\d+)").expect("Invalid regex"),
+            out_time: Regex::new(r"out_time=(\d+):(\d+):(\d+\.\d+)").expect("Invalid regex"),
+            out_time_us: Regex::new(r"out_time_us=(\d+)").expect("Invalid regex"),
+            out_time_ms: Regex::new(r"out_time_ms=(\d+)").expect("Invalid regex"),
+        }
+    }
+
+    fn fps(&self, line: &str) -> Option<f64> {
+        self.fps.captures(line).and_then(|c| c[1].parse().ok())
+    }
+
+    /// Take the `frame=`, `fps=`, `bitrate=` and `speed=` reports into the
+    /// progress; `N/A` values are skipped.
+    fn stats(&self, line: &str, progress: &mut ConversionProgress) {
+        if let Some(c) = self.frame.captures(line) {
+            progress.frames_encoded = c[1].parse().ok();
+        } else if let Some(fps) = self.fps(line) {
+            progress.fps = Some(fps);
+        } else if let Some(c) = self.bitrate.captures(line) {
+            progress.bitrate_kbps = c[1].parse().ok();
+        } else if let Some(c) = self.speed.captures(line) {
+            progress.speed = c[1].parse().ok().filter(|s: &f64| *s > 0.0);
+        }
+    }
+
+    fn time(&self, line: &str) -> Option<f64> {
+        let clock = |c: regex::Captures| {
+            c[1].parse::<f64>().unwrap_or(0.0) * 3600.0
+                + c[2].parse::<f64>().unwrap_or(0.0) * 60.0
+                + c[3].parse::<f64>().unwrap_or(0.0)
+        };
+        if let Some(c) = self.time.captures(line) {
+            Some(clock(c))
+        } else if let Some(c) = self.out_time.captures(line) {
+            Some(clock(c))
+        } else if let Some(c) = self.out_time_us.captures(line) {
+            c[1].parse::<f64>().map(|us| us / 1_000_000.0).ok()
+        } else if let Some(c) = self.out_time_ms.captures(line) {
+            c[1].parse::<f64>().map(|ms| ms / 1_000_000.0).ok()
+        } else {
+            None
+        }
+    }
+}
+
+/// Where FFmpeg writes `-progress` reports. Jobs never write media to
+/// stdout, so it carries nothing else, while stderr keeps the diagnostics.
+pub const PROGRESS_PIPE: &str = "pipe:1";
+
+/// Read a running FFmpeg's progress reports (stdout) and diagnostics
+/// (stderr) side by side until both close. Both must be piped.
+async fn read_ffmpeg_output(child: &mut Child, mut on_progress: impl FnMut(&str), mut on_diagnostic: impl FnMut(String)) {
+    let stdout = child.stdout.take().expect("FFmpeg stdout stream not available");
+    let stderr = child.stderr.take().expect("FFmpeg stderr stream not available");
+    let progress = async {
+        let mut reader = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            on_progress(&line);
+        }
+    };
+    let diagnostics = async {
+        let mut reader = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            on_diagnostic(line);
+        }
+    };
+    tokio::join!(progress, diagnostics);
+}
+
+/// Read stream and duration information, through ffprobe when it sits next
+/// to FFmpeg and from `ffmpeg -i` stderr otherwise.
+pub async fn probe_info(ffmpeg_path: &str, input_file: &str) -> Option<VideoInfo> {
+    if let Some(ffprobe) = probe::ffprobe_path_for(ffmpeg_path) {
+        match probe::probe_media(&ffprobe, input_file).await {
+            Ok(media) => return Some(media.to_video_info()),
+            Err(e) => warn!("ffprobe failed for {}, falling back to FFmpeg: {}", input_file, e),
+        }
+    }
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-i", input_file])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = match watchdog::output(&mut cmd).await {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Probing {} failed: {}", input_file, e);
+            return None;
+        }
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    VideoInfo::parse(&stderr).ok()
+}
+
+/// Read the input duration from `ffmpeg -i` stderr, if FFmpeg reports one.
+async fn probe_duration(ffmpeg_path: &str, input_file: &str) -> Option<f64> {
+    probe_info(ffmpeg_path, input_file).await.and_then(|info| info.duration)
+}
+
 /// Validate that an output file is actually playable by decoding a few frames.
 /// Returns `None` if the file looks good, or `Some(reason)` if it is corrupt.
 async fn validate_output(ffmpeg_path: &str, output_file: &str) -> Option<String> {
@@ -861,9 +2350,9 @@ async fn validate_output(ffmpeg_path: &str, output_file: &str) -> Option<String>
     #[cfg(target_os = "windows")]
     cmd.creation_flags(CREATE_NO_WINDOW);
 
-    let output = match cmd.output().await {
+    let output = match watchdog::output(&mut cmd).await {
         Ok(o) => o,
-        Err(e) => return Some(format!("Validation probe failed to start: {}", e)),
+        Err(e) => return Some(format!("Validation probe failed: {}", e)),
     };
 
     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -885,173 +2374,391 @@ async fn validate_output(ffmpeg_path: &str, output_file: &str) -> Option<String>
     None
 }
 
-async fn run_conversion_task(task_arc: Arc<Mutex<ConversionTask>>) {
-    let (
-        input_file,
-        output_file,
-        ffmpeg_path,
-        encoder,
-        gpu_index,
-        cpu_threads,
-        preset,
-        is_adobe_preset,
-        adobe_preset,
-    ) = {
-        let task = task_arc.lock().expect("Failed to lock task mutex");
-        (
-            task.input_file.clone(),
-            task.output_file.clone(),
-            task.ffmpeg_path.clone(),
-            task.encoder.clone(),
-            task.gpu_index,
-            task.cpu_threads,
-            task.preset.clone(),
-            task.is_adobe_preset,
-            task.adobe_preset.clone(),
-        )
+/// Run a job started with `FfmpegManager::start_job`: a single FFmpeg
+/// invocation with progress tracking and output validation, no retries.
+async fn run_prepared_job(task_arc: Arc<Mutex<ConversionTask>>, args: Vec<String>) {
+    let (label, output_file, ffmpeg_path) = {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.enter_phase(ConversionPhase::Encoding);
+        task.progress.log.push(format!("FFmpeg args: {}", args.join(" ")));
+        (task.input_file.clone(), task.output_file.clone(), task.ffmpeg_path.clone())
     };
 
-    let output_ext = Path::new(&output_file)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("mp4")
-        .to_lowercase();
-    let format_info = get_format_info(&output_ext);
+    info!("=== FFmpeg Start ({}) ===", label);
+    debug!("Args: {:?}", args);
 
-    let is_nvenc = encoder.contains("nvenc");
-    let is_amf = encoder.contains("amf");
-    let is_qsv = encoder.contains("qsv");
-    let is_gpu_encoder = is_nvenc || is_amf || is_qsv;
-    // GPU encoders: 3 GPU attempts + 1 CPU software fallback = 4
-    // CPU encoders: 1 attempt only
-    let max_attempts: usize = if is_gpu_encoder { 4 } else { 1 };
+    let mut cmd = Command::new(&ffmpeg_path);
+    cmd.args(&args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
 
-    // Determine the CPU fallback encoder that matches the GPU codec family.
-    let cpu_fallback_encoder = if encoder.contains("h264") || encoder.contains("264") {
-        "libx264"
-    } else if encoder.contains("hevc") || encoder.contains("265") {
-        "libx265"
-    } else {
-        "libx264"
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let message = format!("Failed to start ffmpeg: {} (path: {})", e, ffmpeg_path);
+            error!("{}", message);
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.progress.status = ConversionStatus::Failed(message.clone());
+            task.progress.error_message = Some(message);
+            return;
+        }
     };
 
-    for attempt in 0..max_attempts {
-        let is_cpu_fallback = is_gpu_encoder && attempt == 3;
-        let use_hw_decode = is_gpu_encoder && attempt == 0;
-        let force_nv12 = is_gpu_encoder && attempt == 2;
-
-        // Pick the encoder for this attempt.
-        let attempt_encoder = if is_cpu_fallback {
-            cpu_fallback_encoder.to_string()
-        } else {
-            encoder.clone()
-        };
-
-        let mut args = vec![
-            "-y".to_string(),
-            "-hide_banner".to_string(),
-            "-progress".to_string(),
-            "pipe:2".to_string(),
-            "-nostats".to_string(),
-        ];
-
-        if use_hw_decode {
-            args.push("-hwaccel".to_string());
-            if is_nvenc {
-                args.push("cuda".to_string());
-                if let Some(index) = gpu_index {
-                    args.push("-hwaccel_device".to_string());
-                    args.push(index.to_string());
-                }
-            } else {
-                args.push("auto".to_string());
-            }
+    let pid = child.id();
+    {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.pid = pid;
+        if let (Some(pid), Some(stdin)) = (task.pid, child.stdin.take()) {
+            task.cancel.register_quit_pipe(pid, stdin);
         }
-
-        if let Some(threads) = cpu_threads {
-            args.push("-threads".to_string());
-            args.push(threads.to_string());
+        if let Some(pid) = task.pid {
+            task.cancel.register(pid);
         }
+    }
 
-        args.push("-i".to_string());
-        args.push(input_file.clone());
+    let progress_parser = ProgressParser::new();
+    let mut full_stderr = Vec::new();
+    read_ffmpeg_output(
+        &mut child,
+        |line| {
+            progress_parser.stats(line, &mut task_arc.lock().expect("Failed to lock task mutex").progress);
+            if let Some(current_time) = progress_parser.time(line) {
+                task_arc.lock().expect("Failed to lock task mutex").advance(current_time);
+            }
+            if let Some(fps) = progress_parser.fps(line) {
+                metrics::record_fps(fps);
+            }
+        },
+        |line| {
+            full_stderr.push(line.clone());
+            task_arc.lock().expect("Failed to lock task mutex").progress.log.push(line);
+        },
+    )
+    .await;
 
-        if format_info.supports_video {
-            // Map only the first video stream to avoid picking up embedded
-            // thumbnails / cover art (e.g. MJPEG attached pics) that would
-            // cause container errors when re-encoded.
-            args.push("-map".to_string());
-            args.push("0:v:0?".to_string());
+    let status = child.wait().await;
+    if let Some(pid) = pid {
+        task_arc.lock().expect("Failed to lock task mutex").cancel.unregister(pid);
+    }
+    if stop_if_cancelled(&task_arc) {
+        return;
+    }
+    let failure = match status {
+        Ok(exit_status) if exit_status.success() => {
+            task_arc
+                .lock()
+                .expect("Failed to lock task mutex")
+                .progress
+                .enter_phase(ConversionPhase::Validating);
+            validate_output(&ffmpeg_path, &output_file)
+                .await
+                .map(|problem| format!("Conversion produced corrupt output: {}", problem))
         }
-        if format_info.supports_audio {
-            args.push("-map".to_string());
-            if format_info.supports_video {
-                args.push("0:a?".to_string());
-            } else {
-                args.push("0:a:0?".to_string());
-            }
+        Ok(exit_status) => {
+            error!("FFmpeg stderr: \n{}", full_stderr.join("\n"));
+            Some(format!(
+                "FFmpeg exited with code: {}",
+                exit_status.code().map_or("None".to_string(), |c| c.to_string())
+            ))
         }
+        Err(e) => Some(format!("Failed to wait for FFmpeg process: {}", e)),
+    };
 
-        if is_adobe_preset && !is_cpu_fallback {
-            if let Some(ref preset_config) = adobe_preset {
-                args.push("-c:v".to_string());
-                args.push(preset_config.encoder.clone());
-                args.extend(preset_config.encoder_options.iter().cloned());
-                args.push("-pix_fmt".to_string());
-                args.push(preset_config.pixel_format.clone());
-                if preset_config.encoder == "prores_ks" || preset_config.encoder == "dnxhd" {
-                    args.push("-c:a".to_string());
-                    args.push("pcm_s16le".to_string());
-                }
-            }
-        } else {
-            if format_info.supports_video {
-                args.push("-c:v".to_string());
-                args.push(attempt_encoder.clone());
-                if is_nvenc && !is_cpu_fallback {
-                    args.push("-preset".to_string());
-                    args.push(translate_nvenc_preset(&preset));
-                } else if attempt_encoder == "libx264" || attempt_encoder == "libx265" {
-                    args.push("-preset".to_string());
-                    args.push(preset.clone());
-                }
-                if is_nvenc && !is_cpu_fallback {
-                    if let Some(index) = gpu_index {
-                        args.push("-gpu".to_string());
-                        args.push(index.to_string());
-                    }
-                }
-                if force_nv12 {
-                    args.push("-pix_fmt".to_string());
-                    args.push("nv12".to_string());
-                }
-            }
-            if format_info.supports_audio {
-                args.push("-c:a".to_string());
-                if format_info.default_audio_codec.is_empty() {
-                    args.push("copy".to_string());
-                } else {
-                    args.push(format_info.default_audio_codec.to_string());
-                }
-            }
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    task.pid = None;
+    if task.cancel.is_cancelled() {
+        task.progress.status = ConversionStatus::Cancelled;
+        return;
+    }
+    match failure {
+        Some(message) => {
+            error!("{} for {}", message, label);
+            task.progress.status = ConversionStatus::Failed(message.clone());
+            task.progress.error_message = Some(message);
         }
-
-        // Place the moov atom at the start of MP4/MOV files so players can
-        // open the file without reading until the very end.
-        if output_ext == "mp4" || output_ext == "mov" || output_ext == "m4a" {
-            args.push("-movflags".to_string());
-            args.push("+faststart".to_string());
+        None => {
+            info!("Job completed and validated for {}", label);
+            task.progress.status = ConversionStatus::Completed;
+            task.progress.percentage = 100.0;
         }
+    }
+}
 
-        args.push(output_file.clone());
+/// What decides whether decoded frames can stay on the GPU until the
+/// encoder takes them.
+struct HardwarePath<'a> {
+    encoder: &'a str,
+    options: &'a ConversionOptions,
+    supports_video: bool,
+    compositing: bool,
+    video_filters: bool,
+    /// `-pix_fmt` a media or screen preset sets for this encoder.
+    preset_pixel_format: Option<&'a str>,
+    skip_hw_decode: bool,
+    hwaccel: Option<&'static str>,
+    input_codec: Option<&'a str>,
+}
+
+impl HardwarePath<'_> {
+    /// Why the full hardware pipeline can't be used; empty when it can.
+    /// Anything converting frames in system memory makes FFmpeg fail on
+    /// GPU frames instead of falling back by itself.
+    fn blockers(&self) -> Vec<String> {
+        let mut blockers = Vec::new();
+        if self.compositing {
+            blockers.push("compositing runs on the CPU".to_string());
+        } else if self.video_filters {
+            blockers.push("video filters run on the CPU".to_string());
+        }
+        if !self.supports_video {
+            blockers.push("audio-only output".to_string());
+        }
+        if !self.options.extra_outputs.is_empty() {
+            blockers.push("extra outputs are encoded from system memory".to_string());
+        }
+        if let Some(format) = self.preset_pixel_format {
+            blockers.push(format!("the preset converts frames to {} on the CPU", format));
+        }
+        if self.options.extra_output_args.iter().any(|a| a == "-pix_fmt" || a.starts_with("-pix_fmt:")) {
+            blockers.push("a custom -pix_fmt converts frames on the CPU".to_string());
+        }
+        if self.skip_hw_decode {
+            blockers.push("hardware decoding disabled".to_string());
+        } else if self.hwaccel != gpu::native_hwaccel(self.encoder) {
+            blockers.push(format!(
+                "this FFmpeg build lacks {} decoding",
+                gpu::native_hwaccel(self.encoder).unwrap_or("native")
+            ));
+        }
+        match self.input_codec {
+            Some(codec) if HW_DECODABLE_CODECS.contains(&codec) => {}
+            Some(codec) => blockers.push(format!("no hardware decoder for {}", codec)),
+            None => blockers.push("input codec unknown".to_string()),
+        }
+        blockers
+    }
+}
+
+/// Everything the FFmpeg arguments of a conversion depend on, worked out
+/// once per job by `plan_conversion`.
+struct ConversionPlan {
+    task_id: String,
+    input_file: String,
+    output_file: String,
+    ffmpeg_path: String,
+    output_ext: String,
+    format_info: FormatInfo,
+    encoder: String,
+    gpu_index: Option<u32>,
+    cpu_threads: Option<u32>,
+    preset: String,
+    is_adobe_preset: bool,
+    adobe_preset: Option<AdobePreset>,
+    options: ConversionOptions,
+    media_preset: Option<MediaServerPreset>,
+    screen_preset: Option<ScreenRecordingPreset>,
+    concat_list: Option<String>,
+    clip_length: Option<f64>,
+    include_audio: bool,
+    video_filters: Vec<String>,
+    audio_filters: Vec<String>,
+    /// Inputs after the main one, with the seek applied to each.
+    extra_inputs: Vec<(String, Option<f64>)>,
+    /// `-filter_complex` graph and whether it produces `[aout]`.
+    filter_graph: Option<(String, bool)>,
+    graph_has_audio: bool,
+    audio_export: Option<audio_export::AudioExportOptions>,
+    extra_outputs: Vec<multi_output::OutputSpec>,
+    chapters_input: Option<usize>,
+    /// Input index and language of each OCR'd subtitle track.
+    ocr_inputs: Vec<(usize, Option<String>)>,
+    keep_surround: bool,
+    is_nvenc: bool,
+    is_amf: bool,
+    is_qsv: bool,
+    is_gpu_encoder: bool,
+    cpu_fallback_encoder: &'static str,
+    input_probe: Option<VideoInfo>,
+    probe_target: String,
+    caption_sources: Vec<captions::CaptionSource>,
+    audio_adjustment: audio_limits::AudioAdjustment,
+    input_codec: Option<String>,
+    decoder: Option<String>,
+    /// `-hwaccel` for the hardware decode attempt; `None` skips that attempt.
+    hwaccel: Option<&'static str>,
+    first_attempt: usize,
+    max_attempts: usize,
+    full_hardware: bool,
+    audio_tracks: Option<Vec<usize>>,
+    remux: bool,
+    video_stream: usize,
+}
+
+/// The FFmpeg invocation for one attempt of a conversion.
+struct FfmpegArgs {
+    encoder: String,
+    args: Vec<String>,
+    /// Analysis pass run before `args` for two-pass encodes.
+    first_pass: Option<Vec<String>>,
+}
+
+/// The FFmpeg command a conversion would run, without running it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandPreview {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Analysis pass run first for two-pass encodes.
+    pub first_pass: Option<Vec<String>>,
+    /// What planning decided, as the job log would show it.
+    pub notes: Vec<String>,
+}
+
+/// Extension and format of the output file.
+fn output_format(output_file: &str) -> (String, FormatInfo) {
+    let output_ext = Path::new(output_file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("mp4")
+        .to_lowercase();
+    let format_info = get_format_info(&output_ext);
+    (output_ext, format_info)
+}
+
+/// Media server presets pin the software encoder so the output profile and
+/// level are exactly what direct-play clients expect; screen presets pin
+/// the encoder their tuning is for.
+fn preset_encoder(
+    options: &ConversionOptions,
+    encoder: String,
+) -> (Option<MediaServerPreset>, Option<ScreenRecordingPreset>, String) {
+    let media_preset = options
+        .media_server_preset
+        .as_deref()
+        .and_then(|name| get_media_server_presets().into_iter().find(|p| p.name == name));
+    let screen_preset = options
+        .screen_preset
+        .as_deref()
+        .and_then(|name| get_screen_recording_presets().into_iter().find(|p| p.name == name));
+    let encoder = media_preset
+        .as_ref()
+        .map(|p| p.encoder.clone())
+        .or_else(|| screen_preset.as_ref().map(|p| p.encoder.clone()))
+        .unwrap_or(encoder);
+    (media_preset, screen_preset, encoder)
+}
+
+/// Plan `request` like a started conversion and return the command of its
+/// first attempt. The input is probed, but nothing is encoded, analysed or
+/// written.
+pub async fn preview_conversion(task_id: String, request: ConversionRequest) -> Result<CommandPreview, AppError> {
+    let task_arc = Arc::new(Mutex::new(ConversionTask::new(task_id, request, None)));
+    let plan = match prepare_request(&task_arc, true).await {
+        Some(_) => plan_conversion(&task_arc, true).await,
+        None => None,
+    };
+
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    let Some(plan) = plan else {
+        let message = task.progress.error_message.take().unwrap_or_else(|| "Conversion can't start".to_string());
+        return Err(AppError::Ffmpeg(message));
+    };
+    let FfmpegArgs { args, first_pass, .. } = build_ffmpeg_args(&plan, plan.first_attempt);
+    let mut notes = std::mem::take(&mut task.progress.log);
+    if plan.options.chunked_encoding
+        && chunked::ineligible_reason(&plan.options, &plan.encoder, &plan.format_info, plan.is_adobe_preset).is_none()
+    {
+        notes.push("Runs as a chunked encode; each chunk gets its own command".to_string());
+    }
+    Ok(CommandPreview {
+        program: plan.ffmpeg_path.clone(),
+        args,
+        first_pass,
+        notes,
+    })
+}
+
+async fn run_conversion_task(task_arc: Arc<Mutex<ConversionTask>>) {
+    let (task_id, request) = {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.enter_phase(ConversionPhase::Probing);
+        (task.id.clone(), task.request())
+    };
+    let options = &request.options;
+
+    if let Some(secs) = options.wait_until_stable.filter(|s| *s > 0 && !options.growing_file) {
+        let cancel = {
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.progress
+                .log
+                .push(format!("Waiting until the input is unchanged for {}s...", secs));
+            task.cancel.clone()
+        };
+        info!("Waiting for {} to stop growing", request.input_file);
+        if !growing::wait_until_stable(Path::new(&request.input_file), secs, &cancel).await {
+            stop_if_cancelled(&task_arc);
+            return;
+        }
+    }
+
+    let Some(request) = prepare_request(&task_arc, false).await else {
+        return;
+    };
+    if run_chunked(&task_arc, &task_id, &request).await {
+        return;
+    }
+    let Some(plan) = plan_conversion(&task_arc, false).await else {
+        return;
+    };
+    let ConversionPlan {
+        ref ffmpeg_path,
+        ref input_file,
+        ref output_file,
+        ref options,
+        ref format_info,
+        ref input_probe,
+        ref probe_target,
+        ref caption_sources,
+        ref input_codec,
+        ref audio_export,
+        clip_length,
+        is_nvenc,
+        is_amf,
+        is_gpu_encoder,
+        cpu_fallback_encoder,
+        first_attempt,
+        max_attempts,
+        ..
+    } = plan;
+    let passlog = ratecontrol::passlog_prefix(&plan.task_id);
+
+    // A transient failure puts its attempt back at the front.
+    let mut pending: VecDeque<usize> = (first_attempt..max_attempts).collect();
+    let mut transient_retries = 0;
+    while let Some(attempt) = pending.pop_front() {
+        if stop_if_cancelled(&task_arc) {
+            return;
+        }
+        let FfmpegArgs {
+            encoder: attempt_encoder,
+            args,
+            first_pass,
+        } = build_ffmpeg_args(&plan, attempt);
+        let two_pass = first_pass.is_some();
 
         {
             let mut task = task_arc.lock().expect("Failed to lock task mutex");
-            task.progress.status = ConversionStatus::Running;
+            task.progress.enter_phase(ConversionPhase::Encoding);
+            task.progress.attempts += 1;
             let log_msg = match attempt {
+                1 if first_attempt == 1 => "Software decode + GPU encode...",
+                3 if first_attempt == 3 => {
+                    metrics::gpu_fallback();
+                    "Encoding with CPU software encoder..."
+                }
                 1 => "Retrying with software decode + GPU encode...",
                 2 => "Retrying with forced NV12 pixel format...",
                 3 => {
+                    metrics::gpu_fallback();
                     info!("GPU encode failed. Falling back to CPU software encoder: {}", cpu_fallback_encoder);
                     "GPU encode failed. Falling back to CPU software encoder..."
                 }
@@ -1080,14 +2787,43 @@ async fn run_conversion_task(task_arc: Arc<Mutex<ConversionTask>>) {
         info!("Output: {}", output_file);
         debug!("Args: {:?}", args);
 
-        let mut cmd = Command::new(&ffmpeg_path);
+        if let Some(ref first_pass) = first_pass {
+            task_arc.lock().expect("Failed to lock task mutex").progress.log.push("Pass 1 of 2...".to_string());
+            let result = ratecontrol::run_first_pass(&task_arc, ffmpeg_path, first_pass, options).await;
+            if stop_if_cancelled(&task_arc) {
+                ratecontrol::remove_passlogs(&passlog);
+                return;
+            }
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            if let Err(message) = result {
+                error!("{} for input: {}", message, input_file);
+                ratecontrol::remove_passlogs(&passlog);
+                task.progress.status = ConversionStatus::Failed(message.clone());
+                task.progress.error_message = Some(message);
+                if attempt < max_attempts - 1 {
+                    continue;
+                }
+                return;
+            }
+            task.progress.current_time = 0.0;
+            task.progress.percentage = 0.0;
+            task.progress.log.push("Pass 2 of 2...".to_string());
+        }
+
+        let mut cmd = Command::new(ffmpeg_path);
+        // Stdin stays open so a cancel can send `q` instead of killing.
         cmd.args(&args)
+            .envs(&options.env_vars)
+            .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        if let Some(ref dir) = options.working_dir {
+            cmd.current_dir(dir);
+        }
         #[cfg(target_os = "windows")]
         cmd.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
 
-        let child = match cmd.spawn() {
+        let mut child = match cmd.spawn() {
             Ok(child) => child,
             Err(e) => {
                 error!("Failed to start ffmpeg: {}", e);
@@ -1104,63 +2840,75 @@ async fn run_conversion_task(task_arc: Arc<Mutex<ConversionTask>>) {
             }
         };
 
-        let time_regex = Regex::new(r"time=(\d+):(\d+):(\d+\.\d+)").expect("Invalid regex");
-        let out_time_regex = Regex::new(r"out_time=(\d+):(\d+):(\d+\.\d+)").expect("Invalid regex");
-        let out_time_us_regex = Regex::new(r"out_time_us=(\d+)").expect("Invalid regex");
-        let out_time_ms_regex = Regex::new(r"out_time_ms=(\d+)").expect("Invalid regex");
+        let progress_parser = ProgressParser::new();
         let duration_regex = Regex::new(r"Duration: (\d+):(\d+):(\d+\.\d+)").expect("Invalid regex");
 
+        // Read before `wait`, after which the child no longer reports its id.
+        let pid = child.id();
         let mut process_ref = {
             let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.pid = pid;
+            // Stdin input takes FFmpeg's stdin, so cancelling kills instead.
+            match (task.pid, child.stdin.take()) {
+                (_, Some(stdin)) if pipe_input::is_stdin(input_file) => {
+                    tokio::spawn(pipe_input::forward_stdin(stdin));
+                }
+                (Some(pid), Some(stdin)) => task.cancel.register_quit_pipe(pid, stdin),
+                _ => {}
+            }
             task.process = Some(child);
-            task.pid = task.process.as_ref().and_then(|proc| proc.id());
+            if let Some(pid) = task.pid {
+                task.cancel.register(pid);
+            }
             task.process.take().expect("Child process should be present")
         };
 
-        let stderr = process_ref.stderr.take().expect("FFmpeg stderr stream not available");
-        let mut reader = BufReader::new(stderr).lines();
         let mut full_stderr = Vec::new();
-
-        while let Ok(Some(line)) = reader.next_line().await {
-            full_stderr.push(line.clone());
-            let mut task = task_arc.lock().expect("Failed to lock task mutex");
-            task.progress.log.push(line.clone());
-
-            if task.progress.duration == 0.0 {
-                if let Some(captures) = duration_regex.captures(&line) {
-                    if let (Ok(h), Ok(m), Ok(s)) = (
-                        captures[1].parse::<f64>(),
-                        captures[2].parse::<f64>(),
-                        captures[3].parse::<f64>(),
-                    ) {
-                        let total_seconds: f64 = h * 3600.0 + m * 60.0 + s;
-                        task.progress.duration = total_seconds;
-                        debug!("Parsed duration: {} seconds", total_seconds);
-                    }
+        read_ffmpeg_output(
+            &mut process_ref,
+            |line| {
+                progress_parser.stats(line, &mut task_arc.lock().expect("Failed to lock task mutex").progress);
+                if let Some(current_time) = progress_parser.time(line) {
+                    task_arc.lock().expect("Failed to lock task mutex").advance(current_time);
                 }
-            }
-            
-            let parsed_time = if let Some(c) = time_regex.captures(&line) {
-                Some(c[1].parse::<f64>().unwrap_or(0.0) * 3600.0 + c[2].parse::<f64>().unwrap_or(0.0) * 60.0 + c[3].parse::<f64>().unwrap_or(0.0))
-            } else if let Some(c) = out_time_regex.captures(&line) {
-                Some(c[1].parse::<f64>().unwrap_or(0.0) * 3600.0 + c[2].parse::<f64>().unwrap_or(0.0) * 60.0 + c[3].parse::<f64>().unwrap_or(0.0))
-            } else if let Some(c) = out_time_us_regex.captures(&line) {
-                c[1].parse::<f64>().map(|us| us / 1_000_000.0).ok()
-            } else if let Some(c) = out_time_ms_regex.captures(&line) {
-                c[1].parse::<f64>().map(|ms| ms / 1_000_000.0).ok()
-            } else {
-                None
-            };
-
-            if let Some(current_time) = parsed_time {
-                task.progress.current_time = current_time.max(task.progress.current_time);
-                if task.progress.duration > 0.0 {
-                    task.progress.percentage = (task.progress.current_time / task.progress.duration * 100.0).min(100.0);
+                if let Some(fps) = progress_parser.fps(line) {
+                    metrics::record_fps(fps);
                 }
-            }
-        }
+            },
+            |line| {
+                full_stderr.push(line.clone());
+                let mut task = task_arc.lock().expect("Failed to lock task mutex");
+                if task.progress.duration == 0.0 {
+                    if let Some(captures) = duration_regex.captures(&line) {
+                        if let (Ok(h), Ok(m), Ok(s)) = (
+                            captures[1].parse::<f64>(),
+                            captures[2].parse::<f64>(),
+                            captures[3].parse::<f64>(),
+                        ) {
+                            let total_seconds: f64 = h * 3600.0 + m * 60.0 + s;
+                            task.progress.duration = clip_length.unwrap_or(
+                                (total_seconds - options.trim_start.unwrap_or(0.0)).max(0.0),
+                            );
+                            debug!("Parsed duration: {} seconds", total_seconds);
+                        }
+                    }
+                }
+                task.progress.log.push(line);
+            },
+        )
+        .await;
 
         let status = process_ref.wait().await;
+        if let Some(pid) = pid {
+            task_arc.lock().expect("Failed to lock task mutex").cancel.unregister(pid);
+        }
+        if two_pass {
+            ratecontrol::remove_passlogs(&passlog);
+        }
+        // A killed FFmpeg exits with an error; don't report or retry it.
+        if stop_if_cancelled(&task_arc) {
+            return;
+        }
         let succeeded = {
             let mut task = task_arc.lock().expect("Failed to lock task mutex");
             task.process = None;
@@ -1194,13 +2942,18 @@ async fn run_conversion_task(task_arc: Arc<Mutex<ConversionTask>>) {
         // GPU encoders (especially AMF) can produce corrupt output while still
         // returning exit code 0.
         if succeeded {
-            if let Some(problem) = validate_output(&ffmpeg_path, &output_file).await {
+            task_arc
+                .lock()
+                .expect("Failed to lock task mutex")
+                .progress
+                .enter_phase(ConversionPhase::Validating);
+            if let Some(problem) = validate_output(ffmpeg_path, output_file).await {
                 warn!("Output validation failed for {}: {}", output_file, problem);
                 let mut task = task_arc.lock().expect("Failed to lock task mutex");
                 task.progress.log.push(format!("Output validation failed: {}. Retrying...", problem));
                 if attempt < max_attempts - 1 {
                     // Not the last attempt — remove corrupt file and retry
-                    let _ = std::fs::remove_file(&output_file);
+                    let _ = std::fs::remove_file(output_file);
                     continue;
                 } else {
                     // Last attempt also produced bad output
@@ -1211,183 +2964,2098 @@ async fn run_conversion_task(task_arc: Arc<Mutex<ConversionTask>>) {
                 }
             }
 
+            // Software decode worked where hardware decode failed: remember
+            // the codec so later jobs skip the failing attempt.
+            if is_gpu_encoder && first_attempt == 0 && attempt == 1 {
+                if let Some(codec) = input_codec.as_deref() {
+                    decoders::blacklist(codec);
+                }
+            }
+
+            if options.replay_gain && !format_info.supports_video {
+                task_arc
+                    .lock()
+                    .expect("Failed to lock task mutex")
+                    .progress
+                    .enter_phase(ConversionPhase::Finalizing);
+                let message = match loudness::tag_file(ffmpeg_path, output_file).await {
+                    Ok(tags) if tags.is_empty() => "No loudness tag convention for this format".to_string(),
+                    Ok(tags) => format!(
+                        "Loudness tags: {}",
+                        tags.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(", ")
+                    ),
+                    Err(e) => {
+                        warn!("Loudness tagging failed for {}: {}", output_file, e);
+                        format!("Loudness tagging failed: {}", e)
+                    }
+                };
+                task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+            }
+
+            if options.extract_captions && !caption_sources.is_empty() {
+                if let Some(ref info) = input_probe {
+                    task_arc
+                        .lock()
+                        .expect("Failed to lock task mutex")
+                        .progress
+                        .enter_phase(ConversionPhase::Finalizing);
+                    let message =
+                        match captions::extract_all(ffmpeg_path, probe_target, info, Path::new(output_file)).await {
+                            Ok(files) if files.is_empty() => "Caption tracks found but they held no cues".to_string(),
+                            Ok(files) => format!(
+                                "Captions written: {}",
+                                files.iter().map(|f| f.to_string_lossy()).collect::<Vec<_>>().join(", ")
+                            ),
+                            Err(e) => {
+                                warn!("Caption extraction failed for {}: {}", input_file, e);
+                                format!("Caption extraction failed: {}", e)
+                            }
+                        };
+                    task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+                }
+            }
+
             // Output is valid — mark completed
             let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            if let Some(export) = audio_export {
+                task.progress.log.push(format!("Audio written: {}", export.path_for(output_file)));
+            }
+            for spec in &plan.extra_outputs {
+                task.progress.log.push(format!("Also written: {}", spec.output_file));
+            }
             info!("Conversion completed and validated for {}", input_file);
+            task.hardware_encode = is_gpu_encoder && attempt < 3;
+            task.encoder_used = Some(attempt_encoder.clone());
             task.progress.status = ConversionStatus::Completed;
             task.progress.percentage = 100.0;
             return;
         }
 
+        // Share dropouts and driver resets would fail any strategy, so try
+        // the same one again after a pause before falling back.
+        let can_retry = transient_retries < transient::MAX_RETRIES && !pipe_input::is_pipe(input_file);
+        if let Some(reason) = transient::classify(&full_stderr).filter(|_| can_retry) {
+            let delay = transient::backoff(transient_retries);
+            warn!("Transient failure ({}) for {}; retrying in {}s", reason, input_file, delay.as_secs());
+            let cancel = {
+                let mut task = task_arc.lock().expect("Failed to lock task mutex");
+                task.progress.log.push(format!(
+                    "Transient failure ({}). Retrying the same strategy in {}s...",
+                    reason,
+                    delay.as_secs()
+                ));
+                task.cancel.clone()
+            };
+            let _ = std::fs::remove_file(output_file);
+            if !transient::wait(transient_retries, &cancel).await {
+                stop_if_cancelled(&task_arc);
+                return;
+            }
+            transient_retries += 1;
+            pending.push_front(attempt);
+            continue;
+        }
+
         if attempt < max_attempts - 1 {
             warn!("Conversion failed. Trying next fallback strategy for {}", input_file);
-            let _ = std::fs::remove_file(&output_file);
+            let _ = std::fs::remove_file(output_file);
         }
     }
 }
 
-// FFmpeg download and management
-pub struct FfmpegDownloader;
-
-impl FfmpegDownloader {
-    pub fn new() -> Self {
-        Self
+/// Run the steps that rewrite the request before it is planned: subtitle
+/// OCR, then the loudness measurement. A dry run only works out what they
+/// would add. `None` when the job can't go on; the reason is set on the task.
+async fn prepare_request(task_arc: &Arc<Mutex<ConversionTask>>, dry_run: bool) -> Option<ConversionRequest> {
+    let (task_id, request) = {
+        let task = task_arc.lock().expect("Failed to lock task mutex");
+        (task.id.clone(), task.request())
+    };
+    if !run_subtitle_ocr(task_arc, &task_id, &request, dry_run).await {
+        return None;
     }
-
-    pub fn get_ffmpeg_app_dir() -> Result<PathBuf, AppError> {
-        let app_dir = dirs::data_dir()
-            .ok_or_else(|| AppError::Internal("Could not find app data directory".to_string()))?
-            .join("Dreamcodec");
-        Ok(app_dir)
+    // OCR may have swapped in text subtitles.
+    let request = task_arc.lock().expect("Failed to lock task mutex").request();
+    if !run_loudness_analysis(task_arc, &request, dry_run).await {
+        return None;
     }
+    Some(task_arc.lock().expect("Failed to lock task mutex").request())
+}
 
-    pub fn get_ffmpeg_path() -> Result<PathBuf, AppError> {
-        let app_dir = Self::get_ffmpeg_app_dir()?;
-        Ok(app_dir.join("ffmpeg.exe"))
+/// Turn the picture-based subtitle tracks the job needs as text into SRT
+/// with the configured OCR tool: the burned-in track is then rendered from
+/// its SRT, the others are muxed after the copied tracks. A track the tool
+/// fails on is left out with a warning. A dry run assumes every track
+/// succeeds without running the tool. Returns `false` if the job was
+/// cancelled meanwhile.
+async fn run_subtitle_ocr(
+    task_arc: &Arc<Mutex<ConversionTask>>,
+    task_id: &str,
+    request: &ConversionRequest,
+    dry_run: bool,
+) -> bool {
+    let options = &request.options;
+    let Some(tool) = options.ocr_tool.as_ref().filter(|_| options.ocr_subtitles) else {
+        return true;
+    };
+    let (output_ext, format_info) = output_format(&request.output_file);
+    if !format_info.supports_video {
+        return true;
     }
-
-    pub fn get_ffprobe_path() -> Result<PathBuf, AppError> {
-        let app_dir = Self::get_ffmpeg_app_dir()?;
-        Ok(app_dir.join("ffprobe.exe"))
+    let burn_track = options
+        .subtitle_stream
+        .filter(|_| options.subtitle_file.is_none())
+        .map(|s| s as usize);
+    let Some(info) = probe_info(&request.ffmpeg_path, &request.input_file).await else {
+        warn!("Could not probe {} for subtitle OCR", request.input_file);
+        return true;
+    };
+    let positions = ocr::tracks_to_ocr(&info.subtitle_streams, &output_ext, options.copy_subtitles, burn_track);
+    if positions.is_empty() {
+        return true;
     }
 
-    pub async fn is_ffmpeg_available() -> bool {
-        // First try the auto-locator
-        if let Some(path) = FfmpegLocator::find_ffmpeg().await {
-            return path.exists();
+    if !dry_run {
+        task_arc
+            .lock()
+            .expect("Failed to lock task mutex")
+            .progress
+            .enter_phase(ConversionPhase::SubtitleOcr);
+    }
+    let dir = ocr::work_dir(task_id);
+    let mut results = Vec::new();
+    for (done, &position) in positions.iter().enumerate() {
+        if stop_if_cancelled(task_arc) {
+            return false;
+        }
+        let stream = &info.subtitle_streams[position];
+        {
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.progress.percentage = done as f64 / positions.len() as f64 * 100.0;
+            task.progress.log.push(format!(
+                "Subtitle OCR: track {} of {} ({}, {})",
+                done + 1,
+                positions.len(),
+                stream.codec,
+                stream.language.as_deref().unwrap_or("unknown language")
+            ));
+        }
+        let srt = dir.join(format!("track_{}.srt", position));
+        let result = if dry_run {
+            Ok(())
+        } else {
+            match ocr::extract_track(&request.ffmpeg_path, &request.input_file, position, &stream.codec, &dir).await {
+                Ok(extracted) => ocr::run_tool(tool, &extracted, &srt, stream.language.as_deref()).await,
+                Err(e) => Err(e),
+            }
+        };
+        match result {
+            Ok(()) => {
+                let mux = options.copy_subtitles && tracks::subtitle_codec(&output_ext, &stream.codec).is_none();
+                let subtitle = ocr::OcrSubtitle {
+                    position,
+                    language: stream.language.clone(),
+                    path: srt.to_string_lossy().to_string(),
+                };
+                results.push((subtitle, mux));
+            }
+            Err(e) => {
+                let message = format!("Subtitle OCR skipped track {}: {}", position, e);
+                warn!("{} ({})", message, request.input_file);
+                task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+            }
         }
+    }
+    if stop_if_cancelled(task_arc) {
+        return false;
+    }
 
-        // Fallback to app directory check
-        match Self::get_ffmpeg_path() {
-            Ok(path) => path.exists(),
-            Err(_) => false,
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    task.progress.percentage = 0.0;
+    task.progress.enter_phase(ConversionPhase::Probing);
+    for (subtitle, mux) in results {
+        if burn_track == Some(subtitle.position) {
+            task.options.subtitle_stream = None;
+            task.options.subtitle_file = Some(subtitle.path.clone());
+        }
+        if mux {
+            task.options.ocr_tracks.push(subtitle);
         }
     }
+    true
+}
 
-    pub async fn download_and_extract_ffmpeg<F>(progress_callback: F) -> Result<PathBuf, AppError>
-    where
-        F: Fn(u64, u64) + Send + 'static,
+/// Measure the audio with a first `loudnorm` pass when the job normalizes
+/// loudness; the encode applies the measured values. A dry run logs the
+/// analysis command and stands in the targets for the measurement. Returns
+/// `false` if the analysis failed or the job was cancelled meanwhile.
+async fn run_loudness_analysis(task_arc: &Arc<Mutex<ConversionTask>>, request: &ConversionRequest, dry_run: bool) -> bool {
+    let options = &request.options;
+    let Some(ref target) = options.normalize_audio else {
+        return true;
+    };
+    let format_info = output_format(&request.output_file).1;
+    if !format_info.supports_audio || options.timelapse.is_some() {
+        return true;
+    }
+    let info = probe_info(&request.ffmpeg_path, &request.input_file).await;
+    // The measurement only fits the track it was taken on, and the encode
+    // applies it to every audio track it keeps.
+    let streams = info.as_ref().map(|i| i.audio_streams.as_slice()).unwrap_or_default();
+    let all_tracks = format_info.supports_video && options.media_server_preset.is_none();
+    let kept = tracks::mapped_audio_tracks(streams, &options.audio_streams, &options.audio_languages, all_tracks);
+    if kept.len() > 1 {
+        let message = format!(
+            "Loudness normalization measures one audio track, but this output keeps {} (tracks {:?}). Pick a single audio stream or language.",
+            kept.len(),
+            kept
+        );
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.status = ConversionStatus::Failed(message.clone());
+        task.progress.error_message = Some(message);
+        return false;
+    }
+    let track = kept.first().copied().unwrap_or(0);
+    let clip_length = filters::clip_length(options, info.as_ref().and_then(|i| i.duration));
+    let audio_filters = filters::audio_filter_chain(options, clip_length);
+    let args = loudness::analysis_args(
+        &request.input_file,
+        track,
+        (options.trim_start, options.trim_duration),
+        &audio_filters,
+        target,
+    );
+    if dry_run {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.log.push(format!("Loudness analysis pass: {}", args.join(" ")));
+        task.progress
+            .log
+            .push("The loudnorm filter shows the targets where the measured values go.".to_string());
+        task.options.loudnorm_stats = Some(loudness::LoudnormStats {
+            input_i: target.target_lufs,
+            input_tp: target.true_peak,
+            input_lra: target.loudness_range,
+            input_thresh: target.target_lufs - 10.0,
+            target_offset: 0.0,
+            sample_rate: info.as_ref().and_then(|i| i.audio_streams.get(track)).and_then(|s| s.sample_rate),
+        });
+        return true;
+    }
     {
-        let app_dir = Self::get_ffmpeg_app_dir()?;
-        let ffmpeg_path = app_dir.join("ffmpeg.exe");
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.enter_phase(ConversionPhase::LoudnessAnalysis);
+        task.progress.log.push(format!("Measuring loudness for a {:.1} LUFS target...", target.target_lufs));
+    }
+    let result = loudness::analyze(task_arc, &request.ffmpeg_path, &args).await;
+    if stop_if_cancelled(task_arc) {
+        return false;
+    }
 
-        // Check if already exists
-        if ffmpeg_path.exists() {
-            return Ok(ffmpeg_path);
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    match result {
+        Ok(mut stats) => {
+            stats.sample_rate = info.as_ref().and_then(|i| i.audio_streams.get(track)).and_then(|s| s.sample_rate);
+            let message = format!(
+                "Measured {:.1} LUFS, {:.1} dBTP true peak; adjusting to {:.1} LUFS",
+                stats.input_i, stats.input_tp, target.target_lufs
+            );
+            info!("{} ({})", message, request.input_file);
+            task.progress.log.push(message);
+            task.progress.current_time = 0.0;
+            task.progress.percentage = 0.0;
+            task.options.loudnorm_stats = Some(stats);
+            true
         }
-
-        // Create directory if needed
-        fs::create_dir_all(&app_dir)
-            .await
-            .map_err(|e| AppError::Io(e.to_string()))?;
-
-        let zip_url = "https://www.gyan.dev/ffmpeg/builds/ffmpeg-release-essentials.zip";
-        let zip_path = app_dir.join("ffmpeg.zip");
-
-        // Download the zip file with progress
-        let client = reqwest::Client::new();
-        let response = client
-            .get(zip_url)
-            .send()
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to download FFmpeg: {}", e)))?;
-
-        let total_size = response.content_length().unwrap_or(0);
-        let mut downloaded = 0u64;
-
-        let mut file = fs::File::create(&zip_path)
-            .await
-            .map_err(|e| AppError::Io(e.to_string()))?;
-
-        let mut stream = response.bytes_stream();
-
-        while let Some(chunk) = stream.next().await {
-            let chunk =
-                chunk.map_err(|e| AppError::Internal(format!("Download error: {}", e)))?;
-            file.write_all(&chunk)
-                .await
-                .map_err(|e| AppError::Io(e.to_string()))?;
-            downloaded += chunk.len() as u64;
-            progress_callback(downloaded, total_size);
+        Err(message) => {
+            error!("{} for input: {}", message, request.input_file);
+            task.progress.status = ConversionStatus::Failed(message.clone());
+            task.progress.error_message = Some(message);
+            false
         }
+    }
+}
 
-        file.flush()
-            .await
-            .map_err(|e| AppError::Io(e.to_string()))?;
-        drop(file);
-
-        // Extract the zip file
-        Self::extract_ffmpeg(&zip_path, &app_dir).await?;
-
-        // Clean up zip file
-        let _ = fs::remove_file(&zip_path).await;
-
-        if !ffmpeg_path.exists() {
-            return Err(AppError::Ffmpeg("FFmpeg extraction failed".to_string()));
+/// Run the job as a chunked encode when it asks for one and qualifies;
+/// `false` when it has to be encoded in one go.
+async fn run_chunked(task_arc: &Arc<Mutex<ConversionTask>>, task_id: &str, request: &ConversionRequest) -> bool {
+    let ConversionRequest {
+        ref input_file,
+        ref output_file,
+        ref ffmpeg_path,
+        ref preset,
+        cpu_threads,
+        is_adobe_preset,
+        ref options,
+        ..
+    } = *request;
+    let (_, format_info) = output_format(output_file);
+    let (media_preset, _, encoder) = preset_encoder(options, request.encoder.clone());
+
+    if options.chunked_encoding {
+        let ineligible = if pipe_input::is_pipe(input_file) {
+            Some("piped input can't be split")
+        } else {
+            chunked::ineligible_reason(options, &encoder, &format_info, is_adobe_preset)
+        };
+        match ineligible {
+            None => {
+                let start = options.trim_start.unwrap_or(0.0);
+                let info = probe_info(ffmpeg_path, input_file).await;
+                let clip_length = match options.trim_duration {
+                    Some(duration) => Some(duration),
+                    None => info.as_ref().and_then(|i| i.duration).map(|d| (d - start).max(0.0)),
+                };
+                // Same fitting as `plan_conversion` for the separate audio pass.
+                let audio_adjustment = info
+                    .as_ref()
+                    .filter(|_| format_info.supports_audio && media_preset.is_none())
+                    .map(|info| audio_limits::adjust(format_info.default_audio_codec, &info.audio_streams))
+                    .unwrap_or_default();
+                if let Some(clip_length) = clip_length.filter(|l| *l > 0.0) {
+                    if let Some(message) = audio_adjustment.describe(format_info.default_audio_codec) {
+                        info!("{} ({})", message, input_file);
+                        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+                    }
+                    let job = chunked::ChunkedJob {
+                        task_id,
+                        ffmpeg_path,
+                        input_file,
+                        output_file,
+                        encoder: &encoder,
+                        preset,
+                        cpu_threads,
+                        options,
+                        media_preset: media_preset.as_ref(),
+                        audio_codec: format_info.default_audio_codec,
+                        audio_adjustment,
+                        clip_length,
+                    };
+                    chunked::run(task_arc.clone(), job, chunked::chunk_count(options.chunk_count)).await;
+                    return true;
+                }
+                warn!("Input duration unknown; encoding {} without chunks", input_file);
+            }
+            Some(reason) => {
+                let mut task = task_arc.lock().expect("Failed to lock task mutex");
+                task.progress.log.push(format!("Chunked encoding not used: {}", reason));
+            }
         }
-
-        Ok(ffmpeg_path)
     }
 
-    async fn extract_ffmpeg(zip_path: &Path, output_dir: &Path) -> Result<(), AppError> {
-        // Read and extract the zip file
-        let file =
-            std::fs::File::open(zip_path).map_err(|e| AppError::Io(format!("Failed to open zip file: {}", e)))?;
-
-        let mut archive = zip::ZipArchive::new(file)
-            .map_err(|e| AppError::Internal(format!("Failed to read zip archive: {}", e)))?;
-
-        // Find the ffmpeg.exe and ffprobe.exe in the archive
-        let mut ffmpeg_entry_name = String::new();
-        let mut ffprobe_entry_name = String::new();
+    false
+}
 
-        for i in 0..archive.len() {
-            let entry = archive.by_index(i).map_err(|e| {
-                AppError::Internal(format!("Failed to read zip entry: {}", e))
-            })?;
-            let name = entry.name().to_lowercase();
-            if name.ends_with("ffmpeg.exe") && !name.contains("doc") {
-                ffmpeg_entry_name = entry.name().to_string();
-            } else if name.ends_with("ffprobe.exe") && !name.contains("doc") {
-                ffprobe_entry_name = entry.name().to_string();
-            }
+/// Probe the input and work out everything `build_ffmpeg_args` needs,
+/// logging the decisions to the task. A dry run writes no files, skips
+/// scene detection and counts no GPU job. `None` when the job can't run;
+/// the reason is set on the task.
+async fn plan_conversion(task_arc: &Arc<Mutex<ConversionTask>>, dry_run: bool) -> Option<ConversionPlan> {
+    let (task_id, request, adobe_preset) = {
+        let task = task_arc.lock().expect("Failed to lock task mutex");
+        (task.id.clone(), task.request(), task.adobe_preset.clone())
+    };
+    let ConversionRequest {
+        input_file,
+        output_file,
+        ffmpeg_path,
+        encoder,
+        gpu_index,
+        cpu_threads,
+        preset,
+        is_adobe_preset,
+        options,
+    } = request;
+    let (output_ext, format_info) = output_format(&output_file);
+    let (media_preset, screen_preset, encoder) = preset_encoder(&options, encoder);
+    {
+        // Taken from the task so retried attempts don't log it again.
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        if let Some(message) = task.options.encoder_substitution.take() {
+            task.progress.log.push(message);
         }
+    }
 
-        if ffmpeg_entry_name.is_empty() {
-            return Err(AppError::Ffmpeg(
-                "Could not find ffmpeg.exe in archive".to_string(),
-            ));
+    // Joined segments are read through the concat demuxer, which doesn't
+    // report a total duration, so it is summed from the individual files.
+    let concat_list = if options.input_segments.is_empty() {
+        None
+    } else {
+        let list_path = segments::concat_list_path(&task_id);
+        let written = if dry_run {
+            Ok(())
+        } else {
+            segments::write_concat_list(&options.input_segments, &list_path)
+        };
+        if let Err(e) = written {
+            let message = format!("Failed to write segment list: {}", e);
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.progress.status = ConversionStatus::Failed(message.clone());
+            task.progress.error_message = Some(message);
+            return None;
         }
+        Some(list_path.to_string_lossy().to_string())
+    };
 
-        // Extract ffmpeg.exe
-        {
-            let mut ffmpeg_file = archive
-                .by_name(&ffmpeg_entry_name)
-                .map_err(|e| AppError::Internal(format!("Failed to find ffmpeg in archive: {}", e)))?;
-            let out_path = output_dir.join("ffmpeg.exe");
-            let mut outfile = std::fs::File::create(&out_path)
-                .map_err(|e| AppError::Io(format!("Failed to create output file: {}", e)))?;
-            std::io::copy(&mut ffmpeg_file, &mut outfile)
-                .map_err(|e| AppError::Io(format!("Failed to extract ffmpeg: {}", e)))?;
-        }
-
-        // Extract ffprobe.exe
-        if !ffprobe_entry_name.is_empty() {
-            let mut archive = zip::ZipArchive::new(
-                std::fs::File::open(zip_path).map_err(|e| AppError::Io(format!("Failed to reopen zip: {}", e)))?,
-            )
-            .map_err(|e| AppError::Internal(format!("Failed to read zip archive: {}", e)))?;
+    let wants_chapters = (options.chapters_file.is_some() || options.scene_chapters)
+        && matches!(output_ext.as_str(), "mkv" | "mp4" | "mov" | "m4a" | "m4v");
+
+    // Fade-out is anchored to the end of the clip, so the length must be known
+    // up front when the job isn't already bounded by a trim duration.
+    let pipe_input = pipe_input::is_pipe(&input_file);
+    let source_duration = if pipe_input {
+        options.input_duration.filter(|d| *d > 0.0)
+    } else if concat_list.is_some() {
+        let mut total = 0.0;
+        for segment in &options.input_segments {
+            total += probe_duration(&ffmpeg_path, segment).await.unwrap_or(0.0);
+        }
+        Some(total).filter(|t| *t > 0.0)
+    } else if (options.fade_out.is_some()
+        || options.timelapse.is_some()
+        || wants_chapters
+        || options.audio_edit.as_ref().is_some_and(|e| e.fade_out_ms.is_some() && e.end_ms.is_none()))
+        && options.trim_duration.is_none()
+    {
+        probe_duration(&ffmpeg_path, &input_file).await
+    } else {
+        None
+    };
+    let clip_length = filters::clip_length(&options, source_duration);
+    if options.fade_out.is_some() && clip_length.is_none() {
+        warn!("Could not determine clip length for {}; skipping fade-out", input_file);
+    }
+    // FFmpeg reports progress in output time, which a timelapse shortens.
+    let speed_factor = options.timelapse.as_ref().map_or(1.0, |t| t.speed_factor());
+    if let Some(length) = filters::audio_edit_length(&options, clip_length).or(clip_length) {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.duration = length / speed_factor;
+    }
+    let include_audio = format_info.supports_audio && options.timelapse.is_none();
+    let video_filters = filters::video_filter_chain(&options, &input_file, clip_length);
+    let audio_filters = filters::audio_filter_chain(&options, clip_length);
+
+    // Extra inputs (picture-in-picture overlay, intro/outro bumpers) are
+    // combined with the main input in a `-filter_complex` graph ending in
+    // `[vout]`. The flag records whether the graph also produces `[aout]`.
+    let mut extra_inputs: Vec<(String, Option<f64>)> = Vec::new();
+    let mut filter_graph: Option<(String, bool)> = None;
+    let overlay = options
+        .overlay
+        .as_ref()
+        .filter(|o| format_info.supports_video && !o.file.is_empty());
+    if let Some(overlay) = overlay {
+        // The overlay is recorded alongside the main input, so seek it the same way.
+        extra_inputs.push((overlay.file.clone(), options.trim_start.filter(|s| *s > 0.0)));
+    }
+    let overlay_input = overlay.map(|o| (o, 1));
+    let video_stream = options.video_stream.unwrap_or(0);
+
+    if format_info.supports_video && (options.intro_file.is_some() || options.outro_file.is_some()) {
+        let probe_target = options.input_segments.first().unwrap_or(&input_file);
+        match probe_info(&ffmpeg_path, probe_target).await {
+            Some(VideoInfo { width: Some(width), height: Some(height), duration, audio_streams, .. }) => {
+                let mut bumpers = Vec::new();
+                let mut total = clip_length
+                    .or_else(|| duration.map(|d| (d - options.trim_start.unwrap_or(0.0)).max(0.0)))
+                    .unwrap_or(0.0)
+                    / speed_factor;
+                for file in [&options.intro_file, &options.outro_file] {
+                    let Some(file) = file else {
+                        bumpers.push(None);
+                        continue;
+                    };
+                    let info = probe_info(&ffmpeg_path, file).await;
+                    let bumper_duration = info.as_ref().and_then(|i| i.duration).unwrap_or(0.0);
+                    total += bumper_duration;
+                    extra_inputs.push((file.clone(), None));
+                    bumpers.push(Some(filters::Bumper {
+                        input: extra_inputs.len(),
+                        duration: bumper_duration,
+                        has_audio: info.is_some_and(|i| !i.audio_streams.is_empty()),
+                    }));
+                }
 
-            let mut ffprobe_file = archive.by_name(&ffprobe_entry_name).map_err(|e| {
-                AppError::Internal(format!("Failed to find ffprobe in archive: {}", e))
-            })?;
-            let out_path = output_dir.join("ffprobe.exe");
-            let mut outfile = std::fs::File::create(&out_path)
-                .map_err(|e| AppError::Io(format!("Failed to create output file: {}", e)))?;
-            std::io::copy(&mut ffprobe_file, &mut outfile)
-                .map_err(|e| AppError::Io(format!("Failed to extract ffprobe: {}", e)))?;
+                let main_has_audio = include_audio && !audio_streams.is_empty();
+                let graph = filters::bumper_graph(
+                    &filters::main_video_graph(&video_filters, video_stream, overlay_input, "vbase"),
+                    &audio_filters,
+                    main_has_audio,
+                    filters::output_size(&options, (width, height)),
+                    bumpers[0].as_ref(),
+                    bumpers[1].as_ref(),
+                );
+                filter_graph = Some((graph, main_has_audio));
+                if total > 0.0 {
+                    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+                    task.progress.duration = total;
+                }
+            }
+            _ => warn!("Could not read the video size of {}; skipping intro/outro", input_file),
         }
+    }
+    if filter_graph.is_none() && overlay_input.is_some() {
+        filter_graph = Some((filters::main_video_graph(&video_filters, video_stream, overlay_input, "vout"), false));
+    }
+    let graph_has_audio = filter_graph.as_ref().is_some_and(|(_, audio)| *audio);
+    let audio_export = options.audio_export.as_ref().filter(|_| include_audio);
+    if audio_export.is_some() && graph_has_audio {
+        let message = "Separate audio export isn't available with intro/outro clips; skipping it".to_string();
+        warn!("{} ({})", message, input_file);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+    }
+    let audio_export = audio_export.filter(|_| !graph_has_audio);
+    // Graph outputs can only be mapped once.
+    let extra_outputs = if filter_graph.is_some() && !options.extra_outputs.is_empty() {
+        let message = "Extra outputs aren't available with compositing or intro/outro clips; skipping them".to_string();
+        warn!("{} ({})", message, input_file);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+        Vec::new()
+    } else {
+        options.extra_outputs.clone()
+    };
 
+    // Chapters are read from an FFMETADATA file added as the last input.
+    let mut chapters_input = None;
+    if wants_chapters && dry_run && options.chapters_file.is_none() {
+        let message = "Scene changes are detected for chapters when the job runs.".to_string();
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+        extra_inputs.push((chapters::metadata_path(&task_id).to_string_lossy().to_string(), None));
+        chapters_input = Some(extra_inputs.len());
+    } else if wants_chapters {
+        let source = if let Some(ref file) = options.chapters_file {
+            std::fs::read_to_string(file)
+                .map_err(AppError::from)
+                .and_then(|content| chapters::parse_chapter_list(&content))
+        } else {
+            {
+                let mut task = task_arc.lock().expect("Failed to lock task mutex");
+                task.progress.log.push("Detecting scene changes for chapters...".to_string());
+            }
+            chapters::detect_scene_chapters(&ffmpeg_path, &input_file).await
+        };
+        let end = clip_length.unwrap_or(0.0);
+        let metadata_path = chapters::metadata_path(&task_id);
+        match source {
+            Ok(list) => {
+                let list = chapters::trim_chapters(&list, options.trim_start.unwrap_or(0.0), clip_length);
+                let written = if list.is_empty() || dry_run {
+                    Ok(())
+                } else {
+                    chapters::write_ffmetadata(&list, end, &metadata_path)
+                };
+                if list.is_empty() {
+                    warn!("No chapters to write for {}", input_file);
+                } else if let Err(e) = written {
+                    warn!("Failed to write chapter metadata: {}", e);
+                } else {
+                    extra_inputs.push((metadata_path.to_string_lossy().to_string(), None));
+                    chapters_input = Some(extra_inputs.len());
+                }
+            }
+            Err(e) => warn!("Skipping chapters for {}: {}", input_file, e),
+        }
+    }
+    // OCR'd subtitle tracks follow as SRT inputs, seeked like the main input.
+    let mut ocr_inputs = Vec::new();
+    if !options.ocr_tracks.is_empty() {
+        if options.intro_file.is_some() || options.outro_file.is_some() {
+            let message = "OCR'd subtitles can't follow intro/outro clips; leaving them out".to_string();
+            warn!("{} ({})", message, input_file);
+            task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+        } else {
+            for subtitle in &options.ocr_tracks {
+                extra_inputs.push((subtitle.path.clone(), options.trim_start.filter(|s| *s > 0.0)));
+                ocr_inputs.push((extra_inputs.len(), subtitle.language.clone()));
+            }
+        }
+    }
+    // The copied surround track can't follow the concat graph.
+    let keep_surround = media_preset.is_some() && options.keep_surround_track && !graph_has_audio;
+
+    let is_nvenc = encoder.contains("nvenc");
+    let is_amf = encoder.contains("amf");
+    let is_qsv = encoder.contains("qsv");
+    let is_gpu_encoder = is_nvenc || is_amf || is_qsv;
+    // GPU encoders: 3 GPU attempts + 1 CPU software fallback = 4
+    // CPU encoders: 1 attempt only
+    let mut max_attempts: usize = if is_gpu_encoder { 4 } else { 1 };
+
+    // Determine the CPU fallback encoder that matches the GPU codec family.
+    let cpu_fallback_encoder = if encoder.contains("h264") || encoder.contains("264") {
+        "libx264"
+    } else if encoder.contains("hevc") || encoder.contains("265") {
+        "libx265"
+    } else {
+        "libx264"
+    };
+
+    if is_gpu_encoder && !dry_run {
+        metrics::gpu_job_started();
+    }
+
+    // Video outputs are probed so closed captions the encode would drop can
+    // be reported, audio outputs so their format can be fitted to the encoder.
+    let probe_target = options.input_segments.first().unwrap_or(&input_file).clone();
+    let input_probe = if pipe_input {
+        None
+    } else if is_gpu_encoder
+        || format_info.supports_video
+        || include_audio
+        || options.extract_captions
+        || !options.audio_languages.is_empty()
+        || !options.audio_streams.is_empty()
+    {
+        probe_info(&ffmpeg_path, &probe_target).await
+    } else {
+        None
+    };
+    let caption_sources = input_probe.as_ref().map(captions::detect).unwrap_or_default();
+    if format_info.supports_video && !options.extract_captions {
+        let embedded_lost = caption_sources.contains(&captions::CaptionSource::Embedded)
+            && !captions::carries_embedded_captions(&encoder);
+        let teletext_lost = caption_sources
+            .iter()
+            .any(|source| matches!(source, captions::CaptionSource::Teletext { .. }));
+        if embedded_lost || teletext_lost {
+            let what = match (embedded_lost, teletext_lost) {
+                (true, true) => "closed captions and teletext",
+                (true, false) => "closed captions",
+                _ => "teletext",
+            };
+            let message = format!(
+                "The source has {} that this output won't keep. Enable caption extraction to save them as SRT.",
+                what
+            );
+            warn!("{} ({})", message, input_file);
+            task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+        }
+    }
+    // Resample/downmix up front instead of failing at mux time when the
+    // source audio exceeds what the container's default encoder accepts.
+    let audio_adjustment = input_probe
+        .as_ref()
+        .filter(|_| include_audio && !is_adobe_preset && media_preset.is_none())
+        .map(|info| audio_limits::adjust(format_info.default_audio_codec, &info.audio_streams))
+        .unwrap_or_default();
+    if let Some(message) = audio_adjustment.describe(format_info.default_audio_codec) {
+        info!("{} ({})", message, input_file);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+    }
+    // The stream the job encodes, which isn't the first one when it picks another.
+    let input_codec = input_probe
+        .as_ref()
+        .and_then(|info| info.video_streams.get(options.video_stream.unwrap_or(0)))
+        .map(|s| s.codec.clone());
+
+    // Frames beyond the encoder's size limit or the free VRAM would fail every
+    // GPU attempt, so go straight to the CPU fallback.
+    let frame_size = input_probe.as_ref().and_then(|info| match options.video_stream {
+        Some(index) => info.video_streams.get(index).and_then(|s| s.width.zip(s.height)),
+        None => info.width.zip(info.height),
+    });
+    let gpu_refusal = match frame_size {
+        _ if is_gpu_encoder && gpu::self_test_failed(&encoder) => {
+            Some(format!("{} failed the encoder self-test", encoder))
+        }
+        Some((width, height)) if is_gpu_encoder => {
+            let free_vram = if is_nvenc { gpu::free_vram_mb(gpu_index).await } else { None };
+            gpu::hardware_encode_refusal(&encoder, width, height, free_vram)
+        }
+        _ => None,
+    };
+    if let Some(ref reason) = gpu_refusal {
+        let message = format!("Hardware encoding skipped: {}. Encoding with {}.", reason, cpu_fallback_encoder);
+        warn!("{}", message);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+    }
+
+    // Hardware decode is skipped when the job picks its own decoder, asks for
+    // software decoding, or hardware decoding of this codec failed before.
+    let mut decoder = options.decoder.as_deref().map(str::trim).filter(|d| !d.is_empty());
+    let decode_support = if is_gpu_encoder || decoder.is_some() {
+        gpu::decode_support(&ffmpeg_path).await
+    } else {
+        gpu::DecodeSupport::default()
+    };
+    if let (Some(name), Some(available)) = (decoder, decode_support.decoders.as_ref()) {
+        if !available.iter().any(|d| d == name) {
+            let message = format!("Decoder {} isn't in this FFmpeg build; letting FFmpeg choose.", name);
+            warn!("{}", message);
+            task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+            decoder = None;
+        }
+    }
+    // Decode with what this build actually lists rather than assuming the
+    // encoder's own API is there.
+    let hwaccel = if is_gpu_encoder {
+        gpu::pick_hwaccel(decode_support.hwaccels.as_deref(), &encoder)
+    } else {
+        None
+    };
+    let blacklisted_codec = input_codec.as_deref().filter(|codec| decoders::is_blacklisted(codec));
+    let skip_hw_decode = options.software_decode
+        || decoder.is_some()
+        || blacklisted_codec.is_some()
+        || hwaccel.is_none()
+        || pipe_input;
+    if let Some(codec) = blacklisted_codec.filter(|_| is_gpu_encoder) {
+        let message = format!("Hardware decoding of {} failed before; decoding on the CPU.", codec);
+        info!("{}", message);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+    } else if is_gpu_encoder && hwaccel.is_none() {
+        let message = "This FFmpeg build has no hardware decoders; decoding on the CPU.".to_string();
+        info!("{}", message);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+    }
+    // Attempt 0 is the hardware decode attempt; without it start at 1.
+    let first_attempt = if gpu_refusal.is_some() {
+        3
+    } else if is_gpu_encoder && skip_hw_decode {
+        1
+    } else {
+        0
+    };
+    // Piped data is gone once read, so there is no fallback to retry with;
+    // start with the attempt least likely to fail instead.
+    if pipe_input {
+        max_attempts = first_attempt + 1;
+        let message = "Piped input: a single attempt with software decoding.".to_string();
+        info!("{}", message);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+    }
+
+    // Full hardware pipeline: frames go from the hardware decoder to the
+    // encoder without a round trip through system memory. That only works
+    // when the input has a hardware decoder and no CPU filter touches frames.
+    let full_hardware = if is_gpu_encoder && gpu_refusal.is_none() && options.full_hardware != Some(false) {
+        let preset_pixel_format = adobe_preset
+            .as_ref()
+            .map(|p| p.pixel_format.as_str())
+            .or_else(|| media_preset.as_ref().map(|p| p.pixel_format.as_str()))
+            .or_else(|| {
+                screen_preset
+                    .as_ref()
+                    .filter(|p| p.encoder == encoder)
+                    .map(|p| p.pixel_format.as_str())
+            });
+        let blockers = HardwarePath {
+            encoder: &encoder,
+            options: &options,
+            supports_video: format_info.supports_video,
+            compositing: filter_graph.is_some(),
+            video_filters: !video_filters.is_empty(),
+            preset_pixel_format,
+            skip_hw_decode,
+            hwaccel,
+            input_codec: input_codec.as_deref(),
+        }
+        .blockers();
+
+        let message = if blockers.is_empty() {
+            "Full hardware pipeline: decode and encode stay on the GPU.".to_string()
+        } else {
+            format!("Full hardware pipeline not used: {}.", blockers.join("; "))
+        };
+        info!("{}", message);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+        blockers.is_empty()
+    } else {
+        false
+    };
+
+    if let Some(ref info) = input_probe {
+        let selection = tracks::check_stream_selection(
+            &info.video_streams,
+            &info.audio_streams,
+            options.video_stream.filter(|_| format_info.supports_video),
+            &options.audio_streams,
+        );
+        if let Err(message) = selection {
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.progress.status = ConversionStatus::Failed(message.clone());
+            task.progress.error_message = Some(message);
+            return None;
+        }
+    }
+
+    // Audio tracks picked by index or language; `None` keeps the default mapping.
+    let audio_tracks = match input_probe.as_ref().filter(|_| !options.audio_languages.is_empty()) {
+        _ if !options.audio_streams.is_empty() => {
+            info!("Keeping audio stream(s) {:?}", options.audio_streams);
+            Some(options.audio_streams.clone())
+        }
+        Some(info) => {
+            let tracks = tracks::select_audio_tracks(&info.audio_streams, &options.audio_languages);
+            let message = format!(
+                "Audio languages {}: keeping track(s) {:?} of {}",
+                options.audio_languages.join("+"),
+                tracks,
+                info.audio_streams.len()
+            );
+            info!("{}", message);
+            task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+            Some(tracks)
+        }
+        None => None,
+    };
+
+    let remux = options.smart_copy && {
+        let blocker = if !video_filters.is_empty() || !audio_filters.is_empty() || filter_graph.is_some() {
+            Some("filters need re-encoding".to_string())
+        } else if is_adobe_preset || media_preset.is_some() || screen_preset.is_some() {
+            Some("the preset sets its own encoding".to_string())
+        } else if options.rate_control.is_some() || options.audio_bitrate.is_some() {
+            Some("quality or bitrate settings need re-encoding".to_string())
+        } else if audio_export.is_some() || !extra_outputs.is_empty() {
+            Some("the extra outputs are encoded".to_string())
+        } else if !options.extra_output_args.is_empty() {
+            Some("custom output arguments may change the encoding".to_string())
+        } else {
+            match input_probe {
+                Some(ref info) => {
+                    let video = info
+                        .video_streams
+                        .get(options.video_stream.unwrap_or(0))
+                        .map(|s| s.codec.as_str())
+                        .filter(|_| format_info.supports_video);
+                    let audio: Vec<&str> = match audio_tracks {
+                        Some(ref tracks) => tracks
+                            .iter()
+                            .filter_map(|t| info.audio_streams.get(*t))
+                            .map(|s| s.codec.as_str())
+                            .collect(),
+                        None if format_info.supports_video => info.audio_streams.iter().map(|s| s.codec.as_str()).collect(),
+                        None => info.audio_streams.first().map(|s| s.codec.as_str()).into_iter().collect(),
+                    };
+                    let audio = if include_audio { audio } else { Vec::new() };
+                    containers::copy_blocker(&output_ext, video, &audio)
+                }
+                None => Some("the input couldn't be probed".to_string()),
+            }
+        };
+        let message = match blocker {
+            None => "Smart copy: the streams already fit the container; remuxing without re-encoding.".to_string(),
+            Some(ref reason) => format!("Smart copy not used: {}. Re-encoding.", reason),
+        };
+        info!("{} ({})", message, input_file);
+        task_arc.lock().expect("Failed to lock task mutex").progress.log.push(message);
+        blocker.is_none()
+    };
+
+    Some(ConversionPlan {
+        task_id,
+        input_file,
+        output_file,
+        ffmpeg_path,
+        output_ext,
+        format_info,
+        encoder,
+        gpu_index,
+        cpu_threads,
+        preset,
+        is_adobe_preset,
+        adobe_preset,
+        media_preset,
+        screen_preset,
+        concat_list,
+        clip_length,
+        include_audio,
+        video_filters,
+        audio_filters,
+        extra_inputs,
+        filter_graph,
+        graph_has_audio,
+        audio_export: audio_export.cloned(),
+        extra_outputs,
+        chapters_input,
+        ocr_inputs,
+        keep_surround,
+        is_nvenc,
+        is_amf,
+        is_qsv,
+        is_gpu_encoder,
+        cpu_fallback_encoder,
+        input_probe,
+        probe_target,
+        caption_sources,
+        audio_adjustment,
+        input_codec,
+        decoder: decoder.map(str::to_string),
+        hwaccel,
+        first_attempt,
+        max_attempts,
+        full_hardware,
+        audio_tracks,
+        remux,
+        video_stream,
+        options,
+    })
+}
+
+/// FFmpeg arguments for one attempt of `plan`: attempt 0 decodes on the
+/// GPU, 1 decodes in software, 2 forces NV12 and 3 is the CPU fallback.
+/// CPU encoders only have attempt 0.
+fn build_ffmpeg_args(plan: &ConversionPlan, attempt: usize) -> FfmpegArgs {
+    let &ConversionPlan {
+        ref task_id,
+        ref input_file,
+        ref output_file,
+        ref output_ext,
+        ref format_info,
+        ref encoder,
+        gpu_index,
+        cpu_threads,
+        ref preset,
+        is_adobe_preset,
+        ref adobe_preset,
+        ref options,
+        ref media_preset,
+        ref screen_preset,
+        ref concat_list,
+        include_audio,
+        ref video_filters,
+        ref audio_filters,
+        ref extra_inputs,
+        ref filter_graph,
+        graph_has_audio,
+        ref audio_export,
+        ref extra_outputs,
+        chapters_input,
+        ref ocr_inputs,
+        keep_surround,
+        is_nvenc,
+        is_qsv,
+        is_gpu_encoder,
+        cpu_fallback_encoder,
+        ref input_probe,
+        ref audio_adjustment,
+        ref decoder,
+        hwaccel,
+        full_hardware,
+        ref audio_tracks,
+        remux,
+        video_stream,
+        ..
+    } = plan;
+    let is_cpu_fallback = is_gpu_encoder && attempt == 3;
+    let use_hw_decode = is_gpu_encoder && attempt == 0 && !remux;
+    let force_nv12 = is_gpu_encoder && attempt == 2;
+
+    // Pick the encoder for this attempt.
+    let attempt_encoder = if is_cpu_fallback {
+        cpu_fallback_encoder.to_string()
+    } else {
+        encoder.clone()
+    };
+
+    let mut args = vec![
+        "-y".to_string(),
+        "-hide_banner".to_string(),
+        "-progress".to_string(),
+        PROGRESS_PIPE.to_string(),
+        "-nostats".to_string(),
+    ];
+
+    if use_hw_decode {
+        // QSV and AMF only decode with their own API when frames stay on
+        // the GPU; otherwise FFmpeg picks whichever listed method works.
+        let method = match hwaccel.unwrap_or("auto") {
+            "cuda" => "cuda",
+            native if full_hardware => native,
+            _ => "auto",
+        };
+        args.push("-hwaccel".to_string());
+        args.push(method.to_string());
+        if method == "cuda" {
+            if let Some(index) = gpu_index {
+                args.push("-hwaccel_device".to_string());
+                args.push(index.to_string());
+            }
+        }
+        if full_hardware {
+            // Keep decoded frames in GPU memory for the encoder.
+            args.push("-hwaccel_output_format".to_string());
+            args.push(
+                if is_nvenc { "cuda" } else if is_qsv { "qsv" } else { "d3d11" }.to_string(),
+            );
+        }
+    }
+
+    if let Some(threads) = cpu_threads {
+        args.push("-threads".to_string());
+        args.push(threads.to_string());
+    }
+
+    if let Some(start) = options.trim_start.filter(|s| *s > 0.0) {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    // With extra inputs `-t` has to limit the main input, not the whole output.
+    let trim_duration = options.trim_duration.filter(|d| *d > 0.0);
+    let trim_as_input_option = !extra_inputs.is_empty();
+    if let Some(duration) = trim_duration.filter(|_| trim_as_input_option) {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    if let Some(decoder) = decoder {
+        args.push("-c:v".to_string());
+        args.push(decoder.to_string());
+    }
+    if options.growing_file && concat_list.is_none() {
+        args.extend(growing::follow_args(options.growing_stall_secs.unwrap_or(10)));
+    }
+    args.extend(options.extra_input_args.iter().cloned());
+    args.push("-protocol_whitelist".to_string());
+    args.push(passthrough::input_whitelist(input_file).to_string());
+    if let Some(ref list) = concat_list {
+        args.extend(["-f", "concat", "-safe", "0"].iter().map(|s| s.to_string()));
+        args.push("-i".to_string());
+        args.push(list.clone());
+    } else {
+        if let Some(ref format) = options.input_format {
+            args.push("-f".to_string());
+            args.push(format.clone());
+        }
+        args.push("-i".to_string());
+        args.push(if pipe_input::is_stdin(input_file) { "pipe:0".to_string() } else { input_file.clone() });
+    }
+
+    if let Some(duration) = trim_duration.filter(|_| !trim_as_input_option) {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    for (extra, seek) in extra_inputs {
+        if let Some(start) = seek {
+            args.push("-ss".to_string());
+            args.push(format!("{:.3}", start));
+        }
+        args.push("-i".to_string());
+        args.push(extra.clone());
+    }
+
+    if let Some((ref graph, has_audio)) = *filter_graph {
+        args.push("-filter_complex".to_string());
+        args.push(graph.clone());
+        args.push("-map".to_string());
+        args.push("[vout]".to_string());
+        if has_audio {
+            args.push("-map".to_string());
+            args.push("[aout]".to_string());
+        }
+    } else if format_info.supports_video {
+        // Map only the first video stream to avoid picking up embedded
+        // thumbnails / cover art (e.g. MJPEG attached pics) that would
+        // cause container errors when re-encoded.
+        args.push("-map".to_string());
+        if options.strip_metadata {
+            // `V` skips attached pictures, so a cover thumbnail can't
+            // slip in as the first video stream.
+            args.push(format!("0:V:{}?", video_stream));
+        } else {
+            args.push(format!("0:v:{}?", video_stream));
+        }
+    }
+    if let Some(index) = chapters_input {
+        args.push("-map_chapters".to_string());
+        args.push(index.to_string());
+    } else if options.strip_metadata {
+        args.push("-map_chapters".to_string());
+        args.push("-1".to_string());
+    }
+    if options.strip_metadata {
+        // Drops global, stream and chapter tags (titles, GPS, creation
+        // time, device names) plus data tracks such as GoPro telemetry.
+        // Bitexact keeps muxers from adding their own encoder tags.
+        args.extend(
+            ["-map_metadata", "-1", "-dn", "-fflags", "+bitexact", "-flags:v", "+bitexact", "-flags:a", "+bitexact"]
+                .iter()
+                .map(|s| s.to_string()),
+        );
+    }
+    if include_audio && !graph_has_audio {
+        let first_track = audio_tracks.as_ref().and_then(|t| t.first()).copied().unwrap_or(0);
+        match audio_tracks {
+            Some(ref tracks) if format_info.supports_video && media_preset.is_none() => {
+                for track in tracks {
+                    args.push("-map".to_string());
+                    args.push(format!("0:a:{}", track));
+                }
+                if let Some(ref info) = input_probe {
+                    args.extend(tracks::audio_disposition_args(&info.audio_streams, tracks));
+                }
+            }
+            _ => {
+                args.push("-map".to_string());
+                if format_info.supports_video && media_preset.is_none() {
+                    args.push("0:a?".to_string());
+                } else {
+                    args.push(format!("0:a:{}?", first_track));
+                }
+            }
+        }
+        if keep_surround {
+            args.push("-map".to_string());
+            args.push(format!("0:a:{}?", first_track));
+        }
+    }
+    if options.copy_subtitles && format_info.supports_video {
+        if let Some(ref info) = input_probe {
+            args.extend(tracks::subtitle_args(
+                &info.subtitle_streams,
+                output_ext,
+                options.default_subtitle,
+                options.forced_subtitle,
+            ));
+        }
+    }
+    // OCR'd tracks come after the copied ones, as the container's text format.
+    if let Some(codec) = tracks::subtitle_codec(output_ext, "subrip").filter(|_| format_info.supports_video) {
+        let copied = match input_probe {
+            Some(ref info) if options.copy_subtitles => info
+                .subtitle_streams
+                .iter()
+                .filter(|s| tracks::subtitle_codec(output_ext, &s.codec).is_some())
+                .count(),
+            _ => 0,
+        };
+        for (output_position, (index, language)) in (copied..).zip(ocr_inputs) {
+            args.push("-map".to_string());
+            args.push(format!("{}:s:0", index));
+            args.push(format!("-c:s:{}", output_position));
+            args.push(codec.to_string());
+            if let Some(language) = language {
+                args.push(format!("-metadata:s:s:{}", output_position));
+                args.push(format!("language={}", language));
+            }
+        }
+    }
+
+    if remux {
+        args.extend(["-c:v", "copy", "-c:a", "copy"].iter().map(|s| s.to_string()));
+    } else if is_adobe_preset && !is_cpu_fallback {
+        if let Some(ref preset_config) = adobe_preset {
+            args.push("-c:v".to_string());
+            args.push(preset_config.encoder.clone());
+            args.extend(preset_config.encoder_options.iter().cloned());
+            args.push("-pix_fmt".to_string());
+            args.push(preset_config.pixel_format.clone());
+            let audio_codec = match preset_config.audio_codec {
+                Some(ref codec) => Some(codec.as_str()),
+                None if preset_config.encoder == "prores_ks" || preset_config.encoder == "dnxhd" => Some("pcm_s16le"),
+                None => None,
+            };
+            if let Some(codec) = audio_codec {
+                args.push("-c:a".to_string());
+                args.push(codec.to_string());
+            }
+        }
+    } else {
+        if format_info.supports_video {
+            args.push("-c:v".to_string());
+            args.push(attempt_encoder.clone());
+            if is_nvenc && !is_cpu_fallback {
+                args.push("-preset".to_string());
+                args.push(translate_nvenc_preset(preset));
+            } else if attempt_encoder == "libx264" || attempt_encoder == "libx265" {
+                args.push("-preset".to_string());
+                args.push(translate_cpu_preset(preset));
+            }
+            if is_nvenc && !is_cpu_fallback {
+                if let Some(index) = gpu_index {
+                    args.push("-gpu".to_string());
+                    args.push(index.to_string());
+                }
+            }
+            if force_nv12 {
+                args.push("-pix_fmt".to_string());
+                args.push("nv12".to_string());
+            }
+            if let Some(ref server_preset) = media_preset {
+                args.extend(server_preset.encoder_options.iter().cloned());
+                args.push("-pix_fmt".to_string());
+                args.push(server_preset.pixel_format.clone());
+                // Variable frame rate sources stutter on some TV clients.
+                args.push("-fps_mode".to_string());
+                args.push("cfr".to_string());
+            }
+            if let Some(ref screen) = screen_preset {
+                // The tuning flags are encoder-specific, so a CPU fallback
+                // keeps only the frame rate fix.
+                if attempt_encoder == screen.encoder {
+                    args.extend(screen.encoder_options.iter().cloned());
+                    if !force_nv12 {
+                        args.push("-pix_fmt".to_string());
+                        args.push(screen.pixel_format.clone());
+                    }
+                }
+                // Captures are usually variable frame rate, which editors
+                // and many players handle badly.
+                args.push("-fps_mode".to_string());
+                args.push("cfr".to_string());
+            }
+            if let Some(ref rate_control) = options.rate_control {
+                args.extend(rate_control.args(&attempt_encoder));
+            }
+        }
+        if include_audio {
+            if let Some(ref server_preset) = media_preset {
+                args.extend(server_preset.audio_options.iter().cloned());
+                if keep_surround {
+                    args.extend(
+                        ["-c:a:1", "copy", "-disposition:a:0", "default", "-disposition:a:1", "0"]
+                            .iter()
+                            .map(|s| s.to_string()),
+                    );
+                }
+            } else {
+                args.push("-c:a".to_string());
+                if format_info.default_audio_codec.is_empty() {
+                    args.push("copy".to_string());
+                } else {
+                    args.push(format_info.default_audio_codec.to_string());
+                    args.extend(audio_adjustment.to_args());
+                    if let Some(bitrate) = options.audio_bitrate {
+                        args.push("-b:a".to_string());
+                        args.push(format!("{}k", bitrate));
+                    }
+                }
+            }
+        }
+    }
+
+    if format_info.supports_video && !video_filters.is_empty() && filter_graph.is_none() {
+        args.push("-vf".to_string());
+        args.push(video_filters.join(","));
+    }
+    // A timelapse squeezes the timestamps; VFR muxers (MKV, WebM) would keep
+    // every frame, so hold the output at the source frame rate. The presets
+    // already set this.
+    if format_info.supports_video && options.timelapse.is_some() && media_preset.is_none() && screen_preset.is_none() {
+        args.push("-fps_mode".to_string());
+        args.push("cfr".to_string());
+    }
+    if !include_audio {
+        args.push("-an".to_string());
+    } else if !audio_filters.is_empty() && !graph_has_audio {
+        // A copied surround track can't be filtered; only touch the first one.
+        args.push(if keep_surround { "-filter:a:0" } else { "-af" }.to_string());
+        args.push(audio_filters.join(","));
+    }
+
+    // Place the moov atom at the start of MP4/MOV files so players can
+    // open the file without reading until the very end.
+    if matches!(output_ext.as_str(), "mp4" | "mov" | "m4a" | "m4v" | "3gp") {
+        args.push("-movflags".to_string());
+        if options.metadata.as_ref().is_some_and(|m| m.has_custom_tags()) {
+            args.push("+faststart+use_metadata_tags".to_string());
+        } else {
+            args.push("+faststart".to_string());
+        }
+    }
+
+    if let Some(ref metadata) = options.metadata {
+        args.extend(metadata.to_args());
+    }
+    args.extend(tracks::metadata_args(&options.track_metadata));
+    args.extend(options.extra_output_args.iter().cloned());
+
+    let passlog = ratecontrol::passlog_prefix(task_id);
+    let two_pass = format_info.supports_video
+        && (!is_adobe_preset || is_cpu_fallback)
+        && ratecontrol::is_two_pass(options, &attempt_encoder);
+    let first_pass = two_pass.then(|| ratecontrol::first_pass_args(&args, &attempt_encoder, &passlog));
+    if two_pass {
+        args.extend(ratecontrol::pass_args(&attempt_encoder, 2, &passlog));
+    }
+
+    args.push(output_file.clone());
+    if let Some(export) = audio_export {
+        let track = audio_tracks.as_ref().and_then(|t| t.first()).copied().unwrap_or(0);
+        let duration = trim_duration.filter(|_| !trim_as_input_option);
+        args.extend(audio_export::output_args(export, output_file, track, audio_filters, duration));
+    }
+    if !extra_outputs.is_empty() {
+        let shared = multi_output::SharedStreams {
+            video_stream,
+            audio_track: include_audio.then(|| audio_tracks.as_ref().and_then(|t| t.first()).copied().unwrap_or(0)),
+            video_filters,
+            audio_filters,
+            duration: trim_duration.filter(|_| !trim_as_input_option),
+        };
+        for spec in extra_outputs {
+            args.extend(multi_output::output_args(spec, &shared));
+        }
+    }
+
+    FfmpegArgs {
+        encoder: attempt_encoder,
+        args,
+        first_pass,
+    }
+}
+
+// FFmpeg download and management
+pub struct FfmpegDownloader;
+
+/// Builds for this platform, tried in order when downloading FFmpeg. Each
+/// mirror lists the archives to fetch: macOS builds ship ffmpeg and
+/// ffprobe separately.
+#[cfg(target_os = "windows")]
+const FFMPEG_MIRRORS: &[&[&str]] = &[
+    &["https://www.gyan.dev/ffmpeg/builds/ffmpeg-release-essentials.zip"],
+    &["https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/ffmpeg-master-latest-win64-gpl.zip"],
+];
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const FFMPEG_MIRRORS: &[&[&str]] = &[
+    &["https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz"],
+    &["https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/ffmpeg-master-latest-linux64-gpl.tar.xz"],
+];
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const FFMPEG_MIRRORS: &[&[&str]] = &[
+    &["https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-arm64-static.tar.xz"],
+    &["https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/ffmpeg-master-latest-linuxarm64-gpl.tar.xz"],
+];
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+const FFMPEG_MIRRORS: &[&[&str]] = &[
+    &[
+        "https://evermeet.cx/ffmpeg/getrelease/zip",
+        "https://evermeet.cx/ffmpeg/getrelease/ffprobe/zip",
+    ],
+    &[
+        "https://ffmpeg.martin-riedl.de/redirect/latest/macos/amd64/release/ffmpeg.zip",
+        "https://ffmpeg.martin-riedl.de/redirect/latest/macos/amd64/release/ffprobe.zip",
+    ],
+];
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+const FFMPEG_MIRRORS: &[&[&str]] = &[&[
+    "https://ffmpeg.martin-riedl.de/redirect/latest/macos/arm64/release/ffmpeg.zip",
+    "https://ffmpeg.martin-riedl.de/redirect/latest/macos/arm64/release/ffprobe.zip",
+]];
+#[cfg(not(any(
+    target_os = "windows",
+    all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")),
+    all(target_os = "macos", any(target_arch = "x86_64", target_arch = "aarch64")),
+)))]
+const FFMPEG_MIRRORS: &[&[&str]] = &[];
+
+/// Times an interrupted FFmpeg download is resumed before trying the next mirror.
+const DOWNLOAD_RETRIES: u32 = 5;
+
+/// Payload of the `ffmpeg-download-retry` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadRetry {
+    pub url: String,
+    /// Retry number, from 1.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_secs: u64,
+    /// Bytes already downloaded that the retry continues from.
+    pub resume_from: u64,
+    pub error: String,
+}
+
+/// Archive formats FFmpeg builds are shipped in, told apart by their magic
+/// bytes since some download URLs have no extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+}
+
+impl ArchiveKind {
+    fn detect(path: &Path) -> Result<Self, AppError> {
+        use std::io::Read;
+        let mut header = Vec::with_capacity(262);
+        std::fs::File::open(path)
+            .and_then(|file| file.take(262).read_to_end(&mut header))
+            .map_err(|e| AppError::Io(format!("Failed to open archive: {}", e)))?;
+        if header.starts_with(b"PK\x03\x04") {
+            Ok(Self::Zip)
+        } else if header.starts_with(&[0x1f, 0x8b]) {
+            Ok(Self::TarGz)
+        } else if header.starts_with(b"\xfd7zXZ\x00") {
+            Ok(Self::TarXz)
+        } else if header.get(257..262) == Some(b"ustar") {
+            Ok(Self::Tar)
+        } else {
+            Err(AppError::Ffmpeg(
+                "Unsupported archive format; use a .zip, .tar, .tar.gz or .tar.xz file".to_string(),
+            ))
+        }
+    }
+}
+
+impl FfmpegDownloader {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn get_ffmpeg_app_dir() -> Result<PathBuf, AppError> {
+        let app_dir = dirs::data_dir()
+            .ok_or_else(|| AppError::Internal("Could not find app data directory".to_string()))?
+            .join("Dreamcodec");
+        Ok(app_dir)
+    }
+
+    pub fn get_ffmpeg_path() -> Result<PathBuf, AppError> {
+        let app_dir = Self::get_ffmpeg_app_dir()?;
+        Ok(app_dir.join(FFMPEG_BINARY))
+    }
+
+    pub fn get_ffprobe_path() -> Result<PathBuf, AppError> {
+        let app_dir = Self::get_ffmpeg_app_dir()?;
+        Ok(app_dir.join(FFPROBE_BINARY))
+    }
+
+    pub async fn is_ffmpeg_available() -> bool {
+        // First try the auto-locator
+        if let Some(path) = FfmpegLocator::find_ffmpeg().await {
+            return path.exists();
+        }
+
+        // Fallback to app directory check
+        match Self::get_ffmpeg_path() {
+            Ok(path) => path.exists(),
+            Err(_) => false,
+        }
+    }
+
+    /// Download FFmpeg into the app directory, trying each mirror in turn
+    /// (or only `custom_url` when set). Interrupted downloads are resumed
+    /// after a backoff, reported through `retry_callback`.
+    pub async fn download_and_extract_ffmpeg<F, R>(
+        custom_url: Option<String>,
+        progress_callback: F,
+        retry_callback: R,
+    ) -> Result<PathBuf, AppError>
+    where
+        F: Fn(u64, u64) + Send + 'static,
+        R: Fn(&DownloadRetry) + Send + 'static,
+    {
+        let app_dir = Self::get_ffmpeg_app_dir()?;
+        let ffmpeg_path = app_dir.join(FFMPEG_BINARY);
+
+        // Check if already exists
+        if ffmpeg_path.exists() {
+            return Ok(ffmpeg_path);
+        }
+
+        // Create directory if needed
+        fs::create_dir_all(&app_dir)
+            .await
+            .map_err(|e| AppError::Io(e.to_string()))?;
+
+        let mirrors = Self::mirror_urls(custom_url.as_deref());
+        if mirrors.is_empty() {
+            return Err(AppError::Ffmpeg(
+                "No FFmpeg download is available for this platform; install FFmpeg and add it to PATH".to_string(),
+            ));
+        }
+        let mut failures = Vec::new();
+        'mirrors: for urls in &mirrors {
+            for url in urls {
+                info!("Downloading FFmpeg from {}", url);
+                if let Err(e) = Self::download_from(url, &app_dir, &progress_callback, &retry_callback).await {
+                    failures.push(format!("{}: {}", url, e));
+                    warn!("FFmpeg download failed: {}", failures.last().map(String::as_str).unwrap_or_default());
+                    continue 'mirrors;
+                }
+            }
+            if ffmpeg_path.exists() {
+                return Ok(ffmpeg_path);
+            }
+            failures.push(format!("{}: FFmpeg extraction failed", urls.join(", ")));
+        }
+
+        Err(AppError::Ffmpeg(format!(
+            "Failed to download FFmpeg from every mirror ({})",
+            failures.join("; ")
+        )))
+    }
+
+    /// Mirrors in the order they are tried, each with the archives to fetch.
+    /// A custom URL is the only one used, so a pinned build is never
+    /// silently swapped for another.
+    pub fn mirror_urls(custom_url: Option<&str>) -> Vec<Vec<String>> {
+        match custom_url.map(str::trim).filter(|url| !url.is_empty()) {
+            Some(url) => vec![vec![url.to_string()]],
+            None => FFMPEG_MIRRORS
+                .iter()
+                .map(|urls| urls.iter().map(|url| url.to_string()).collect())
+                .collect(),
+        }
+    }
+
+    /// Partial download of `url`, kept between attempts (and app restarts)
+    /// so a retry continues where the last one stopped.
+    fn partial_download_path(app_dir: &Path, url: &str) -> PathBuf {
+        use sha2::{Digest, Sha256};
+        let hash = Sha256::digest(url.as_bytes());
+        let id: String = hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+        app_dir.join(format!("ffmpeg-download-{}.part", id))
+    }
+
+    async fn download_from<F, R>(
+        url: &str,
+        app_dir: &Path,
+        progress_callback: &F,
+        retry_callback: &R,
+    ) -> Result<(), AppError>
+    where
+        F: Fn(u64, u64),
+        R: Fn(&DownloadRetry),
+    {
+        let archive_path = Self::partial_download_path(app_dir, url);
+        let mut retries = 0;
+        loop {
+            match Self::fetch_resumable(url, &archive_path, progress_callback).await {
+                Ok(()) => break,
+                Err((e, true)) if retries < DOWNLOAD_RETRIES => {
+                    let delay = transient::backoff(retries);
+                    retries += 1;
+                    let retry = DownloadRetry {
+                        url: url.to_string(),
+                        attempt: retries,
+                        max_attempts: DOWNLOAD_RETRIES,
+                        delay_secs: delay.as_secs(),
+                        resume_from: std::fs::metadata(&archive_path).map(|m| m.len()).unwrap_or(0),
+                        error: e.to_string(),
+                    };
+                    warn!(
+                        "FFmpeg download interrupted ({}); retry {}/{} in {}s from byte {}",
+                        retry.error, retry.attempt, retry.max_attempts, retry.delay_secs, retry.resume_from
+                    );
+                    retry_callback(&retry);
+                    tokio::time::sleep(delay).await;
+                }
+                // The partial file stays for the next download.
+                Err((e, _)) => return Err(e),
+            }
+        }
+
+        let result = Self::extract_archive(&archive_path, app_dir).await;
+        let _ = fs::remove_file(&archive_path).await;
+        let _ = fs::remove_file(Self::validator_path(&archive_path)).await;
+        result
+    }
+
+    /// Where the ETag or Last-Modified of a partial download is kept.
+    fn validator_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".validator");
+        path.with_file_name(name)
+    }
+
+    /// `If-Range` value identifying the file behind a response: a strong
+    /// ETag, else Last-Modified. Weak ETags can't be used with ranges.
+    fn response_validator(headers: &reqwest::header::HeaderMap) -> Option<String> {
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+        header(reqwest::header::ETAG)
+            .filter(|etag| !etag.is_empty() && !etag.starts_with("W/"))
+            .or_else(|| header(reqwest::header::LAST_MODIFIED).filter(|date| !date.is_empty()))
+            .map(str::to_string)
+    }
+
+    /// Download `url` into `path`, continuing after the bytes already there
+    /// when the server supports ranges. The flag on an error tells whether
+    /// trying again could help.
+    async fn fetch_resumable<F>(url: &str, path: &Path, progress_callback: &F) -> Result<(), (AppError, bool)>
+    where
+        F: Fn(u64, u64),
+    {
+        let retryable = |e: reqwest::Error| {
+            let permanent = e
+                .status()
+                .is_some_and(|s| s.is_client_error() && s.as_u16() != 408 && s.as_u16() != 429);
+            (AppError::Internal(format!("Failed to download FFmpeg: {}", e)), !permanent)
+        };
+        let io_error = |e: std::io::Error| (AppError::Io(e.to_string()), false);
+
+        // The mirrors serve moving "latest" builds, so bytes are only appended
+        // when the server confirms with `If-Range` that the file is unchanged;
+        // otherwise it answers 200 with the whole new file.
+        let validator_path = Self::validator_path(path);
+        let validator = std::fs::read_to_string(&validator_path).ok().filter(|v| !v.trim().is_empty());
+        let existing = match validator {
+            Some(_) => std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            None => 0,
+        };
+        let mut request = reqwest::Client::new().get(url);
+        if let Some(ref validator) = validator.filter(|_| existing > 0) {
+            request = request
+                .header(reqwest::header::RANGE, format!("bytes={}-", existing))
+                .header(reqwest::header::IF_RANGE, validator.trim());
+        }
+        let response = request.send().await.map_err(retryable)?;
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            // The partial file doesn't fit what the server has now.
+            let _ = fs::remove_file(path).await;
+            let _ = fs::remove_file(&validator_path).await;
+            return Err((AppError::Internal("Partial download is stale; starting over".to_string()), true));
+        }
+        let response = response.error_for_status().map_err(retryable)?;
+
+        let resumed = existing > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        if !resumed {
+            if existing > 0 {
+                info!("FFmpeg build changed on the server; restarting the download");
+            }
+            // Without a validator a later resume can't be checked, so it starts over.
+            match Self::response_validator(response.headers()) {
+                Some(validator) => fs::write(&validator_path, validator).await.map_err(io_error)?,
+                None => {
+                    let _ = fs::remove_file(&validator_path).await;
+                }
+            }
+        }
+        let mut downloaded = if resumed { existing } else { 0 };
+        let total_size = response.content_length().map_or(0, |len| len + downloaded);
+        if resumed {
+            info!("Resuming FFmpeg download at byte {}", existing);
+        }
+        progress_callback(downloaded, total_size);
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(path)
+            .await
+            .map_err(io_error)?;
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| (AppError::Internal(format!("Download error: {}", e)), true))?;
+            file.write_all(&chunk).await.map_err(io_error)?;
+            downloaded += chunk.len() as u64;
+            progress_callback(downloaded, total_size);
+        }
+        file.flush().await.map_err(io_error)?;
+
+        if total_size > 0 && downloaded < total_size {
+            return Err((
+                AppError::Internal(format!("Download ended early at {} of {} bytes", downloaded, total_size)),
+                true,
+            ));
+        }
         Ok(())
     }
+
+    /// Install FFmpeg from a local zip or tar(.gz/.xz) archive, for machines that
+    /// can't download. The binaries are extracted to a staging folder and
+    /// only replace the current install once `ffmpeg -version` runs.
+    pub async fn install_from_archive(archive: &Path) -> Result<PathBuf, AppError> {
+        if !archive.is_file() {
+            return Err(AppError::Io(format!("Archive not found: {}", archive.display())));
+        }
+        let app_dir = Self::get_ffmpeg_app_dir()?;
+        let staging = app_dir.join("ffmpeg-install");
+        let _ = fs::remove_dir_all(&staging).await;
+        fs::create_dir_all(&staging)
+            .await
+            .map_err(|e| AppError::Io(e.to_string()))?;
+
+        let result = async {
+            Self::extract_archive(archive, &staging).await?;
+            if !staging.join(FFMPEG_BINARY).exists() {
+                return Err(AppError::Ffmpeg("Could not find ffmpeg in archive".to_string()));
+            }
+
+            let staged = staging.join(FFMPEG_BINARY);
+            let version = Self::binary_version(&staged).await.ok_or_else(|| {
+                AppError::Ffmpeg("The archive's ffmpeg binary doesn't run on this machine".to_string())
+            })?;
+            info!("Installing {} from {}", version, archive.display());
+
+            for binary in [FFMPEG_BINARY, FFPROBE_BINARY] {
+                let source = staging.join(binary);
+                if source.exists() {
+                    fs::rename(&source, app_dir.join(binary))
+                        .await
+                        .map_err(|e| AppError::Io(format!("Failed to install {}: {}", binary, e)))?;
+                }
+            }
+            Ok(app_dir.join(FFMPEG_BINARY))
+        }
+        .await;
+
+        let _ = fs::remove_dir_all(&staging).await;
+        result
+    }
+
+    /// First line of `<binary> -version`, if it runs.
+    async fn binary_version(binary: &Path) -> Option<String> {
+        let mut cmd = Command::new(binary);
+        cmd.arg("-version").stdout(Stdio::piped()).stderr(Stdio::null());
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+        let output = watchdog::output(&mut cmd).await.ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let first_line = stdout.lines().next()?.trim().to_string();
+        (output.status.success() && first_line.starts_with("ffmpeg version")).then_some(first_line)
+    }
+
+    /// Extract `ffmpeg`/`ffprobe` from a zip or tar archive into
+    /// `output_dir`. An archive may hold only one of them (macOS builds).
+    async fn extract_archive(archive: &Path, output_dir: &Path) -> Result<(), AppError> {
+        match ArchiveKind::detect(archive)? {
+            ArchiveKind::Zip => Self::extract_ffmpeg(archive, output_dir).await,
+            kind => Self::extract_ffmpeg_tar(archive, kind, output_dir),
+        }
+    }
+
+    /// Installed name for an archive entry that is `ffmpeg` or `ffprobe`
+    /// (with or without `.exe`); documentation folders are skipped.
+    fn install_name(entry: &Path) -> Option<&'static str> {
+        if entry.to_string_lossy().to_lowercase().contains("doc") {
+            return None;
+        }
+        match entry.file_name()?.to_string_lossy().to_lowercase().as_str() {
+            "ffmpeg" | "ffmpeg.exe" => Some(FFMPEG_BINARY),
+            "ffprobe" | "ffprobe.exe" => Some(FFPROBE_BINARY),
+            _ => None,
+        }
+    }
+
+    /// Write an extracted binary and make it executable.
+    fn write_binary(reader: &mut impl std::io::Read, out_path: &Path) -> Result<(), AppError> {
+        let mut outfile = std::fs::File::create(out_path)
+            .map_err(|e| AppError::Io(format!("Failed to create output file: {}", e)))?;
+        std::io::copy(reader, &mut outfile)
+            .map_err(|e| AppError::Io(format!("Failed to extract {}: {}", out_path.display(), e)))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(out_path, std::fs::Permissions::from_mode(0o755))
+                .map_err(|e| AppError::Io(format!("Failed to make {} executable: {}", out_path.display(), e)))?;
+        }
+        Ok(())
+    }
+
+    fn extract_ffmpeg_tar(tar_path: &Path, kind: ArchiveKind, output_dir: &Path) -> Result<(), AppError> {
+        let file = std::fs::File::open(tar_path)
+            .map_err(|e| AppError::Io(format!("Failed to open archive: {}", e)))?;
+        let reader: Box<dyn std::io::Read> = match kind {
+            ArchiveKind::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+            ArchiveKind::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+            _ => Box::new(file),
+        };
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive
+            .entries()
+            .map_err(|e| AppError::Internal(format!("Failed to read tar archive: {}", e)))?;
+
+        let mut found = false;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| AppError::Internal(format!("Failed to read tar entry: {}", e)))?;
+            let path = entry
+                .path()
+                .map_err(|e| AppError::Internal(format!("Invalid tar entry: {}", e)))?
+                .to_path_buf();
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            if let Some(target) = Self::install_name(&path) {
+                Self::write_binary(&mut entry, &output_dir.join(target))?;
+                found = true;
+            }
+        }
+
+        if !found {
+            return Err(AppError::Ffmpeg("Could not find ffmpeg or ffprobe in archive".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn extract_ffmpeg(zip_path: &Path, output_dir: &Path) -> Result<(), AppError> {
+        let file =
+            std::fs::File::open(zip_path).map_err(|e| AppError::Io(format!("Failed to open zip file: {}", e)))?;
+
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| AppError::Internal(format!("Failed to read zip archive: {}", e)))?;
+
+        let mut found = false;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(|e| {
+                AppError::Internal(format!("Failed to read zip entry: {}", e))
+            })?;
+            if !entry.is_file() {
+                continue;
+            }
+            if let Some(target) = Self::install_name(Path::new(entry.name())) {
+                Self::write_binary(&mut entry, &output_dir.join(target))?;
+                found = true;
+            }
+        }
+
+        if !found {
+            return Err(AppError::Ffmpeg(
+                "Could not find ffmpeg or ffprobe in archive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::EncoderDefaults;
+
+    /// An unprobed MKV to MP4 job with nothing beyond `options` to plan.
+    fn plan(encoder: &str, preset: &str, options: ConversionOptions) -> ConversionPlan {
+        let is_nvenc = encoder.contains("nvenc");
+        let is_amf = encoder.contains("amf");
+        let is_qsv = encoder.contains("qsv");
+        let (output_ext, format_info) = output_format("out.mp4");
+        ConversionPlan {
+            task_id: "test".to_string(),
+            input_file: "in.mkv".to_string(),
+            output_file: "out.mp4".to_string(),
+            ffmpeg_path: "ffmpeg".to_string(),
+            output_ext,
+            format_info,
+            encoder: encoder.to_string(),
+            gpu_index: None,
+            cpu_threads: None,
+            preset: preset.to_string(),
+            is_adobe_preset: false,
+            adobe_preset: None,
+            media_preset: None,
+            screen_preset: None,
+            concat_list: None,
+            clip_length: None,
+            include_audio: true,
+            video_filters: Vec::new(),
+            audio_filters: Vec::new(),
+            extra_inputs: Vec::new(),
+            filter_graph: None,
+            graph_has_audio: false,
+            audio_export: None,
+            extra_outputs: Vec::new(),
+            chapters_input: None,
+            ocr_inputs: Vec::new(),
+            keep_surround: false,
+            is_nvenc,
+            is_amf,
+            is_qsv,
+            is_gpu_encoder: is_nvenc || is_amf || is_qsv,
+            cpu_fallback_encoder: if encoder.contains("hevc") { "libx265" } else { "libx264" },
+            input_probe: None,
+            probe_target: "in.mkv".to_string(),
+            caption_sources: Vec::new(),
+            audio_adjustment: audio_limits::AudioAdjustment::default(),
+            input_codec: None,
+            decoder: None,
+            hwaccel: None,
+            first_attempt: 0,
+            max_attempts: 1,
+            full_hardware: false,
+            audio_tracks: None,
+            remux: false,
+            video_stream: 0,
+            options,
+        }
+    }
+
+    fn has_pair(args: &[String], flag: &str, value: &str) -> bool {
+        args.windows(2).any(|w| w[0] == flag && w[1] == value)
+    }
+
+    #[test]
+    fn encoder_default_preset_reaches_nvenc_and_its_cpu_fallback() {
+        let defaults = EncoderDefaults {
+            preset: Some("p5".to_string()),
+            rate_control: None,
+        };
+        let mut preset = String::new();
+        let mut options = ConversionOptions::default();
+        defaults.apply(&mut preset, &mut options);
+
+        let plan = plan("hevc_nvenc", &preset, options);
+        assert!(has_pair(&build_ffmpeg_args(&plan, 1).args, "-preset", "p5"));
+        let fallback = build_ffmpeg_args(&plan, 3);
+        assert_eq!(fallback.encoder, "libx265");
+        assert!(has_pair(&fallback.args, "-preset", "slow"));
+    }
+
+    fn hardware_path<'a>(options: &'a ConversionOptions, preset_pixel_format: Option<&'a str>) -> HardwarePath<'a> {
+        HardwarePath {
+            encoder: "h264_nvenc",
+            options,
+            supports_video: true,
+            compositing: false,
+            video_filters: false,
+            preset_pixel_format,
+            skip_hw_decode: false,
+            hwaccel: Some("cuda"),
+            input_codec: Some("h264"),
+        }
+    }
+
+    #[test]
+    fn full_hardware_needs_frames_left_untouched() {
+        let options = ConversionOptions::default();
+        assert!(hardware_path(&options, None).blockers().is_empty());
+
+        let blockers = hardware_path(&options, Some("yuv420p")).blockers();
+        assert_eq!(blockers, vec!["the preset converts frames to yuv420p on the CPU".to_string()]);
+        assert_eq!(hardware_path(&options, Some("nv12")).blockers().len(), 1);
+
+        let options = ConversionOptions {
+            extra_output_args: vec!["-pix_fmt".to_string(), "nv12".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            hardware_path(&options, None).blockers(),
+            vec!["a custom -pix_fmt converts frames on the CPU".to_string()]
+        );
+    }
+
+    #[test]
+    fn full_hardware_needs_a_native_hardware_decoder() {
+        let options = ConversionOptions::default();
+        let mut path = hardware_path(&options, None);
+        path.hwaccel = Some("d3d11va");
+        assert_eq!(path.blockers(), vec!["this FFmpeg build lacks cuda decoding".to_string()]);
+
+        let mut path = hardware_path(&options, None);
+        path.input_codec = Some("prores");
+        assert_eq!(path.blockers(), vec!["no hardware decoder for prores".to_string()]);
+    }
+
+    #[test]
+    fn download_resumes_only_against_a_strong_validator() {
+        use reqwest::header::{HeaderMap, HeaderValue, ETAG, LAST_MODIFIED};
+        let mut headers = HeaderMap::new();
+        assert_eq!(FfmpegDownloader::response_validator(&headers), None);
+        headers.insert(LAST_MODIFIED, HeaderValue::from_static("Tue, 06 Oct 2026 10:00:00 GMT"));
+        headers.insert(ETAG, HeaderValue::from_static("W/\"abc\""));
+        assert_eq!(
+            FfmpegDownloader::response_validator(&headers).as_deref(),
+            Some("Tue, 06 Oct 2026 10:00:00 GMT")
+        );
+        headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+        assert_eq!(FfmpegDownloader::response_validator(&headers).as_deref(), Some("\"abc\""));
+    }
+
+    #[test]
+    fn timelapse_holds_a_constant_frame_rate() {
+        for timelapse in [
+            TimelapseOptions {
+                speed: Some(60.0),
+                ..Default::default()
+            },
+            TimelapseOptions {
+                every_nth: Some(30),
+                ..Default::default()
+            },
+        ] {
+            let options = ConversionOptions {
+                timelapse: Some(timelapse),
+                ..Default::default()
+            };
+            let video_filters = filters::video_filter_chain(&options, "in.mkv", None);
+            let mut plan = plan("libx264", "medium", options);
+            plan.output_file = "out.mkv".to_string();
+            plan.output_ext = "mkv".to_string();
+            plan.video_filters = video_filters;
+            plan.include_audio = false;
+            let args = build_ffmpeg_args(&plan, 0).args;
+            assert!(has_pair(&args, "-fps_mode", "cfr"));
+            assert!(args.iter().any(|a| a.contains("setpts=PTS/")));
+        }
+    }
+
+    #[test]
+    fn strip_metadata_drops_tags_and_chapters() {
+        let options = ConversionOptions {
+            strip_metadata: true,
+            ..Default::default()
+        };
+        let args = build_ffmpeg_args(&plan("libx264", "medium", options), 0).args;
+        assert!(has_pair(&args, "-map_metadata", "-1"));
+        assert!(has_pair(&args, "-map_chapters", "-1"));
+
+        let args = build_ffmpeg_args(&plan("libx264", "medium", ConversionOptions::default()), 0).args;
+        assert!(!args.iter().any(|a| a == "-map_metadata" || a == "-map_chapters"));
+    }
+
+    #[test]
+    fn idempotency_claims_hold_until_released() {
+        let mut manager = FfmpegManager::new();
+        assert_eq!(manager.claim_idempotency_key("upload-1", "a"), KeyClaim::Claimed);
+        assert_eq!(manager.claim_idempotency_key("upload-1", "b"), KeyClaim::InFlight);
+
+        manager.release_idempotency_key("upload-1", "b");
+        assert_eq!(manager.claim_idempotency_key("upload-1", "c"), KeyClaim::InFlight);
+        manager.release_idempotency_key("upload-1", "a");
+        assert_eq!(manager.claim_idempotency_key("upload-1", "c"), KeyClaim::Claimed);
+    }
+
+    /// Plans a job against a stand-in FFmpeg that lists CUDA decoding and
+    /// probes as MJPEG cover art followed by an 8K HEVC stream.
+    #[cfg(unix)]
+    async fn plan_with_two_video_streams(encoder: &str, video_stream: Option<usize>) -> ConversionPlan {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("dreamcodec-plan-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("probe.txt"),
+            "Input #0, matroska,webm, from 'in.mkv':\n\
+             \x20 Duration: 00:01:00.00, start: 0.000000, bitrate: 40000 kb/s\n\
+             \x20 Stream #0:0: Video: mjpeg (Baseline), yuvj420p(pc), 640x480, 25 fps\n\
+             \x20 Stream #0:1: Video: hevc (Main), yuv420p(tv), 7680x4320, 25 fps\n",
+        )
+        .unwrap();
+        let ffmpeg = dir.join("ffmpeg");
+        std::fs::write(
+            &ffmpeg,
+            "#!/bin/sh\n\
+             case \"$2\" in\n\
+             -hwaccels) printf 'Hardware acceleration methods:\\ncuda\\n' ;;\n\
+             -decoders) ;;\n\
+             *) cat \"$(dirname \"$0\")/probe.txt\" >&2 ;;\n\
+             esac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let request = ConversionRequest {
+            input_file: dir.join("in.mkv").to_string_lossy().to_string(),
+            output_file: dir.join("out.mkv").to_string_lossy().to_string(),
+            ffmpeg_path: ffmpeg.to_string_lossy().to_string(),
+            encoder: encoder.to_string(),
+            gpu_index: None,
+            cpu_threads: None,
+            preset: String::new(),
+            is_adobe_preset: false,
+            options: ConversionOptions {
+                video_stream,
+                ..Default::default()
+            },
+        };
+        let task = Arc::new(Mutex::new(ConversionTask::new("plan-test".to_string(), request, None)));
+        plan_conversion(&task, true).await.expect("plan")
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn plan_reads_codec_and_size_from_the_selected_video_stream() {
+        let plan = plan_with_two_video_streams("hevc_nvenc", Some(1)).await;
+        assert_eq!(plan.input_codec.as_deref(), Some("hevc"));
+        let plan = plan_with_two_video_streams("hevc_nvenc", None).await;
+        assert_eq!(plan.input_codec.as_deref(), Some("mjpeg"));
+
+        // The 8K stream is over the H.264 NVENC size limit; the cover art isn't.
+        let plan = plan_with_two_video_streams("h264_nvenc", Some(1)).await;
+        assert_eq!(plan.first_attempt, 3);
+        let plan = plan_with_two_video_streams("h264_nvenc", None).await;
+        assert_ne!(plan.first_attempt, 3);
+    }
 }
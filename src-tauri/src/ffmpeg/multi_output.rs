@@ -0,0 +1,159 @@
+use super::get_format_info;
+use super::ratecontrol::RateControl;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Another file written by the same FFmpeg run, encoded from the one decode
+/// of the input, e.g. a ProRes master next to the MP4 delivery.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSpec {
+    #[serde(alias = "outputFile")]
+    pub output_file: String,
+    /// Video encoder, `copy` to keep the source stream; the container's
+    /// default codec when unset. Ignored for audio-only formats.
+    pub encoder: Option<String>,
+    pub preset: Option<String>,
+    #[serde(alias = "rateControl")]
+    pub rate_control: Option<RateControl>,
+    /// Audio codec, `copy` to keep the source stream; the container's
+    /// default codec when unset.
+    #[serde(alias = "audioCodec")]
+    pub audio_codec: Option<String>,
+    /// Audio bitrate in kbit/s.
+    #[serde(alias = "audioBitrate")]
+    pub audio_bitrate: Option<u32>,
+    /// Output arguments for this file only.
+    #[serde(alias = "extraArgs")]
+    pub extra_args: Vec<String>,
+}
+
+impl OutputSpec {
+    pub fn extension(&self) -> String {
+        Path::new(&self.output_file)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    /// Encoder for the video stream, if the format has one.
+    pub fn video_encoder(&self) -> Option<String> {
+        let format_info = get_format_info(&self.extension());
+        format_info.supports_video.then(|| {
+            self.encoder
+                .clone()
+                .filter(|e| !e.trim().is_empty())
+                .unwrap_or_else(|| format_info.default_video_codec.to_string())
+        })
+    }
+}
+
+/// Streams and edits of the main output that every extra output repeats.
+pub struct SharedStreams<'a> {
+    pub video_stream: usize,
+    /// First kept audio track; `None` when the job drops audio.
+    pub audio_track: Option<usize>,
+    pub video_filters: &'a [String],
+    pub audio_filters: &'a [String],
+    /// `-t` of the main output, when trimming is an output option.
+    pub duration: Option<f64>,
+}
+
+/// Arguments for one extra output, appended after the main output so they
+/// apply to this output only.
+pub fn output_args(spec: &OutputSpec, shared: &SharedStreams) -> Vec<String> {
+    let ext = spec.extension();
+    let format_info = get_format_info(&ext);
+    let mut args: Vec<String> = Vec::new();
+
+    match spec.video_encoder() {
+        Some(encoder) => {
+            args.extend(["-map".to_string(), format!("0:v:{}?", shared.video_stream)]);
+            if encoder != "copy" {
+                if !shared.video_filters.is_empty() {
+                    args.extend(["-vf".to_string(), shared.video_filters.join(",")]);
+                }
+                if let Some(ref preset) = spec.preset {
+                    args.extend(["-preset".to_string(), preset.clone()]);
+                }
+            }
+            args.extend(["-c:v".to_string(), encoder.clone()]);
+            if encoder != "copy" {
+                args.extend(spec.rate_control.iter().flat_map(|rc| rc.args(&encoder)));
+            }
+        }
+        None => args.push("-vn".to_string()),
+    }
+
+    match shared.audio_track.filter(|_| format_info.supports_audio) {
+        Some(track) => {
+            args.extend(["-map".to_string(), format!("0:a:{}?", track)]);
+            let codec = spec
+                .audio_codec
+                .clone()
+                .filter(|c| !c.trim().is_empty())
+                .unwrap_or_else(|| format_info.default_audio_codec.to_string());
+            if codec != "copy" {
+                if !shared.audio_filters.is_empty() {
+                    args.extend(["-af".to_string(), shared.audio_filters.join(",")]);
+                }
+                if let Some(bitrate) = spec.audio_bitrate {
+                    args.extend(["-b:a".to_string(), format!("{}k", bitrate)]);
+                }
+            }
+            args.extend(["-c:a".to_string(), codec]);
+        }
+        None => args.push("-an".to_string()),
+    }
+    args.push("-sn".to_string());
+
+    if let Some(duration) = shared.duration {
+        args.extend(["-t".to_string(), format!("{:.3}", duration)]);
+    }
+    if matches!(ext.as_str(), "mp4" | "mov" | "m4a" | "m4v" | "3gp") {
+        args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+    }
+    args.extend(spec.extra_args.iter().cloned());
+    args.push(spec.output_file.clone());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_each_output_with_its_own_codecs() {
+        let shared = SharedStreams {
+            video_stream: 0,
+            audio_track: Some(1),
+            video_filters: &["yadif".to_string()],
+            audio_filters: &[],
+            duration: None,
+        };
+        let master = OutputSpec {
+            output_file: "/out/master.mov".to_string(),
+            encoder: Some("prores_ks".to_string()),
+            audio_codec: Some("pcm_s24le".to_string()),
+            extra_args: vec!["-profile:v".to_string(), "3".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            output_args(&master, &shared),
+            [
+                "-map", "0:v:0?", "-vf", "yadif", "-c:v", "prores_ks", "-map", "0:a:1?", "-c:a", "pcm_s24le", "-sn",
+                "-movflags", "+faststart", "-profile:v", "3", "/out/master.mov"
+            ]
+        );
+
+        let audio = OutputSpec {
+            output_file: "/out/mix.mp3".to_string(),
+            audio_bitrate: Some(192),
+            ..Default::default()
+        };
+        assert_eq!(
+            output_args(&audio, &shared),
+            ["-vn", "-map", "0:a:1?", "-b:a", "192k", "-c:a", "libmp3lame", "-sn", "/out/mix.mp3"]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use super::PROGRESS_PIPE;
+use serde::{Deserialize, Serialize};
+
+/// An external audio file to add as its own track.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioTrackInput {
+    pub file: String,
+    /// ISO 639-2 language code, e.g. `jpn`.
+    pub language: Option<String>,
+    pub title: Option<String>,
+    /// Mark this track as the default one.
+    pub default: bool,
+    /// Encoder to use; copied when unset and the container accepts the codec.
+    pub codec: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MuxOptions {
+    #[serde(alias = "audioTracks")]
+    pub audio_tracks: Vec<AudioTrackInput>,
+    /// Keep the video file's own audio tracks ahead of the added ones.
+    #[serde(alias = "keepSourceAudio")]
+    pub keep_source_audio: bool,
+}
+
+/// Whether `container` can hold `codec` without re-encoding.
+pub fn container_accepts(container: &str, codec: &str) -> bool {
+    match container {
+        "mkv" => true,
+        "mp4" | "mov" => matches!(
+            codec,
+            "aac" | "ac3" | "eac3" | "mp3" | "opus" | "flac" | "alac"
+        ),
+        _ => false,
+    }
+}
+
+/// Codec for an added track: the requested one, a copy when the container
+/// accepts the source codec, AAC otherwise.
+pub fn track_codec<'a>(track: &'a AudioTrackInput, container: &str, source_codec: Option<&str>) -> &'a str {
+    match track.codec.as_deref().filter(|c| !c.trim().is_empty()) {
+        Some(codec) => codec,
+        None if source_codec.is_some_and(|c| container_accepts(container, c)) => "copy",
+        None => "aac",
+    }
+}
+
+/// Arguments muxing `video_file` with the added audio tracks into `output`.
+/// `source_audio_count` is the number of audio tracks the video file has;
+/// `source_codecs` holds each added file's probed audio codec.
+pub fn build_args(
+    video_file: &str,
+    options: &MuxOptions,
+    source_audio_count: usize,
+    source_codecs: &[Option<String>],
+    output: &str,
+) -> Vec<String> {
+    let container = output.rsplit('.').next().unwrap_or("").to_lowercase();
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-progress", PROGRESS_PIPE, "-nostats", "-i", video_file]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    for track in &options.audio_tracks {
+        args.push("-i".to_string());
+        args.push(track.file.clone());
+    }
+
+    args.extend(["-map", "0:v", "-map", "0:s?", "-c:v", "copy"].iter().map(|s| s.to_string()));
+    args.push("-c:s".to_string());
+    args.push(if container == "mkv" { "copy" } else { "mov_text" }.to_string());
+
+    let kept = if options.keep_source_audio { source_audio_count } else { 0 };
+    if kept > 0 {
+        args.extend(["-map", "0:a", "-c:a", "copy"].iter().map(|s| s.to_string()));
+    }
+
+    let has_default = options.audio_tracks.iter().any(|t| t.default);
+    for (i, track) in options.audio_tracks.iter().enumerate() {
+        let output_position = kept + i;
+        args.push("-map".to_string());
+        args.push(format!("{}:a:0", i + 1));
+        let codec = track_codec(track, &container, source_codecs.get(i).and_then(|c| c.as_deref()));
+        args.push(format!("-c:a:{}", output_position));
+        args.push(codec.to_string());
+        if codec == "aac" && track.codec.is_none() {
+            args.push(format!("-b:a:{}", output_position));
+            args.push("192k".to_string());
+        }
+        if let Some(ref language) = track.language {
+            args.push(format!("-metadata:s:a:{}", output_position));
+            args.push(format!("language={}", language));
+        }
+        if let Some(ref title) = track.title {
+            args.push(format!("-metadata:s:a:{}", output_position));
+            args.push(format!("title={}", title));
+        }
+        let default = if has_default { track.default } else { kept == 0 && i == 0 };
+        args.push(format!("-disposition:a:{}", output_position));
+        args.push(if default { "default" } else { "0" }.to_string());
+    }
+    if has_default {
+        // The added default track replaces the source's default flag.
+        for position in 0..kept {
+            args.push(format!("-disposition:a:{}", position));
+            args.push("0".to_string());
+        }
+    }
+
+    if container == "mp4" || container == "mov" {
+        args.push("-movflags".to_string());
+        args.push("+faststart".to_string());
+    }
+    args.push(output.to_string());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_compatible_tracks_and_encodes_the_rest() {
+        let options = MuxOptions {
+            audio_tracks: vec![
+                AudioTrackInput {
+                    file: "jpn.flac".to_string(),
+                    language: Some("jpn".to_string()),
+                    ..Default::default()
+                },
+                AudioTrackInput {
+                    file: "eng.wav".to_string(),
+                    language: Some("eng".to_string()),
+                    default: true,
+                    ..Default::default()
+                },
+            ],
+            keep_source_audio: false,
+        };
+        let codecs = [Some("flac".to_string()), Some("pcm_s16le".to_string())];
+        let args = build_args("video.mkv", &options, 1, &codecs, "out.mp4").join(" ");
+        assert!(args.contains("-map 1:a:0 -c:a:0 copy -metadata:s:a:0 language=jpn -disposition:a:0 0"));
+        assert!(args.contains("-map 2:a:0 -c:a:1 aac -b:a:1 192k -metadata:s:a:1 language=eng -disposition:a:1 default"));
+    }
+}
@@ -0,0 +1,186 @@
+use super::{tracks, watchdog, StreamInfo};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Picture-based subtitle codecs and the file each track is extracted to
+/// for the OCR tool: raw `.sup` for PGS, a Matroska subtitle file for
+/// VobSub, which FFmpeg can't write as `.idx`/`.sub`.
+const BITMAP_CODECS: &[(&str, &str)] = &[("hdmv_pgs_subtitle", "sup"), ("dvd_subtitle", "mks")];
+
+/// OCR of a full-length track can take a while; limit for one track.
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+/// External program turning an extracted subtitle track into SRT.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrTool {
+    /// Executable, e.g. an OCR program or a wrapper script around one.
+    pub command: String,
+    /// Arguments; `{input}` is the extracted track, `{output}` the SRT to
+    /// write and `{language}` the track's language code (`und` if unknown).
+    pub args: Vec<String>,
+    #[serde(alias = "timeoutSecs")]
+    pub timeout_secs: Option<u64>,
+}
+
+/// A picture-based track turned into text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrSubtitle {
+    /// Source track, as in `0:s:N`.
+    pub position: usize,
+    pub language: Option<String>,
+    pub path: String,
+}
+
+pub fn is_bitmap(codec: &str) -> bool {
+    BITMAP_CODECS.iter().any(|(name, _)| *name == codec)
+}
+
+/// Picture-based tracks that need OCR: those `container` can't carry when
+/// subtitles are copied, plus the track chosen for burn-in, which the
+/// `subtitles` filter can only render from text.
+pub fn tracks_to_ocr(
+    streams: &[StreamInfo],
+    container: &str,
+    copy_subtitles: bool,
+    burn_track: Option<usize>,
+) -> Vec<usize> {
+    streams
+        .iter()
+        .enumerate()
+        .filter(|(position, stream)| {
+            is_bitmap(&stream.codec)
+                && ((copy_subtitles && tracks::subtitle_codec(container, &stream.codec).is_none())
+                    || burn_track == Some(*position))
+        })
+        .map(|(position, _)| position)
+        .collect()
+}
+
+/// The tool's arguments with the placeholders filled in.
+pub fn tool_args(tool: &OcrTool, input: &Path, output: &Path, language: Option<&str>) -> Vec<String> {
+    tool.args
+        .iter()
+        .map(|arg| {
+            arg.replace("{input}", &input.to_string_lossy())
+                .replace("{output}", &output.to_string_lossy())
+                .replace("{language}", language.unwrap_or("und"))
+        })
+        .collect()
+}
+
+/// Scratch folder for a job's extracted tracks and SRT files.
+pub fn work_dir(task_id: &str) -> PathBuf {
+    std::env::temp_dir().join("dreamcodec_ocr").join(task_id)
+}
+
+/// Copy track `0:s:<position>` out of `input_file` unchanged; returns the
+/// file written into `dir`.
+pub async fn extract_track(
+    ffmpeg_path: &str,
+    input_file: &str,
+    position: usize,
+    codec: &str,
+    dir: &Path,
+) -> Result<PathBuf, AppError> {
+    let ext = BITMAP_CODECS
+        .iter()
+        .find(|(name, _)| *name == codec)
+        .map(|(_, ext)| *ext)
+        .ok_or_else(|| AppError::Internal(format!("{} is not a picture-based subtitle codec", codec)))?;
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("track_{}.{}", position, ext));
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-v", "error", "-y", "-i", input_file, "-map", &format!("0:s:{}", position), "-c:s", "copy"])
+        .arg(&path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = watchdog::output(&mut cmd).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Ffmpeg(format!(
+            "Could not extract subtitle track {}: {}",
+            position,
+            stderr.lines().next().unwrap_or("no output")
+        )));
+    }
+    Ok(path)
+}
+
+/// Run the OCR tool on an extracted track and check it wrote `output`.
+pub async fn run_tool(tool: &OcrTool, input: &Path, output: &Path, language: Option<&str>) -> Result<(), AppError> {
+    if tool.command.trim().is_empty() {
+        return Err(AppError::Internal("No subtitle OCR tool is configured".to_string()));
+    }
+    let mut cmd = Command::new(&tool.command);
+    cmd.args(tool_args(tool, input, output, language))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let secs = tool.timeout_secs.filter(|s| *s > 0).unwrap_or(DEFAULT_TIMEOUT_SECS);
+    let result = tokio::time::timeout(Duration::from_secs(secs), cmd.output())
+        .await
+        .map_err(|_| AppError::Timeout(format!("{} was stopped after running for {}s", tool.command, secs)))?
+        .map_err(|e| AppError::Io(format!("Failed to run {}: {}", tool.command, e)))?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(AppError::Internal(format!(
+            "{} failed: {}",
+            tool.command,
+            stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no output")
+        )));
+    }
+    if std::fs::metadata(output).map_or(true, |m| m.len() == 0) {
+        return Err(AppError::Internal(format!("{} wrote no subtitles", tool.command)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_bitmap_tracks_the_output_cannot_hold() {
+        let stream = |codec: &str| StreamInfo {
+            index: 0,
+            codec: codec.to_string(),
+            language: None,
+            title: None,
+            default: false,
+            forced: false,
+            closed_captions: false,
+            sample_rate: None,
+            channels: None,
+            width: None,
+            height: None,
+        };
+        let streams = [stream("hdmv_pgs_subtitle"), stream("subrip"), stream("dvd_subtitle")];
+        assert_eq!(tracks_to_ocr(&streams, "mp4", true, None), vec![0, 2]);
+        assert_eq!(tracks_to_ocr(&streams, "mkv", true, None), Vec::<usize>::new());
+        assert_eq!(tracks_to_ocr(&streams, "mkv", false, Some(2)), vec![2]);
+
+        let tool = OcrTool {
+            command: "ocr".to_string(),
+            args: vec!["-l".to_string(), "{language}".to_string(), "{input}".to_string(), "-o".to_string(), "{output}".to_string()],
+            timeout_secs: None,
+        };
+        let args = tool_args(&tool, Path::new("t.sup"), Path::new("t.srt"), None);
+        assert_eq!(args, ["-l", "und", "t.sup", "-o", "t.srt"]);
+    }
+}
@@ -0,0 +1,177 @@
+use regex::Regex;
+
+/// Flags the app sets itself or that would change what FFmpeg may touch:
+/// overwrite handling, inputs, the progress pipe, stdin (used to quit on
+/// cancel) and the protocol whitelist that keeps inputs to local files.
+const BLOCKED_FLAGS: &[&str] = &[
+    "-y",
+    "-n",
+    "-i",
+    "-progress",
+    "-nostats",
+    "-stats",
+    "-nostdin",
+    "-stdin",
+    "-hide_banner",
+    "-loglevel",
+    "-v",
+    "-report",
+    "-protocol_whitelist",
+    "-protocol_blacklist",
+    "-dump_attachment",
+    "-filter_script",
+    "-filter_complex_script",
+    "-/filter",
+    "-/filter_complex",
+    "-f",
+    "-pass",
+    "-passlogfile",
+];
+
+/// Protocols a value may name; anything else could read or write beyond
+/// the job's own files.
+const ALLOWED_PROTOCOLS: &[&str] = &["file"];
+
+/// Flags whose value starts with a keyword that only looks like a protocol,
+/// e.g. `-force_key_frames expr:gte(t,n_forced*2)`.
+const EXPRESSION_FLAGS: &[&str] = &["-force_key_frames"];
+
+/// Flags that take no value. Any other flag takes the next argument, so a
+/// bare argument after one of these would be an extra output file.
+const VALUELESS_FLAGS: &[&str] = &[
+    "-an",
+    "-vn",
+    "-sn",
+    "-dn",
+    "-shortest",
+    "-copyts",
+    "-start_at_zero",
+    "-re",
+    "-accurate_seek",
+    "-noaccurate_seek",
+    "-autorotate",
+    "-noautorotate",
+    "-autoscale",
+    "-noautoscale",
+    "-bitexact",
+    "-copyinkf",
+    "-fix_sub_duration",
+    "-ignore_unknown",
+    "-copy_unknown",
+    "-benchmark",
+    "-benchmark_all",
+    "-xerror",
+    "-debug_ts",
+    "-psnr",
+    "-vstats",
+];
+
+/// Flags whose value is a filtergraph, where `movie=`, `amovie=` and
+/// `subtitles=` open files of their own.
+const FILTER_FLAGS: &[&str] = &["-vf", "-af", "-filter", "-filter_complex", "-lavfi"];
+
+/// Network and container protocols looked for inside filtergraphs. Filter
+/// options use `:` as a separator, so only these names count as protocols
+/// there.
+const REMOTE_PROTOCOLS: &[&str] = &[
+    "http", "https", "tcp", "udp", "tls", "unix", "rtmp", "rtmps", "rtmpe", "rtmpt", "rtmpte", "rtsp", "rtp", "srtp",
+    "srt", "rist", "sctp", "ftp", "sftp", "smb", "gopher", "gophers", "icecast", "mmsh", "mmst", "hls", "ipfs", "ipns",
+    "zmq", "prompeg", "concat", "concatf", "subfile", "data", "pipe", "fd", "crypto", "async", "cache", "bluray",
+];
+
+/// First remote protocol named anywhere in a filtergraph, escaped
+/// (`http\://`) or quoted (`'http://'`) included.
+fn filter_protocol(graph: &str) -> Option<String> {
+    let regex = Regex::new(r"(?i)(?:^|[^a-z0-9+.-])([a-z][a-z0-9+.-]*)\\*:").expect("Invalid regex");
+    let scheme = regex
+        .captures_iter(graph)
+        .map(|caps| caps[1].to_lowercase())
+        .find(|scheme| REMOTE_PROTOCOLS.contains(&scheme.as_str()));
+    scheme
+}
+
+/// `http://host`, `concat:a|b`, `tcp:...`; Windows drive letters aren't protocols.
+fn protocol(value: &str) -> Option<&str> {
+    let (scheme, _) = value.split_once(':')?;
+    let is_scheme = scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    is_scheme.then_some(scheme)
+}
+
+/// Check user-supplied arguments against the blocklist; `position` is
+/// "input" or "output" for the error message.
+pub fn validate(args: &[String], position: &str) -> Result<(), String> {
+    // The flag waiting for its value, if any.
+    let mut pending: Option<&str> = None;
+    for arg in args {
+        let arg = arg.trim();
+        if arg.is_empty() {
+            return Err(format!("Empty extra {} argument", position));
+        }
+        // Negative numbers are values, e.g. `-itsoffset -1.5`.
+        if arg.starts_with('-') && arg.parse::<f64>().is_err() {
+            // `-c:v` and `-b:a:0` are checked by their base flag.
+            let flag = arg.split(':').next().unwrap_or(arg);
+            if BLOCKED_FLAGS.contains(&flag) {
+                return Err(format!("{} can't be set as an extra {} argument", flag, position));
+            }
+            pending = (!VALUELESS_FLAGS.contains(&flag)).then_some(flag);
+            continue;
+        }
+        let Some(flag) = pending.take() else {
+            return Err(format!("Extra {} argument {:?} isn't the value of an option", position, arg));
+        };
+        let scheme = if FILTER_FLAGS.contains(&flag) {
+            filter_protocol(arg)
+        } else if EXPRESSION_FLAGS.contains(&flag) {
+            None
+        } else {
+            protocol(arg).map(str::to_lowercase).filter(|s| !ALLOWED_PROTOCOLS.contains(&s.as_str()))
+        };
+        if let Some(scheme) = scheme {
+            return Err(format!("Extra {} arguments can't use the {} protocol", position, scheme));
+        }
+    }
+    Ok(())
+}
+
+/// Protocols the main input may use: local files, pipes, and the concat
+/// protocol for disc titles.
+pub fn input_whitelist(input: &str) -> &'static str {
+    if input.starts_with("concat:") {
+        "file,pipe,concat"
+    } else {
+        "file,pipe"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn blocks_app_owned_flags_and_remote_protocols() {
+        let ok = args(&["-x264-params", "keyint=60:min-keyint=60", "-vf", "scale=1280:720", "-aspect", "16:9"]);
+        assert_eq!(validate(&ok, "output"), Ok(()));
+        assert!(validate(&args(&["-metadata", "comment=C:\\clips"]), "output").is_ok());
+
+        assert!(validate(&args(&["-y"]), "output").is_err());
+        assert!(validate(&args(&["-f:v", "rawvideo"]), "output").is_err());
+        assert!(validate(&args(&["-i", "other.mp4"]), "input").is_err());
+        assert!(validate(&args(&["-attach", "http://example.com/a.ttf"]), "output").is_err());
+        assert!(validate(&args(&["-vf", "subtitles=file:subs.srt"]), "output").is_ok());
+        assert!(validate(&args(&["-force_key_frames", "expr:gte(t,n_forced*2)"]), "output").is_ok());
+        assert!(validate(&args(&["-itsoffset", "-1.5", "-an"]), "input").is_ok());
+        assert!(validate(&args(&["-vf", "drawtext=text=note:fontsize=24"]), "output").is_ok());
+
+        assert!(validate(&args(&["-vf", "movie=http://example.com/a.mp4[m];[in][m]overlay"]), "output").is_err());
+        assert!(validate(&args(&["-filter_complex", "amovie='tcp\\://host:9000'"]), "output").is_err());
+        assert!(validate(&args(&["-an", "copy.mp4"]), "output").is_err());
+        assert!(validate(&args(&["/tmp/other.mkv"]), "output").is_err());
+    }
+}
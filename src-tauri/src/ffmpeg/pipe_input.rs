@@ -0,0 +1,81 @@
+use super::ConversionOptions;
+use log::{info, warn};
+use std::path::Path;
+use tokio::process::ChildStdin;
+
+/// Whether `input` means the app's own stdin.
+pub fn is_stdin(input: &str) -> bool {
+    matches!(input, "-" | "pipe:" | "pipe:0")
+}
+
+/// Whether `input` is a stream that can only be read once: stdin, a Windows
+/// named pipe (`\\.\pipe\name`) or a FIFO.
+pub fn is_pipe(input: &str) -> bool {
+    is_stdin(input) || input.to_lowercase().starts_with(r"\\.\pipe\") || is_fifo(Path::new(input))
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// Why the options need to read or seek the input more than once, which a
+/// pipe can't do; `None` if they work with one.
+pub fn unsupported(options: &ConversionOptions) -> Option<&'static str> {
+    if !options.input_segments.is_empty() {
+        Some("joined segments are read from files")
+    } else if options.wait_until_stable.is_some() || options.growing_file {
+        Some("there is no file to watch")
+    } else if options.intro_file.is_some() || options.outro_file.is_some() {
+        Some("intro/outro clips need the input probed up front")
+    } else if options.scene_chapters {
+        Some("scene chapter detection reads the input separately")
+    } else if options.extract_captions {
+        Some("captions are extracted in a second read")
+    } else if options.ocr_subtitles {
+        Some("subtitle tracks are extracted for OCR in a second read")
+    } else if options.smart_reframe {
+        Some("black bars are detected in a separate read")
+    } else if options.normalize_audio.is_some() {
+        Some("loudness normalization reads the input twice")
+    } else if options.telemetry.is_some() {
+        Some("telemetry is read from the input file")
+    } else if options.target_size_mb.is_some() || options.rate_control.as_ref().is_some_and(|rc| rc.two_pass) {
+        Some("two-pass encoding reads the input twice")
+    } else {
+        None
+    }
+}
+
+/// Copy the app's stdin into FFmpeg's until either side closes.
+pub async fn forward_stdin(mut child_stdin: ChildStdin) {
+    match tokio::io::copy(&mut tokio::io::stdin(), &mut child_stdin).await {
+        Ok(bytes) => info!("Forwarded {} byte(s) of stdin to FFmpeg", bytes),
+        Err(e) => warn!("Forwarding stdin to FFmpeg stopped: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_pipes_and_one_read_limits() {
+        assert!(is_stdin("-") && is_stdin("pipe:0"));
+        assert!(is_pipe(r"\\.\PIPE\capture"));
+        assert!(!is_pipe("clip.mkv"));
+
+        let options = ConversionOptions {
+            scene_chapters: true,
+            ..Default::default()
+        };
+        assert!(unsupported(&options).is_some());
+        assert_eq!(unsupported(&ConversionOptions::default()), None);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::AppError;
+use super::watchdog;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Default width of in-app preview frames; keeps IPC payloads small.
+pub const DEFAULT_PREVIEW_WIDTH: u32 = 960;
+
+/// Decode a single frame at `timestamp` seconds and return it as JPEG bytes.
+pub async fn frame_at(
+    ffmpeg_path: &str,
+    input_file: &str,
+    timestamp: f64,
+    max_width: Option<u32>,
+) -> Result<Vec<u8>, AppError> {
+    let width = max_width.unwrap_or(DEFAULT_PREVIEW_WIDTH);
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args([
+        "-v",
+        "error",
+        "-ss",
+        &format!("{:.3}", timestamp.max(0.0)),
+        "-i",
+        input_file,
+        "-frames:v",
+        "1",
+        "-vf",
+        &format!("scale='min({},iw)':-2", width),
+        "-f",
+        "image2pipe",
+        "-c:v",
+        "mjpeg",
+        "-q:v",
+        "3",
+        "-",
+    ])
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = watchdog::output(&mut cmd).await?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Ffmpeg(format!(
+            "Could not decode a frame at {:.3}s: {}",
+            timestamp,
+            stderr.lines().next().unwrap_or("no frame produced")
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameComparison {
+    pub timestamp: f64,
+    pub input_frame: String,
+    pub output_frame: String,
+    pub diff_frame: String,
+}
+
+/// Extract the same timestamp from `input_file` and `output_file` plus a
+/// difference heatmap, written as PNGs into `out_dir`. The output frame is
+/// scaled to the input frame's size so the pair lines up in a slider.
+pub async fn compare_frames(
+    ffmpeg_path: &str,
+    input_file: &str,
+    output_file: &str,
+    timestamp: f64,
+    max_width: Option<u32>,
+    out_dir: &Path,
+) -> Result<FrameComparison, AppError> {
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| AppError::Io(format!("Failed to create comparison directory: {}", e)))?;
+
+    let width = max_width.unwrap_or(DEFAULT_PREVIEW_WIDTH);
+    let seek = format!("{:.3}", timestamp.max(0.0));
+    let input_frame = out_dir.join("input.png");
+    let output_frame = out_dir.join("output.png");
+    let diff_frame = out_dir.join("diff.png");
+
+    // Differences are amplified 4x on luma and colored so small encoding
+    // losses are visible at a glance.
+    let graph = format!(
+        "[0:v]scale='min({},iw)':-2,format=yuv444p,split[a0][a1];\
+         [1:v][a1]scale2ref=w=iw:h=ih[b][a2];[b]format=yuv444p,split[b0][b1];\
+         [a2][b1]blend=all_mode=difference,lutyuv=y=val*4:u=128:v=128,pseudocolor=p=inferno[d]",
+        width
+    );
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-v", "error", "-y", "-ss", &seek, "-i", input_file, "-ss", &seek, "-i", output_file])
+        .args(["-filter_complex", &graph])
+        .args(["-map", "[a0]", "-frames:v", "1"])
+        .arg(&input_frame)
+        .args(["-map", "[b0]", "-frames:v", "1"])
+        .arg(&output_frame)
+        .args(["-map", "[d]", "-frames:v", "1"])
+        .arg(&diff_frame)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = watchdog::output(&mut cmd).await?;
+
+    if !output.status.success() || !diff_frame.exists() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Ffmpeg(format!(
+            "Could not compare frames at {}s: {}",
+            seek,
+            stderr.lines().next().unwrap_or("no frames produced")
+        )));
+    }
+
+    Ok(FrameComparison {
+        timestamp,
+        input_frame: input_frame.to_string_lossy().to_string(),
+        output_frame: output_frame.to_string_lossy().to_string(),
+        diff_frame: diff_frame.to_string_lossy().to_string(),
+    })
+}
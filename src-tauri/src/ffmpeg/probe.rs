@@ -0,0 +1,346 @@
+use super::{watchdog, StreamInfo, VideoInfo, FFPROBE_BINARY};
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Container-level information.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContainerInfo {
+    pub format_name: String,
+    pub duration: Option<f64>,
+    pub bit_rate: Option<u64>,
+    pub size: Option<u64>,
+    pub title: Option<String>,
+}
+
+/// HDR signalling of a video stream.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HdrInfo {
+    /// `HDR10`, `HLG` or `Dolby Vision`.
+    pub format: String,
+    /// Mastering display luminance range in cd/m².
+    pub max_luminance: Option<f64>,
+    pub min_luminance: Option<f64>,
+    /// Content light level (MaxCLL / MaxFALL).
+    pub max_cll: Option<u32>,
+    pub max_fall: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaStream {
+    pub index: u32,
+    /// `video`, `audio`, `subtitle`, `data` or `attachment`.
+    pub kind: String,
+    pub codec: String,
+    pub profile: Option<String>,
+    pub bit_rate: Option<u64>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+    /// Cover art stored as a video stream.
+    pub attached_pic: bool,
+    // Video
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub pixel_format: Option<String>,
+    pub bit_depth: Option<u32>,
+    pub frame_rate: Option<f64>,
+    /// e.g. `1/90000`.
+    pub time_base: Option<String>,
+    pub sample_aspect: Option<(u32, u32)>,
+    pub display_aspect: Option<(u32, u32)>,
+    pub color_space: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_range: Option<String>,
+    pub field_order: Option<String>,
+    pub closed_captions: bool,
+    pub hdr: Option<HdrInfo>,
+    // Audio
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    /// Hash of the codec setup data (H.264 SPS/PPS, AAC config).
+    pub extradata_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaChapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+/// Everything ffprobe reports about a file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub container: ContainerInfo,
+    pub streams: Vec<MediaStream>,
+    pub chapters: Vec<MediaChapter>,
+}
+
+/// ffprobe next to the FFmpeg binary, if it is there.
+pub fn ffprobe_path_for(ffmpeg_path: &str) -> Option<PathBuf> {
+    let path = Path::new(ffmpeg_path).with_file_name(FFPROBE_BINARY);
+    path.is_file().then_some(path)
+}
+
+// ffprobe prints most numbers as strings.
+fn number<T: std::str::FromStr>(value: &Value) -> Option<T> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => n.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().filter(|s| !s.is_empty() && *s != "unknown").map(str::to_string)
+}
+
+fn ratio(value: &Value) -> Option<(u32, u32)> {
+    let (a, b) = value.as_str()?.split_once(':')?;
+    Some((a.parse().ok()?, b.parse().ok()?)).filter(|(a, b)| *a > 0 && *b > 0)
+}
+
+fn frame_rate(value: &Value) -> Option<f64> {
+    let (num, den) = value.as_str()?.split_once('/')?;
+    let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+    (num > 0.0 && den > 0.0).then(|| num / den)
+}
+
+/// Luminance values come as rationals such as `10000000/10000`.
+fn rational(value: &Value) -> Option<f64> {
+    match value.as_str() {
+        Some(s) if s.contains('/') => {
+            let (num, den) = s.split_once('/')?;
+            let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+            (den != 0.0).then(|| num / den)
+        }
+        _ => number(value),
+    }
+}
+
+/// Bit depth from `bits_per_raw_sample`, or from the pixel format name
+/// (`yuv420p10le` -> 10) when the decoder doesn't report it.
+fn bit_depth(stream: &Value, pixel_format: Option<&str>) -> Option<u32> {
+    number::<u32>(&stream["bits_per_raw_sample"])
+        .or_else(|| number::<u32>(&stream["bits_per_sample"]))
+        .filter(|d| *d > 0)
+        .or_else(|| {
+            let format = pixel_format?;
+            let depth = [16, 14, 12, 10]
+                .into_iter()
+                .find(|depth| {
+                    [format!("p{}", depth), format!("p0{}", depth), format!("{}le", depth), format!("{}be", depth)]
+                        .iter()
+                        .any(|marker| format.contains(marker.as_str()))
+                })
+                .unwrap_or(8);
+            Some(depth)
+        })
+}
+
+fn hdr_info(stream: &Value) -> Option<HdrInfo> {
+    let side_data = stream["side_data_list"].as_array().cloned().unwrap_or_default();
+    let find = |kind: &str| side_data.iter().find(|d| d["side_data_type"].as_str() == Some(kind));
+    let format = if find("DOVI configuration record").is_some() {
+        "Dolby Vision"
+    } else {
+        match stream["color_transfer"].as_str() {
+            Some("smpte2084") => "HDR10",
+            Some("arib-std-b67") => "HLG",
+            _ => return None,
+        }
+    };
+    let mastering = find("Mastering display metadata");
+    let light = find("Content light level metadata");
+    Some(HdrInfo {
+        format: format.to_string(),
+        max_luminance: mastering.and_then(|m| rational(&m["max_luminance"])),
+        min_luminance: mastering.and_then(|m| rational(&m["min_luminance"])),
+        max_cll: light.and_then(|l| number(&l["max_content"])),
+        max_fall: light.and_then(|l| number(&l["max_average"])),
+    })
+}
+
+fn parse_stream(stream: &Value) -> MediaStream {
+    let disposition = &stream["disposition"];
+    let flag = |name: &str| number::<u32>(&disposition[name]).unwrap_or(0) == 1;
+    let pixel_format = text(&stream["pix_fmt"]);
+    let is_video = stream["codec_type"].as_str() == Some("video");
+    MediaStream {
+        index: number(&stream["index"]).unwrap_or(0),
+        kind: text(&stream["codec_type"]).unwrap_or_default(),
+        codec: text(&stream["codec_name"]).unwrap_or_default(),
+        profile: text(&stream["profile"]),
+        bit_rate: number(&stream["bit_rate"]),
+        language: text(&stream["tags"]["language"]).filter(|l| l != "und"),
+        title: text(&stream["tags"]["title"]),
+        default: flag("default"),
+        forced: flag("forced"),
+        attached_pic: flag("attached_pic"),
+        width: number(&stream["width"]),
+        height: number(&stream["height"]),
+        bit_depth: if is_video {
+            bit_depth(stream, pixel_format.as_deref())
+        } else {
+            number(&stream["bits_per_raw_sample"]).filter(|d| *d > 0)
+        },
+        pixel_format,
+        frame_rate: frame_rate(&stream["avg_frame_rate"]).or_else(|| frame_rate(&stream["r_frame_rate"])),
+        time_base: text(&stream["time_base"]),
+        sample_aspect: ratio(&stream["sample_aspect_ratio"]),
+        display_aspect: ratio(&stream["display_aspect_ratio"]),
+        color_space: text(&stream["color_space"]),
+        color_transfer: text(&stream["color_transfer"]),
+        color_primaries: text(&stream["color_primaries"]),
+        color_range: text(&stream["color_range"]),
+        field_order: text(&stream["field_order"]),
+        closed_captions: number::<u32>(&stream["closed_captions"]).unwrap_or(0) == 1,
+        hdr: if is_video { hdr_info(stream) } else { None },
+        sample_rate: number(&stream["sample_rate"]),
+        channels: number(&stream["channels"]),
+        channel_layout: text(&stream["channel_layout"]),
+        extradata_hash: text(&stream["extradata_hash"]),
+    }
+}
+
+/// Parse `ffprobe -print_format json -show_format -show_streams -show_chapters`.
+pub fn parse_ffprobe_json(json: &str) -> Result<MediaInfo, AppError> {
+    let root: Value =
+        serde_json::from_str(json).map_err(|e| AppError::Ffmpeg(format!("Unreadable ffprobe output: {}", e)))?;
+    let format = &root["format"];
+    let container = ContainerInfo {
+        format_name: text(&format["format_name"]).unwrap_or_default(),
+        duration: number(&format["duration"]),
+        bit_rate: number(&format["bit_rate"]),
+        size: number(&format["size"]),
+        title: text(&format["tags"]["title"]),
+    };
+    let streams = root["streams"].as_array().map(|s| s.iter().map(parse_stream).collect()).unwrap_or_default();
+    let chapters = root["chapters"]
+        .as_array()
+        .map(|chapters| {
+            chapters
+                .iter()
+                .map(|c| MediaChapter {
+                    start: number(&c["start_time"]).unwrap_or(0.0),
+                    end: number(&c["end_time"]).unwrap_or(0.0),
+                    title: text(&c["tags"]["title"]),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(MediaInfo { container, streams, chapters })
+}
+
+/// Run ffprobe on `input_file`.
+pub async fn probe_media(ffprobe_path: &Path, input_file: &str) -> Result<MediaInfo, AppError> {
+    let mut cmd = Command::new(ffprobe_path);
+    cmd.args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams", "-show_chapters"])
+        .args(["-show_data_hash", "sha256"])
+        .arg(input_file)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = watchdog::output(&mut cmd).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Ffmpeg(format!(
+            "ffprobe failed: {}",
+            stderr.lines().next().unwrap_or("unknown error")
+        )));
+    }
+    parse_ffprobe_json(&String::from_utf8_lossy(&output.stdout))
+}
+
+impl MediaInfo {
+    /// The summary the conversion pipeline works with.
+    pub fn to_video_info(&self) -> VideoInfo {
+        let streams_of = |kind: &str| -> Vec<StreamInfo> {
+            self.streams
+                .iter()
+                .filter(|s| s.kind == kind)
+                .map(|s| StreamInfo {
+                    index: s.index,
+                    codec: s.codec.clone(),
+                    language: s.language.clone(),
+                    title: s.title.clone(),
+                    default: s.default,
+                    forced: s.forced,
+                    closed_captions: s.closed_captions,
+                    sample_rate: s.sample_rate,
+                    channels: s.channels,
+                    width: s.width,
+                    height: s.height,
+                })
+                .collect()
+        };
+        let main_video = self.streams.iter().find(|s| s.kind == "video" && !s.attached_pic);
+        VideoInfo {
+            duration: self.container.duration,
+            width: main_video.and_then(|s| s.width),
+            height: main_video.and_then(|s| s.height),
+            video_streams: streams_of("video"),
+            audio_streams: streams_of("audio"),
+            subtitle_streams: streams_of("subtitle"),
+            sample_aspect: main_video.and_then(|s| s.sample_aspect),
+            display_aspect: main_video.and_then(|s| s.display_aspect),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hdr_stream_and_chapters() {
+        let json = r#"{
+            "streams": [
+                {"index": 0, "codec_name": "hevc", "codec_type": "video", "profile": "Main 10",
+                 "width": 3840, "height": 2160, "pix_fmt": "yuv420p10le", "avg_frame_rate": "24000/1001",
+                 "sample_aspect_ratio": "1:1", "color_transfer": "smpte2084", "color_primaries": "bt2020",
+                 "disposition": {"default": 1, "forced": 0, "attached_pic": 0},
+                 "side_data_list": [
+                    {"side_data_type": "Mastering display metadata", "max_luminance": "10000000/10000", "min_luminance": "50/10000"},
+                    {"side_data_type": "Content light level metadata", "max_content": 1000, "max_average": 400}
+                 ]},
+                {"index": 1, "codec_name": "eac3", "codec_type": "audio", "sample_rate": "48000", "channels": 6,
+                 "channel_layout": "5.1(side)", "tags": {"language": "eng"}, "disposition": {"default": 1}}
+            ],
+            "chapters": [{"start_time": "0.000000", "end_time": "300.5", "tags": {"title": "Opening"}}],
+            "format": {"format_name": "matroska,webm", "duration": "5400.250000", "bit_rate": "18000000"}
+        }"#;
+        let info = parse_ffprobe_json(json).unwrap();
+        let video = &info.streams[0];
+        assert_eq!(video.bit_depth, Some(10));
+        assert!((video.frame_rate.unwrap() - 23.976).abs() < 0.001);
+        assert_eq!(
+            video.hdr,
+            Some(HdrInfo {
+                format: "HDR10".to_string(),
+                max_luminance: Some(1000.0),
+                min_luminance: Some(0.005),
+                max_cll: Some(1000),
+                max_fall: Some(400),
+            })
+        );
+        assert_eq!(info.chapters[0].title.as_deref(), Some("Opening"));
+
+        let summary = info.to_video_info();
+        assert_eq!((summary.width, summary.height), (Some(3840), Some(2160)));
+        assert_eq!(summary.audio_streams[0].language.as_deref(), Some("eng"));
+        assert_eq!(summary.duration, Some(5400.25));
+    }
+}
@@ -0,0 +1,47 @@
+use super::ConversionProgress;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// Tauri event carrying a task's `ConversionProgress`.
+pub const CONVERSION_PROGRESS: &str = "conversion-progress";
+/// At most four events per second for each task.
+const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Pushes a task's progress to the frontend so it doesn't have to poll
+/// `get_conversion_progress`. Each event's `log` only holds the lines added
+/// since the previous event; the command still returns the full log.
+pub struct ProgressEmitter {
+    app_handle: AppHandle,
+    last_emit: Option<Instant>,
+    log_sent: usize,
+}
+
+impl ProgressEmitter {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self {
+            app_handle,
+            last_emit: None,
+            log_sent: 0,
+        }
+    }
+
+    /// Emit unless the previous event went out less than `MIN_INTERVAL` ago.
+    pub fn throttled(&mut self, progress: &ConversionProgress) {
+        if self.last_emit.is_some_and(|at| at.elapsed() < MIN_INTERVAL) {
+            return;
+        }
+        self.emit(progress);
+    }
+
+    /// Emit now, e.g. for a final status that must not be dropped.
+    pub fn emit(&mut self, progress: &ConversionProgress) {
+        let start = self.log_sent.min(progress.log.len());
+        let payload = ConversionProgress {
+            log: progress.log[start..].to_vec(),
+            ..progress.clone()
+        };
+        self.last_emit = Some(Instant::now());
+        self.log_sent = progress.log.len();
+        let _ = self.app_handle.emit(CONVERSION_PROGRESS, payload);
+    }
+}
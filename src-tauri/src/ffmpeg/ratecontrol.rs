@@ -0,0 +1,307 @@
+use super::{read_ffmpeg_output, ConversionOptions, ConversionTask, ProgressParser};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::{BELOW_NORMAL_PRIORITY_CLASS, CREATE_NO_WINDOW};
+
+/// Quality and bitrate settings for the video encoder. Unset fields keep the
+/// encoder's defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateControl {
+    /// Constant quality: CRF for x264/x265/VP9/AV1, CQ for NVENC,
+    /// global_quality for QSV, QP for AMF. Lower is better.
+    pub quality: Option<u32>,
+    /// Target video bitrate in kbit/s.
+    pub bitrate: Option<u32>,
+    /// Peak bitrate in kbit/s.
+    pub maxrate: Option<u32>,
+    /// Rate-control buffer in kbit; twice `maxrate` when unset.
+    pub bufsize: Option<u32>,
+    /// Analyse the input in a first pass to hit `bitrate` more accurately.
+    #[serde(alias = "twoPass")]
+    pub two_pass: bool,
+}
+
+/// Encoders that take `-pass`/`-passlogfile` (or x265's own stats file).
+pub fn supports_two_pass(encoder: &str) -> bool {
+    matches!(encoder, "libx264" | "libx265" | "libvpx-vp9" | "libaom-av1")
+}
+
+impl RateControl {
+    /// Check the settings before a job starts.
+    pub fn validate(&self, encoder: &str) -> Result<(), String> {
+        if self.quality.is_some() && self.bitrate.is_some() && !encoder.contains("nvenc") {
+            return Err("Set either a quality value or a target bitrate, not both".to_string());
+        }
+        if self.two_pass {
+            if self.bitrate.is_none() {
+                return Err("Two-pass encoding needs a target bitrate".to_string());
+            }
+            if !supports_two_pass(encoder) {
+                return Err(format!("{} doesn't support two-pass encoding", encoder));
+            }
+        }
+        if let (Some(bitrate), Some(maxrate)) = (self.bitrate, self.maxrate) {
+            if maxrate < bitrate {
+                return Err("Maximum bitrate is lower than the target bitrate".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Encoder arguments, placed after `-c:v`.
+    pub fn args(&self, encoder: &str) -> Vec<String> {
+        let mut args: Vec<String> = Vec::new();
+        let mut push = |flag: &str, value: String| {
+            args.push(flag.to_string());
+            args.push(value);
+        };
+        if let Some(quality) = self.quality {
+            if encoder.contains("nvenc") {
+                push("-rc", "vbr".to_string());
+                push("-cq", quality.to_string());
+            } else if encoder.contains("qsv") {
+                push("-global_quality", quality.to_string());
+            } else if encoder.contains("amf") {
+                push("-rc", "cqp".to_string());
+                push("-qp_i", quality.to_string());
+                push("-qp_p", quality.to_string());
+                push("-qp_b", quality.to_string());
+            } else if encoder.starts_with("lib") {
+                push("-crf", quality.to_string());
+            } else {
+                push("-q:v", quality.to_string());
+            }
+            // VP9/AV1 only run in constant-quality mode with an unlimited bitrate.
+            if self.bitrate.is_none() && (encoder.contains("nvenc") || matches!(encoder, "libvpx-vp9" | "libaom-av1")) {
+                push("-b:v", "0".to_string());
+            }
+        }
+        if let Some(bitrate) = self.bitrate {
+            push("-b:v", format!("{}k", bitrate));
+        }
+        if let Some(maxrate) = self.maxrate {
+            push("-maxrate", format!("{}k", maxrate));
+            push("-bufsize", format!("{}k", self.bufsize.unwrap_or(maxrate * 2)));
+        } else if let Some(bufsize) = self.bufsize {
+            push("-bufsize", format!("{}k", bufsize));
+        }
+        args
+    }
+}
+
+/// Audio bitrate assumed for target-size encodes when none is requested.
+pub const DEFAULT_TARGET_AUDIO_KBPS: u32 = 128;
+/// Share of a target size kept free for container overhead.
+const CONTAINER_OVERHEAD: f64 = 0.02;
+/// Below this the picture falls apart, so smaller budgets are rejected.
+const MIN_VIDEO_KBPS: u32 = 100;
+
+/// Video bitrate (kbit/s) that keeps `duration` seconds of output with
+/// `audio_kbps` of audio under `target_mb` (10^6 bytes).
+pub fn video_bitrate_for_size(target_mb: f64, duration: f64, audio_kbps: u32) -> Result<u32, String> {
+    if target_mb <= 0.0 {
+        return Err("Target size must be greater than zero".to_string());
+    }
+    if duration <= 0.0 {
+        return Err("Target size needs a known output duration".to_string());
+    }
+    let total_kbps = target_mb * 8000.0 * (1.0 - CONTAINER_OVERHEAD) / duration;
+    let video_kbps = (total_kbps - audio_kbps as f64).floor();
+    if video_kbps < MIN_VIDEO_KBPS as f64 {
+        let minimum_mb = (MIN_VIDEO_KBPS + audio_kbps) as f64 * duration / 8000.0 / (1.0 - CONTAINER_OVERHEAD);
+        return Err(format!(
+            "{} MB is too small for {:.0}s of video; it needs at least {:.1} MB",
+            target_mb, duration, minimum_mb
+        ));
+    }
+    Ok(video_kbps as u32)
+}
+
+/// Whether the job runs two passes with this encoder.
+pub fn is_two_pass(options: &ConversionOptions, encoder: &str) -> bool {
+    options
+        .rate_control
+        .as_ref()
+        .is_some_and(|rc| rc.two_pass && rc.bitrate.is_some() && supports_two_pass(encoder))
+}
+
+/// Prefix for the pass log files of a task.
+pub fn passlog_prefix(task_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("dreamcodec_2pass_{}", task_id))
+}
+
+/// Escape a `-x265-params` value; `:` separates the parameters, so a
+/// Windows path like `C:\Temp` has to be escaped.
+fn escape_x265_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ':' | '\'' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Arguments selecting `pass` (1 or 2), inserted before the output.
+pub fn pass_args(encoder: &str, pass: u8, passlog: &Path) -> Vec<String> {
+    let passlog = passlog.to_string_lossy();
+    if encoder == "libx265" {
+        vec![
+            "-x265-params".to_string(),
+            format!("pass={}:stats={}", pass, escape_x265_value(&format!("{}.log", passlog))),
+        ]
+    } else {
+        vec![
+            "-pass".to_string(),
+            pass.to_string(),
+            "-passlogfile".to_string(),
+            passlog.to_string(),
+        ]
+    }
+}
+
+/// First-pass command from the encode arguments (without the output file):
+/// same video settings, no audio or subtitles, discarded output.
+pub fn first_pass_args(args: &[String], encoder: &str, passlog: &Path) -> Vec<String> {
+    let mut first = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        // Muxer options the null muxer would reject.
+        if arg == "-movflags" {
+            iter.next();
+            continue;
+        }
+        first.push(arg.clone());
+    }
+    first.extend(pass_args(encoder, 1, passlog));
+    let null_sink = if cfg!(target_os = "windows") { "NUL" } else { "/dev/null" };
+    first.extend(["-an", "-sn", "-dn", "-f", "null", null_sink].iter().map(|s| s.to_string()));
+    first
+}
+
+/// Remove the log files FFmpeg wrote for both passes.
+pub fn remove_passlogs(passlog: &Path) {
+    let (Some(dir), Some(prefix)) = (passlog.parent(), passlog.file_name()) else {
+        return;
+    };
+    let prefix = prefix.to_string_lossy();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(prefix.as_ref()) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Run the first pass, reporting its progress like a regular encode.
+pub async fn run_first_pass(
+    task_arc: &Arc<Mutex<ConversionTask>>,
+    ffmpeg_path: &str,
+    args: &[String],
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    info!("Running first pass: {:?}", args);
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(args)
+        .envs(&options.env_vars)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(ref dir) = options.working_dir {
+        cmd.current_dir(dir);
+    }
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    let pid = child.id();
+    let cancel = task_arc.lock().expect("Failed to lock task mutex").cancel.clone();
+    if let Some(pid) = pid {
+        cancel.register(pid);
+    }
+
+    let parser = ProgressParser::new();
+    let mut last_lines = Vec::new();
+    read_ffmpeg_output(
+        &mut child,
+        |line| {
+            if let Some(time) = parser.time(line) {
+                task_arc.lock().expect("Failed to lock task mutex").advance(time);
+            }
+        },
+        |line| last_lines.push(line),
+    )
+    .await;
+
+    let status = child.wait().await;
+    if let Some(pid) = pid {
+        cancel.unregister(pid);
+    }
+    let status = status.map_err(|e| format!("Failed to wait for FFmpeg: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "First pass exited with code {}: {}",
+            status.code().map_or("None".to_string(), |c| c.to_string()),
+            last_lines.last().map(String::as_str).unwrap_or("no output")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_quality_per_encoder_and_builds_first_pass() {
+        let crf = RateControl {
+            quality: Some(23),
+            ..Default::default()
+        };
+        assert_eq!(crf.args("libx264"), vec!["-crf", "23"]);
+        assert_eq!(crf.args("hevc_nvenc"), vec!["-rc", "vbr", "-cq", "23", "-b:v", "0"]);
+
+        let two_pass = RateControl {
+            bitrate: Some(4000),
+            maxrate: Some(6000),
+            two_pass: true,
+            ..Default::default()
+        };
+        assert!(two_pass.validate("libx264").is_ok());
+        assert!(two_pass.validate("h264_nvenc").is_err());
+        assert_eq!(two_pass.args("libx264"), vec!["-b:v", "4000k", "-maxrate", "6000k", "-bufsize", "12000k"]);
+
+        let args: Vec<String> = ["-i", "in.mov", "-c:v", "libx264", "-movflags", "+faststart"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let first = first_pass_args(&args, "libx264", Path::new("log"));
+        assert_eq!(first[..6], ["-i", "in.mov", "-c:v", "libx264", "-pass", "1"]);
+        assert_eq!(first[first.len() - 3..first.len() - 1], ["-f", "null"]);
+
+        let x265 = pass_args("libx265", 2, Path::new(r"C:\Temp\dreamcodec_2pass_a"));
+        assert_eq!(x265[1], r"pass=2:stats=C\:\\Temp\\dreamcodec_2pass_a.log");
+    }
+
+    #[test]
+    fn budgets_video_bitrate_for_target_size() {
+        // 25 MB over 60 s: 3266 kbit/s in total after overhead, minus audio.
+        assert_eq!(video_bitrate_for_size(25.0, 60.0, 128), Ok(3138));
+        assert!(video_bitrate_for_size(25.0, 3600.0, 128).is_err());
+
+        // Target sizes run both passes through the same escaped stats file.
+        let args = vec!["-c:v".to_string(), "libx265".to_string()];
+        let first = first_pass_args(&args, "libx265", Path::new(r"D:\Work\log"));
+        assert_eq!(first[3], r"pass=1:stats=D\:\\Work\\log.log");
+    }
+}
@@ -0,0 +1,94 @@
+use super::FfmpegLocator;
+use crate::error::AppError;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// FFmpeg a preset was validated against: a registered installation, a
+/// minimum version, or both. Jobs that can't meet it fail before starting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FfmpegRequirement {
+    /// Name of an installation registered in the settings.
+    pub installation: Option<String>,
+    /// Lowest accepted release, e.g. `6.1`.
+    #[serde(alias = "minVersion")]
+    pub min_version: Option<String>,
+}
+
+/// Release number from an `ffmpeg -version` line or a bare version string:
+/// `ffmpeg version 6.1.1-essentials_build` and `n7.0` give `[6, 1, 1]` and
+/// `[7, 0]`. Git snapshot builds (`N-113000-g...`) have none.
+pub fn parse_version(text: &str) -> Option<Vec<u32>> {
+    let version = text.trim().strip_prefix("ffmpeg version ").unwrap_or(text.trim());
+    let version = version.split_whitespace().next()?;
+    let version = version.strip_prefix('n').unwrap_or(version);
+    let numbers: Vec<u32> = version
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+    (!numbers.is_empty()).then_some(numbers)
+}
+
+/// Whether `found` is at least `minimum`; missing components count as 0.
+fn at_least(found: &[u32], minimum: &[u32]) -> bool {
+    let len = found.len().max(minimum.len());
+    let pad = |v: &[u32]| (0..len).map(|i| v.get(i).copied().unwrap_or(0)).collect::<Vec<_>>();
+    pad(found) >= pad(minimum)
+}
+
+/// Path of the registered installation `name`.
+pub fn installation_path(name: &str, installations: &HashMap<String, String>) -> Result<PathBuf, AppError> {
+    let path = installations.get(name).map(PathBuf::from).ok_or_else(|| {
+        AppError::Ffmpeg(format!("This preset needs the FFmpeg installation '{}', which isn't registered.", name))
+    })?;
+    if !path.exists() {
+        return Err(AppError::Ffmpeg(format!(
+            "FFmpeg installation '{}' is missing: {}",
+            name,
+            path.display()
+        )));
+    }
+    Ok(path)
+}
+
+/// Fail unless the FFmpeg at `path` is release `minimum` or newer.
+pub async fn check_min_version(path: &Path, minimum: &str) -> Result<(), AppError> {
+    let wanted = parse_version(minimum)
+        .ok_or_else(|| AppError::Ffmpeg(format!("Invalid minimum FFmpeg version: {}", minimum)))?;
+    let line = FfmpegLocator::get_version(path)
+        .await
+        .ok_or_else(|| AppError::Ffmpeg(format!("Could not read the version of {}", path.display())))?;
+    match parse_version(&line) {
+        Some(found) if !at_least(&found, &wanted) => Err(AppError::Ffmpeg(format!(
+            "This preset needs FFmpeg {} or newer, but {} is {}.",
+            minimum,
+            path.display(),
+            line
+        ))),
+        Some(_) => Ok(()),
+        // Snapshot builds are usually newer than any release.
+        None => {
+            warn!("Can't compare snapshot build '{}' with minimum {}; allowing it", line, minimum);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_release_versions() {
+        let found = parse_version("ffmpeg version 6.1.1-essentials_build-www.gyan.dev Copyright").unwrap();
+        assert_eq!(found, vec![6, 1, 1]);
+        assert!(at_least(&found, &parse_version("6.1").unwrap()));
+        assert!(!at_least(&found, &parse_version("7").unwrap()));
+        assert_eq!(parse_version("ffmpeg version n7.0 Copyright"), Some(vec![7, 0]));
+        assert_eq!(parse_version("ffmpeg version N-113000-g1a2b3c-20240101"), None);
+    }
+}
@@ -0,0 +1,106 @@
+use super::ratecontrol::RateControl;
+use super::{get_format_info, ConversionOptions};
+use std::path::Path;
+
+/// Conservative video filters: fit within 1080p at even dimensions and
+/// square pixels, which High@4.0 decoders (old TVs, phones, browsers) all
+/// accept.
+pub const VIDEO_FILTERS: &[&str] = &[
+    "scale=w='min(1920,iw)':h='min(1080,ih)':force_original_aspect_ratio=decrease:force_divisible_by=2",
+    "setsar=1",
+];
+
+/// H.264 High@4.0, 8-bit 4:2:0 at a constant frame rate.
+const OUTPUT_ARGS: &[&str] = &["-profile:v", "high", "-level:v", "4.0", "-pix_fmt", "yuv420p", "-fps_mode", "cfr"];
+
+/// Containers that already take H.264 + AAC (or AAC alone) and are kept.
+const COMPATIBLE_VIDEO: &[&str] = &["mp4", "m4v", "mov", "mkv"];
+const COMPATIBLE_AUDIO: &[&str] = &["m4a", "mka"];
+
+const AUDIO_BITRATE: u32 = 160;
+const QUALITY: u32 = 21;
+
+/// Turn a job into the "maximum compatibility" profile: libx264 + AAC in
+/// MP4 (M4A for audio-only outputs) with faststart, no hardware paths and
+/// none of the settings that tend to fail on unusual inputs. Trims, fades,
+/// burned-in subtitles and tags are kept. Outputs in other containers are
+/// renamed to MP4/M4A; returns whether the output path changed.
+pub fn apply(encoder: &mut String, preset: &mut String, output_file: &mut String, options: &mut ConversionOptions) -> bool {
+    let ext = Path::new(output_file.as_str())
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let is_video = get_format_info(&ext).supports_video;
+    let (compatible, safe_ext) = if is_video { (COMPATIBLE_VIDEO, "mp4") } else { (COMPATIBLE_AUDIO, "m4a") };
+    let renamed = !compatible.contains(&ext.as_str());
+    if renamed {
+        *output_file = Path::new(output_file.as_str())
+            .with_extension(safe_ext)
+            .to_string_lossy()
+            .to_string();
+    }
+
+    *encoder = "libx264".to_string();
+    *preset = "medium".to_string();
+
+    options.media_server_preset = None;
+    options.screen_preset = None;
+    options.keep_surround_track = false;
+    options.decoder = None;
+    // Only touches frames flagged as interlaced.
+    options.deinterlace = Some(true);
+    options.full_hardware = Some(false);
+    options.software_decode = true;
+    options.chunked_encoding = false;
+    options.smart_copy = false;
+    options.copy_subtitles = false;
+    options.audio_export = None;
+    options.target_size_mb = None;
+    options.rate_control = Some(RateControl {
+        quality: Some(QUALITY),
+        ..Default::default()
+    });
+    options.audio_bitrate = Some(AUDIO_BITRATE);
+    options.extra_input_args.clear();
+    options.extra_output_args.clear();
+    if is_video {
+        options.extra_output_args.extend(OUTPUT_ARGS.iter().map(|s| s.to_string()));
+    }
+    renamed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_exotic_settings_with_the_compatible_profile() {
+        let mut encoder = "av1_nvenc".to_string();
+        let mut preset = "p7".to_string();
+        let mut output_file = "out/clip.webm".to_string();
+        let mut options = ConversionOptions {
+            screen_preset: Some("screen_x264_text_444".to_string()),
+            chunked_encoding: true,
+            extra_output_args: vec!["-tune".to_string(), "film".to_string()],
+            trim_start: Some(5.0),
+            ..Default::default()
+        };
+        assert!(apply(&mut encoder, &mut preset, &mut output_file, &mut options));
+
+        assert_eq!((encoder.as_str(), preset.as_str()), ("libx264", "medium"));
+        assert_eq!(output_file, "out/clip.mp4");
+        assert!(options.screen_preset.is_none() && !options.chunked_encoding);
+        assert_eq!(options.full_hardware, Some(false));
+        assert_eq!(options.extra_output_args[..4], ["-profile:v", "high", "-level:v", "4.0"]);
+        assert_eq!(options.trim_start, Some(5.0));
+
+        let mut output_file = "song.flac".to_string();
+        assert!(apply(&mut encoder, &mut preset, &mut output_file, &mut options));
+        assert_eq!(output_file, "song.m4a");
+        assert!(options.extra_output_args.is_empty());
+
+        let mut output_file = "clip.mkv".to_string();
+        assert!(!apply(&mut encoder, &mut preset, &mut output_file, &mut options));
+        assert_eq!(output_file, "clip.mkv");
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error::AppError;
+use super::probe_info;
+use log::{info, warn};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Width of scrub thumbnails; small enough to swap while dragging.
+pub const DEFAULT_SCRUB_WIDTH: u32 = 320;
+/// Upper bound on thumbnails per input, so all-intra files (ProRes, DNxHD)
+/// don't produce one per frame.
+const MAX_THUMBNAILS: f64 = 3000.0;
+/// Inputs whose thumbnails are kept; the oldest is dropped beyond that.
+const MAX_CACHED_INPUTS: usize = 4;
+
+/// Keyframe thumbnails of one input, in timestamp order.
+#[derive(Debug, Default)]
+struct ScrubIndex {
+    ready: bool,
+    error: Option<String>,
+    frames: Vec<(f64, String)>,
+    dir: PathBuf,
+    /// Order of `prepare` calls, for eviction and to spot stale results.
+    created: u64,
+}
+
+/// What `prepare` reports back to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrubStatus {
+    pub ready: bool,
+    pub error: Option<String>,
+    pub thumbnails: usize,
+}
+
+static CACHE: Mutex<Option<HashMap<String, ScrubIndex>>> = Mutex::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// `(frame number, pts_time)` of each frame the `showinfo` filter logged.
+pub fn parse_showinfo(stderr: &str) -> Vec<(usize, f64)> {
+    let regex = Regex::new(r"Parsed_showinfo.*\sn:\s*(\d+)\s.*pts_time:(-?[\d.]+)").expect("Invalid regex");
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let caps = regex.captures(line)?;
+            Some((caps[1].parse().ok()?, caps[2].parse().ok()?))
+        })
+        .collect()
+}
+
+/// Thumbnail shown at `timestamp`: the last keyframe at or before it, or the
+/// first one for timestamps before any keyframe.
+pub fn nearest(frames: &[(f64, String)], timestamp: f64) -> Option<&str> {
+    let after = frames.partition_point(|(t, _)| *t <= timestamp);
+    frames.get(after.saturating_sub(1)).map(|(_, path)| path.as_str())
+}
+
+fn status(index: &ScrubIndex) -> ScrubStatus {
+    ScrubStatus {
+        ready: index.ready,
+        error: index.error.clone(),
+        thumbnails: index.frames.len(),
+    }
+}
+
+/// Start generating thumbnails for `input_file` in the background unless
+/// they exist or are being made; returns the current state.
+pub fn prepare(ffmpeg_path: &str, input_file: &str, max_width: Option<u32>) -> ScrubStatus {
+    let mut cache = CACHE.lock().expect("Failed to lock scrub cache");
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if let Some(index) = cache.get(input_file).filter(|i| i.error.is_none()) {
+        return status(index);
+    }
+
+    let created = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    if cache.len() >= MAX_CACHED_INPUTS {
+        if let Some(oldest) = cache.iter().min_by_key(|(_, i)| i.created).map(|(k, _)| k.clone()) {
+            if let Some(index) = cache.remove(&oldest) {
+                let _ = std::fs::remove_dir_all(&index.dir);
+            }
+        }
+    }
+    let dir = std::env::temp_dir()
+        .join("dreamcodec_scrub")
+        .join(created.to_string());
+    let index = ScrubIndex {
+        dir: dir.clone(),
+        created,
+        ..Default::default()
+    };
+    let reply = status(&index);
+    cache.insert(input_file.to_string(), index);
+
+    let (ffmpeg_path, input_file) = (ffmpeg_path.to_string(), input_file.to_string());
+    let width = max_width.unwrap_or(DEFAULT_SCRUB_WIDTH);
+    tokio::spawn(async move {
+        let result = generate(&ffmpeg_path, &input_file, width, &dir).await;
+        let mut cache = CACHE.lock().expect("Failed to lock scrub cache");
+        let Some(index) = cache.as_mut().and_then(|c| c.get_mut(&input_file)).filter(|i| i.created == created) else {
+            // Evicted while generating.
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        };
+        match result {
+            Ok(frames) => {
+                info!("Scrub preview ready for {}: {} thumbnail(s)", input_file, frames.len());
+                index.frames = frames;
+                index.ready = true;
+            }
+            Err(e) => {
+                warn!("Scrub preview failed for {}: {}", input_file, e);
+                index.error = Some(e.to_string());
+            }
+        }
+    });
+    reply
+}
+
+/// Decode only the keyframes (on the GPU when FFmpeg finds a working
+/// hwaccel) and write a small JPEG of each, thinned to `MAX_THUMBNAILS`.
+async fn generate(ffmpeg_path: &str, input_file: &str, width: u32, dir: &Path) -> Result<Vec<(f64, String)>, AppError> {
+    std::fs::create_dir_all(dir)?;
+    let duration = probe_info(ffmpeg_path, input_file).await.and_then(|i| i.duration).unwrap_or(0.0);
+    let interval = (duration / MAX_THUMBNAILS).max(1.0);
+    let filter = format!(
+        "select='isnan(prev_selected_t)+gte(t-prev_selected_t\\,{:.3})',scale='min({},iw)':-2,showinfo",
+        interval, width
+    );
+    let pattern = dir.join("scrub_%06d.jpg");
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-nostats", "-y", "-hwaccel", "auto", "-skip_frame", "nokey", "-i", input_file])
+        .args(["-map", "0:v:0", "-an", "-sn", "-vf", &filter, "-fps_mode", "passthrough", "-q:v", "5"])
+        .arg(&pattern)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| AppError::Ffmpeg(format!("Failed to run {}: {}", ffmpeg_path, e)))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(AppError::Ffmpeg(format!(
+            "Keyframe thumbnails failed: {}",
+            stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no output")
+        )));
+    }
+    let frames: Vec<(f64, String)> = parse_showinfo(&stderr)
+        .into_iter()
+        .map(|(n, time)| (time, dir.join(format!("scrub_{:06}.jpg", n + 1)).to_string_lossy().to_string()))
+        .filter(|(_, path)| Path::new(path).exists())
+        .collect();
+    if frames.is_empty() {
+        return Err(AppError::Ffmpeg(format!("No keyframes decoded from {}", input_file)));
+    }
+    Ok(frames)
+}
+
+/// JPEG bytes of the thumbnail for `timestamp`, once `prepare` finished
+/// for `input_file`.
+pub fn frame_at(input_file: &str, timestamp: f64) -> Option<Vec<u8>> {
+    let path = {
+        let cache = CACHE.lock().expect("Failed to lock scrub cache");
+        let index = cache.as_ref()?.get(input_file).filter(|i| i.ready)?;
+        nearest(&index.frames, timestamp)?.to_string()
+    };
+    std::fs::read(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_timestamps_to_the_preceding_keyframe() {
+        let stderr = "[Parsed_showinfo_2 @ 0x1] n:   0 pts:      0 pts_time:0       duration:1\n\
+                      frame=    2 fps=0.0\n\
+                      [Parsed_showinfo_2 @ 0x1] n:   1 pts: 256000 pts_time:2.002   duration:1";
+        assert_eq!(parse_showinfo(stderr), vec![(0, 0.0), (1, 2.002)]);
+
+        let frames = vec![(0.5, "a".to_string()), (2.0, "b".to_string()), (4.0, "c".to_string())];
+        assert_eq!(nearest(&frames, 0.0), Some("a"));
+        assert_eq!(nearest(&frames, 3.9), Some("b"));
+        assert_eq!(nearest(&frames, 99.0), Some("c"));
+        assert_eq!(nearest(&[], 1.0), None);
+    }
+}
@@ -0,0 +1,127 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// AVCHD cameras split recordings at the FAT32 limit; a file at least this
+/// large is assumed to continue in the next numbered file.
+const AVCHD_SPLIT_THRESHOLD: u64 = 1_900 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SegmentKind {
+    GoPro,
+    Avchd,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentGroup {
+    pub kind: SegmentKind,
+    pub files: Vec<String>,
+    pub total_size: u64,
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Look for sibling chapter files belonging to the same recording as `path`.
+/// Returns `None` when the file is not part of a multi-segment recording.
+pub fn detect_segments(path: &Path) -> Option<SegmentGroup> {
+    let dir = path.parent()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+
+    let (kind, files) = if let Some(files) = gopro_chapters(dir, &name) {
+        (SegmentKind::GoPro, files)
+    } else if let Some(files) = avchd_chain(dir, &name) {
+        (SegmentKind::Avchd, files)
+    } else {
+        return None;
+    };
+
+    if files.len() < 2 {
+        return None;
+    }
+
+    Some(SegmentGroup {
+        kind,
+        total_size: files.iter().map(|f| file_size(f)).sum(),
+        files: files
+            .into_iter()
+            .map(|f| f.to_string_lossy().to_string())
+            .collect(),
+    })
+}
+
+/// GoPro chapters share a 4-digit file number with an increasing 2-digit
+/// chapter: `GX010001.MP4`, `GX020001.MP4`, ... (HERO6+), or the older
+/// `GOPR0001.MP4` followed by `GP010001.MP4`, `GP020001.MP4`, ...
+fn gopro_chapters(dir: &Path, name: &str) -> Option<Vec<PathBuf>> {
+    let modern = Regex::new(r"(?i)^(G[HXP])(\d{2})(\d{4})\.(MP4)$").expect("Invalid regex");
+    let legacy = Regex::new(r"(?i)^GOPR(\d{4})\.(MP4)$").expect("Invalid regex");
+
+    let (prefix, number, ext) = if let Some(caps) = modern.captures(name) {
+        let prefix = caps[1].to_uppercase();
+        // GP01xxxx is the second chapter of a legacy GOPRxxxx recording.
+        if prefix == "GP" {
+            let legacy_first = dir.join(format!("GOPR{}.{}", &caps[3], &caps[4]));
+            if legacy_first.exists() {
+                return gopro_chapters(dir, &format!("GOPR{}.{}", &caps[3], &caps[4]));
+            }
+        }
+        (prefix, caps[3].to_string(), caps[4].to_string())
+    } else if let Some(caps) = legacy.captures(name) {
+        ("GP".to_string(), caps[1].to_string(), caps[2].to_string())
+    } else {
+        return None;
+    };
+
+    let mut files = Vec::new();
+    if legacy.is_match(name) {
+        files.push(dir.join(name));
+    }
+
+    for chapter in 1..=99 {
+        let candidate = dir.join(format!("{}{:02}{}.{}", prefix, chapter, number, ext));
+        if candidate.exists() {
+            files.push(candidate);
+        } else if !files.is_empty() || chapter > 1 {
+            break;
+        }
+    }
+
+    Some(files)
+}
+
+/// AVCHD clips are numbered `00000.MTS`, `00001.MTS`, ...; consecutive files
+/// belong together while the previous one was cut at the split threshold.
+fn avchd_chain(dir: &Path, name: &str) -> Option<Vec<PathBuf>> {
+    let regex = Regex::new(r"(?i)^(\d{5})\.(MTS|M2TS)$").expect("Invalid regex");
+    let caps = regex.captures(name)?;
+    let ext = caps[2].to_string();
+    let mut number: u32 = caps[1].parse().ok()?;
+
+    let mut files = vec![dir.join(name)];
+    while file_size(files.last()?) >= AVCHD_SPLIT_THRESHOLD {
+        number += 1;
+        let next = dir.join(format!("{:05}.{}", number, ext));
+        if !next.exists() {
+            break;
+        }
+        files.push(next);
+    }
+
+    Some(files)
+}
+
+/// Path of the concat demuxer list written for a task.
+pub fn concat_list_path(task_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("dreamcodec_concat_{}.txt", task_id))
+}
+
+/// Write an FFmpeg concat demuxer list (`file '...'` per line).
+pub fn write_concat_list(files: &[String], list_path: &Path) -> std::io::Result<()> {
+    let body: String = files
+        .iter()
+        .map(|f| format!("file '{}'\n", f.replace('\'', "'\\''")))
+        .collect();
+    std::fs::write(list_path, body)
+}
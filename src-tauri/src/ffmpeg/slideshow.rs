@@ -0,0 +1,168 @@
+use super::PROGRESS_PIPE;
+use serde::{Deserialize, Serialize};
+
+/// Music fades out over this many seconds at the end of the slideshow.
+const MUSIC_FADE_OUT: f64 = 2.0;
+
+/// Settings for rendering a video from a list of still images.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlideshowOptions {
+    /// Images in display order.
+    pub images: Vec<String>,
+    /// Seconds each image stays on screen, including its crossfade.
+    #[serde(alias = "imageDuration")]
+    pub image_duration: f64,
+    /// Crossfade length between images; hard cuts when unset.
+    pub crossfade: Option<f64>,
+    /// Background music, cut (with a short fade) to the slideshow length.
+    #[serde(alias = "musicFile")]
+    pub music_file: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub encoder: String,
+}
+
+impl Default for SlideshowOptions {
+    fn default() -> Self {
+        Self {
+            images: Vec::new(),
+            image_duration: 5.0,
+            crossfade: None,
+            music_file: None,
+            width: 1920,
+            height: 1080,
+            fps: 30,
+            encoder: "libx264".to_string(),
+        }
+    }
+}
+
+impl SlideshowOptions {
+    /// Crossfade length actually used; it can't exceed an image's duration.
+    fn crossfade(&self) -> f64 {
+        self.crossfade
+            .filter(|c| *c > 0.0 && self.images.len() > 1)
+            .map(|c| c.min(self.image_duration * 0.9))
+            .unwrap_or(0.0)
+    }
+
+    /// Length of the rendered video in seconds.
+    pub fn total_duration(&self) -> f64 {
+        let count = self.images.len() as f64;
+        (count * self.image_duration - (count - 1.0).max(0.0) * self.crossfade()).max(0.0)
+    }
+}
+
+/// Filtergraph normalizing every image to the output size and joining them
+/// with `xfade` (or `concat` for hard cuts) into `[vout]`.
+fn slideshow_graph(options: &SlideshowOptions) -> String {
+    let mut chains: Vec<String> = (0..options.images.len())
+        .map(|i| {
+            format!(
+                "[{i}:v]scale={w}:{h}:force_original_aspect_ratio=decrease,\
+                 pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:color=black,setsar=1,fps={fps},format=yuv420p[v{i}]",
+                i = i,
+                w = options.width,
+                h = options.height,
+                fps = options.fps
+            )
+        })
+        .collect();
+
+    let count = options.images.len();
+    let crossfade = options.crossfade();
+    if count == 1 {
+        chains.push("[v0]null[vout]".to_string());
+    } else if crossfade > 0.0 {
+        let mut previous = "v0".to_string();
+        for i in 1..count {
+            let label = if i == count - 1 { "vout".to_string() } else { format!("x{}", i) };
+            chains.push(format!(
+                "[{}][v{}]xfade=transition=fade:duration={:.3}:offset={:.3}[{}]",
+                previous,
+                i,
+                crossfade,
+                i as f64 * (options.image_duration - crossfade),
+                label
+            ));
+            previous = label;
+        }
+    } else {
+        let pads: String = (0..count).map(|i| format!("[v{}]", i)).collect();
+        chains.push(format!("{}concat=n={}:v=1:a=0[vout]", pads, count));
+    }
+
+    chains.join(";")
+}
+
+/// Build the full FFmpeg argument list for a slideshow written to `output_file`.
+pub fn build_args(options: &SlideshowOptions, output_file: &str) -> Vec<String> {
+    let total = options.total_duration();
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-progress", PROGRESS_PIPE, "-nostats"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    // Each still is looped by the image2 demuxer for exactly its time on screen.
+    for image in &options.images {
+        args.extend(["-loop", "1", "-framerate"].iter().map(|s| s.to_string()));
+        args.push(options.fps.to_string());
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", options.image_duration));
+        args.push("-i".to_string());
+        args.push(image.clone());
+    }
+    if let Some(ref music) = options.music_file {
+        args.push("-i".to_string());
+        args.push(music.clone());
+    }
+
+    args.push("-filter_complex".to_string());
+    args.push(slideshow_graph(options));
+    args.push("-map".to_string());
+    args.push("[vout]".to_string());
+    args.push("-c:v".to_string());
+    args.push(options.encoder.clone());
+    args.push("-pix_fmt".to_string());
+    args.push("yuv420p".to_string());
+
+    if options.music_file.is_some() {
+        let fade = MUSIC_FADE_OUT.min(total);
+        args.push("-map".to_string());
+        args.push(format!("{}:a:0", options.images.len()));
+        args.push("-af".to_string());
+        args.push(format!("afade=t=out:st={:.3}:d={:.3}", total - fade, fade));
+        args.extend(["-c:a", "aac", "-b:a", "192k"].iter().map(|s| s.to_string()));
+    }
+
+    args.push("-t".to_string());
+    args.push(format!("{:.3}", total));
+    let lower = output_file.to_lowercase();
+    if lower.ends_with(".mp4") || lower.ends_with(".mov") {
+        args.push("-movflags".to_string());
+        args.push("+faststart".to_string());
+    }
+    args.push(output_file.to_string());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossfades_overlap_consecutive_images() {
+        let options = SlideshowOptions {
+            images: vec!["a.jpg".into(), "b.jpg".into(), "c.jpg".into()],
+            image_duration: 4.0,
+            crossfade: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(options.total_duration(), 10.0);
+        let graph = slideshow_graph(&options);
+        assert!(graph.contains("[v0][v1]xfade=transition=fade:duration=1.000:offset=3.000[x1]"));
+        assert!(graph.ends_with("[x1][v2]xfade=transition=fade:duration=1.000:offset=6.000[vout]"));
+    }
+}
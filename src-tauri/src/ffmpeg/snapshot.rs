@@ -0,0 +1,130 @@
+use crate::error::AppError;
+use super::{probe_info, watchdog};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Most thumbnails a strip may ask for.
+pub const MAX_STRIP_COUNT: u32 = 200;
+/// Default width of strip thumbnails, sized for a scrubber bar.
+pub const DEFAULT_STRIP_WIDTH: u32 = 160;
+
+/// One thumbnail of a strip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub timestamp: f64,
+    pub path: String,
+}
+
+/// Image format from the output extension; only PNG and JPEG are written.
+fn image_format(output_file: &str) -> Result<&'static str, AppError> {
+    let ext = Path::new(output_file)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => Ok("png"),
+        "jpg" | "jpeg" => Ok("jpg"),
+        _ => Err(AppError::Internal(format!(
+            "Snapshots must be .png or .jpg files: {}",
+            output_file
+        ))),
+    }
+}
+
+/// `count` timestamps at the middle of equal slices of `duration`, so the
+/// strip never lands on the black first or last frame.
+pub fn strip_timestamps(duration: f64, count: u32) -> Vec<f64> {
+    let slice = duration / count as f64;
+    (0..count).map(|i| slice * (i as f64 + 0.5)).collect()
+}
+
+/// Write the frame at `timestamp` seconds to `output_file` (PNG or JPEG),
+/// scaled down to `max_width` if given.
+pub async fn extract_frame(
+    ffmpeg_path: &str,
+    input_file: &str,
+    timestamp: f64,
+    output_file: &str,
+    max_width: Option<u32>,
+) -> Result<(), AppError> {
+    let format = image_format(output_file)?;
+    if let Some(parent) = Path::new(output_file).parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-v", "error", "-y", "-ss", &format!("{:.3}", timestamp.max(0.0)), "-i", input_file])
+        .args(["-frames:v", "1"]);
+    if let Some(width) = max_width {
+        cmd.args(["-vf", &format!("scale='min({},iw)':-2", width)]);
+    }
+    if format == "jpg" {
+        cmd.args(["-q:v", "2"]);
+    }
+    cmd.arg(output_file).stdout(Stdio::null()).stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = watchdog::output(&mut cmd).await?;
+
+    if !output.status.success() || !Path::new(output_file).exists() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Ffmpeg(format!(
+            "Could not extract a frame at {:.3}s: {}",
+            timestamp,
+            stderr.lines().next().unwrap_or("no frame produced")
+        )));
+    }
+    Ok(())
+}
+
+/// `count` evenly spaced thumbnails of `input_file`, written into `out_dir`
+/// as `thumb_0001.jpg`, ... in timestamp order.
+pub async fn thumbnail_strip(
+    ffmpeg_path: &str,
+    input_file: &str,
+    count: u32,
+    max_width: Option<u32>,
+    out_dir: &Path,
+) -> Result<Vec<Thumbnail>, AppError> {
+    if count == 0 || count > MAX_STRIP_COUNT {
+        return Err(AppError::Internal(format!(
+            "Thumbnail count must be between 1 and {}",
+            MAX_STRIP_COUNT
+        )));
+    }
+    let duration = probe_info(ffmpeg_path, input_file)
+        .await
+        .and_then(|info| info.duration)
+        .filter(|d| *d > 0.0)
+        .ok_or_else(|| AppError::Ffmpeg(format!("Could not determine the duration of {}", input_file)))?;
+
+    let width = max_width.unwrap_or(DEFAULT_STRIP_WIDTH);
+    let mut thumbnails = Vec::with_capacity(count as usize);
+    // One run per frame: input seeking makes each a short decode, where a
+    // single `fps` filter pass would read the whole file.
+    for (i, timestamp) in strip_timestamps(duration, count).into_iter().enumerate() {
+        let path = out_dir.join(format!("thumb_{:04}.jpg", i + 1));
+        let path = path.to_string_lossy().to_string();
+        extract_frame(ffmpeg_path, input_file, timestamp, &path, Some(width)).await?;
+        thumbnails.push(Thumbnail { timestamp, path });
+    }
+    Ok(thumbnails)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_thumbnails_at_slice_midpoints() {
+        assert_eq!(strip_timestamps(100.0, 4), vec![12.5, 37.5, 62.5, 87.5]);
+        assert!(image_format("a/frame.JPEG").is_ok());
+        assert!(image_format("frame.webp").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use super::{run_prepared_job, ConversionStatus, ConversionTask, PROGRESS_PIPE};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Ladder used when the job lists no renditions: height, video and audio
+/// bitrate (kbit/s).
+const DEFAULT_LADDER: &[(u32, u32, u32)] = &[(1080, 5000, 192), (720, 3000, 128), (480, 1400, 96), (360, 800, 96)];
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StreamingFormat {
+    /// HLS: a master playlist over one media playlist per rendition.
+    #[default]
+    Hls,
+    /// MPEG-DASH: one manifest with video and audio adaptation sets.
+    Dash,
+}
+
+impl StreamingFormat {
+    pub fn manifest_name(self) -> &'static str {
+        match self {
+            StreamingFormat::Hls => "master.m3u8",
+            StreamingFormat::Dash => "manifest.mpd",
+        }
+    }
+}
+
+/// One step of the bitrate ladder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rendition {
+    pub height: u32,
+    /// Video bitrate in kbit/s.
+    #[serde(alias = "videoBitrate")]
+    pub video_bitrate: u32,
+    /// Audio bitrate in kbit/s.
+    #[serde(alias = "audioBitrate")]
+    pub audio_bitrate: u32,
+}
+
+impl Default for Rendition {
+    fn default() -> Self {
+        Self {
+            height: 720,
+            video_bitrate: 3000,
+            audio_bitrate: 128,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingOptions {
+    pub format: StreamingFormat,
+    /// Renditions to encode; the default ladder when empty.
+    pub renditions: Vec<Rendition>,
+    /// Segment length in seconds; every rendition gets a keyframe at each
+    /// segment boundary so players can switch between them.
+    #[serde(alias = "segmentSeconds")]
+    pub segment_seconds: u32,
+    pub encoder: String,
+    pub preset: Option<String>,
+}
+
+impl Default for StreamingOptions {
+    fn default() -> Self {
+        Self {
+            format: StreamingFormat::Hls,
+            renditions: Vec::new(),
+            segment_seconds: 6,
+            encoder: "libx264".to_string(),
+            preset: None,
+        }
+    }
+}
+
+impl StreamingOptions {
+    /// Renditions to encode, tallest first. Steps taller than the source
+    /// would only upscale, so they are dropped while at least one remains.
+    pub fn ladder(&self, source_height: Option<u32>) -> Vec<Rendition> {
+        let mut ladder = if self.renditions.is_empty() {
+            DEFAULT_LADDER
+                .iter()
+                .map(|&(height, video_bitrate, audio_bitrate)| Rendition {
+                    height,
+                    video_bitrate,
+                    audio_bitrate,
+                })
+                .collect()
+        } else {
+            self.renditions.clone()
+        };
+        ladder.sort_by_key(|r| std::cmp::Reverse(r.height));
+        if let Some(source_height) = source_height {
+            let smallest = ladder.last().cloned();
+            ladder.retain(|r| r.height <= source_height);
+            if ladder.is_empty() {
+                ladder.extend(smallest);
+            }
+        }
+        ladder
+    }
+}
+
+/// Everything the packaging job needs once the input was probed.
+pub struct PackageJob {
+    pub output_dir: PathBuf,
+    pub options: StreamingOptions,
+    pub ladder: Vec<Rendition>,
+    pub has_audio: bool,
+}
+
+/// Scratch folder for a job's rendition encodes.
+pub fn work_dir(task_id: &str) -> PathBuf {
+    std::env::temp_dir().join("dreamcodec_streaming").join(task_id)
+}
+
+fn base_args() -> Vec<String> {
+    ["-y", "-hide_banner", "-progress", PROGRESS_PIPE, "-nostats"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Encode one rendition to an MP4 with keyframes on segment boundaries.
+pub fn rendition_args(
+    input_file: &str,
+    rendition: &Rendition,
+    options: &StreamingOptions,
+    has_audio: bool,
+    output_file: &str,
+) -> Vec<String> {
+    let mut args = base_args();
+    args.extend(["-i", input_file, "-map", "0:v:0"].iter().map(|s| s.to_string()));
+    args.push("-vf".to_string());
+    args.push(format!("scale=-2:{}", rendition.height));
+    args.push("-c:v".to_string());
+    args.push(options.encoder.clone());
+    if let Some(ref preset) = options.preset {
+        args.push("-preset".to_string());
+        args.push(preset.clone());
+    }
+    args.push("-b:v".to_string());
+    args.push(format!("{}k", rendition.video_bitrate));
+    args.push("-maxrate".to_string());
+    args.push(format!("{}k", rendition.video_bitrate * 107 / 100));
+    args.push("-bufsize".to_string());
+    args.push(format!("{}k", rendition.video_bitrate * 3 / 2));
+    args.push("-force_key_frames".to_string());
+    args.push(format!("expr:gte(t,n_forced*{})", options.segment_seconds.max(1)));
+    args.extend(["-pix_fmt", "yuv420p"].iter().map(|s| s.to_string()));
+    if has_audio {
+        args.extend(["-map", "0:a:0", "-c:a", "aac", "-ac", "2"].iter().map(|s| s.to_string()));
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", rendition.audio_bitrate));
+    }
+    args.extend(["-sn", "-movflags", "+faststart", output_file].iter().map(|s| s.to_string()));
+    args
+}
+
+/// Package the encoded renditions without re-encoding.
+pub fn package_args(rendition_files: &[String], options: &StreamingOptions, has_audio: bool, output_dir: &Path) -> Vec<String> {
+    let mut args = base_args();
+    for file in rendition_files {
+        args.push("-i".to_string());
+        args.push(file.clone());
+    }
+    for index in 0..rendition_files.len() {
+        args.push("-map".to_string());
+        args.push(format!("{}:v:0", index));
+        if has_audio {
+            args.push("-map".to_string());
+            args.push(format!("{}:a:0", index));
+        }
+    }
+    args.extend(["-c", "copy"].iter().map(|s| s.to_string()));
+    let segment = options.segment_seconds.max(1).to_string();
+    match options.format {
+        StreamingFormat::Hls => {
+            let streams = (0..rendition_files.len())
+                .map(|i| if has_audio { format!("v:{},a:{}", i, i) } else { format!("v:{}", i) })
+                .collect::<Vec<_>>()
+                .join(" ");
+            args.extend(["-f", "hls", "-hls_time", &segment, "-hls_playlist_type", "vod"].iter().map(|s| s.to_string()));
+            args.push("-hls_segment_filename".to_string());
+            args.push(output_dir.join("stream_%v").join("segment_%05d.ts").to_string_lossy().to_string());
+            args.extend(["-master_pl_name", StreamingFormat::Hls.manifest_name(), "-var_stream_map"].iter().map(|s| s.to_string()));
+            args.push(streams);
+            args.push(output_dir.join("stream_%v").join("index.m3u8").to_string_lossy().to_string());
+        }
+        StreamingFormat::Dash => {
+            let sets = if has_audio { "id=0,streams=v id=1,streams=a" } else { "id=0,streams=v" };
+            args.extend(["-f", "dash", "-seg_duration", &segment, "-use_template", "1", "-use_timeline", "1"].iter().map(|s| s.to_string()));
+            args.push("-adaptation_sets".to_string());
+            args.push(sets.to_string());
+            args.push(output_dir.join(StreamingFormat::Dash.manifest_name()).to_string_lossy().to_string());
+        }
+    }
+    args
+}
+
+fn completed(task_arc: &Arc<Mutex<ConversionTask>>) -> bool {
+    matches!(
+        task_arc.lock().expect("Failed to lock task mutex").progress.status,
+        ConversionStatus::Completed
+    )
+}
+
+/// Encode each rendition in turn, reporting progress per rendition, then
+/// package them into the manifest named by the task's output file.
+pub async fn run(task_arc: Arc<Mutex<ConversionTask>>, job: PackageJob) {
+    let (task_id, input_file, manifest) = {
+        let task = task_arc.lock().expect("Failed to lock task mutex");
+        (task.id.clone(), task.input_file.clone(), task.output_file.clone())
+    };
+    let work = work_dir(&task_id);
+    if let Err(e) = std::fs::create_dir_all(&work) {
+        let message = format!("Failed to create {}: {}", work.display(), e);
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.status = ConversionStatus::Failed(message.clone());
+        task.progress.error_message = Some(message);
+        return;
+    }
+    info!("Packaging {} as {:?} with {} rendition(s)", input_file, job.options.format, job.ladder.len());
+
+    let mut rendition_files = Vec::new();
+    for (index, rendition) in job.ladder.iter().enumerate() {
+        let file = work.join(format!("rendition_{}p.mp4", rendition.height)).to_string_lossy().to_string();
+        {
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.output_file = file.clone();
+            task.progress.current_time = 0.0;
+            task.progress.percentage = 0.0;
+            task.progress.log.push(format!(
+                "Rendition {} of {}: {}p at {} kbit/s",
+                index + 1,
+                job.ladder.len(),
+                rendition.height,
+                rendition.video_bitrate
+            ));
+        }
+        run_prepared_job(
+            task_arc.clone(),
+            rendition_args(&input_file, rendition, &job.options, job.has_audio, &file),
+        )
+        .await;
+        if !completed(&task_arc) {
+            let _ = std::fs::remove_dir_all(&work);
+            return;
+        }
+        rendition_files.push(file);
+    }
+
+    {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.output_file = manifest.clone();
+        task.progress.current_time = 0.0;
+        task.progress.percentage = 0.0;
+        task.progress.log.push(format!("Packaging {} rendition(s) into {}", rendition_files.len(), manifest));
+    }
+    run_prepared_job(
+        task_arc.clone(),
+        package_args(&rendition_files, &job.options, job.has_audio, &job.output_dir),
+    )
+    .await;
+    if let Err(e) = std::fs::remove_dir_all(&work) {
+        warn!("Failed to remove {}: {}", work.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_upscaled_rungs_and_maps_every_rendition() {
+        let options = StreamingOptions::default();
+        let heights: Vec<u32> = options.ladder(Some(720)).iter().map(|r| r.height).collect();
+        assert_eq!(heights, [720, 480, 360]);
+        assert_eq!(options.ladder(Some(240)).len(), 1);
+
+        let files = ["a.mp4".to_string(), "b.mp4".to_string()];
+        let args = package_args(&files, &options, true, Path::new("out"));
+        let map = args.iter().position(|a| a == "-var_stream_map").unwrap();
+        assert_eq!(args[map + 1], "v:0,a:0 v:1,a:1");
+        assert!(args.contains(&"master.m3u8".to_string()));
+    }
+}
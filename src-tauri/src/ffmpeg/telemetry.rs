@@ -0,0 +1,394 @@
+use super::filters::escape_filter_path;
+use super::OverlayCorner;
+use crate::error::AppError;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[cfg(target_os = "windows")]
+use super::CREATE_NO_WINDOW;
+
+/// Distance from the chosen corner in pixels.
+const MARGIN: u32 = 20;
+const DEFAULT_FONT_SIZE: u32 = 36;
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SpeedUnit {
+    /// km/h and metres.
+    #[default]
+    Metric,
+    /// mph and feet.
+    Imperial,
+}
+
+/// Speed/altitude HUD burned into the video.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryOptions {
+    /// GPX or CSV sidecar. When unset the GoPro GPMF track of the input is used.
+    pub file: Option<String>,
+    /// Seconds of video before the first telemetry sample (negative when the
+    /// recording started after the log).
+    pub offset: f64,
+    pub units: SpeedUnit,
+    pub corner: OverlayCorner,
+    #[serde(alias = "fontSize")]
+    pub font_size: Option<u32>,
+    /// `sendcmd` script with the HUD text; written when the job starts.
+    #[serde(skip)]
+    pub script: Option<String>,
+}
+
+/// One telemetry reading, `time` in seconds from the start of the log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub time: f64,
+    pub speed_mps: Option<f64>,
+    pub altitude_m: Option<f64>,
+}
+
+/// Seconds since 1970 for an ISO 8601 timestamp. The zone suffix is ignored:
+/// only differences between samples of one log matter.
+fn parse_iso_seconds(value: &str) -> Option<f64> {
+    let regex = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2}(?:\.\d+)?)").ok()?;
+    let caps = regex.captures(value.trim())?;
+    let (year, month, day): (i64, i64, i64) = (caps[1].parse().ok()?, caps[2].parse().ok()?, caps[3].parse().ok()?);
+    // Days from civil date (Howard Hinnant's algorithm).
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let hours: f64 = caps[4].parse().ok()?;
+    let minutes: f64 = caps[5].parse().ok()?;
+    let seconds: f64 = caps[6].parse().ok()?;
+    Some(days as f64 * 86_400.0 + hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+fn haversine_m(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Track points of a GPX file. Speed comes from a `<speed>` element when
+/// present, otherwise from the distance to the previous point.
+pub fn parse_gpx(text: &str) -> Vec<Sample> {
+    let point_regex = Regex::new(r"(?s)<trkpt\s([^>]*)>(.*?)</trkpt>").expect("Invalid regex");
+    let lat_regex = Regex::new(r#"lat="(-?[\d.]+)""#).expect("Invalid regex");
+    let lon_regex = Regex::new(r#"lon="(-?[\d.]+)""#).expect("Invalid regex");
+    let tag = |name: &str| Regex::new(&format!(r"<(?:\w+:)?{}>([^<]+)</", name)).expect("Invalid regex");
+    let (ele_regex, time_regex, speed_regex) = (tag("ele"), tag("time"), tag("speed"));
+
+    let mut samples = Vec::new();
+    let mut start = None;
+    let mut previous: Option<(f64, (f64, f64))> = None;
+    for caps in point_regex.captures_iter(text) {
+        let (attributes, body) = (&caps[1], &caps[2]);
+        let Some(time) = time_regex.captures(body).and_then(|c| parse_iso_seconds(&c[1])) else {
+            continue;
+        };
+        let start = *start.get_or_insert(time);
+        let position = lat_regex
+            .captures(attributes)
+            .zip(lon_regex.captures(attributes))
+            .and_then(|(lat, lon)| Some((lat[1].parse().ok()?, lon[1].parse().ok()?)));
+        let speed = speed_regex.captures(body).and_then(|c| c[1].trim().parse().ok()).or_else(|| {
+            let (prev_time, prev_position) = previous?;
+            let dt = time - prev_time;
+            let position = position?;
+            (dt > 0.0).then(|| haversine_m(prev_position, position) / dt)
+        });
+        if let Some(position) = position {
+            previous = Some((time, position));
+        }
+        samples.push(Sample {
+            time: time - start,
+            speed_mps: speed,
+            altitude_m: ele_regex.captures(body).and_then(|c| c[1].trim().parse().ok()),
+        });
+    }
+    samples
+}
+
+/// Rows of a CSV log with a header naming its time, speed and altitude
+/// columns. Times are seconds or ISO timestamps; speed is m/s unless the
+/// header says km/h or mph.
+pub fn parse_csv(text: &str) -> Vec<Sample> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+    let columns: Vec<String> = header.split(',').map(|c| c.trim().to_lowercase()).collect();
+    let find = |test: &dyn Fn(&str) -> bool| columns.iter().position(|c| test(c));
+    let Some(time_col) = find(&|c| c.contains("time") || c == "t" || c.starts_with("sec")) else {
+        return Vec::new();
+    };
+    let speed_col = find(&|c| c.contains("speed"));
+    let alt_col = find(&|c| c.contains("alt") || c.contains("ele"));
+    let speed_factor = match speed_col.map(|i| columns[i].as_str()) {
+        Some(c) if c.contains("km") || c.contains("kph") => 1.0 / 3.6,
+        Some(c) if c.contains("mph") => 0.447_04,
+        _ => 1.0,
+    };
+
+    let mut samples = Vec::new();
+    let mut start = None;
+    for line in lines {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let value = |col: Option<usize>| col.and_then(|i| fields.get(i)?.parse::<f64>().ok());
+        let Some(time) = value(Some(time_col)).or_else(|| parse_iso_seconds(fields.get(time_col)?)) else {
+            continue;
+        };
+        let start = *start.get_or_insert(time);
+        samples.push(Sample {
+            time: time - start,
+            speed_mps: value(speed_col).map(|s| s * speed_factor),
+            altitude_m: value(alt_col),
+        });
+    }
+    samples
+}
+
+/// One GPMF key-length-value entry.
+struct Klv<'a> {
+    key: &'a [u8],
+    kind: u8,
+    size: usize,
+    repeat: usize,
+    data: &'a [u8],
+}
+
+fn klv_entries(mut data: &[u8]) -> Vec<Klv<'_>> {
+    let mut entries = Vec::new();
+    while data.len() >= 8 {
+        let size = data[5] as usize;
+        let repeat = u16::from_be_bytes([data[6], data[7]]) as usize;
+        let length = size * repeat;
+        // Payloads are padded to 32-bit boundaries.
+        let padded = (length + 3) & !3;
+        if data.len() < 8 + padded {
+            break;
+        }
+        entries.push(Klv {
+            key: &data[..4],
+            kind: data[4],
+            size,
+            repeat,
+            data: &data[8..8 + length],
+        });
+        data = &data[8 + padded..];
+    }
+    entries
+}
+
+/// Integer values of a `SCAL` entry.
+fn scale_values(entry: &Klv) -> Vec<f64> {
+    let width = match entry.kind {
+        b'l' | b'L' => 4,
+        b's' | b'S' => 2,
+        _ => return Vec::new(),
+    };
+    entry
+        .data
+        .chunks_exact(width)
+        .map(|b| match entry.kind {
+            b'l' => i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            b'L' => u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            b's' => i16::from_be_bytes([b[0], b[1]]) as f64,
+            _ => u16::from_be_bytes([b[0], b[1]]) as f64,
+        })
+        .collect()
+}
+
+/// GPS samples of a GoPro GPMF track. Each `DEVC` payload covers about one
+/// second; its `GPS5` samples (lat, lon, alt, 2D speed, 3D speed) are spread
+/// evenly across it.
+pub fn parse_gpmf(data: &[u8]) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    let devices = klv_entries(data).into_iter().filter(|e| e.key == b"DEVC" && e.kind == 0);
+    for (second, device) in devices.enumerate() {
+        for stream in klv_entries(device.data).into_iter().filter(|e| e.key == b"STRM" && e.kind == 0) {
+            let entries = klv_entries(stream.data);
+            let Some(gps) = entries.iter().find(|e| e.key == b"GPS5" && e.kind == b'l' && e.size == 20) else {
+                continue;
+            };
+            let scale = entries.iter().find(|e| e.key == b"SCAL").map(scale_values).unwrap_or_default();
+            let divisor = |i: usize| scale.get(i).or(scale.first()).copied().filter(|s| *s != 0.0).unwrap_or(1.0);
+            for (k, values) in gps.data.chunks_exact(20).enumerate() {
+                let value = |i: usize| {
+                    i32::from_be_bytes([values[i * 4], values[i * 4 + 1], values[i * 4 + 2], values[i * 4 + 3]]) as f64
+                        / divisor(i)
+                };
+                samples.push(Sample {
+                    time: second as f64 + k as f64 / gps.repeat as f64,
+                    speed_mps: Some(value(3)),
+                    altitude_m: Some(value(2)),
+                });
+            }
+        }
+    }
+    samples
+}
+
+/// Read the GPMF track of a GoPro recording.
+async fn read_gpmf(ffmpeg_path: &str, input_file: &str) -> Result<Vec<u8>, AppError> {
+    let mut probe = Command::new(ffmpeg_path);
+    probe.args(["-hide_banner", "-i", input_file]).stdout(Stdio::null()).stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    probe.creation_flags(CREATE_NO_WINDOW);
+    let probe = probe
+        .output()
+        .await
+        .map_err(|e| AppError::Ffmpeg(format!("Failed to probe {}: {}", input_file, e)))?;
+    let stream_regex = Regex::new(r"Stream #0:(\d+)[^\n]*: Data: [^\n]*gpmd").expect("Invalid regex");
+    let stderr = String::from_utf8_lossy(&probe.stderr);
+    let stream = stream_regex
+        .captures(&stderr)
+        .map(|c| c[1].to_string())
+        .ok_or_else(|| AppError::Ffmpeg("No GoPro telemetry (GPMF) track in this file".to_string()))?;
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-v", "error", "-i", input_file, "-map", &format!("0:{}", stream)])
+        .args(["-c", "copy", "-f", "rawvideo", "pipe:1"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    let output = cmd
+        .output()
+        .await
+        .map_err(|e| AppError::Ffmpeg(format!("Failed to read GPMF track: {}", e)))?;
+    if !output.status.success() {
+        return Err(AppError::Ffmpeg("Failed to read GPMF track".to_string()));
+    }
+    Ok(output.stdout)
+}
+
+/// Load the samples for a job from its sidecar or the input's GPMF track.
+pub async fn load_samples(
+    ffmpeg_path: &str,
+    input_file: &str,
+    options: &TelemetryOptions,
+) -> Result<Vec<Sample>, AppError> {
+    let samples = match options.file {
+        Some(ref file) => {
+            let text = std::fs::read_to_string(file)
+                .map_err(|e| AppError::Io(format!("Failed to read telemetry file {}: {}", file, e)))?;
+            let ext = Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+            match ext.as_str() {
+                "gpx" => parse_gpx(&text),
+                "csv" => parse_csv(&text),
+                _ => return Err(AppError::Io(format!("Unsupported telemetry file (use GPX or CSV): {}", file))),
+            }
+        }
+        None => parse_gpmf(&read_gpmf(ffmpeg_path, input_file).await?),
+    };
+    if samples.is_empty() {
+        return Err(AppError::Io("The telemetry source has no usable samples".to_string()));
+    }
+    Ok(samples)
+}
+
+/// HUD text for one sample.
+fn hud_text(sample: &Sample, units: SpeedUnit) -> String {
+    let (speed_factor, speed_unit, alt_factor, alt_unit) = match units {
+        SpeedUnit::Metric => (3.6, "km/h", 1.0, "m"),
+        SpeedUnit::Imperial => (2.236_936, "mph", 3.280_84, "ft"),
+    };
+    let mut parts = Vec::new();
+    if let Some(speed) = sample.speed_mps {
+        parts.push(format!("{:.0} {}", speed * speed_factor, speed_unit));
+    }
+    if let Some(altitude) = sample.altitude_m {
+        parts.push(format!("ALT {:.0} {}", altitude * alt_factor, alt_unit));
+    }
+    parts.join("   ")
+}
+
+/// `sendcmd` script updating the HUD text. Times are in output time: shifted
+/// by the telemetry offset and the job's trim start.
+pub fn sendcmd_script(samples: &[Sample], options: &TelemetryOptions, trim_start: f64) -> String {
+    let mut script = String::new();
+    let mut last_text = String::new();
+    for (i, sample) in samples.iter().enumerate() {
+        let time = sample.time + options.offset - trim_start;
+        // Before the clip starts only the latest reading matters.
+        let next_before_start = samples.get(i + 1).is_some_and(|n| n.time + options.offset - trim_start <= 0.0);
+        if next_before_start {
+            continue;
+        }
+        let text = hud_text(sample, options.units);
+        if text == last_text {
+            continue;
+        }
+        script.push_str(&format!("{:.3} drawtext@hud reinit 'text={}';\n", time.max(0.0), text));
+        last_text = text;
+    }
+    script
+}
+
+/// `sendcmd` + `drawtext` filters rendering the HUD, once the script exists.
+pub fn overlay_filter(options: &TelemetryOptions) -> Option<String> {
+    let script = options.script.as_ref()?;
+    let (x, y) = match options.corner {
+        OverlayCorner::TopLeft => (MARGIN.to_string(), MARGIN.to_string()),
+        OverlayCorner::TopRight => (format!("w-tw-{}", MARGIN), MARGIN.to_string()),
+        OverlayCorner::BottomLeft => (MARGIN.to_string(), format!("h-th-{}", MARGIN)),
+        OverlayCorner::BottomRight => (format!("w-tw-{}", MARGIN), format!("h-th-{}", MARGIN)),
+    };
+    Some(format!(
+        "sendcmd=f={},drawtext@hud=text=' ':fontsize={}:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=10:x={}:y={}",
+        escape_filter_path(script),
+        options.font_size.unwrap_or(DEFAULT_FONT_SIZE),
+        x,
+        y
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_csv_log_into_sendcmd_script() {
+        let csv = "time,speed_kmh,altitude\n0,36,100\n1,36,100\n2,72,101.4\n";
+        let samples = parse_csv(csv);
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[2].speed_mps, Some(20.0));
+
+        let options = TelemetryOptions { offset: 0.5, ..Default::default() };
+        assert_eq!(
+            sendcmd_script(&samples, &options, 1.0),
+            "0.000 drawtext@hud reinit 'text=36 km/h   ALT 100 m';\n\
+             1.500 drawtext@hud reinit 'text=72 km/h   ALT 101 m';\n"
+        );
+    }
+
+    #[test]
+    fn reads_gps5_samples_from_gpmf() {
+        let klv = |key: &[u8], kind: u8, size: u8, repeat: u16, data: &[u8]| {
+            let mut out = key.to_vec();
+            out.extend([kind, size]);
+            out.extend(repeat.to_be_bytes());
+            out.extend(data);
+            out.resize(out.len().div_ceil(4) * 4, 0);
+            out
+        };
+        let scal: Vec<u8> = [10_000_000i32, 10_000_000, 1000, 1000, 100].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let gps: Vec<u8> = [473_000_000i32, 85_000_000, 420_500, 12_000, 1200].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let stream = [klv(b"SCAL", b'l', 4, 5, &scal), klv(b"GPS5", b'l', 20, 1, &gps)].concat();
+        let device = klv(b"STRM", 0, 4, (stream.len() / 4) as u16, &stream);
+        let data = klv(b"DEVC", 0, 4, (device.len() / 4) as u16, &device);
+
+        let samples = parse_gpmf(&data);
+        assert_eq!(samples, vec![Sample { time: 0.0, speed_mps: Some(12.0), altitude_m: Some(420.5) }]);
+    }
+}
@@ -0,0 +1,328 @@
+use super::{captions, StreamInfo};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TrackKind {
+    Video,
+    #[default]
+    Audio,
+    Subtitle,
+}
+
+impl TrackKind {
+    fn specifier(self) -> &'static str {
+        match self {
+            TrackKind::Video => "v",
+            TrackKind::Audio => "a",
+            TrackKind::Subtitle => "s",
+        }
+    }
+}
+
+/// Stream-level tags for output tracks, e.g. audio track 1 titled
+/// "Director's Commentary". `None` fields are left as they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackMetadata {
+    pub kind: TrackKind,
+    /// Output track of that kind (0-based); every track of the kind when
+    /// unset, which suits batch rules like "all audio is Japanese".
+    pub track: Option<usize>,
+    /// ISO 639-2 language code.
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+/// `-metadata:s:<kind>[:N]` arguments for the given track tags.
+pub fn metadata_args(tags: &[TrackMetadata]) -> Vec<String> {
+    let mut args = Vec::new();
+    for tag in tags {
+        let specifier = match tag.track {
+            Some(track) => format!("-metadata:s:{}:{}", tag.kind.specifier(), track),
+            None => format!("-metadata:s:{}", tag.kind.specifier()),
+        };
+        for (key, value) in [("language", &tag.language), ("title", &tag.title)] {
+            if let Some(value) = value {
+                args.push(specifier.clone());
+                args.push(format!("{}={}", key, value.trim()));
+            }
+        }
+    }
+    args
+}
+
+/// Whether a stream's language tag is one of `languages` (ISO 639-2 codes
+/// as FFmpeg reports them, e.g. `eng`, `jpn`; `und` for untagged tracks).
+fn language_matches(stream: &StreamInfo, languages: &[String]) -> bool {
+    let language = stream.language.as_deref().unwrap_or("und");
+    languages.iter().any(|l| l.trim().eq_ignore_ascii_case(language))
+}
+
+/// Positions (as in `0:a:N`) of the audio tracks whose language is wanted,
+/// in source order. Falls back to the first track when nothing matches so
+/// the output never ends up silent.
+pub fn select_audio_tracks(streams: &[StreamInfo], languages: &[String]) -> Vec<usize> {
+    let selected: Vec<usize> = streams
+        .iter()
+        .enumerate()
+        .filter(|(_, stream)| language_matches(stream, languages))
+        .map(|(position, _)| position)
+        .collect();
+    if selected.is_empty() && !streams.is_empty() {
+        vec![0]
+    } else {
+        selected
+    }
+}
+
+/// Audio tracks a conversion maps: the chosen positions, else the wanted
+/// languages, else every track. Outputs that keep one track (`all` false)
+/// get the first of those.
+pub fn mapped_audio_tracks(streams: &[StreamInfo], chosen: &[usize], languages: &[String], all: bool) -> Vec<usize> {
+    let selection = if !chosen.is_empty() {
+        chosen.to_vec()
+    } else if !languages.is_empty() {
+        select_audio_tracks(streams, languages)
+    } else {
+        (0..streams.len()).collect()
+    };
+    if all {
+        selection
+    } else {
+        selection.into_iter().take(1).collect()
+    }
+}
+
+/// Check caller-chosen stream positions (`0:v:N` / `0:a:N`) against what
+/// the input actually has.
+pub fn check_stream_selection(
+    video_streams: &[StreamInfo],
+    audio_streams: &[StreamInfo],
+    video_stream: Option<usize>,
+    selected_audio: &[usize],
+) -> Result<(), String> {
+    if let Some(video) = video_stream.filter(|v| *v >= video_streams.len()) {
+        return Err(format!(
+            "Video stream {} doesn't exist; the input has {} video stream(s)",
+            video,
+            video_streams.len()
+        ));
+    }
+    if let Some(audio) = selected_audio.iter().find(|a| **a >= audio_streams.len()) {
+        return Err(format!(
+            "Audio stream {} doesn't exist; the input has {} audio stream(s)",
+            audio,
+            audio_streams.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Forced tracks only carry signs and foreign-language lines. Many releases
+/// only say so in the track title, so check that too.
+pub fn is_forced(stream: &StreamInfo) -> bool {
+    stream.forced
+        || stream.title.as_deref().is_some_and(|title| {
+            let title = title.to_lowercase();
+            title.contains("forced") || title.contains("signs")
+        })
+}
+
+/// Subtitle codec to use for a source subtitle track in `container`, or
+/// `None` when the container can't hold it (e.g. bitmap subtitles in MP4).
+pub fn subtitle_codec(container: &str, source_codec: &str) -> Option<&'static str> {
+    let is_text = matches!(source_codec, "subrip" | "srt" | "ass" | "ssa" | "mov_text" | "webvtt" | "text");
+    match container {
+        // Matroska has no teletext codec ID; those tracks go to SRT through
+        // caption extraction instead.
+        "mkv" if captions::is_teletext(source_codec) => None,
+        "mkv" => Some("copy"),
+        "mp4" | "mov" | "m4v" | "3gp" if is_text => Some("mov_text"),
+        "webm" if is_text => Some("webvtt"),
+        _ => None,
+    }
+}
+
+/// `-disposition` value for a track.
+fn disposition(default: bool, forced: bool) -> &'static str {
+    match (default, forced) {
+        (true, true) => "default+forced",
+        (true, false) => "default",
+        (false, true) => "forced",
+        (false, false) => "0",
+    }
+}
+
+/// Arguments that map the subtitle tracks `container` can hold and set their
+/// dispositions. Source flags are kept unless `default_track`/`forced_track`
+/// (positions as in `0:s:N`) pick a track explicitly.
+pub fn subtitle_args(
+    streams: &[StreamInfo],
+    container: &str,
+    default_track: Option<usize>,
+    forced_track: Option<usize>,
+) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut output_position = 0;
+    for (position, stream) in streams.iter().enumerate() {
+        let Some(codec) = subtitle_codec(container, &stream.codec) else {
+            continue;
+        };
+        let default = default_track.map_or(stream.default, |t| t == position);
+        let forced = forced_track.map_or(is_forced(stream), |t| t == position);
+        args.extend([
+            "-map".to_string(),
+            format!("0:s:{}", position),
+            format!("-c:s:{}", output_position),
+            codec.to_string(),
+            format!("-disposition:s:{}", output_position),
+            disposition(default, forced).to_string(),
+        ]);
+        output_position += 1;
+    }
+    args
+}
+
+/// Dispositions for the kept audio tracks: the source's default track stays
+/// default, or the first kept track becomes default if it was dropped.
+pub fn audio_disposition_args(streams: &[StreamInfo], kept: &[usize]) -> Vec<String> {
+    let has_default = kept.iter().any(|&p| streams.get(p).is_some_and(|s| s.default));
+    kept.iter()
+        .enumerate()
+        .flat_map(|(output_position, &position)| {
+            let default = if has_default {
+                streams.get(position).is_some_and(|s| s.default)
+            } else {
+                output_position == 0
+            };
+            [format!("-disposition:a:{}", output_position), disposition(default, false).to_string()]
+        })
+        .collect()
+}
+
+/// What to do with lossless surround tracks for players that can't
+/// decode them.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LosslessAudioMode {
+    /// Keep DTS cores (dropping the DTS-HD extension) without re-encoding.
+    /// TrueHD has no core FFmpeg can split off, so it becomes EAC-3.
+    #[default]
+    Core,
+    /// Transcode every lossless track to EAC-3 640k.
+    Eac3,
+}
+
+/// EAC-3 bitrate used for lossless tracks, the usual Blu-ray companion rate.
+const EAC3_BITRATE: &str = "640k";
+
+/// Remux arguments that copy video and subtitles and reduce DTS-HD/TrueHD
+/// audio tracks according to `mode`. Other audio tracks are copied.
+pub fn core_extraction_args(audio_streams: &[StreamInfo], mode: LosslessAudioMode) -> Vec<String> {
+    let mut args: Vec<String> = ["-map", "0:v?", "-map", "0:a?", "-map", "0:s?", "-c", "copy"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    for (position, stream) in audio_streams.iter().enumerate() {
+        match (stream.codec.as_str(), mode) {
+            ("dts", LosslessAudioMode::Core) => {
+                // A plain DTS track passes through dca_core unchanged.
+                args.push(format!("-bsf:a:{}", position));
+                args.push("dca_core".to_string());
+            }
+            ("dts" | "truehd" | "mlp", _) => {
+                args.push(format!("-c:a:{}", position));
+                args.push("eac3".to_string());
+                args.push(format!("-b:a:{}", position));
+                args.push(EAC3_BITRATE.to_string());
+            }
+            _ => {}
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(language: Option<&str>) -> StreamInfo {
+        StreamInfo {
+            index: 0,
+            codec: "aac".to_string(),
+            language: language.map(str::to_string),
+            title: None,
+            default: false,
+            forced: false,
+            closed_captions: false,
+            sample_rate: None,
+            channels: None,
+            width: None,
+            height: None,
+        }
+    }
+
+    #[test]
+    fn keeps_wanted_languages_or_falls_back_to_first() {
+        let streams = [audio(Some("ger")), audio(Some("eng")), audio(None), audio(Some("jpn"))];
+        let wanted = ["eng".to_string(), "JPN".to_string()];
+        assert_eq!(select_audio_tracks(&streams, &wanted), vec![1, 3]);
+        assert_eq!(select_audio_tracks(&streams, &["fre".to_string()]), vec![0]);
+        assert!(check_stream_selection(&[], &streams, None, &[1, 3]).is_ok());
+        assert!(check_stream_selection(&[], &streams, Some(0), &[]).is_err());
+        assert!(check_stream_selection(&[], &streams, None, &[4]).is_err());
+
+        assert_eq!(mapped_audio_tracks(&streams, &[], &wanted, true), vec![1, 3]);
+        assert_eq!(mapped_audio_tracks(&streams, &[2], &wanted, true), vec![2]);
+        assert_eq!(mapped_audio_tracks(&streams, &[], &[], true), vec![0, 1, 2, 3]);
+        assert_eq!(mapped_audio_tracks(&streams, &[], &wanted, false), vec![1]);
+    }
+
+    #[test]
+    fn tags_single_tracks_or_every_track_of_a_kind() {
+        let tags = [
+            TrackMetadata {
+                kind: TrackKind::Audio,
+                track: Some(1),
+                language: None,
+                title: Some("Director's Commentary".to_string()),
+            },
+            TrackMetadata {
+                kind: TrackKind::Subtitle,
+                track: None,
+                language: Some("eng".to_string()),
+                title: None,
+            },
+        ];
+        assert_eq!(
+            metadata_args(&tags),
+            ["-metadata:s:a:1", "title=Director's Commentary", "-metadata:s:s", "language=eng"]
+        );
+    }
+
+    #[test]
+    fn detects_forced_tracks_and_parses_dispositions() {
+        let probe = "  Stream #0:0(jpn): Video: h264 (High), yuv420p(tv, bt709, progressive), 1920x1080 [SAR 1:1 DAR 16:9], 23.98 fps (default)\n\
+                     \x20 Stream #0:1(eng): Subtitle: ass (default)\n\
+                     \x20   Metadata:\n\
+                     \x20     title           : Full Subtitles\n\
+                     \x20 Stream #0:2(eng): Subtitle: ass\n\
+                     \x20   Metadata:\n\
+                     \x20     title           : Signs & Songs\n\
+                     \x20 Stream #0:3(eng): Subtitle: hdmv_pgs_subtitle (forced)\n";
+        let info = super::super::VideoInfo::parse(probe).unwrap();
+        assert_eq!((info.width, info.height), (Some(1920), Some(1080)));
+        assert_eq!(info.subtitle_streams.len(), 3);
+        assert!(!is_forced(&info.subtitle_streams[0]) && info.subtitle_streams[0].default);
+        assert!(is_forced(&info.subtitle_streams[1]) && is_forced(&info.subtitle_streams[2]));
+
+        // The PGS track can't go into MP4, so only two tracks are mapped.
+        let args = subtitle_args(&info.subtitle_streams, "mp4", None, None);
+        assert_eq!(
+            args,
+            [
+                "-map", "0:s:0", "-c:s:0", "mov_text", "-disposition:s:0", "default",
+                "-map", "0:s:1", "-c:s:1", "mov_text", "-disposition:s:1", "forced",
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use super::{probe_info, run_prepared_job, ConversionPhase, ConversionStatus, ConversionTask, PROGRESS_PIPE};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+/// Largest duration difference (seconds) accepted after a stream copy.
+const DURATION_TOLERANCE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CopyMode {
+    /// Byte-for-byte copy, verified by comparing SHA-256 hashes.
+    #[default]
+    Raw,
+    /// Remux every stream without re-encoding (allows a container change),
+    /// verified by decoding the output and comparing durations.
+    StreamCopy,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CopyOptions {
+    pub mode: CopyMode,
+    /// Delete the source once the copy verified.
+    #[serde(alias = "moveSource")]
+    pub move_source: bool,
+    #[serde(alias = "outputTemplate")]
+    pub output_template: Option<String>,
+}
+
+/// FFmpeg arguments for a stream copy of every stream.
+pub fn stream_copy_args(input_file: &str, output_file: &str) -> Vec<String> {
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-progress", PROGRESS_PIPE, "-nostats", "-i", input_file]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.extend(["-map", "0", "-c", "copy", "-map_metadata", "0"].iter().map(|s| s.to_string()));
+    args.push(output_file.to_string());
+    args
+}
+
+pub async fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Copy `input` to `output` while hashing it; returns the source hash.
+async fn copy_hashed(
+    task_arc: &Arc<Mutex<ConversionTask>>,
+    input: &Path,
+    output: &Path,
+) -> Result<String, String> {
+    let mut source = tokio::fs::File::open(input)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", input.display(), e))?;
+    let total = source.metadata().await.map(|m| m.len()).unwrap_or(0);
+    let mut target = tokio::fs::File::create(output)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let cancel = task_arc.lock().expect("Failed to lock task mutex").cancel.clone();
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let mut copied: u64 = 0;
+    loop {
+        if cancel.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        let read = source
+            .read(&mut buffer)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        target
+            .write_all(&buffer[..read])
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        copied += read as u64;
+        if total > 0 {
+            let mut task = task_arc.lock().expect("Failed to lock task mutex");
+            task.progress.percentage = (copied as f64 / total as f64 * 100.0).min(100.0);
+        }
+    }
+    target
+        .sync_all()
+        .await
+        .map_err(|e| format!("Failed to flush {}: {}", output.display(), e))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+async fn raw_copy(task_arc: &Arc<Mutex<ConversionTask>>, input: &Path, output: &Path) -> Result<(), String> {
+    let source_hash = copy_hashed(task_arc, input, output).await?;
+    task_arc
+        .lock()
+        .expect("Failed to lock task mutex")
+        .progress
+        .enter_phase(ConversionPhase::Validating);
+    let copy_hash = sha256_file(output)
+        .await
+        .map_err(|e| format!("Failed to read back {}: {}", output.display(), e))?;
+    if copy_hash != source_hash {
+        return Err(format!(
+            "Checksum mismatch after copy (source {}, copy {})",
+            source_hash, copy_hash
+        ));
+    }
+    task_arc
+        .lock()
+        .expect("Failed to lock task mutex")
+        .progress
+        .log
+        .push(format!("SHA-256 verified: {}", copy_hash));
+    Ok(())
+}
+
+/// Stream copies can't be compared byte for byte, so check the output
+/// decodes (done by the job runner) and keeps the source duration.
+async fn verify_stream_copy(ffmpeg_path: &str, input: &str, output: &str) -> Result<(), String> {
+    let source = probe_info(ffmpeg_path, input).await.and_then(|i| i.duration);
+    let copy = probe_info(ffmpeg_path, output).await.and_then(|i| i.duration);
+    match (source, copy) {
+        (Some(source), Some(copy)) if (source - copy).abs() > DURATION_TOLERANCE => Err(format!(
+            "Duration changed during stream copy ({:.2}s -> {:.2}s)",
+            source, copy
+        )),
+        (Some(_), None) => Err("Copied file has no readable duration".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Run a copy job started with `FfmpegManager::start_copy`.
+pub async fn run_copy(task_arc: Arc<Mutex<ConversionTask>>, mode: CopyMode, move_source: bool) {
+    let (input_file, output_file, ffmpeg_path) = {
+        let mut task = task_arc.lock().expect("Failed to lock task mutex");
+        task.progress.enter_phase(ConversionPhase::Encoding);
+        (task.input_file.clone(), task.output_file.clone(), task.ffmpeg_path.clone())
+    };
+    info!("Verified copy ({:?}) of {} to {}", mode, input_file, output_file);
+
+    let result = match mode {
+        CopyMode::Raw => raw_copy(&task_arc, Path::new(&input_file), Path::new(&output_file)).await,
+        CopyMode::StreamCopy => {
+            run_prepared_job(task_arc.clone(), stream_copy_args(&input_file, &output_file)).await;
+            let completed = matches!(
+                task_arc.lock().expect("Failed to lock task mutex").progress.status,
+                ConversionStatus::Completed
+            );
+            if !completed {
+                return;
+            }
+            verify_stream_copy(&ffmpeg_path, &input_file, &output_file).await
+        }
+    };
+
+    let mut task = task_arc.lock().expect("Failed to lock task mutex");
+    if task.cancel.is_cancelled() {
+        let _ = std::fs::remove_file(&output_file);
+        task.progress.status = ConversionStatus::Cancelled;
+        return;
+    }
+    if let Err(message) = result {
+        warn!("Verified copy failed for {}: {}", input_file, message);
+        let _ = std::fs::remove_file(&output_file);
+        task.progress.status = ConversionStatus::Failed(message.clone());
+        task.progress.error_message = Some(message);
+        return;
+    }
+
+    if move_source {
+        task.progress.enter_phase(ConversionPhase::Finalizing);
+        match std::fs::remove_file(&input_file) {
+            Ok(()) => task.progress.log.push(format!("Removed source {}", input_file)),
+            Err(e) => task.progress.log.push(format!("Copy verified but the source could not be removed: {}", e)),
+        }
+    }
+    task.progress.status = ConversionStatus::Completed;
+    task.progress.percentage = 100.0;
+}
@@ -0,0 +1,77 @@
+use super::CancelToken;
+use std::time::Duration;
+
+/// Retries of the same strategy after transient failures, per job.
+pub const MAX_RETRIES: u32 = 3;
+
+/// Delay before the first retry; doubled for each further one.
+const BASE_DELAY: Duration = Duration::from_secs(2);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Lowercase stderr fragments of failures that tend to pass on their own:
+/// network shares dropping out and GPU driver resets.
+const TRANSIENT_PATTERNS: &[(&str, &str)] = &[
+    ("input/output error", "I/O error"),
+    ("connection timed out", "network timeout"),
+    ("operation timed out", "network timeout"),
+    ("connection reset by peer", "connection reset"),
+    ("resource temporarily unavailable", "resource temporarily unavailable"),
+    ("stale file handle", "stale network file handle"),
+    ("network name is no longer available", "network share unavailable"),
+    ("device_lost", "GPU device lost"),
+    ("device lost", "GPU device lost"),
+    ("device_removed", "GPU device removed"),
+    ("device_hung", "GPU device hung"),
+    ("cuda_error_launch_failed", "CUDA launch failure"),
+];
+
+/// Why the FFmpeg stderr looks like a transient failure, if it does.
+pub fn classify(stderr: &[String]) -> Option<&'static str> {
+    stderr.iter().rev().find_map(|line| {
+        let line = line.to_lowercase();
+        TRANSIENT_PATTERNS
+            .iter()
+            .find(|(pattern, _)| line.contains(pattern))
+            .map(|(_, reason)| *reason)
+    })
+}
+
+/// Wait before retry number `retry` (0-based): 2s, 4s, 8s, ... capped at 30s.
+pub fn backoff(retry: u32) -> Duration {
+    BASE_DELAY.saturating_mul(1 << retry.min(8)).min(MAX_DELAY)
+}
+
+/// Sleep for the backoff; `false` if the job was cancelled meanwhile.
+pub async fn wait(retry: u32, cancel: &CancelToken) -> bool {
+    let mut remaining = backoff(retry);
+    let step = Duration::from_millis(250);
+    while !remaining.is_zero() {
+        if cancel.is_cancelled() {
+            return false;
+        }
+        let nap = remaining.min(step);
+        tokio::time::sleep(nap).await;
+        remaining -= nap;
+    }
+    !cancel.is_cancelled()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_share_and_driver_failures() {
+        let lines = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            classify(&lines(&["frame= 100", "\\\\nas\\media\\a.mov: Input/output error"])),
+            Some("I/O error")
+        );
+        assert_eq!(classify(&lines(&["[h264_nvenc] CUDA_ERROR_LAUNCH_FAILED"])), Some("CUDA launch failure"));
+        assert_eq!(classify(&lines(&["Invalid data found when processing input"])), None);
+
+        assert_eq!(backoff(0), Duration::from_secs(2));
+        assert_eq!(backoff(2), Duration::from_secs(8));
+        assert_eq!(backoff(10), MAX_DELAY);
+    }
+}
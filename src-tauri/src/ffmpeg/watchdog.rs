@@ -0,0 +1,30 @@
+use crate::error::AppError;
+use std::process::Output;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Limit for auxiliary runs until the settings set another.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT_SECS);
+
+/// Apply the `aux_timeout_secs` setting; `None` or 0 restores the default.
+pub fn set_timeout(secs: Option<u64>) {
+    let secs = secs.filter(|s| *s > 0).unwrap_or(DEFAULT_TIMEOUT_SECS);
+    TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+}
+
+/// `cmd.output()` for probes, validation and thumbnails, which should be
+/// quick: a process still running after the limit (e.g. stuck on a corrupt
+/// file) is killed and reported as `AppError::Timeout`.
+pub async fn output(cmd: &mut Command) -> Result<Output, AppError> {
+    let secs = TIMEOUT_SECS.load(Ordering::Relaxed);
+    let program = cmd.as_std().get_program().to_string_lossy().to_string();
+    // Dropping the timed-out future drops the child, which kills it.
+    cmd.kill_on_drop(true);
+    match tokio::time::timeout(Duration::from_secs(secs), cmd.output()).await {
+        Ok(result) => result.map_err(|e| AppError::Ffmpeg(format!("Failed to run {}: {}", program, e))),
+        Err(_) => Err(AppError::Timeout(format!("{} was stopped after running for {}s", program, secs))),
+    }
+}
@@ -1,7 +1,11 @@
+use crate::ffmpeg::watchdog;
 use serde::{Deserialize, Serialize};
 use tokio::process::Command;
 use std::path::Path;
 use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 
 // Windows creation flag to hide console window
@@ -52,6 +56,22 @@ pub enum EncoderType {
     Adobe,
 }
 
+/// Outcome of encoding a second of test pattern with one encoder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncoderTestResult {
+    pub encoder: String,
+    pub works: bool,
+    /// First line of FFmpeg's error output when the encode failed.
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+/// A hung driver shouldn't stall the self-test forever.
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Self-test outcome per encoder name; encoders not tested yet are absent.
+static SELF_TEST: Mutex<BTreeMap<String, EncoderTestResult>> = Mutex::new(BTreeMap::new());
+
 pub struct GpuDetector;
 
 impl GpuDetector {
@@ -287,8 +307,10 @@ impl GpuDetector {
         let primary_adapter_id = primary.map(|a| a.id.clone());
         let gpu_type = primary.map(|a| a.gpu_type).unwrap_or(GpuType::None);
 
-        // Get available encoders by running ffmpeg -encoders
-        let available_encoders = Self::get_available_encoders(ffmpeg_path).await?;
+        // Get available encoders by running ffmpeg -encoders, minus those
+        // that are compiled in but failed the self-test.
+        let mut available_encoders = Self::get_available_encoders(ffmpeg_path).await?;
+        available_encoders.retain(|e| !self_test_failed(&e.name));
 
         Ok(GpuInfo {
             detected: !matches!(gpu_type, GpuType::None),
@@ -406,6 +428,87 @@ impl GpuDetector {
         Ok(encoders)
     }
 
+    /// Encode one second of `testsrc` with `encoder` to the null muxer and
+    /// remember whether it worked. `-encoders` lists NVENC/AMF/QSV whenever
+    /// they are compiled in, driver or not; this shows they actually run.
+    pub async fn test_encoder(ffmpeg_path: Option<&str>, encoder: &str) -> EncoderTestResult {
+        let mut cmd = Command::new(ffmpeg_path.unwrap_or("ffmpeg"));
+        cmd.args(["-hide_banner", "-v", "error", "-f", "lavfi", "-i", "testsrc=duration=1:size=640x480:rate=30"])
+            .args(["-c:v", encoder, "-f", "null", "-"])
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true);
+
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        let started = Instant::now();
+        let error = match tokio::time::timeout(SELF_TEST_TIMEOUT, cmd.output()).await {
+            Ok(Ok(output)) if output.status.success() => None,
+            Ok(Ok(output)) => Some(
+                String::from_utf8_lossy(&output.stderr)
+                    .lines()
+                    .find(|line| !line.trim().is_empty())
+                    .unwrap_or("FFmpeg exited with an error")
+                    .trim()
+                    .to_string(),
+            ),
+            Ok(Err(e)) => Some(format!("Failed to run FFmpeg: {}", e)),
+            Err(_) => Some(format!("No result within {}s", SELF_TEST_TIMEOUT.as_secs())),
+        };
+        let result = EncoderTestResult {
+            encoder: encoder.to_string(),
+            works: error.is_none(),
+            error,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        };
+        SELF_TEST
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(encoder.to_string(), result.clone());
+        result
+    }
+
+    /// Test every GPU encoder `-encoders` lists, one at a time so they don't
+    /// compete for the same hardware session.
+    pub async fn self_test(ffmpeg_path: Option<&str>) -> Result<Vec<EncoderTestResult>, Box<dyn std::error::Error>> {
+        let encoders = Self::get_available_encoders(ffmpeg_path).await?;
+        let mut results = Vec::new();
+        for encoder in encoders.iter().filter(|e| {
+            matches!(e.encoder_type, EncoderType::GpuNvidia | EncoderType::GpuAmd | EncoderType::GpuIntel)
+        }) {
+            results.push(Self::test_encoder(ffmpeg_path, &encoder.name).await);
+        }
+        Ok(results)
+    }
+
+    /// Hardware acceleration methods this FFmpeg build supports
+    /// (`ffmpeg -hwaccels`), e.g. `cuda`, `qsv`, `d3d11va`.
+    pub async fn get_available_hwaccels(ffmpeg_path: Option<&str>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let output = Self::ffmpeg_list(ffmpeg_path, "-hwaccels").await?;
+        Ok(parse_hwaccels(&output))
+    }
+
+    /// Names of the video decoders this FFmpeg build has (`ffmpeg -decoders`),
+    /// including hardware ones such as `h264_cuvid` and `hevc_qsv`.
+    pub async fn get_available_decoders(ffmpeg_path: Option<&str>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let output = Self::ffmpeg_list(ffmpeg_path, "-decoders").await?;
+        Ok(parse_decoders(&output))
+    }
+
+    async fn ffmpeg_list(ffmpeg_path: Option<&str>, flag: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::new(ffmpeg_path.unwrap_or("ffmpeg"));
+        cmd.args(["-hide_banner", flag]);
+
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        let output = watchdog::output(&mut cmd).await?;
+        if !output.status.success() {
+            return Err(format!("ffmpeg {} failed: {}", flag, String::from_utf8_lossy(&output.stderr).trim()).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
     /// Classify encoder by type based on name
     fn classify_encoder(name: &str) -> Option<EncoderType> {
         let name_lower = name.to_lowercase();
@@ -579,6 +682,30 @@ mod tests {
         assert_eq!(adapters[0].id, "gpu-0");
         assert_eq!(adapters[1].id, "gpu-1");
     }
+
+    #[test]
+    fn parses_hwaccels_and_decoders() {
+        let hwaccels = "Hardware acceleration methods:\ncuda\ndxva2\nd3d11va\n\n";
+        let hwaccels = parse_hwaccels(hwaccels);
+        assert_eq!(hwaccels, ["cuda", "dxva2", "d3d11va"]);
+
+        let decoders = "Decoders:\n V..... = Video\n ------\n V....D h264                 H.264 / AVC\n \
+                        V..... h264_cuvid           Nvidia CUVID H264 decoder (codec h264)\n A....D aac                  AAC\n";
+        assert_eq!(parse_decoders(decoders), ["h264", "h264_cuvid"]);
+
+        assert_eq!(pick_hwaccel(Some(&hwaccels), "h264_nvenc"), Some("cuda"));
+        assert_eq!(pick_hwaccel(Some(&hwaccels), "hevc_qsv"), Some("auto"));
+        assert_eq!(pick_hwaccel(Some(&[]), "h264_amf"), None);
+        assert_eq!(pick_hwaccel(None, "h264_amf"), Some("d3d11va"));
+    }
+
+    #[test]
+    fn refuses_frames_beyond_encoder_and_memory_limits() {
+        assert!(hardware_encode_refusal("h264_nvenc", 7680, 4320, None).is_some());
+        assert!(hardware_encode_refusal("hevc_nvenc", 7680, 4320, Some(8192)).is_none());
+        assert!(hardware_encode_refusal("hevc_nvenc", 7680, 4320, Some(1024)).is_some());
+        assert!(hardware_encode_refusal("libx264", 7680, 4320, None).is_none());
+    }
 }
 
 /// Get encoder display name based on encoder info
@@ -609,3 +736,164 @@ pub async fn is_encoder_available(ffmpeg_path: &str, encoder_name: &str) -> bool
         Err(_) => false,
     }
 }
+
+/// Whether `encoder` was self-tested and failed; untested encoders are
+/// given the benefit of the doubt.
+pub fn self_test_failed(encoder: &str) -> bool {
+    SELF_TEST
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(encoder)
+        .is_some_and(|result| !result.works)
+}
+
+/// Self-test results so far, by encoder name.
+pub fn self_test_results() -> Vec<EncoderTestResult> {
+    SELF_TEST.lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect()
+}
+
+/// Method names listed after the `Hardware acceleration methods:` header.
+fn parse_hwaccels(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("Hardware acceleration methods"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Video decoder names from the `-decoders` table.
+/// Format: V....D h264_cuvid           Nvidia CUVID H264 decoder (codec h264)
+fn parse_decoders(output: &str) -> Vec<String> {
+    let decoder_regex = Regex::new(r"^\s*V[A-Z\.]{5}\s+(\S+)\s").unwrap();
+    output
+        .lines()
+        .filter_map(|line| decoder_regex.captures(line))
+        .map(|captures| captures[1].to_string())
+        .filter(|name| name != "=")
+        .collect()
+}
+
+/// What an FFmpeg build can decode, looked up once per FFmpeg path.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeSupport {
+    /// `None` when `-hwaccels` couldn't be run.
+    pub hwaccels: Option<Vec<String>>,
+    /// `None` when `-decoders` couldn't be run.
+    pub decoders: Option<Vec<String>>,
+}
+
+static DECODE_SUPPORT: Mutex<Option<(String, DecodeSupport)>> = Mutex::new(None);
+
+pub async fn decode_support(ffmpeg_path: &str) -> DecodeSupport {
+    if let Some((path, support)) = DECODE_SUPPORT.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        if path == ffmpeg_path {
+            return support.clone();
+        }
+    }
+    let support = DecodeSupport {
+        hwaccels: GpuDetector::get_available_hwaccels(Some(ffmpeg_path)).await.ok(),
+        decoders: GpuDetector::get_available_decoders(Some(ffmpeg_path)).await.ok(),
+    };
+    *DECODE_SUPPORT.lock().unwrap_or_else(|e| e.into_inner()) = Some((ffmpeg_path.to_string(), support.clone()));
+    support
+}
+
+/// The hwaccel that hands frames straight to `encoder_name` without a copy
+/// through system memory.
+pub fn native_hwaccel(encoder_name: &str) -> Option<&'static str> {
+    if encoder_name.contains("nvenc") {
+        Some("cuda")
+    } else if encoder_name.contains("qsv") {
+        Some("qsv")
+    } else if encoder_name.contains("amf") {
+        Some("d3d11va")
+    } else {
+        None
+    }
+}
+
+/// `-hwaccel` to decode with ahead of `encoder_name`: its native method when
+/// the build lists it, otherwise `auto` to let FFmpeg pick any listed one.
+/// `None` when the build has no hardware decoding at all. An unknown list
+/// (`-hwaccels` failed) assumes the native method is there.
+pub fn pick_hwaccel(hwaccels: Option<&[String]>, encoder_name: &str) -> Option<&'static str> {
+    let native = native_hwaccel(encoder_name);
+    match hwaccels {
+        None => Some(native.unwrap_or("auto")),
+        Some([]) => None,
+        Some(list) => native
+            .filter(|native| list.iter().any(|h| h == native))
+            .or(Some("auto")),
+    }
+}
+
+/// Largest frame (width, height) a hardware encoder accepts, per the vendor
+/// limits of current NVENC/AMF/QSV generations. `None` for CPU encoders.
+pub fn max_encode_size(encoder_name: &str) -> Option<(u32, u32)> {
+    let is_h264 = encoder_name.starts_with("h264");
+    if encoder_name.contains("nvenc") || encoder_name.contains("qsv") {
+        Some(if is_h264 { (4096, 4096) } else { (8192, 8192) })
+    } else if encoder_name.contains("amf") {
+        Some(if is_h264 { (4096, 2304) } else { (7680, 4320) })
+    } else {
+        None
+    }
+}
+
+/// Rough VRAM an encode of `width`x`height` needs: a pool of NV12 surfaces
+/// for decode and encode plus fixed driver overhead.
+pub fn estimated_vram_mb(width: u32, height: u32) -> u64 {
+    const SURFACES: u64 = 32;
+    const OVERHEAD_MB: u64 = 256;
+    let frame_bytes = width as u64 * height as u64 * 3 / 2;
+    frame_bytes * SURFACES / (1024 * 1024) + OVERHEAD_MB
+}
+
+/// Free VRAM in MiB reported by `nvidia-smi`. Other vendors don't expose it
+/// through a CLI, so this is `None` for them.
+pub async fn free_vram_mb(gpu_index: Option<u32>) -> Option<u64> {
+    let mut cmd = Command::new("nvidia-smi");
+    cmd.args(["--query-gpu=memory.free", "--format=csv,noheader,nounits"]);
+    if let Some(index) = gpu_index {
+        cmd.arg(format!("--id={}", index));
+    }
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = watchdog::output(&mut cmd).await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .and_then(|line| line.trim().parse().ok())
+}
+
+/// Why a hardware encode of this size can't work, or `None` if it can.
+pub fn hardware_encode_refusal(
+    encoder_name: &str,
+    width: u32,
+    height: u32,
+    free_vram_mb: Option<u64>,
+) -> Option<String> {
+    if let Some((max_width, max_height)) = max_encode_size(encoder_name) {
+        if width > max_width || height > max_height {
+            return Some(format!(
+                "{}x{} exceeds the {}x{} limit of {}",
+                width, height, max_width, max_height, encoder_name
+            ));
+        }
+    }
+    let needed = estimated_vram_mb(width, height);
+    match free_vram_mb {
+        Some(free) if free < needed => Some(format!(
+            "{}x{} needs about {} MB of video memory but only {} MB is free",
+            width, height, needed, free
+        )),
+        _ => None,
+    }
+}
@@ -0,0 +1,347 @@
+use crate::events::{self, JobEvent, JobEventKind};
+use crate::error::AppError;
+use crate::ffmpeg::{FfmpegDownloader, TaskSummary};
+use crate::quarantine::csv_field;
+use crate::StartConversionArgs;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// File inside the app data directory holding the history.
+const HISTORY_FILE: &str = "history.json";
+/// The oldest records are dropped beyond this many.
+const MAX_RECORDS: usize = 5000;
+const CSV_HEADER: &str = "finished_at_ms,status,input_file,output_file,output_bytes,encoder,preset,\
+media_server_preset,screen_preset,external_id,attempts,elapsed_secs,media_duration,error,settings,tags,review,note\n";
+
+/// Verdict of whoever checked a job's output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    Approved,
+    Rejected,
+}
+
+impl ReviewStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            ReviewStatus::Approved => "approved",
+            ReviewStatus::Rejected => "rejected",
+        }
+    }
+}
+
+/// A finished, failed or cancelled job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub id: String,
+    pub task_id: String,
+    pub finished_at_ms: u64,
+    /// `completed`, `failed` or `cancelled`.
+    pub status: String,
+    pub error: Option<String>,
+    pub input_file: String,
+    pub output_file: String,
+    pub output_bytes: Option<u64>,
+    /// Encoder that produced the output, or the requested one when unknown.
+    pub encoder: Option<String>,
+    /// FFmpeg runs, including fallback retries.
+    pub attempts: u32,
+    pub elapsed_secs: f64,
+    pub media_duration: f64,
+    /// The request as submitted; `None` for jobs that can't be requeued
+    /// (slideshows, copies and other prepared jobs).
+    pub request: Option<StartConversionArgs>,
+    /// Tags the job was submitted with.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Free-form note, e.g. what a reviewer wants changed.
+    #[serde(default)]
+    pub note: Option<String>,
+    /// `None` until someone reviewed the output.
+    #[serde(default)]
+    pub review: Option<ReviewStatus>,
+    #[serde(default)]
+    pub reviewed_at_ms: Option<u64>,
+}
+
+/// Persistent record of past jobs, for re-running them and seeing which
+/// settings produced which file.
+#[derive(Debug, Default)]
+pub struct HistoryStore {
+    records: Vec<HistoryRecord>,
+    /// Requests of conversions still running, by task ID.
+    submitted: HashMap<String, StartConversionArgs>,
+    /// Where the history is persisted; `None` keeps it in memory only.
+    path: Option<PathBuf>,
+}
+
+fn history_path() -> Option<PathBuf> {
+    FfmpegDownloader::get_ffmpeg_app_dir()
+        .ok()
+        .map(|dir| dir.join(HISTORY_FILE))
+}
+
+impl HistoryStore {
+    pub fn load() -> Self {
+        let path = history_path();
+        let records = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            records,
+            submitted: HashMap::new(),
+            path,
+        }
+    }
+
+    fn save(&self) {
+        let Some(ref path) = self.path else {
+            return;
+        };
+        let result = serde_json::to_string(&self.records)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(path, content).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Failed to save history {:?}: {}", path, e);
+        }
+    }
+
+    /// Remember the request a conversion task was started with.
+    pub fn track(&mut self, task_id: String, request: StartConversionArgs) {
+        self.submitted.insert(task_id, request);
+    }
+
+    /// Record a `job-finished` event; other events are ignored.
+    pub fn record(&mut self, event: &JobEvent, task: Option<TaskSummary>) {
+        let (JobEventKind::JobFinished { status, error, stats }, Some(task_id)) = (&event.kind, &event.task_id) else {
+            return;
+        };
+        let request = self.submitted.remove(task_id);
+        let input_file = task
+            .as_ref()
+            .map(|t| t.input_file.clone())
+            .or_else(|| request.as_ref().map(|r| r.input_file.clone()))
+            .unwrap_or_default();
+        let encoder = task
+            .and_then(|t| t.encoder)
+            .or_else(|| request.as_ref().map(|r| r.encoder.clone()));
+        let tags = request.as_ref().map(|r| r.options.tags.clone()).unwrap_or_default();
+        self.records.push(HistoryRecord {
+            id: Uuid::new_v4().to_string(),
+            task_id: task_id.clone(),
+            finished_at_ms: event.timestamp_ms,
+            status: status.to_string(),
+            error: error.clone(),
+            input_file,
+            output_file: stats.output_file.clone(),
+            output_bytes: stats.output_bytes,
+            encoder,
+            attempts: stats.attempts,
+            elapsed_secs: stats.elapsed_secs,
+            media_duration: stats.media_duration,
+            request,
+            tags,
+            note: None,
+            review: None,
+            reviewed_at_ms: None,
+        });
+        let excess = self.records.len().saturating_sub(MAX_RECORDS);
+        self.records.drain(..excess);
+        self.save();
+    }
+
+    /// Newest first.
+    pub fn records(&self) -> Vec<HistoryRecord> {
+        self.records.iter().rev().cloned().collect()
+    }
+
+    /// Records carrying `tag`, newest first.
+    pub fn tagged(&self, tag: &str) -> Vec<HistoryRecord> {
+        self.records.iter().rev().filter(|r| has_tag(&r.tags, tag)).cloned().collect()
+    }
+
+    /// Every tag used in the history.
+    pub fn tags(&self) -> impl Iterator<Item = &String> {
+        self.records.iter().flat_map(|r| &r.tags)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&HistoryRecord> {
+        self.records.iter().find(|record| record.id == id)
+    }
+
+    /// Attach `note` to a record; an empty note removes it.
+    pub fn set_note(&mut self, id: &str, note: Option<String>) -> Result<HistoryRecord, AppError> {
+        let record = self.get_mut(id)?;
+        record.note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        let record = record.clone();
+        self.save();
+        Ok(record)
+    }
+
+    /// Mark a record approved or rejected; `None` resets it to unreviewed.
+    pub fn set_review(&mut self, id: &str, review: Option<ReviewStatus>) -> Result<HistoryRecord, AppError> {
+        let record = self.get_mut(id)?;
+        record.review = review;
+        record.reviewed_at_ms = review.map(|_| events::now_ms());
+        let record = record.clone();
+        self.save();
+        Ok(record)
+    }
+
+    fn get_mut(&mut self, id: &str) -> Result<&mut HistoryRecord, AppError> {
+        self.records
+            .iter_mut()
+            .find(|record| record.id == id)
+            .ok_or_else(|| AppError::Internal("History record not found".to_string()))
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.save();
+    }
+
+    /// Write the records (only those carrying `tag`, if given) to `path` as
+    /// `csv` or `json`, oldest first.
+    pub fn export(&self, path: &str, format: &str, tag: Option<&str>) -> Result<usize, AppError> {
+        let records: Vec<HistoryRecord> = self
+            .records
+            .iter()
+            .filter(|r| tag.is_none_or(|tag| has_tag(&r.tags, tag)))
+            .cloned()
+            .collect();
+        let content = match format {
+            "csv" => to_csv(&records),
+            "json" => serde_json::to_string_pretty(&records).map_err(|e| AppError::Internal(e.to_string()))?,
+            other => return Err(AppError::Internal(format!("Unsupported history export format: {}", other))),
+        };
+        std::fs::write(path, content)?;
+        Ok(records.len())
+    }
+}
+
+/// Tags match ignoring case and surrounding spaces.
+pub fn has_tag(tags: &[String], tag: &str) -> bool {
+    tags.iter().any(|t| t.trim().eq_ignore_ascii_case(tag.trim()))
+}
+
+/// One line per record; `settings` holds the submitted request as JSON
+/// for auditing the exact options used.
+pub fn to_csv(records: &[HistoryRecord]) -> String {
+    let mut out = String::from(CSV_HEADER);
+    for record in records {
+        let request = record.request.as_ref();
+        let options = request.map(|r| &r.options);
+        let settings = request.and_then(|r| serde_json::to_string(r).ok()).unwrap_or_default();
+        let fields = [
+            record.finished_at_ms.to_string(),
+            record.status.clone(),
+            csv_field(&record.input_file),
+            csv_field(&record.output_file),
+            record.output_bytes.map(|b| b.to_string()).unwrap_or_default(),
+            csv_field(record.encoder.as_deref().unwrap_or("")),
+            csv_field(request.map_or("", |r| r.preset.as_str())),
+            csv_field(options.and_then(|o| o.media_server_preset.as_deref()).unwrap_or("")),
+            csv_field(options.and_then(|o| o.screen_preset.as_deref()).unwrap_or("")),
+            csv_field(options.and_then(|o| o.external_id.as_deref()).unwrap_or("")),
+            record.attempts.to_string(),
+            format!("{:.1}", record.elapsed_secs),
+            format!("{:.3}", record.media_duration),
+            csv_field(record.error.as_deref().unwrap_or("")),
+            csv_field(&settings),
+            csv_field(&record.tags.join(";")),
+            record.review.map_or("", ReviewStatus::as_str).to_string(),
+            csv_field(record.note.as_deref().unwrap_or("")),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{JobStats, SCHEMA_VERSION};
+    use crate::ffmpeg::ConversionOptions;
+
+    fn event(kind: JobEventKind) -> JobEvent {
+        JobEvent {
+            schema_version: SCHEMA_VERSION,
+            timestamp_ms: 1_700_000_000_000,
+            task_id: Some("t1".to_string()),
+            queue_job_id: None,
+            external_id: None,
+            kind,
+        }
+    }
+
+    #[test]
+    fn records_finished_jobs_with_their_request() {
+        let mut history = HistoryStore::default();
+        history.track(
+            "t1".to_string(),
+            StartConversionArgs {
+                input_file: "in.mov".to_string(),
+                output_file: "out.mp4".to_string(),
+                encoder: "h264_nvenc".to_string(),
+                gpu_index: None,
+                cpu_threads: None,
+                preset: "fast".to_string(),
+                is_adobe_preset: None,
+                options: ConversionOptions {
+                    tags: vec!["clientA".to_string()],
+                    ..Default::default()
+                },
+            },
+        );
+        history.record(&event(JobEventKind::JobStarted), None);
+        assert!(history.records().is_empty());
+
+        let finished = JobEventKind::JobFinished {
+            status: "completed",
+            error: None,
+            stats: JobStats {
+                elapsed_secs: 12.0,
+                media_duration: 60.0,
+                attempts: 4,
+                output_file: "out.mp4".to_string(),
+                output_bytes: Some(1024),
+            },
+        };
+        let summary = TaskSummary {
+            input_file: "in.mov".to_string(),
+            encoder: Some("libx264".to_string()),
+        };
+        history.record(&event(finished), Some(summary));
+        let records = history.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].encoder.as_deref(), Some("libx264"));
+        assert_eq!(records[0].request.as_ref().map(|r| r.encoder.as_str()), Some("h264_nvenc"));
+        assert!(history.submitted.is_empty());
+        assert_eq!(history.tagged(" CLIENTA").len(), 1);
+        assert!(history.tagged("archive").is_empty());
+
+        let csv = to_csv(&history.records);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("1700000000000,completed,in.mov,out.mp4,1024,libx264,fast,,,,4,12.0,60.000,,\"{"));
+        assert!(row.contains("\"\"encoder\"\":\"\"h264_nvenc\"\""));
+        assert!(row.ends_with(",clientA,,"));
+
+        let id = records[0].id.clone();
+        history.set_note(&id, Some("Audio drifts after 10:00, please redo".to_string())).unwrap();
+        let record = history.set_review(&id, Some(ReviewStatus::Rejected)).unwrap();
+        assert!(record.reviewed_at_ms.is_some());
+        assert!(to_csv(&history.records).ends_with(",clientA,rejected,\"Audio drifts after 10:00, please redo\"\n"));
+        assert!(history.set_review("missing", None).is_err());
+    }
+}
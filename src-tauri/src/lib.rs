@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::sync::{Arc, Mutex};
 use std::path::{PathBuf, Path};
 use tauri::{State, Manager, Emitter};
@@ -6,16 +6,62 @@ use tokio::process::Command;
 use regex::Regex;
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
-use log::{info, error};
+use log::{info, warn, error};
 
 mod ffmpeg;
 mod gpu;
 mod logger;
 mod error;
-
-use ffmpeg::{FfmpegManager, ConversionProgress, FfmpegDownloader, FfmpegLocator, AdobePreset, get_adobe_presets, VIDEO_FORMATS, AUDIO_FORMATS, get_format_info};
+mod events;
+mod settings;
+mod naming;
+mod metrics;
+mod stats;
+mod sidecars;
+mod quarantine;
+mod presets;
+mod queue;
+mod history;
+mod storage;
+mod watch;
+mod recommend;
+mod droplets;
+
+use ffmpeg::availability::{self, Annotated, Capabilities};
+use ffmpeg::audio_extract::{self, AudioExtractOptions};
+use ffmpeg::concat::{self, ConcatJob, ConcatMode, ConcatOptions};
+use ffmpeg::decoders;
+use ffmpeg::disc::{self, DiscInfo};
+use ffmpeg::segments::{self, SegmentGroup};
+use ffmpeg::metadata::MetadataFields;
+use ffmpeg::multi_output::OutputSpec;
+use ffmpeg::mux::MuxOptions;
+use ffmpeg::probe::MediaInfo;
+use ffmpeg::slideshow::{self, SlideshowOptions};
+use ffmpeg::streaming::{PackageJob, StreamingOptions};
+use ffmpeg::tracks::LosslessAudioMode;
+use ffmpeg::transfer::{CopyMode, CopyOptions};
+use ffmpeg::watchdog;
+use ffmpeg::{FfmpegManager, KeyClaim, ConversionProgress, ConversionOptions, ConversionRequest, FfmpegDownloader, FfmpegLocator, AdobePreset, ClipPreset, MediaServerPreset, ScreenRecordingPreset, get_adobe_presets, get_clip_presets, get_media_server_presets, get_screen_recording_presets, VIDEO_FORMATS, AUDIO_FORMATS, get_format_info};
 use gpu::{GpuDetector, EncoderInfo, GpuInfo};
 use error::AppError;
+use settings::AppSettings;
+use naming::EpisodeInfo;
+use presets::CustomPreset;
+use droplets::{LauncherKind, QuickConvert};
+use queue::{QueueManager, QueuedJob};
+use history::{HistoryRecord, HistoryStore, ReviewStatus};
+
+/// Speed preset for jobs that send none and have no encoder default.
+const DEFAULT_PRESET: &str = "fast";
+/// How often the job queue checks for free slots.
+const QUEUE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often task progress is checked for job events.
+const JOB_EVENT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// How often a submission re-checks an idempotency key another one holds.
+const IDEMPOTENCY_WAIT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+/// How long a submission waits for another one holding its idempotency key.
+const IDEMPOTENCY_WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
 
 // Windows creation flag to hide console window
 #[cfg(target_os = "windows")]
@@ -25,6 +71,9 @@ const CREATE_NO_WINDOW: u32 = 0x08000000;
 pub struct AppState {
     ffmpeg_manager: Arc<Mutex<FfmpegManager>>,
     ffmpeg_path: Arc<Mutex<Option<std::path::PathBuf>>>,
+    settings: Arc<Mutex<AppSettings>>,
+    queue: Arc<Mutex<QueueManager>>,
+    history: Arc<Mutex<HistoryStore>>,
 }
 
 impl AppState {
@@ -32,6 +81,9 @@ impl AppState {
         Self {
             ffmpeg_manager: Arc::new(Mutex::new(FfmpegManager::new())),
             ffmpeg_path: Arc::new(Mutex::new(None)),
+            settings: Arc::new(Mutex::new(settings::load())),
+            queue: Arc::new(Mutex::new(QueueManager::load())),
+            history: Arc::new(Mutex::new(HistoryStore::load())),
         }
     }
 }
@@ -45,6 +97,32 @@ pub struct FfmpegStatus {
     pub source: Option<String>, // bundled, path, common, winget, downloaded
 }
 
+/// What `start_conversion` scheduled.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobHandle {
+    pub task_id: String,
+    /// Output path after templates, safe mode and in-place staging were applied.
+    pub output_file: String,
+    /// `chunked`, `hardware` or `software`.
+    pub pipeline: String,
+    /// Expected output length in seconds, if the input could be probed.
+    pub estimated_duration: Option<f64>,
+    /// Other jobs pending or running when this one was scheduled.
+    pub queue_position: usize,
+}
+
+impl JobHandle {
+    async fn new(task_id: String, request: &ConversionRequest, queue_position: usize) -> Self {
+        Self {
+            task_id,
+            output_file: request.final_output().to_string(),
+            pipeline: request.pipeline().to_string(),
+            estimated_duration: request.estimated_duration().await,
+            queue_position,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadProgress {
     pub downloaded: u64,
@@ -64,7 +142,7 @@ pub struct CpuInfo {
     pub logical_cores: usize,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct StartConversionArgs {
     #[serde(alias = "inputFile")]
     input_file: String,
@@ -75,9 +153,13 @@ struct StartConversionArgs {
     gpu_index: Option<u32>,
     #[serde(alias = "cpuThreads")]
     cpu_threads: Option<u32>,
+    /// Empty when the caller leaves it to the encoder's default preset.
+    #[serde(default)]
     preset: String,
     #[serde(alias = "isAdobePreset")]
     is_adobe_preset: Option<bool>,
+    #[serde(flatten)]
+    options: ConversionOptions,
 }
 
 #[tauri::command]
@@ -304,8 +386,20 @@ async fn download_ffmpeg(app_handle: tauri::AppHandle, state: State<'_, AppState
             let _: Result<(), _> = win.emit("ffmpeg-download-progress", progress);
         }
     };
+    let retry_window = app_handle.get_webview_window("main");
+    let retry_callback = move |retry: &ffmpeg::DownloadRetry| {
+        if let Some(ref win) = retry_window {
+            let _: Result<(), _> = win.emit("ffmpeg-download-retry", retry);
+        }
+    };
 
-    let ffmpeg_path = FfmpegDownloader::download_and_extract_ffmpeg(progress_callback).await?;
+    let custom_url = state
+        .settings
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .ffmpeg_download_url
+        .clone();
+    let ffmpeg_path = FfmpegDownloader::download_and_extract_ffmpeg(custom_url, progress_callback, retry_callback).await?;
     
     // Update state with the new path
     let mut state_path = state.ffmpeg_path.lock().map_err(|e| AppError::Internal(e.to_string()))?;
@@ -314,6 +408,66 @@ async fn download_ffmpeg(app_handle: tauri::AppHandle, state: State<'_, AppState
     Ok(ffmpeg_path.to_string_lossy().to_string())
 }
 
+// Command: Install FFmpeg from a local zip/tar archive (offline setup)
+#[tauri::command]
+async fn install_ffmpeg_from_file(state: State<'_, AppState>, path: String) -> Result<String, AppError> {
+    let ffmpeg_path = FfmpegDownloader::install_from_archive(Path::new(&path)).await?;
+
+    let mut state_path = state.ffmpeg_path.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    *state_path = Some(ffmpeg_path.clone());
+
+    Ok(ffmpeg_path.to_string_lossy().to_string())
+}
+
+// Command: Register a named FFmpeg installation that presets can pin
+#[tauri::command]
+async fn register_ffmpeg_installation(
+    state: State<'_, AppState>,
+    name: String,
+    path: String,
+) -> Result<String, AppError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::Internal("Installation name must not be empty".to_string()));
+    }
+    let version = FfmpegLocator::get_version(Path::new(&path))
+        .await
+        .ok_or_else(|| AppError::Ffmpeg(format!("Not a working FFmpeg binary: {}", path)))?;
+
+    let mut settings = state.settings.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut updated = settings.clone();
+    updated.ffmpeg_installations.insert(name, path);
+    settings::save(&updated)?;
+    *settings = updated;
+    Ok(version)
+}
+
+// Command: Set (or clear, with no defaults) the tuned defaults for an encoder
+#[tauri::command]
+async fn set_encoder_defaults(
+    state: State<'_, AppState>,
+    encoder: String,
+    defaults: Option<settings::EncoderDefaults>,
+) -> Result<(), AppError> {
+    let encoder = encoder.trim().to_string();
+    if encoder.is_empty() {
+        return Err(AppError::Internal("Encoder name must not be empty".to_string()));
+    }
+    if let Some(rate_control) = defaults.as_ref().and_then(|d| d.rate_control.as_ref()) {
+        rate_control.validate(&encoder).map_err(AppError::Ffmpeg)?;
+    }
+
+    let mut settings = state.settings.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut updated = settings.clone();
+    match defaults {
+        Some(defaults) => updated.encoder_defaults.insert(encoder, defaults),
+        None => updated.encoder_defaults.remove(&encoder),
+    };
+    settings::save(&updated)?;
+    *settings = updated;
+    Ok(())
+}
+
 // Get the FFmpeg path from state or auto-detect
 async fn get_ffmpeg_path(state: &AppState) -> Result<PathBuf, AppError> {
     // First check if we have a stored path
@@ -349,6 +503,45 @@ async fn get_gpu_info(state: State<'_, AppState>) -> Result<GpuInfo, AppError> {
     })
 }
 
+// Command: Run the encoder self-test for one encoder, or every GPU encoder
+#[tauri::command]
+async fn test_encoder(state: State<'_, AppState>, encoder: Option<String>) -> Result<Vec<gpu::EncoderTestResult>, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    match encoder {
+        Some(encoder) => Ok(vec![GpuDetector::test_encoder(Some(&ffmpeg_path), &encoder).await]),
+        None => GpuDetector::self_test(Some(&ffmpeg_path))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string())),
+    }
+}
+
+// Command: Recommend a default encoder, preset and concurrency for this machine
+#[tauri::command]
+async fn get_recommended_defaults(state: State<'_, AppState>) -> Result<recommend::RecommendedDefaults, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    let gpu_info = GpuDetector::detect_with_ffmpeg(Some(&ffmpeg_path))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let encoders: Vec<String> = gpu_info.available_encoders.into_iter().map(|e| e.name).collect();
+    let self_test = gpu::self_test_results();
+    let storage = storage::last_benchmark();
+    let defaults = recommend::recommend(&recommend::MachineFacts {
+        logical_cores: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(0),
+        gpu_type: gpu_info.gpu_type,
+        encoders: &encoders,
+        self_test: &self_test,
+        storage: storage.as_ref(),
+    });
+    info!(
+        "Recommended defaults: {} {}, {} job(s) ({})",
+        defaults.encoder,
+        defaults.preset,
+        defaults.max_concurrent_jobs,
+        defaults.reasons.join("; ")
+    );
+    Ok(defaults)
+}
+
 // Command: Get available encoders from ffmpeg
 #[tauri::command]
 async fn get_available_encoders(state: State<'_, AppState>) -> Result<Vec<EncoderInfo>, AppError> {
@@ -361,11 +554,7 @@ async fn get_available_encoders(state: State<'_, AppState>) -> Result<Vec<Encode
 #[tauri::command]
 async fn get_ffmpeg_version(state: State<'_, AppState>) -> Result<String, AppError> {
     let ffmpeg_path = get_ffmpeg_path(&state).await?;
-    let output = Command::new(&ffmpeg_path)
-        .args(&["-version"])
-        .output()
-        .await
-        .map_err(|e| AppError::Ffmpeg(format!("Failed to run FFmpeg: {}", e)))?;
+    let output = watchdog::output(Command::new(&ffmpeg_path).args(&["-version"])).await?;
     
     if !output.status.success() {
         return Err(AppError::Ffmpeg("FFmpeg returned error".to_string()));
@@ -375,6 +564,46 @@ async fn get_ffmpeg_version(state: State<'_, AppState>) -> Result<String, AppErr
     Ok(version.lines().next().unwrap_or("Unknown version").to_string())
 }
 
+// Command: Encode several outputs (e.g. MP4 delivery, ProRes master, MP3)
+// from one decode of the input. The first output is the job's main output.
+#[tauri::command]
+async fn start_multi_conversion(
+    state: State<'_, AppState>,
+    input_file: String,
+    outputs: Vec<OutputSpec>,
+    gpu_index: Option<u32>,
+    cpu_threads: Option<u32>,
+    options: Option<ConversionOptions>,
+) -> Result<JobHandle, AppError> {
+    let mut outputs = outputs.into_iter();
+    let main = outputs
+        .next()
+        .ok_or_else(|| AppError::Internal("At least one output is needed".to_string()))?;
+    let mut options = options.unwrap_or_default();
+    if main.rate_control.is_some() {
+        options.rate_control = main.rate_control.clone();
+    }
+    options.audio_bitrate = main.audio_bitrate.or(options.audio_bitrate);
+    if let Some(ref codec) = main.audio_codec {
+        options.extra_output_args.extend(["-c:a".to_string(), codec.clone()]);
+    }
+    options.extra_output_args.extend(main.extra_args.iter().cloned());
+    options.extra_outputs.extend(outputs);
+    info!("Multi-output job: {} plus {} more output(s)", main.output_file, options.extra_outputs.len());
+
+    let resolved = StartConversionArgs {
+        input_file,
+        encoder: main.video_encoder().unwrap_or_else(|| "libx264".to_string()),
+        output_file: main.output_file,
+        gpu_index,
+        cpu_threads,
+        preset: main.preset.unwrap_or_default(),
+        is_adobe_preset: None,
+        options,
+    };
+    begin_conversion(&state, Uuid::new_v4().to_string(), resolved).await
+}
+
 // Command: Start conversion
 #[tauri::command]
 async fn start_conversion(
@@ -387,7 +616,7 @@ async fn start_conversion(
     is_adobe_preset: Option<bool>,
     args: Option<StartConversionArgs>,
     payload: Option<StartConversionArgs>,
-) -> Result<String, AppError> {
+) -> Result<JobHandle, AppError> {
     let task_id = Uuid::new_v4().to_string();
     let resolved = if let Some(args) = args {
         args
@@ -400,221 +629,2012 @@ async fn start_conversion(
             encoder: encoder.unwrap_or_else(|| "libx264".to_string()),
             gpu_index,
             cpu_threads: None,
-            preset: preset.unwrap_or_else(|| "fast".to_string()),
+            preset: preset.unwrap_or_default(),
             is_adobe_preset,
+            options: ConversionOptions::default(),
         }
     };
-    let StartConversionArgs {
-        input_file,
-        output_file,
-        encoder,
-        gpu_index,
-        cpu_threads,
-        preset,
-        is_adobe_preset,
-    } = resolved;
+    begin_conversion(&state, task_id, resolved).await
+}
 
-    if !std::path::Path::new(&input_file).exists() {
-        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+// Command: Add a conversion to the persistent job queue
+#[tauri::command]
+async fn enqueue_conversion(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    args: StartConversionArgs,
+) -> Result<String, AppError> {
+    let external_id = args.options.external_id.clone();
+    let mut queue = state.queue.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    if let Some(key) = args.options.idempotency_key.as_deref().filter(|k| !k.is_empty()) {
+        if let Some(existing) = queue.job_for_idempotency_key(key) {
+            info!("Idempotency key {} already queued as job {}", key, existing);
+            return Ok(existing);
+        }
     }
+    let job_id = queue.enqueue(args);
+    info!("Queued job {}", job_id);
+    let _ = app_handle.emit(events::JOB_EVENT, events::JobEvent::queued(job_id.clone(), external_id));
+    Ok(job_id)
+}
 
-    let output_ext = Path::new(&output_file)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-    let format_info = get_format_info(&output_ext);
+// Command: List queued, running and finished queue jobs
+#[tauri::command]
+async fn get_queue(state: State<'_, AppState>, tag: Option<String>) -> Result<Vec<QueuedJob>, AppError> {
+    let queue = state.queue.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(match tag {
+        Some(tag) => queue.tagged(&tag),
+        None => queue.jobs(),
+    })
+}
 
-    if let Some(parent) = std::path::Path::new(&output_file).parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+// Command: Remove a job from the queue, cancelling it if it is running
+#[tauri::command]
+async fn remove_queued_job(state: State<'_, AppState>, job_id: String) -> Result<(), AppError> {
+    let job = state
+        .queue
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .remove(&job_id)
+        .ok_or_else(|| AppError::Internal("Queued job not found".to_string()))?;
+    if let Some(task_id) = job.task_id {
+        let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+        manager.cancel_conversion(&task_id)?;
     }
+    Ok(())
+}
 
-    // Get FFmpeg path automatically
-    let ffmpeg_path = get_ffmpeg_path(&state).await?;
-    let ffmpeg_path_str = ffmpeg_path.to_string_lossy().to_string();
-
-    if !format_info.supports_video && format_info.supports_audio {
-        let mut cmd = Command::new(&ffmpeg_path);
-        cmd.args(&["-hide_banner", "-i", &input_file]);
-        #[cfg(target_os = "windows")]
-        cmd.creation_flags(CREATE_NO_WINDOW);
-
-        let output = cmd.output()
-            .await
-            .map_err(|e| AppError::Ffmpeg(format!("Failed to probe input: {}", e)))?;
+// Command: Past jobs with their settings and outcome, newest first
+#[tauri::command]
+async fn get_history(state: State<'_, AppState>, tag: Option<String>) -> Result<Vec<HistoryRecord>, AppError> {
+    let history = state.history.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(match tag {
+        Some(tag) => history.tagged(&tag),
+        None => history.records(),
+    })
+}
 
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let info = ffmpeg::VideoInfo::parse(&stderr)?;
-        if info.audio_streams.is_empty() {
-            return Err(AppError::Ffmpeg("Input has no audio stream; cannot create audio-only output.".to_string()));
+// Command: List the tags used in the queue and history
+#[tauri::command]
+async fn list_tags(state: State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    let queue = state.queue.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let history = state.history.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    // Case-insensitive, keeping the first spelling seen.
+    let mut tags: BTreeMap<String, String> = BTreeMap::new();
+    for tag in queue.tags().chain(history.tags()) {
+        let tag = tag.trim();
+        if !tag.is_empty() {
+            tags.entry(tag.to_lowercase()).or_insert_with(|| tag.to_string());
         }
     }
-    
-    let manager = state.ffmpeg_manager.clone();
-    let mut manager = manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
-    
-    manager.start_conversion(
-        task_id.clone(),
-        input_file,
-        output_file,
-        ffmpeg_path_str,
-        encoder,
-        gpu_index,
-        cpu_threads,
-        preset,
-        is_adobe_preset.unwrap_or(false),
-    )?;
-    
-    Ok(task_id)
+    Ok(tags.into_values().collect())
 }
 
-// Command: Get conversion progress
+// Command: Attach a note to a history record (empty to remove it)
 #[tauri::command]
-async fn get_conversion_progress(
+async fn set_history_note(
     state: State<'_, AppState>,
-    task_id: String,
-) -> Result<Option<ConversionProgress>, AppError> {
-    let manager = state.ffmpeg_manager.clone();
-    let manager = manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
-    
-    Ok(manager.get_progress(&task_id))
+    record_id: String,
+    note: Option<String>,
+) -> Result<HistoryRecord, AppError> {
+    let mut history = state.history.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    history.set_note(&record_id, note)
 }
 
-// Command: Cancel conversion
+// Command: Approve or reject a history record's output (none to reset)
 #[tauri::command]
-async fn cancel_conversion(
+async fn set_history_review(
     state: State<'_, AppState>,
-    task_id: String,
-) -> Result<(), AppError> {
-    let manager = state.ffmpeg_manager.clone();
-    let mut manager = manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
-    
-    manager.cancel_conversion(&task_id)
+    record_id: String,
+    status: Option<ReviewStatus>,
+) -> Result<HistoryRecord, AppError> {
+    let mut history = state.history.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let record = history.set_review(&record_id, status)?;
+    info!("History record {} review: {:?}", record_id, status);
+    Ok(record)
 }
 
-// Command: Get video duration
+// Command: Delete all history records
 #[tauri::command]
-async fn get_video_duration(state: State<'_, AppState>, input_file: String) -> Result<f64, AppError> {
-    let ffmpeg_path = get_ffmpeg_path(&state).await?;
-    let output = Command::new(&ffmpeg_path)
-        .args(&["-i", &input_file])
-        .output()
-        .await
-        .map_err(|e| AppError::Ffmpeg(format!("Failed to probe video: {}", e)))?;
-    
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    
-    // Parse duration from FFmpeg output
-    let duration_regex = Regex::new(r"Duration: (\d+):(\d+):(\d+\.\d+)").map_err(|e| AppError::Internal(e.to_string()))?;
-    
-    if let Some(captures) = duration_regex.captures(&stderr) {
-        let hours: f64 = captures[1].parse().unwrap_or(0.0);
-        let minutes: f64 = captures[2].parse().unwrap_or(0.0);
-        let seconds: f64 = captures[3].parse().unwrap_or(0.0);
-        
-        let total_seconds = hours * 3600.0 + minutes * 60.0 + seconds;
-        return Ok(total_seconds);
-    }
-    
-    Err(AppError::Ffmpeg("Could not determine video duration".to_string()))
+async fn clear_history(state: State<'_, AppState>) -> Result<(), AppError> {
+    let mut history = state.history.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    history.clear();
+    Ok(())
 }
 
-// Command: Get video streams info
+// Command: Export the history (optionally one tag) as CSV or JSON for billing and audits
 #[tauri::command]
-async fn get_video_info(state: State<'_, AppState>, input_file: String) -> Result<ffmpeg::VideoInfo, AppError> {
-    let ffmpeg_path = get_ffmpeg_path(&state).await?;
-    let output = Command::new(&ffmpeg_path)
-        .args(&["-hide_banner", "-i", &input_file])
-        .output()
-        .await
-        .map_err(|e| AppError::Ffmpeg(format!("Failed to probe video: {}", e)))?;
-    
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    
-    let info = ffmpeg::VideoInfo::parse(&stderr)?;
-    Ok(info)
+async fn export_history(
+    state: State<'_, AppState>,
+    path: String,
+    format: Option<String>,
+    tag: Option<String>,
+) -> Result<usize, AppError> {
+    // Without an explicit format the file extension decides, defaulting to CSV.
+    let format = format.map(|f| f.to_lowercase()).unwrap_or_else(|| {
+        let is_json = Path::new(&path)
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+        if is_json { "json" } else { "csv" }.to_string()
+    });
+    let history = state.history.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let count = history.export(&path, &format, tag.as_deref())?;
+    info!("Exported {} history records to {}", count, path);
+    Ok(count)
 }
 
-// Command: Get supported formats
+// Command: Queue a past job again with the settings it was submitted with
 #[tauri::command]
-async fn get_supported_formats() -> Result<SupportedFormats, AppError> {
-    Ok(SupportedFormats {
-        video: VIDEO_FORMATS.iter().map(|s| s.to_string()).collect(),
-        audio: AUDIO_FORMATS.iter().map(|s| s.to_string()).collect(),
-    })
+async fn requeue_from_history(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    record_id: String,
+) -> Result<String, AppError> {
+    let mut request = {
+        let history = state.history.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+        let record = history
+            .get(&record_id)
+            .ok_or_else(|| AppError::Internal("History record not found".to_string()))?;
+        record
+            .request
+            .clone()
+            .ok_or_else(|| AppError::Internal("This job can't be run again from the history".to_string()))?
+    };
+    // The key would resolve to the original task instead of a new run.
+    request.options.idempotency_key = None;
+    let external_id = request.options.external_id.clone();
+    let mut queue = state.queue.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let job_id = queue.enqueue(request);
+    info!("Requeued history record {} as job {}", record_id, job_id);
+    let _ = app_handle.emit(events::JOB_EVENT, events::JobEvent::queued(job_id.clone(), external_id));
+    Ok(job_id)
 }
 
-// Command: Get Adobe/After Effects presets
+// Command: Drop finished jobs from the queue
 #[tauri::command]
-async fn get_adobe_presets_list() -> Result<Vec<AdobePreset>, AppError> {
-    Ok(get_adobe_presets())
+async fn clear_finished_jobs(state: State<'_, AppState>) -> Result<(), AppError> {
+    let mut queue = state.queue.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    queue.clear_finished();
+    Ok(())
 }
 
-// Command: Get format info
-#[tauri::command]
-async fn get_format_information(extension: String) -> Result<serde_json::Value, AppError> {
-    let info = get_format_info(&extension);
-    
-    Ok(serde_json::json!({
-        "container": info.container,
-        "default_video_codec": info.default_video_codec,
-        "default_audio_codec": info.default_audio_codec,
-        "supports_video": info.supports_video,
-        "supports_audio": info.supports_audio,
-    }))
+/// Emit `job-event`s for every task's lifecycle changes.
+fn spawn_job_event_pump(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(JOB_EVENT_INTERVAL);
+        let mut tracker = events::JobEventTracker::default();
+        loop {
+            interval.tick().await;
+            let state = app_handle.state::<AppState>();
+            let task_jobs = state.queue.lock().map(|queue| queue.task_jobs()).unwrap_or_default();
+            let Ok(snapshots) = state.ffmpeg_manager.lock().map(|manager| manager.snapshots()) else {
+                continue;
+            };
+            let jobs = snapshots
+                .into_iter()
+                .map(|(progress, output_file)| {
+                    let queue_job_id = task_jobs.get(&progress.task_id).cloned();
+                    (progress, output_file, queue_job_id)
+                })
+                .collect();
+            for event in tracker.update(jobs) {
+                if matches!(event.kind, events::JobEventKind::JobFinished { .. }) {
+                    let summary = event.task_id.as_deref().and_then(|id| {
+                        state.ffmpeg_manager.lock().ok().and_then(|manager| manager.task_summary(id))
+                    });
+                    if let Ok(mut history) = state.history.lock() {
+                        history.record(&event, summary);
+                    }
+                }
+                let _ = app_handle.emit(events::JOB_EVENT, event);
+            }
+        }
+    });
 }
 
-// Command: Check if encoder is available
-#[tauri::command]
-async fn check_encoder_available(state: State<'_, AppState>, encoder: String) -> Result<bool, AppError> {
-    let ffmpeg_path = get_ffmpeg_path(&state).await?;
-    Ok(gpu::is_encoder_available(&ffmpeg_path.to_string_lossy(), &encoder).await)
+/// Check at startup which GPU encoders actually run, so jobs with a broken
+/// one go straight to the CPU. Results are emitted as `encoder-self-test`.
+fn spawn_encoder_self_test(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let Ok(ffmpeg_path) = get_ffmpeg_path(&state).await else {
+            return;
+        };
+        match GpuDetector::self_test(Some(&ffmpeg_path.to_string_lossy())).await {
+            Ok(results) => {
+                for result in &results {
+                    match result.error {
+                        None => info!("Encoder self-test: {} works ({} ms)", result.encoder, result.elapsed_ms),
+                        Some(ref e) => warn!("Encoder self-test: {} failed: {}", result.encoder, e),
+                    }
+                }
+                let _ = app_handle.emit("encoder-self-test", results);
+            }
+            Err(e) => warn!("Encoder self-test skipped: {}", e),
+        }
+    });
 }
 
-// Command: Open file location in file explorer
-#[tauri::command]
-async fn open_file_location(file_path: String) -> Result<(), AppError> {
-    let path = std::path::Path::new(&file_path);
+/// Start queued jobs whenever fewer than `max_concurrent_jobs` are running.
+fn spawn_queue_runner(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(QUEUE_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = app_handle.state::<AppState>();
+            let limit = state
+                .settings
+                .lock()
+                .ok()
+                .and_then(|s| s.max_concurrent_jobs)
+                .unwrap_or(1)
+                .max(1);
+
+            loop {
+                let next = {
+                    let (Ok(mut queue), Ok(manager)) = (state.queue.lock(), state.ffmpeg_manager.lock()) else {
+                        break;
+                    };
+                    queue.sync(&manager);
+                    queue.take_next(limit)
+                };
+                let Some((job_id, request)) = next else {
+                    break;
+                };
+
+                let result = begin_conversion(&state, Uuid::new_v4().to_string(), request).await;
+                let Ok(mut queue) = state.queue.lock() else {
+                    break;
+                };
+                match result {
+                    Ok(handle) => {
+                        info!("Queued job {} started as task {}", job_id, handle.task_id);
+                        queue.mark_started(&job_id, handle.task_id);
+                    }
+                    Err(e) => {
+                        error!("Queued job {} could not start: {}", job_id, e);
+                        queue.mark_failed(&job_id, e.to_string());
+                    }
+                }
+            }
+        }
+    });
+}
 
-    if !path.exists() {
-        return Err(AppError::Io(format!("File not found: {}", file_path)));
-    }
+/// Quick-convert mode: run the files a launcher was given through its
+/// preset, wait for them and exit (code 1 if any failed).
+fn spawn_quick_convert(app_handle: tauri::AppHandle, quick: QuickConvert) {
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let code = match run_quick_convert(&state, &quick).await {
+            Ok(true) => 0,
+            Ok(false) => 1,
+            Err(e) => {
+                error!("Quick convert with {} failed: {}", quick.preset, e);
+                1
+            }
+        };
+        app_handle.exit(code);
+    });
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        use std::process::Command;
-        Command::new("explorer")
-            .args(["/select,", &file_path])
-            .spawn()
-            .map_err(|e| AppError::Internal(format!("Failed to open file location: {}", e)))?;
+/// Start every file and wait until all finished; `Ok(false)` if one failed.
+async fn run_quick_convert(state: &AppState, quick: &QuickConvert) -> Result<bool, AppError> {
+    let preset = presets::load()
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(quick.preset.trim()))
+        .ok_or_else(|| AppError::Internal(format!("Custom preset not found: {}", quick.preset)))?;
+    info!("Quick convert: {} file(s) with preset {}", quick.files.len(), preset.name);
+
+    let mut all_ok = true;
+    let mut task_ids = Vec::new();
+    for file in &quick.files {
+        let request = droplets::request_for(&preset, file, quick.output_dir.as_deref());
+        match begin_conversion(state, Uuid::new_v4().to_string(), request).await {
+            Ok(handle) => task_ids.push(handle.task_id),
+            Err(e) => {
+                error!("Quick convert could not start {}: {}", file, e);
+                all_ok = false;
+            }
+        }
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        Command::new("open")
-            .args(["-R", &file_path])
-            .spawn()
-            .map_err(|e| AppError::Internal(format!("Failed to open file location: {}", e)))?;
+    let mut interval = tokio::time::interval(QUEUE_POLL_INTERVAL);
+    while !task_ids.is_empty() {
+        interval.tick().await;
+        let manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+        task_ids.retain(|task_id| {
+            let status = manager.get_progress(task_id).map(|p| p.status);
+            match status {
+                Some(ffmpeg::ConversionStatus::Pending | ffmpeg::ConversionStatus::Running) => true,
+                Some(ffmpeg::ConversionStatus::Completed) => false,
+                _ => {
+                    all_ok = false;
+                    false
+                }
+            }
+        });
     }
+    Ok(all_ok)
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        // For Linux, just open the parent directory
-        if let Some(parent) = path.parent() {
-            use std::process::Command;
-            Command::new("xdg-open")
-                .arg(parent)
-                .spawn()
-                .map_err(|e| AppError::Internal(format!("Failed to open file location: {}", e)))?;
+/// Validate a conversion request, resolve its options against the settings
+/// and start it. Shared by `start_conversion` and the job queue.
+async fn begin_conversion(
+    state: &AppState,
+    task_id: String,
+    resolved: StartConversionArgs,
+) -> Result<JobHandle, AppError> {
+    // The key is claimed before resolving so a concurrent submission with
+    // the same key waits for this one instead of starting a second job.
+    let key = resolved.options.idempotency_key.clone().filter(|k| !k.is_empty());
+    if let Some(ref key) = key {
+        let deadline = tokio::time::Instant::now() + IDEMPOTENCY_WAIT_TIMEOUT;
+        loop {
+            let existing = {
+                let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+                match manager.claim_idempotency_key(key, &task_id) {
+                    KeyClaim::Claimed => break,
+                    KeyClaim::Existing(id) => {
+                        let request = manager.task_request(&id).ok_or_else(|| {
+                            AppError::Internal(format!("Task {} for idempotency key {} can't be read", id, key))
+                        })?;
+                        let (pending, running) = manager.job_counts();
+                        Some((request, id, (pending + running).saturating_sub(1)))
+                    }
+                    KeyClaim::InFlight => None,
+                }
+            };
+            if let Some((request, existing, queue_position)) = existing {
+                info!("Idempotency key {} already submitted as task {}", key, existing);
+                return Ok(JobHandle::new(existing, &request, queue_position).await);
+            }
+            // The other submission may have died without releasing the key.
+            if tokio::time::Instant::now() >= deadline {
+                return Err(AppError::Timeout(format!(
+                    "Another submission with idempotency key {} didn't finish within {}s",
+                    key,
+                    IDEMPOTENCY_WAIT_TIMEOUT.as_secs()
+                )));
+            }
+            tokio::time::sleep(IDEMPOTENCY_WAIT_INTERVAL).await;
         }
     }
 
-    Ok(())
+    let result = submit_conversion(state, task_id.clone(), resolved).await;
+    if let (Err(_), Some(key)) = (&result, &key) {
+        if let Ok(mut manager) = state.ffmpeg_manager.lock() {
+            manager.release_idempotency_key(key, &task_id);
+        }
+    }
+    result
 }
 
-#[tauri::command]
+/// Resolve and start a conversion whose idempotency key, if any, is claimed.
+async fn submit_conversion(
+    state: &AppState,
+    task_id: String,
+    resolved: StartConversionArgs,
+) -> Result<JobHandle, AppError> {
+    let submitted = resolved.clone();
+    let request = resolve_conversion(state, &task_id, resolved).await?;
+    let mut handle = JobHandle::new(task_id.clone(), &request, 0).await;
+    let manager = state.ffmpeg_manager.clone();
+    let mut manager = manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let (pending, running) = manager.job_counts();
+    handle.queue_position = pending + running;
+    manager.start_conversion(task_id.clone(), request)?;
+    if let Ok(mut history) = state.history.lock() {
+        history.track(task_id, submitted);
+    }
+
+    Ok(handle)
+}
+
+/// Validate a conversion request and resolve its options against the
+/// settings and the input. Shared by `begin_conversion` and the preview.
+async fn resolve_conversion(
+    state: &AppState,
+    task_id: &str,
+    resolved: StartConversionArgs,
+) -> Result<ConversionRequest, AppError> {
+    let StartConversionArgs {
+        input_file,
+        mut output_file,
+        mut encoder,
+        gpu_index,
+        cpu_threads,
+        mut preset,
+        mut is_adobe_preset,
+        mut options,
+    } = resolved;
+
+    // Stdin and named pipes are read once, so nothing may probe them first.
+    let pipe_input = ffmpeg::pipe_input::is_pipe(&input_file);
+    if !pipe_input && !std::path::Path::new(&input_file).exists() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+    if let Some(reason) = ffmpeg::pipe_input::unsupported(&options).filter(|_| pipe_input) {
+        return Err(AppError::Ffmpeg(format!("Not available with piped input: {}", reason)));
+    }
+
+    if options.safe_mode {
+        // A renamed output may be the input or an unrelated file; FFmpeg's
+        // `-y` must not overwrite either.
+        if ffmpeg::safe_mode::apply(&mut encoder, &mut preset, &mut output_file, &mut options) {
+            output_file = naming::unused_path(Path::new(&output_file)).to_string_lossy().to_string();
+        }
+        is_adobe_preset = None;
+        info!("Safe mode: converting {} with libx264 High@4.0 + AAC to {}", input_file, output_file);
+    }
+
+    // Job-level environment and working directory override the global settings.
+    {
+        let settings = state.settings.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut env_vars = settings.env_vars.clone();
+        env_vars.extend(options.env_vars.drain());
+        options.env_vars = env_vars;
+        if options.working_dir.is_none() {
+            options.working_dir = settings.working_dir.clone();
+        }
+        if options.fonts_dir.is_none() {
+            options.fonts_dir = settings.font_dir.clone();
+        }
+        if options.quarantine_dir.is_none() {
+            options.quarantine_dir = settings.quarantine_dir.clone();
+        }
+        if options.ocr_subtitles {
+            options.ocr_tool = settings.subtitle_ocr.clone().filter(|t| !t.command.trim().is_empty());
+            if options.ocr_tool.is_none() {
+                return Err(AppError::Ffmpeg(
+                    "Subtitle OCR needs an OCR tool configured in the settings".to_string(),
+                ));
+            }
+        }
+        // Adobe, media server and screen presets bring their own tuning.
+        let uses_builtin_preset = is_adobe_preset.unwrap_or(false)
+            || options.media_server_preset.is_some()
+            || options.screen_preset.is_some()
+            || options.safe_mode;
+        if let Some(defaults) = settings.encoder_defaults.get(&encoder).filter(|_| !uses_builtin_preset) {
+            defaults.apply(&mut preset, &mut options);
+        }
+    }
+    if preset.trim().is_empty() {
+        preset = DEFAULT_PRESET.to_string();
+    }
+
+    // Windows FFmpeg builds ship without a fontconfig setup, which makes
+    // libass fail to find any font when burning in subtitles.
+    if cfg!(target_os = "windows")
+        && (options.burns_subtitles() || options.telemetry.is_some())
+        && !options.env_vars.contains_key("FONTCONFIG_FILE")
+    {
+        let conf = ffmpeg::fonts::ensure_fonts_conf(options.fonts_dir.as_deref())?;
+        options
+            .env_vars
+            .insert("FONTCONFIG_FILE".to_string(), conf.to_string_lossy().to_string());
+    }
+    if let Some(ref file) = options.subtitle_file {
+        if !Path::new(file).exists() {
+            return Err(AppError::Io(format!("Subtitle file not found: {}", file)));
+        }
+    }
+    for bumper in [&options.intro_file, &options.outro_file].into_iter().flatten() {
+        if !Path::new(bumper).exists() {
+            return Err(AppError::Io(format!("Intro/outro clip not found: {}", bumper)));
+        }
+    }
+    if let Some(ref file) = options.chapters_file {
+        if !Path::new(file).exists() {
+            return Err(AppError::Io(format!("Chapters file not found: {}", file)));
+        }
+    }
+    if let Some(ref overlay) = options.overlay {
+        if !Path::new(&overlay.file).exists() {
+            return Err(AppError::Io(format!("Overlay video not found: {}", overlay.file)));
+        }
+    }
+    if let Some(ref dir) = options.working_dir {
+        if !Path::new(dir).is_dir() {
+            return Err(AppError::Io(format!("Working directory not found: {}", dir)));
+        }
+    }
+
+    ffmpeg::passthrough::validate(&options.extra_input_args, "input").map_err(AppError::Ffmpeg)?;
+    ffmpeg::passthrough::validate(&options.extra_output_args, "output").map_err(AppError::Ffmpeg)?;
+
+    if let Some(missing) = options.input_segments.iter().find(|s| !Path::new(s).exists()) {
+        return Err(AppError::Io(format!("Input segment not found: {}", missing)));
+    }
+
+    // Name used for output templates; disc inputs are rewritten to a concat URL below.
+    let source_name = options.input_segments.first().cloned().unwrap_or_else(|| input_file.clone());
+
+    // DVD/Blu-ray folders are converted from a single title's segments.
+    let input_file = if Path::new(&input_file).is_dir() {
+        let disc = disc::scan_disc(Path::new(&input_file))
+            .ok_or_else(|| AppError::Io(format!("Input folder is not a DVD or Blu-ray structure: {}", input_file)))?;
+        let title = disc.title(options.disc_title).ok_or_else(|| {
+            AppError::Io(format!("No playable title found in disc folder: {}", input_file))
+        })?;
+        info!("Disc input {:?}: using title {} ({} segment(s))", disc.kind, title.name, title.files.len());
+        options.deinterlace.get_or_insert(true);
+        disc::concat_url(title)
+    } else {
+        input_file
+    };
+
+    let output_ext = Path::new(&output_file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    // Delivery formats such as AVC-Intra and XAVC S are only valid in
+    // their own containers.
+    if is_adobe_preset.unwrap_or(false) {
+        let adobe = get_adobe_presets().into_iter().find(|p| p.name == preset);
+        if let Some(adobe) = adobe.filter(|p| !p.containers.is_empty() && !p.containers.contains(&output_ext)) {
+            return Err(AppError::Ffmpeg(format!(
+                "{} must be written as {}, not .{}",
+                adobe.description,
+                adobe.containers.iter().map(|c| format!(".{}", c)).collect::<Vec<_>>().join(" or "),
+                output_ext
+            )));
+        }
+    }
+
+    // Series batches: place the output according to the season/episode template.
+    let output_file = match options.output_template.as_deref().filter(|t| !t.trim().is_empty()) {
+        Some(template) => {
+            let output_dir = Path::new(&output_file).parent().unwrap_or(Path::new(""));
+            let rendered = naming::render_output_path(template, &source_name, output_dir, &output_ext);
+            info!("Output template resolved to {:?}", rendered);
+            rendered.to_string_lossy().to_string()
+        }
+        None => output_file,
+    };
+
+    // Writing onto a file FFmpeg reads from destroys it mid-job. The main
+    // input is replaced safely through a staging file; other inputs can't be.
+    let aux_inputs = [&options.subtitle_file, &options.intro_file, &options.outro_file, &options.chapters_file]
+        .into_iter()
+        .flatten()
+        .chain(options.overlay.as_ref().map(|o| &o.file));
+    if let Some(clash) = aux_inputs
+        .into_iter()
+        .find(|file| naming::is_same_file(Path::new(file), Path::new(&output_file)))
+    {
+        return Err(AppError::Io(format!("Output path is the same as an input file: {}", clash)));
+    }
+    let replaces_input = std::iter::once(&input_file)
+        .chain(options.input_segments.iter())
+        .any(|file| naming::is_same_file(Path::new(file), Path::new(&output_file)));
+    let output_file = if replaces_input {
+        let staging = naming::staging_path(Path::new(&output_file));
+        info!("Output equals input; staging to {:?} and replacing {} afterwards", staging, output_file);
+        options.replaces_input = Some(output_file);
+        staging.to_string_lossy().to_string()
+    } else {
+        output_file
+    };
+
+    let format_info = get_format_info(&output_ext);
+    if options.timelapse.is_some() && !format_info.supports_video {
+        return Err(AppError::Ffmpeg("Timelapse needs a video output format.".to_string()));
+    }
+    if let Some(ref normalize) = options.normalize_audio {
+        normalize.validate().map_err(AppError::Ffmpeg)?;
+        if !format_info.supports_audio {
+            return Err(AppError::Ffmpeg("Loudness normalization needs an output format with audio.".to_string()));
+        }
+        if !options.input_segments.is_empty() {
+            return Err(AppError::Ffmpeg("Loudness normalization measures a single input file, not joined segments.".to_string()));
+        }
+    }
+    if options.audio_edit.is_some() && format_info.supports_video {
+        return Err(AppError::Ffmpeg("Audio edits are only available for audio-only outputs.".to_string()));
+    }
+    if let Some(ref dar) = options.display_aspect {
+        let valid = Regex::new(r"^\d+(?:[:/]\d+|\.\d+)?$")
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .is_match(dar.trim());
+        if !valid {
+            return Err(AppError::Ffmpeg(format!("Invalid display aspect ratio: {} (use e.g. 16:9)", dar)));
+        }
+        options.display_aspect = Some(dar.trim().to_string());
+    }
+    if options.smart_reframe {
+        options.reframe.get_or_insert_with(Default::default);
+    }
+    if let Some(ref reframe) = options.reframe {
+        if !format_info.supports_video {
+            return Err(AppError::Ffmpeg("Reframing needs a video output format.".to_string()));
+        }
+        if ffmpeg::filters::parse_aspect(&reframe.aspect).is_none() {
+            return Err(AppError::Ffmpeg(format!("Invalid reframe aspect ratio: {} (use e.g. 9:16)", reframe.aspect)));
+        }
+    }
+    if let Some(ref fit) = options.fit {
+        if !format_info.supports_video {
+            return Err(AppError::Ffmpeg("Fit with padding needs a video output format.".to_string()));
+        }
+        if fit.width == 0 || fit.height == 0 || fit.width % 2 != 0 || fit.height % 2 != 0 {
+            return Err(AppError::Ffmpeg(format!(
+                "Output size must be even and non-zero, got {}x{}",
+                fit.width, fit.height
+            )));
+        }
+    }
+    if options.telemetry.is_some() && !format_info.supports_video {
+        return Err(AppError::Ffmpeg("Telemetry overlays need a video output format.".to_string()));
+    }
+    if let Some(ref export) = options.audio_export {
+        if !format_info.supports_video || options.timelapse.is_some() {
+            return Err(AppError::Ffmpeg("A separate audio export needs a video output with sound.".to_string()));
+        }
+        if !AUDIO_FORMATS.contains(&export.format.to_lowercase().as_str()) {
+            return Err(AppError::Ffmpeg(format!("Unsupported audio export format: {}", export.format)));
+        }
+        let audio_file = export.path_for(&output_file);
+        if Path::new(&audio_file) == Path::new(&output_file) || Path::new(&audio_file) == Path::new(&input_file) {
+            return Err(AppError::Ffmpeg("The audio export must be a different file from the input and output.".to_string()));
+        }
+        naming::check_output_writable(Path::new(&audio_file)).map_err(AppError::Io)?;
+    }
+    let mut extra_paths = HashSet::new();
+    for spec in &options.extra_outputs {
+        let ext = spec.extension();
+        if !VIDEO_FORMATS.contains(&ext.as_str()) && !AUDIO_FORMATS.contains(&ext.as_str()) {
+            return Err(AppError::Ffmpeg(format!("Unsupported output format: {}", spec.output_file)));
+        }
+        let path = Path::new(&spec.output_file);
+        if path == Path::new(&output_file) || path == Path::new(&input_file) || !extra_paths.insert(path) {
+            return Err(AppError::Ffmpeg(format!(
+                "Each output needs its own file, different from the input: {}",
+                spec.output_file
+            )));
+        }
+        if let (Some(rate_control), Some(encoder)) = (&spec.rate_control, spec.video_encoder()) {
+            rate_control.validate(&encoder).map_err(AppError::Ffmpeg)?;
+        }
+        ffmpeg::passthrough::validate(&spec.extra_args, "output").map_err(AppError::Ffmpeg)?;
+        naming::check_output_writable(path).map_err(AppError::Io)?;
+    }
+    if options.target_size_mb.is_some() {
+        if !format_info.supports_video {
+            return Err(AppError::Ffmpeg("Target size needs a video output format.".to_string()));
+        }
+        if options.rate_control.as_ref().is_some_and(|rc| rc.quality.is_some() || rc.bitrate.is_some()) {
+            return Err(AppError::Ffmpeg(
+                "A target size replaces quality and bitrate settings; set only one.".to_string(),
+            ));
+        }
+    }
+
+    if let Some(parent) = std::path::Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+    }
+    for target in std::iter::once(&output_file).chain(options.replaces_input.as_ref()) {
+        naming::check_output_writable(Path::new(target)).map_err(AppError::Io)?;
+    }
+
+    // Get FFmpeg path automatically, unless the preset pins an installation
+    let requirement = options.ffmpeg_requirement.clone().unwrap_or_default();
+    let ffmpeg_path = match requirement.installation {
+        Some(ref name) => {
+            let settings = state.settings.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+            ffmpeg::requirement::installation_path(name, &settings.ffmpeg_installations)?
+        }
+        None => get_ffmpeg_path(state).await?,
+    };
+    if let Some(ref minimum) = requirement.min_version {
+        ffmpeg::requirement::check_min_version(&ffmpeg_path, minimum).await?;
+    }
+    let ffmpeg_path_str = ffmpeg_path.to_string_lossy().to_string();
+
+    // An encoder missing from this build would fail with "Unknown encoder";
+    // suggest the closest one it has, or switch to it when the job allows.
+    // Without substitution, GPU encoders keep their CPU fallback attempt, as
+    // do media server and screen presets.
+    let builtin_encoder = options.media_server_preset.is_some() || options.screen_preset.is_some();
+    if format_info.supports_video && !builtin_encoder {
+        let caps = availability::capabilities(Some(&ffmpeg_path_str)).await;
+        let adobe_presets = if is_adobe_preset.unwrap_or(false) { get_adobe_presets() } else { Vec::new() };
+        let adobe = adobe_presets.iter().find(|p| p.name == preset);
+        let wanted = adobe.map_or(encoder.clone(), |p| p.encoder.clone());
+        let missing = availability::missing_encoder(&caps, &wanted);
+        let gpu_fallback = ["nvenc", "amf", "qsv"].iter().any(|api| wanted.contains(api));
+        if missing && gpu_fallback && !options.substitute_encoder {
+            warn!("This FFmpeg build has no {} encoder; {} will use the CPU fallback", wanted, input_file);
+        } else if missing {
+            let replacement = availability::substitute(&caps, &wanted);
+            let Some(replacement) = replacement.filter(|_| options.substitute_encoder) else {
+                return Err(AppError::Ffmpeg(match replacement {
+                    Some(r) => format!(
+                        "This FFmpeg build has no {} encoder. {} is available; enable encoder substitution to use it.",
+                        wanted, r
+                    ),
+                    None => format!("This FFmpeg build has no {} encoder.", wanted),
+                }));
+            };
+            // Adobe presets move to the sibling preset of the substitute,
+            // preferring the same pixel format, so its tuning comes along.
+            let sibling = adobe.and_then(|adobe| {
+                adobe_presets
+                    .iter()
+                    .filter(|p| p.encoder == replacement)
+                    .filter(|p| p.containers.is_empty() || p.containers.contains(&output_ext))
+                    .min_by_key(|p| p.pixel_format != adobe.pixel_format)
+            });
+            let using = match sibling {
+                Some(sibling) => {
+                    preset = sibling.name.clone();
+                    format!("{} ({})", replacement, sibling.description)
+                }
+                None => {
+                    if adobe.is_some() {
+                        is_adobe_preset = None;
+                        preset = "medium".to_string();
+                    }
+                    replacement.to_string()
+                }
+            };
+            encoder = replacement.to_string();
+            let message = format!("This FFmpeg build has no {} encoder; encoding with {} instead.", wanted, using);
+            warn!("{} ({})", message, input_file);
+            options.encoder_substitution = Some(message);
+        }
+    }
+
+    // The HUD text changes over time, so it is fed to drawtext through a
+    // sendcmd script written ahead of the job.
+    if let Some(ref mut telemetry) = options.telemetry {
+        let source = options.input_segments.first().unwrap_or(&input_file);
+        let samples = ffmpeg::telemetry::load_samples(&ffmpeg_path_str, source, telemetry).await?;
+        let script = ffmpeg::telemetry::sendcmd_script(&samples, telemetry, options.trim_start.unwrap_or(0.0));
+        let dir = FfmpegDownloader::get_ffmpeg_app_dir()?.join("telemetry");
+        std::fs::create_dir_all(&dir)?;
+        let script_path = dir.join(format!("{}.txt", task_id));
+        std::fs::write(&script_path, script)?;
+        info!("Telemetry overlay: {} sample(s), script {:?}", samples.len(), script_path);
+        telemetry.script = Some(script_path.to_string_lossy().to_string());
+    }
+
+    if pipe_input {
+        info!("Piped input {}: skipping input probes", input_file);
+    } else if !format_info.supports_video && format_info.supports_audio {
+        let mut cmd = Command::new(&ffmpeg_path);
+        cmd.args(&["-hide_banner", "-i", &input_file]);
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        let output = watchdog::output(&mut cmd).await?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let info = ffmpeg::VideoInfo::parse(&stderr)?;
+        if info.audio_streams.is_empty() {
+            return Err(AppError::Ffmpeg("Input has no audio stream; cannot create audio-only output.".to_string()));
+        }
+    } else if format_info.supports_video && options.display_aspect.is_none() {
+        // DV and DVB sources store widescreen frames in 720-pixel-wide
+        // pictures; the pixel aspect decides whether they need resampling.
+        let source = options.input_segments.first().unwrap_or(&input_file);
+        if let Some(info) = ffmpeg::probe_info(&ffmpeg_path_str, source).await {
+            if let Some((num, den)) = info.sample_aspect.filter(|(num, den)| num != den) {
+                info!("Source has non-square pixels (SAR {}:{}), aspect mode {:?}", num, den, options.aspect_mode);
+            }
+            options.source_sar = info.sample_aspect;
+        }
+    }
+
+    // Smart reframe: crop letterboxing off 16:9 movies first, so the 9:16
+    // slice is taken from picture rather than bars.
+    if options.smart_reframe && !pipe_input && format_info.supports_video {
+        let source = options.input_segments.first().unwrap_or(&input_file);
+        if let Some(info) = ffmpeg::probe_info(&ffmpeg_path_str, source).await {
+            if let (Some(width), Some(height)) = (info.width, info.height) {
+                options.letterbox_crop =
+                    ffmpeg::cropdetect::detect(&ffmpeg_path_str, source, info.duration, width, height).await;
+            }
+        }
+    }
+    
+    // A target size becomes a bitrate budget over the output duration.
+    if let Some(target_mb) = options.target_size_mb {
+        let source = options.input_segments.first().unwrap_or(&input_file);
+        let info = ffmpeg::probe_info(&ffmpeg_path_str, source).await;
+        let speed_factor = options.timelapse.as_ref().map_or(1.0, |t| t.speed_factor());
+        let length = ffmpeg::filters::clip_length(&options, info.as_ref().and_then(|i| i.duration))
+            .map(|length| length / speed_factor)
+            .ok_or_else(|| AppError::Ffmpeg("Could not determine the input duration for a target size.".to_string()))?;
+        let has_audio = format_info.supports_audio
+            && options.timelapse.is_none()
+            && info.as_ref().is_some_and(|i| !i.audio_streams.is_empty());
+        let audio_kbps = if has_audio {
+            *options.audio_bitrate.get_or_insert(ffmpeg::ratecontrol::DEFAULT_TARGET_AUDIO_KBPS)
+        } else {
+            0
+        };
+        let video_kbps = ffmpeg::ratecontrol::video_bitrate_for_size(target_mb, length, audio_kbps)
+            .map_err(AppError::Ffmpeg)?;
+        let two_pass = ffmpeg::ratecontrol::supports_two_pass(&encoder);
+        info!(
+            "Target size {} MB over {:.1}s: video {} kbit/s, audio {} kbit/s, two-pass {}",
+            target_mb, length, video_kbps, audio_kbps, two_pass
+        );
+        let rate_control = options.rate_control.get_or_insert_with(Default::default);
+        rate_control.bitrate = Some(video_kbps);
+        rate_control.two_pass = two_pass;
+        if !two_pass {
+            // Single-pass encoders overshoot without a cap.
+            rate_control.maxrate = Some(video_kbps);
+        }
+    }
+    if let Some(ref rate_control) = options.rate_control {
+        rate_control.validate(&encoder).map_err(AppError::Ffmpeg)?;
+    }
+
+    Ok(ConversionRequest {
+        input_file,
+        output_file,
+        ffmpeg_path: ffmpeg_path_str,
+        encoder,
+        gpu_index,
+        cpu_threads,
+        preset,
+        is_adobe_preset: is_adobe_preset.unwrap_or(false),
+        options,
+    })
+}
+
+// Command: Show the FFmpeg command a conversion would run, without running it
+#[tauri::command]
+async fn preview_conversion_command(
+    state: State<'_, AppState>,
+    args: StartConversionArgs,
+) -> Result<ffmpeg::CommandPreview, AppError> {
+    let task_id = format!("preview-{}", Uuid::new_v4());
+    let request = resolve_conversion(&state, &task_id, args).await?;
+    ffmpeg::preview_conversion(task_id, request).await
+}
+
+// Command: Get conversion progress; running tasks also push it as `conversion-progress` events
+#[tauri::command]
+async fn get_conversion_progress(
+    state: State<'_, AppState>,
+    task_id: String,
+) -> Result<Option<ConversionProgress>, AppError> {
+    {
+        let manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+        if let Some(progress) = manager.get_progress(&task_id) {
+            return Ok(Some(progress));
+        }
+    }
+    // Not started yet: report the queue job's place in line.
+    queued_progress(&state, &task_id)
+}
+
+fn queued_progress(state: &AppState, job_id: &str) -> Result<Option<ConversionProgress>, AppError> {
+    let limit = state
+        .settings
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .max_concurrent_jobs
+        .unwrap_or(1)
+        .max(1);
+    let queue = state.queue.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    let (Some(position), Some(job)) = (queue.waiting_position(job_id), queue.get(job_id)) else {
+        return Ok(None);
+    };
+    let running = state
+        .ffmpeg_manager
+        .lock()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .running_progress(&queue.running_task_ids());
+    let estimated_start = queue::estimate_start(&running, position, limit, stats::average_job_secs());
+    Ok(Some(ConversionProgress::queued(
+        job_id.to_string(),
+        position,
+        estimated_start,
+        job.request.options.external_id.clone(),
+    )))
+}
+
+// Command: Cancel conversion
+#[tauri::command]
+async fn cancel_conversion(
+    state: State<'_, AppState>,
+    task_id: String,
+) -> Result<(), AppError> {
+    let manager = state.ffmpeg_manager.clone();
+    let mut manager = manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    
+    manager.cancel_conversion(&task_id)
+}
+
+// Command: Get video duration
+#[tauri::command]
+async fn get_video_duration(state: State<'_, AppState>, input_file: String) -> Result<f64, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let output = watchdog::output(Command::new(&ffmpeg_path).args(&["-i", &input_file])).await?;
+    
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    
+    // Parse duration from FFmpeg output
+    let duration_regex = Regex::new(r"Duration: (\d+):(\d+):(\d+\.\d+)").map_err(|e| AppError::Internal(e.to_string()))?;
+    
+    if let Some(captures) = duration_regex.captures(&stderr) {
+        let hours: f64 = captures[1].parse().unwrap_or(0.0);
+        let minutes: f64 = captures[2].parse().unwrap_or(0.0);
+        let seconds: f64 = captures[3].parse().unwrap_or(0.0);
+        
+        let total_seconds = hours * 3600.0 + minutes * 60.0 + seconds;
+        return Ok(total_seconds);
+    }
+    
+    Err(AppError::Ffmpeg("Could not determine video duration".to_string()))
+}
+
+// Command: Get video streams info
+#[tauri::command]
+async fn get_video_info(state: State<'_, AppState>, input_file: String) -> Result<ffmpeg::VideoInfo, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    ffmpeg::probe_info(&ffmpeg_path.to_string_lossy(), &input_file)
+        .await
+        .ok_or_else(|| AppError::Ffmpeg(format!("Failed to probe video: {}", input_file)))
+}
+
+// Command: Probe a batch before converting it and report per file what
+// would fail or get lost (missing files, HDR needing tone mapping, dropped
+// subtitles, ...)
+#[tauri::command]
+async fn validate_batch(
+    state: State<'_, AppState>,
+    input_files: Vec<String>,
+    output_format: String,
+    encoder: String,
+    options: Option<ConversionOptions>,
+) -> Result<Vec<ffmpeg::batch_check::FileReport>, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    let options = options.unwrap_or_default();
+    let output_format = output_format.trim().trim_start_matches('.').to_lowercase();
+    let target = ffmpeg::batch_check::BatchTarget {
+        output_format: &output_format,
+        encoder: &encoder,
+        options: &options,
+    };
+    Ok(ffmpeg::batch_check::check_batch(&ffmpeg_path, &input_files, &target).await)
+}
+
+// Command: Full ffprobe inspection (bit depth, frame rate, color, HDR,
+// chapters) of a media file
+#[tauri::command]
+async fn probe_media(state: State<'_, AppState>, input_file: String) -> Result<MediaInfo, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let ffprobe = ffmpeg::probe::ffprobe_path_for(&ffmpeg_path.to_string_lossy())
+        .ok_or_else(|| AppError::Ffmpeg("ffprobe was not found next to FFmpeg".to_string()))?;
+    ffmpeg::probe::probe_media(&ffprobe, &input_file).await
+}
+
+// Command: Write the closed captions and teletext of a file to SRT files
+// next to it (or in `output_dir`)
+#[tauri::command]
+async fn extract_captions(
+    state: State<'_, AppState>,
+    input_file: String,
+    output_dir: Option<String>,
+) -> Result<Vec<String>, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    let info = ffmpeg::probe_info(&ffmpeg_path, &input_file)
+        .await
+        .ok_or_else(|| AppError::Ffmpeg(format!("Failed to probe {}", input_file)))?;
+    if ffmpeg::captions::detect(&info).is_empty() {
+        return Err(AppError::Ffmpeg("No closed captions or teletext found in this file".to_string()));
+    }
+    let input = Path::new(&input_file);
+    let base = match output_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)
+                .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+            Path::new(&dir).join(input.file_name().unwrap_or_default())
+        }
+        None => input.to_path_buf(),
+    };
+    let files = ffmpeg::captions::extract_all(&ffmpeg_path, &input_file, &info, &base).await?;
+    Ok(files.into_iter().map(|f| f.to_string_lossy().to_string()).collect())
+}
+
+// Command: Measure sequential read of the input volume and write of the
+// output volume, to tell slow storage apart from slow encoding
+#[tauri::command]
+async fn benchmark_storage(input_file: String, output_dir: String) -> Result<storage::StorageBenchmark, AppError> {
+    if !Path::new(&input_file).is_file() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+    if !Path::new(&output_dir).is_dir() {
+        return Err(AppError::Io(format!("Output folder not found: {}", output_dir)));
+    }
+    tokio::task::spawn_blocking(move || storage::benchmark(Path::new(&input_file), Path::new(&output_dir)))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(|e| AppError::Io(format!("Storage benchmark failed: {}", e)))
+}
+
+// Command: Result of the last storage benchmark of this session
+#[tauri::command]
+fn get_storage_benchmark() -> Option<storage::StorageBenchmark> {
+    storage::last_benchmark()
+}
+
+// Command: Scan a DVD (VIDEO_TS) or Blu-ray (BDMV) folder for titles
+#[tauri::command]
+async fn scan_disc_folder(path: String) -> Result<DiscInfo, AppError> {
+    disc::scan_disc(Path::new(&path))
+        .ok_or_else(|| AppError::Io(format!("Not a DVD or Blu-ray folder: {}", path)))
+}
+
+// Command: Detect sibling chapter files (GoPro, AVCHD) recorded with the input
+#[tauri::command]
+async fn detect_input_segments(input_file: String) -> Result<Option<SegmentGroup>, AppError> {
+    Ok(segments::detect_segments(Path::new(&input_file)))
+}
+
+// Command: Parse season/episode information from a file name
+#[tauri::command]
+async fn parse_episode_name(file_name: String) -> Result<Option<EpisodeInfo>, AppError> {
+    Ok(naming::parse_episode(&file_name))
+}
+
+// Command: Resolve where an output template places a given input
+#[tauri::command]
+async fn preview_output_path(
+    input_file: String,
+    output_dir: String,
+    template: String,
+    extension: String,
+) -> Result<String, AppError> {
+    let ext = extension.trim_start_matches('.').to_lowercase();
+    Ok(naming::render_output_path(&template, &input_file, Path::new(&output_dir), &ext)
+        .to_string_lossy()
+        .to_string())
+}
+
+// Command: Get supported formats
+#[tauri::command]
+async fn get_supported_formats() -> Result<SupportedFormats, AppError> {
+    Ok(SupportedFormats {
+        video: VIDEO_FORMATS.iter().map(|s| s.to_string()).collect(),
+        audio: AUDIO_FORMATS.iter().map(|s| s.to_string()).collect(),
+    })
+}
+
+// Command: Recommend an output container for the chosen codecs and features
+#[tauri::command]
+fn suggest_container(needs: ffmpeg::containers::ContainerNeeds) -> ffmpeg::containers::ContainerAdvice {
+    ffmpeg::containers::suggest(&needs)
+}
+
+/// Capabilities of the current FFmpeg and hardware, for annotating presets.
+async fn preset_capabilities(state: &AppState) -> Capabilities {
+    let ffmpeg_path = get_ffmpeg_path(state).await.ok().map(|p| p.to_string_lossy().to_string());
+    availability::capabilities(ffmpeg_path.as_deref()).await
+}
+
+// Command: Get Adobe/After Effects presets, annotated with availability
+#[tauri::command]
+async fn get_adobe_presets_list(state: State<'_, AppState>) -> Result<Vec<Annotated<AdobePreset>>, AppError> {
+    let caps = preset_capabilities(&state).await;
+    Ok(get_adobe_presets()
+        .into_iter()
+        .map(|preset| Annotated {
+            availability: availability::check(&caps, &preset.encoder, Some(&preset.pixel_format), &[]),
+            preset,
+        })
+        .collect())
+}
+
+// Command: Re-read the bundled and user Adobe preset files
+#[tauri::command]
+fn reload_adobe_presets() -> Vec<AdobePreset> {
+    ffmpeg::adobe_presets::reload()
+}
+
+// Command: Get ringtone / short clip presets (trim + fade), annotated with availability
+#[tauri::command]
+async fn get_clip_presets_list(state: State<'_, AppState>) -> Result<Vec<Annotated<ClipPreset>>, AppError> {
+    let caps = preset_capabilities(&state).await;
+    Ok(get_clip_presets()
+        .into_iter()
+        .map(|preset| {
+            let format_info = get_format_info(&preset.extension);
+            let (encoder, filters): (&str, &[&str]) = if format_info.supports_video {
+                (format_info.default_video_codec, &["fade", "afade"])
+            } else {
+                (format_info.default_audio_codec, &["afade"])
+            };
+            Annotated {
+                availability: availability::check(&caps, encoder, None, filters),
+                preset,
+            }
+        })
+        .collect())
+}
+
+// Command: Get Plex/Jellyfin direct-play presets, annotated with availability
+#[tauri::command]
+async fn get_media_server_presets_list(
+    state: State<'_, AppState>,
+) -> Result<Vec<Annotated<MediaServerPreset>>, AppError> {
+    let caps = preset_capabilities(&state).await;
+    Ok(get_media_server_presets()
+        .into_iter()
+        .map(|preset| Annotated {
+            availability: availability::check(&caps, &preset.encoder, Some(&preset.pixel_format), &[]),
+            preset,
+        })
+        .collect())
+}
+
+// Command: Get screen-recording presets, annotated with availability
+#[tauri::command]
+async fn get_screen_recording_presets_list(
+    state: State<'_, AppState>,
+) -> Result<Vec<Annotated<ScreenRecordingPreset>>, AppError> {
+    let caps = preset_capabilities(&state).await;
+    Ok(get_screen_recording_presets()
+        .into_iter()
+        .map(|preset| Annotated {
+            availability: availability::check(&caps, &preset.encoder, Some(&preset.pixel_format), &[]),
+            preset,
+        })
+        .collect())
+}
+
+// Command: List codecs whose hardware decoding failed and is now skipped
+#[tauri::command]
+async fn get_decoder_blacklist() -> Result<Vec<String>, AppError> {
+    Ok(decoders::list())
+}
+
+// Command: Forget failed hardware decodes so they are tried again
+#[tauri::command]
+async fn clear_decoder_blacklist() -> Result<(), AppError> {
+    decoders::clear();
+    Ok(())
+}
+
+// Command: Get format info
+#[tauri::command]
+async fn get_format_information(extension: String) -> Result<serde_json::Value, AppError> {
+    let info = get_format_info(&extension);
+    
+    Ok(serde_json::json!({
+        "container": info.container,
+        "default_video_codec": info.default_video_codec,
+        "default_audio_codec": info.default_audio_codec,
+        "supports_video": info.supports_video,
+        "supports_audio": info.supports_audio,
+    }))
+}
+
+// Command: Check if encoder is available
+#[tauri::command]
+async fn check_encoder_available(state: State<'_, AppState>, encoder: String) -> Result<bool, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    Ok(gpu::is_encoder_available(&ffmpeg_path.to_string_lossy(), &encoder).await)
+}
+
+// Command: Open file location in file explorer
+#[tauri::command]
+async fn open_file_location(file_path: String) -> Result<(), AppError> {
+    let path = std::path::Path::new(&file_path);
+
+    if !path.exists() {
+        return Err(AppError::Io(format!("File not found: {}", file_path)));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::process::Command;
+        Command::new("explorer")
+            .args(["/select,", &file_path])
+            .spawn()
+            .map_err(|e| AppError::Internal(format!("Failed to open file location: {}", e)))?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::process::Command;
+        Command::new("open")
+            .args(["-R", &file_path])
+            .spawn()
+            .map_err(|e| AppError::Internal(format!("Failed to open file location: {}", e)))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // For Linux, just open the parent directory
+        if let Some(parent) = path.parent() {
+            use std::process::Command;
+            Command::new("xdg-open")
+                .arg(parent)
+                .spawn()
+                .map_err(|e| AppError::Internal(format!("Failed to open file location: {}", e)))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Command: Open a file with the system's default player
+#[tauri::command]
+async fn play_file(file_path: String) -> Result<(), AppError> {
+    if !Path::new(&file_path).exists() {
+        return Err(AppError::Io(format!("File not found: {}", file_path)));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::process::Command;
+        Command::new("explorer")
+            .arg(&file_path)
+            .spawn()
+            .map_err(|e| AppError::Internal(format!("Failed to open file: {}", e)))?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::process::Command;
+        Command::new("open")
+            .arg(&file_path)
+            .spawn()
+            .map_err(|e| AppError::Internal(format!("Failed to open file: {}", e)))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::process::Command;
+        Command::new("xdg-open")
+            .arg(&file_path)
+            .spawn()
+            .map_err(|e| AppError::Internal(format!("Failed to open file: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+// Command: Decode a single frame (JPEG bytes) for the in-app preview slider
+#[tauri::command]
+async fn preview_frame_at(
+    state: State<'_, AppState>,
+    file_path: String,
+    timestamp: f64,
+    max_width: Option<u32>,
+) -> Result<tauri::ipc::Response, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let frame = ffmpeg::preview::frame_at(&ffmpeg_path.to_string_lossy(), &file_path, timestamp, max_width).await?;
+    Ok(tauri::ipc::Response::new(frame))
+}
+
+// Command: Start pre-generating keyframe thumbnails of an input for smooth
+// scrubbing (GPU decode where possible); returns the current state.
+#[tauri::command]
+async fn prepare_scrub_preview(
+    state: State<'_, AppState>,
+    input_file: String,
+    max_width: Option<u32>,
+) -> Result<ffmpeg::scrub::ScrubStatus, AppError> {
+    if !Path::new(&input_file).is_file() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    Ok(ffmpeg::scrub::prepare(&ffmpeg_path.to_string_lossy(), &input_file, max_width))
+}
+
+// Command: Scrub thumbnail (JPEG bytes) nearest to a timestamp; decodes the
+// exact frame while the thumbnails are still being generated.
+#[tauri::command]
+async fn scrub_frame_at(
+    state: State<'_, AppState>,
+    input_file: String,
+    timestamp: f64,
+) -> Result<tauri::ipc::Response, AppError> {
+    if let Some(frame) = ffmpeg::scrub::frame_at(&input_file, timestamp) {
+        return Ok(tauri::ipc::Response::new(frame));
+    }
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let frame = ffmpeg::preview::frame_at(
+        &ffmpeg_path.to_string_lossy(),
+        &input_file,
+        timestamp,
+        Some(ffmpeg::scrub::DEFAULT_SCRUB_WIDTH),
+    )
+    .await?;
+    Ok(tauri::ipc::Response::new(frame))
+}
+
+// Command: Save the frame at a timestamp as a PNG or JPEG file
+#[tauri::command]
+async fn extract_frame(
+    state: State<'_, AppState>,
+    input_file: String,
+    timestamp: f64,
+    output_file: String,
+    max_width: Option<u32>,
+) -> Result<String, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    ffmpeg::snapshot::extract_frame(&ffmpeg_path.to_string_lossy(), &input_file, timestamp, &output_file, max_width)
+        .await?;
+    Ok(output_file)
+}
+
+// Command: Evenly spaced JPEG thumbnails for a scrubber. Without an output
+// directory they go to a fresh temp folder.
+#[tauri::command]
+async fn generate_thumbnail_strip(
+    state: State<'_, AppState>,
+    input_file: String,
+    count: u32,
+    max_width: Option<u32>,
+    out_dir: Option<String>,
+) -> Result<Vec<ffmpeg::snapshot::Thumbnail>, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let out_dir = out_dir.map(PathBuf::from).unwrap_or_else(|| {
+        std::env::temp_dir()
+            .join("dreamcodec_thumbnails")
+            .join(Uuid::new_v4().to_string())
+    });
+    std::fs::create_dir_all(&out_dir)?;
+    ffmpeg::snapshot::thumbnail_strip(&ffmpeg_path.to_string_lossy(), &input_file, count, max_width, &out_dir).await
+}
+
+// Command: Rewrite global metadata without re-encoding (stream copy remux).
+// Without an output file the input is updated in place.
+#[tauri::command]
+async fn set_metadata(
+    state: State<'_, AppState>,
+    input_file: String,
+    output_file: Option<String>,
+    metadata: MetadataFields,
+) -> Result<String, AppError> {
+    let input_path = Path::new(&input_file);
+    if !input_path.exists() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let ffmpeg_path_str = ffmpeg_path.to_string_lossy().to_string();
+
+    let output_file = output_file.filter(|o| !naming::is_same_file(Path::new(o), input_path));
+    let target = output_file.as_deref().unwrap_or(&input_file);
+    naming::check_output_writable(Path::new(target)).map_err(AppError::Io)?;
+
+    match output_file {
+        Some(output_file) => {
+            ffmpeg::metadata::remux_with_metadata(&ffmpeg_path_str, &input_file, &output_file, &metadata).await?;
+            Ok(output_file)
+        }
+        None => {
+            let stem = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+            let ext = input_path.extension().and_then(|e| e.to_str()).unwrap_or("mp4");
+            let temp_file = input_path.with_file_name(format!("{}.metadata-tmp.{}", stem, ext));
+            let temp_str = temp_file.to_string_lossy().to_string();
+            ffmpeg::metadata::remux_with_metadata(&ffmpeg_path_str, &input_file, &temp_str, &metadata).await?;
+            std::fs::rename(&temp_file, input_path).map_err(|e| {
+                let _ = std::fs::remove_file(&temp_file);
+                AppError::Io(format!("Failed to replace {}: {}", input_file, e))
+            })?;
+            Ok(input_file)
+        }
+    }
+}
+
+// Command: Render a slideshow video from images and optional music
+#[tauri::command]
+async fn start_slideshow(
+    state: State<'_, AppState>,
+    output_file: String,
+    options: SlideshowOptions,
+) -> Result<String, AppError> {
+    if options.images.is_empty() {
+        return Err(AppError::Internal("Slideshow needs at least one image".to_string()));
+    }
+    if options.image_duration <= 0.0 {
+        return Err(AppError::Internal("Image duration must be greater than zero".to_string()));
+    }
+    for file in options.images.iter().chain(options.music_file.iter()) {
+        if !Path::new(file).exists() {
+            return Err(AppError::Io(format!("Slideshow input not found: {}", file)));
+        }
+    }
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+    }
+    naming::check_output_writable(Path::new(&output_file)).map_err(AppError::Io)?;
+
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let task_id = Uuid::new_v4().to_string();
+    let args = slideshow::build_args(&options, &output_file);
+
+    let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    manager.start_job(
+        task_id.clone(),
+        format!("slideshow ({} images)", options.images.len()),
+        output_file,
+        ffmpeg_path.to_string_lossy().to_string(),
+        args,
+        options.total_duration(),
+    )?;
+
+    Ok(task_id)
+}
+
+// Command: Encode an adaptive bitrate ladder and package it as HLS or DASH
+// into `output_dir`. Returns the task id; the manifest is the task's output.
+#[tauri::command]
+async fn package_streaming(
+    state: State<'_, AppState>,
+    input_file: String,
+    output_dir: String,
+    options: StreamingOptions,
+) -> Result<String, AppError> {
+    if !Path::new(&input_file).is_file() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+    if options.renditions.iter().any(|r| r.height == 0 || r.height % 2 != 0 || r.video_bitrate == 0) {
+        return Err(AppError::Ffmpeg(
+            "Each rendition needs an even, non-zero height and a video bitrate".to_string(),
+        ));
+    }
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+    let manifest = Path::new(&output_dir).join(options.format.manifest_name());
+    naming::check_output_writable(&manifest).map_err(AppError::Io)?;
+
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    let info = ffmpeg::probe_info(&ffmpeg_path, &input_file)
+        .await
+        .ok_or_else(|| AppError::Ffmpeg(format!("Failed to probe {}", input_file)))?;
+    if info.video_streams.is_empty() {
+        return Err(AppError::Ffmpeg("Streaming packages need a video input".to_string()));
+    }
+    let ladder = options.ladder(info.height);
+    info!(
+        "Streaming ladder for {}: {}",
+        input_file,
+        ladder.iter().map(|r| format!("{}p@{}k", r.height, r.video_bitrate)).collect::<Vec<_>>().join(", ")
+    );
+    let job = PackageJob {
+        output_dir: PathBuf::from(&output_dir),
+        options,
+        ladder,
+        has_audio: !info.audio_streams.is_empty(),
+    };
+
+    let task_id = Uuid::new_v4().to_string();
+    let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    manager.start_packaging(
+        task_id.clone(),
+        input_file,
+        manifest.to_string_lossy().to_string(),
+        ffmpeg_path,
+        job,
+        info.duration.unwrap_or(0.0),
+    )?;
+
+    Ok(task_id)
+}
+
+// Command: Join several files into one, losslessly when their streams match
+// and through the concat filter otherwise
+#[tauri::command]
+async fn concat_files(
+    state: State<'_, AppState>,
+    input_files: Vec<String>,
+    output_file: String,
+    options: ConcatOptions,
+) -> Result<String, AppError> {
+    if input_files.len() < 2 {
+        return Err(AppError::Internal("Choose at least two files to join".to_string()));
+    }
+    for file in &input_files {
+        if !Path::new(file).is_file() {
+            return Err(AppError::Io(format!("Input file not found: {}", file)));
+        }
+        if naming::is_same_file(Path::new(file), Path::new(&output_file)) {
+            return Err(AppError::Io(format!("Output path is the same as an input: {}", output_file)));
+        }
+    }
+    let output_ext = Path::new(&output_file)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !VIDEO_FORMATS.contains(&output_ext.as_str()) {
+        return Err(AppError::Ffmpeg(format!("Unsupported output format for joining: {}", output_ext)));
+    }
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+    }
+    naming::check_output_writable(Path::new(&output_file)).map_err(AppError::Io)?;
+
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    // ffprobe shows the profile, frame rate and codec setup a stream copy
+    // has to match; without it only re-encoding is safe.
+    let ffprobe_path = ffmpeg::probe::ffprobe_path_for(&ffmpeg_path);
+    let mut inputs = Vec::new();
+    let mut details = Vec::new();
+    for file in &input_files {
+        let info = match ffprobe_path {
+            Some(ref ffprobe) => {
+                let media = ffmpeg::probe::probe_media(ffprobe, file).await?;
+                let info = media.to_video_info();
+                details.push(media);
+                info
+            }
+            None => ffmpeg::probe_info(&ffmpeg_path, file)
+                .await
+                .ok_or_else(|| AppError::Ffmpeg(format!("Failed to probe {}", file)))?,
+        };
+        if info.video_streams.is_empty() {
+            return Err(AppError::Ffmpeg(format!("{} has no video stream", file)));
+        }
+        inputs.push(info);
+    }
+
+    let blocker = if ffprobe_path.is_some() {
+        concat::copy_blocker(&details)
+    } else {
+        Some("ffprobe wasn't found to compare the inputs' stream parameters".to_string())
+    };
+    let copy = match (options.mode, blocker) {
+        (ConcatMode::Reencode, _) => false,
+        (_, None) => true,
+        (ConcatMode::Copy, Some(reason)) => {
+            return Err(AppError::Ffmpeg(format!("The files can't be joined without re-encoding: {}", reason)));
+        }
+        (ConcatMode::Auto, Some(reason)) => {
+            info!("Joining by re-encoding: {}", reason);
+            false
+        }
+    };
+    let task_id = Uuid::new_v4().to_string();
+    let (args, list_path) = if copy {
+        let list_path = concat::write_list(&task_id, &input_files)
+            .map_err(|e| AppError::Io(format!("Failed to write concat list: {}", e)))?;
+        (concat::copy_args(&list_path, &output_file), Some(list_path))
+    } else {
+        let args = concat::reencode_args(&input_files, &inputs, &options, &output_file)
+            .map_err(|e| AppError::Ffmpeg(format!("The files can't be joined: {}", e)))?;
+        (args, None)
+    };
+    let duration = inputs.iter().filter_map(|i| i.duration).sum();
+    info!(
+        "Joining {} file(s) into {} ({})",
+        input_files.len(),
+        output_file,
+        if copy { "stream copy" } else { "re-encode" }
+    );
+
+    let label = format!("{} (+{})", input_files[0], input_files.len() - 1);
+    let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    manager.start_concat(task_id.clone(), label, output_file, ffmpeg_path, ConcatJob { args, list_path }, duration)?;
+
+    Ok(task_id)
+}
+
+// Command: Copy or move a file into the output structure and verify it
+// (checksum for raw copies, decode + duration check for stream copies)
+#[tauri::command]
+async fn start_verified_copy(
+    state: State<'_, AppState>,
+    input_file: String,
+    output_file: String,
+    options: CopyOptions,
+) -> Result<String, AppError> {
+    if !Path::new(&input_file).is_file() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+
+    let output_file = match options.output_template.as_deref().filter(|t| !t.trim().is_empty()) {
+        Some(template) => {
+            let output_path = Path::new(&output_file);
+            let output_dir = output_path.parent().unwrap_or(Path::new(""));
+            let ext = output_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            naming::render_output_path(template, &input_file, output_dir, ext)
+                .to_string_lossy()
+                .to_string()
+        }
+        None => output_file,
+    };
+    if naming::is_same_file(Path::new(&input_file), Path::new(&output_file)) {
+        return Err(AppError::Io(format!("Output path is the same as the input: {}", output_file)));
+    }
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+    }
+    naming::check_output_writable(Path::new(&output_file)).map_err(AppError::Io)?;
+
+    // Raw copies don't need FFmpeg at all.
+    let ffmpeg_path = match options.mode {
+        CopyMode::StreamCopy => get_ffmpeg_path(&state).await?.to_string_lossy().to_string(),
+        CopyMode::Raw => String::new(),
+    };
+
+    let task_id = Uuid::new_v4().to_string();
+    let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    manager.start_copy(
+        task_id.clone(),
+        input_file,
+        output_file,
+        ffmpeg_path,
+        options.mode,
+        options.move_source,
+    )?;
+
+    Ok(task_id)
+}
+
+// Command: Copy video while reducing TrueHD/DTS-HD audio to a lossy core or
+// EAC-3 for players that can't decode lossless surround
+#[tauri::command]
+async fn start_core_audio_extraction(
+    state: State<'_, AppState>,
+    input_file: String,
+    output_file: String,
+    mode: Option<LosslessAudioMode>,
+) -> Result<String, AppError> {
+    if !Path::new(&input_file).is_file() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+    if naming::is_same_file(Path::new(&input_file), Path::new(&output_file)) {
+        return Err(AppError::Io(format!("Output path is the same as the input: {}", output_file)));
+    }
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+    }
+    naming::check_output_writable(Path::new(&output_file)).map_err(AppError::Io)?;
+
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let mut cmd = Command::new(&ffmpeg_path);
+    cmd.args(["-hide_banner", "-i", &input_file]);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    let output = watchdog::output(&mut cmd).await?;
+    let info = ffmpeg::VideoInfo::parse(&String::from_utf8_lossy(&output.stderr))?;
+    if !info.audio_streams.iter().any(|s| matches!(s.codec.as_str(), "dts" | "truehd" | "mlp")) {
+        return Err(AppError::Ffmpeg("Input has no TrueHD or DTS audio track.".to_string()));
+    }
+
+    let mut args: Vec<String> = ["-y", "-hide_banner", "-progress", ffmpeg::PROGRESS_PIPE, "-nostats", "-i", &input_file]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.extend(ffmpeg::tracks::core_extraction_args(&info.audio_streams, mode.unwrap_or_default()));
+    args.push(output_file.clone());
+
+    let task_id = Uuid::new_v4().to_string();
+    let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    manager.start_job(
+        task_id.clone(),
+        input_file,
+        output_file,
+        ffmpeg_path.to_string_lossy().to_string(),
+        args,
+        info.duration.unwrap_or(0.0),
+    )?;
+
+    Ok(task_id)
+}
+
+// Command: Write audio tracks to their own files, copied into a container
+// matching their codec or transcoded to one audio format
+#[tauri::command]
+async fn extract_audio(
+    state: State<'_, AppState>,
+    input_file: String,
+    output_dir: String,
+    options: AudioExtractOptions,
+) -> Result<String, AppError> {
+    if !Path::new(&input_file).is_file() {
+        return Err(AppError::Io(format!("Input file not found: {}", input_file)));
+    }
+    if let Some(ref format) = options.format {
+        if !AUDIO_FORMATS.contains(&format.to_lowercase().as_str()) {
+            return Err(AppError::Ffmpeg(format!("Unsupported audio format: {}", format)));
+        }
+    }
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    let info = ffmpeg::probe_info(&ffmpeg_path, &input_file)
+        .await
+        .ok_or_else(|| AppError::Ffmpeg(format!("Failed to probe {}", input_file)))?;
+    if info.audio_streams.is_empty() {
+        return Err(AppError::Ffmpeg("Input has no audio tracks.".to_string()));
+    }
+    let mut tracks = if options.tracks.is_empty() {
+        (0..info.audio_streams.len()).collect()
+    } else {
+        options.tracks.clone()
+    };
+    tracks.sort_unstable();
+    tracks.dedup();
+
+    let mut outputs = Vec::new();
+    for track in tracks {
+        let stream = info
+            .audio_streams
+            .get(track)
+            .ok_or_else(|| AppError::Ffmpeg(format!("Input has no audio track {}", track + 1)))?;
+        let ext = audio_extract::extension(&stream.codec, options.format.as_deref());
+        let path = audio_extract::track_path(&input_file, Path::new(&output_dir), track, stream, &ext);
+        if naming::is_same_file(Path::new(&input_file), Path::new(&path)) {
+            return Err(AppError::Io(format!("Output path is the same as the input: {}", path)));
+        }
+        naming::check_output_writable(Path::new(&path)).map_err(AppError::Io)?;
+        outputs.push((track, path));
+    }
+    info!(
+        "Extracting {} audio track(s) from {}: {}",
+        outputs.len(),
+        input_file,
+        outputs.iter().map(|(_, path)| path.as_str()).collect::<Vec<_>>().join(", ")
+    );
+
+    let args = audio_extract::extract_args(&input_file, &outputs, &options);
+    let task_id = Uuid::new_v4().to_string();
+    let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    manager.start_job(
+        task_id.clone(),
+        input_file,
+        outputs[0].1.clone(),
+        ffmpeg_path,
+        args,
+        info.duration.unwrap_or(0.0),
+    )?;
+
+    Ok(task_id)
+}
+
+// Command: Mux separate per-language audio files with a video into one
+// MKV/MP4, copying tracks the container accepts and encoding the rest
+#[tauri::command]
+async fn start_audio_mux(
+    state: State<'_, AppState>,
+    video_file: String,
+    output_file: String,
+    options: MuxOptions,
+) -> Result<String, AppError> {
+    if options.audio_tracks.is_empty() {
+        return Err(AppError::Internal("Add at least one audio file to mux".to_string()));
+    }
+    let ext = Path::new(&output_file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !matches!(ext.as_str(), "mkv" | "mp4" | "mov") {
+        return Err(AppError::Ffmpeg("Audio muxing needs an MKV, MP4 or MOV output.".to_string()));
+    }
+    for file in std::iter::once(&video_file).chain(options.audio_tracks.iter().map(|t| &t.file)) {
+        if !Path::new(file).is_file() {
+            return Err(AppError::Io(format!("Mux input not found: {}", file)));
+        }
+        if naming::is_same_file(Path::new(file), Path::new(&output_file)) {
+            return Err(AppError::Io(format!("Output path is the same as an input file: {}", file)));
+        }
+    }
+    if let Some(parent) = Path::new(&output_file).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::Io(format!("Failed to create output directory: {}", e)))?;
+    }
+    naming::check_output_writable(Path::new(&output_file)).map_err(AppError::Io)?;
+
+    let ffmpeg_path = get_ffmpeg_path(&state).await?.to_string_lossy().to_string();
+    let video = ffmpeg::probe_info(&ffmpeg_path, &video_file)
+        .await
+        .ok_or_else(|| AppError::Ffmpeg(format!("Failed to probe {}", video_file)))?;
+    let mut source_codecs = Vec::new();
+    for track in &options.audio_tracks {
+        let info = ffmpeg::probe_info(&ffmpeg_path, &track.file).await;
+        let codec = info.and_then(|i| i.audio_streams.first().map(|s| s.codec.clone()));
+        if codec.is_none() {
+            return Err(AppError::Ffmpeg(format!("No audio stream found in {}", track.file)));
+        }
+        source_codecs.push(codec);
+    }
+
+    let args = ffmpeg::mux::build_args(
+        &video_file,
+        &options,
+        video.audio_streams.len(),
+        &source_codecs,
+        &output_file,
+    );
+    let task_id = Uuid::new_v4().to_string();
+    let mut manager = state.ffmpeg_manager.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    manager.start_job(
+        task_id.clone(),
+        video_file,
+        output_file,
+        ffmpeg_path,
+        args,
+        video.duration.unwrap_or(0.0),
+    )?;
+
+    Ok(task_id)
+}
+
+// Command: Extract matching input/output frames and a difference heatmap
+#[tauri::command]
+async fn compare_frames(
+    state: State<'_, AppState>,
+    input_file: String,
+    output_file: String,
+    timestamp: f64,
+    max_width: Option<u32>,
+) -> Result<ffmpeg::preview::FrameComparison, AppError> {
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    let out_dir = std::env::temp_dir()
+        .join("dreamcodec_compare")
+        .join(Uuid::new_v4().to_string());
+    ffmpeg::preview::compare_frames(
+        &ffmpeg_path.to_string_lossy(),
+        &input_file,
+        &output_file,
+        timestamp,
+        max_width,
+        &out_dir,
+    )
+    .await
+}
+
+// Command: Get backend settings
+#[tauri::command]
+async fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, AppError> {
+    let settings = state.settings.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(settings.clone())
+}
+
+// Command: Apply the watch folder rules to a new file: ignore it, wait for
+// it to settle, or convert it with the chosen custom preset
+#[tauri::command]
+async fn evaluate_watch_file(state: State<'_, AppState>, file_path: String) -> Result<watch::WatchDecision, AppError> {
+    let folders = {
+        let settings = state.settings.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+        settings.watch_folders.clone()
+    };
+    let ffmpeg_path = get_ffmpeg_path(&state).await?;
+    Ok(watch::evaluate(&ffmpeg_path.to_string_lossy(), &folders, &file_path).await)
+}
+
+// Command: Totals of the conversions finished since launch
+#[tauri::command]
+fn get_session_stats() -> stats::SessionStats {
+    stats::session_stats()
+}
+
+// Command: Replace and persist backend settings
+#[tauri::command]
+async fn update_settings(state: State<'_, AppState>, settings: AppSettings) -> Result<(), AppError> {
+    settings::save(&settings)?;
+    watchdog::set_timeout(settings.aux_timeout_secs);
+    let mut stored = state.settings.lock().map_err(|e| AppError::Internal(e.to_string()))?;
+    *stored = settings;
+    Ok(())
+}
+
+// Command: Create a drop-on launcher (e.g. "Convert to ProRes") that runs
+// the files dropped on it through a custom preset without opening the window
+#[tauri::command]
+async fn create_droplet(
+    label: String,
+    preset: String,
+    directory: Option<String>,
+    output_dir: Option<String>,
+) -> Result<String, AppError> {
+    let preset = presets::load()
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(preset.trim()))
+        .ok_or_else(|| AppError::Internal(format!("Custom preset not found: {}", preset)))?;
+    let label = match label.trim() {
+        "" => format!("Convert to {}", preset.name),
+        label => label.to_string(),
+    };
+    let directory = match directory {
+        Some(directory) => PathBuf::from(directory),
+        None => dirs::desktop_dir().ok_or_else(|| AppError::Io("No desktop folder found".to_string()))?,
+    };
+    std::fs::create_dir_all(&directory)?;
+    let exe = std::env::current_exe()?;
+    let kind = LauncherKind::current();
+    let contents = droplets::launcher(kind, &exe, &label, &preset.name, output_dir.as_deref())?;
+    let path = directory.join(droplets::file_name(&label, kind));
+
+    let launcher = if kind == LauncherKind::AppleScript {
+        // Only a compiled script app accepts files dropped onto it.
+        let script = std::env::temp_dir().join(droplets::file_name(&label, kind));
+        std::fs::write(&script, contents)?;
+        let app = path.with_extension("app");
+        let mut cmd = Command::new("osacompile");
+        cmd.arg("-o").arg(&app).arg(&script);
+        let output = watchdog::output(&mut cmd).await;
+        let _ = std::fs::remove_file(&script);
+        let output = output?;
+        if !output.status.success() {
+            return Err(AppError::Internal(format!(
+                "osacompile failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        app
+    } else {
+        std::fs::write(&path, contents)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        path
+    };
+    info!("Created launcher {:?} for preset {}", launcher, preset.name);
+    Ok(launcher.to_string_lossy().to_string())
+}
+
+// Command: Save a custom preset, replacing one with the same name
+#[tauri::command]
+fn save_custom_preset(preset: CustomPreset) -> Result<CustomPreset, AppError> {
+    presets::save_preset(preset)
+}
+
+// Command: List the user's custom presets, annotated with availability
+#[tauri::command]
+async fn list_custom_presets(state: State<'_, AppState>) -> Result<Vec<Annotated<CustomPreset>>, AppError> {
+    let caps = preset_capabilities(&state).await;
+    Ok(presets::load()
+        .into_iter()
+        .map(|preset| {
+            let mut chain = ffmpeg::filters::audio_filter_chain(&preset.options, None);
+            if get_format_info(&preset.container).supports_video {
+                chain.extend(ffmpeg::filters::video_filter_chain(&preset.options, "", None));
+            }
+            let names = availability::filter_names(&chain);
+            let filters: Vec<&str> = names.iter().map(String::as_str).collect();
+            Annotated {
+                availability: availability::check(&caps, &preset.encoder, None, &filters),
+                preset,
+            }
+        })
+        .collect())
+}
+
+// Command: Delete a custom preset by name
+#[tauri::command]
+fn delete_custom_preset(name: String) -> Result<(), AppError> {
+    presets::delete_preset(&name)
+}
+
+// Command: Export custom presets (all when no names are given) to a JSON file
+#[tauri::command]
+fn export_presets(path: String, names: Option<Vec<String>>) -> Result<usize, AppError> {
+    presets::export_presets(Path::new(&path), &names.unwrap_or_default())
+}
+
+// Command: Import custom presets from a JSON file
+#[tauri::command]
+fn import_presets(path: String, overwrite: Option<bool>) -> Result<Vec<String>, AppError> {
+    presets::import_presets(Path::new(&path), overwrite.unwrap_or(false))
+}
+
+#[tauri::command]
 fn log_message(level: String, message: String) {
     match level.as_str() {
         "info" => info!("{}", message),
@@ -646,6 +2666,30 @@ pub fn run() {
                 let _ = app_handle.emit("panic", (payload, location));
             }));
 
+            let state = app.state::<AppState>();
+            if let Ok(mut manager) = state.ffmpeg_manager.lock() {
+                manager.set_app_handle(app.handle().clone());
+            }
+            if let Ok(settings) = state.settings.lock() {
+                watchdog::set_timeout(settings.aux_timeout_secs);
+            }
+            info!("Loaded {} Adobe preset(s)", ffmpeg::adobe_presets::reload().len());
+            metrics::spawn_textfile_exporter(state.ffmpeg_manager.clone(), state.queue.clone(), state.settings.clone());
+            // Launchers start the app with files to convert and no window;
+            // the persistent queue is left for the next normal start.
+            let cli_args: Vec<String> = std::env::args().skip(1).collect();
+            match droplets::parse_args(&cli_args) {
+                Some(quick) => {
+                    if let Some(window) = app.get_webview_window("main") {
+                        let _ = window.hide();
+                    }
+                    spawn_quick_convert(app.handle().clone(), quick);
+                }
+                None => spawn_queue_runner(app.handle().clone()),
+            }
+            spawn_job_event_pump(app.handle().clone());
+            spawn_encoder_self_test(app.handle().clone());
+
             // Ensure default output directory is created on app startup
             if let Err(e) = get_default_output_dir() {
                 error!("Warning: Failed to create default output directory: {}", e);
@@ -663,25 +2707,84 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             check_ffmpeg,
             download_ffmpeg,
+            install_ffmpeg_from_file,
+            register_ffmpeg_installation,
+            set_encoder_defaults,
             get_cpu_info,
             get_gpu_info,
             get_available_encoders,
+            test_encoder,
+            get_recommended_defaults,
             get_ffmpeg_version,
             start_conversion,
+            start_multi_conversion,
+            preview_conversion_command,
+            enqueue_conversion,
+            get_queue,
+            remove_queued_job,
+            clear_finished_jobs,
+            get_history,
+            clear_history,
+            set_history_note,
+            set_history_review,
+            create_droplet,
+            list_tags,
+            requeue_from_history,
+            export_history,
             get_conversion_progress,
             cancel_conversion,
             get_video_duration,
             get_video_info,
+            probe_media,
+            validate_batch,
+            benchmark_storage,
+            get_storage_benchmark,
+            save_custom_preset,
+            list_custom_presets,
+            delete_custom_preset,
+            export_presets,
+            import_presets,
+            extract_captions,
+            scan_disc_folder,
+            detect_input_segments,
+            parse_episode_name,
+            preview_output_path,
             get_supported_formats,
+            suggest_container,
             get_adobe_presets_list,
+            reload_adobe_presets,
+            get_clip_presets_list,
+            get_media_server_presets_list,
+            get_screen_recording_presets_list,
+            get_decoder_blacklist,
+            clear_decoder_blacklist,
             get_format_information,
             check_encoder_available,
             get_default_output_dir,
             open_file_location,
+            play_file,
+            preview_frame_at,
+            prepare_scrub_preview,
+            scrub_frame_at,
+            extract_frame,
+            generate_thumbnail_strip,
+            compare_frames,
+            start_slideshow,
+            package_streaming,
+            concat_files,
+            start_verified_copy,
+            start_core_audio_extraction,
+            extract_audio,
+            start_audio_mux,
+            set_metadata,
             get_log_file_path,
             get_log_file_content,
             clear_session_log,
             get_log_dir,
+            get_settings,
+            update_settings,
+            get_session_stats,
+            evaluate_watch_file,
             log_message,
         ])
         .run(tauri::generate_context!())
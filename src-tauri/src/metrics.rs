@@ -0,0 +1,159 @@
+use crate::ffmpeg::FfmpegManager;
+use crate::queue::QueueManager;
+use crate::settings::AppSettings;
+use log::warn;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the Prometheus textfile is rewritten.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(15);
+
+static JOBS_COMPLETED: AtomicU64 = AtomicU64::new(0);
+static JOBS_FAILED: AtomicU64 = AtomicU64::new(0);
+static JOBS_CANCELLED: AtomicU64 = AtomicU64::new(0);
+static GPU_JOBS: AtomicU64 = AtomicU64::new(0);
+static GPU_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+/// Last encode speed reported by FFmpeg, stored as `f64` bits.
+static LAST_ENCODE_FPS: AtomicU64 = AtomicU64::new(0);
+
+pub fn job_completed() {
+    JOBS_COMPLETED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn job_failed() {
+    JOBS_FAILED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn job_cancelled() {
+    JOBS_CANCELLED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn gpu_job_started() {
+    GPU_JOBS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A GPU job gave up on hardware encoding and fell back to the CPU encoder.
+pub fn gpu_fallback() {
+    GPU_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_fps(fps: f64) {
+    LAST_ENCODE_FPS.store(fps.to_bits(), Ordering::Relaxed);
+}
+
+/// Render all metrics in the Prometheus text exposition format.
+pub fn render(pending: usize, running: usize) -> String {
+    let gpu_jobs = GPU_JOBS.load(Ordering::Relaxed);
+    let gpu_fallbacks = GPU_FALLBACKS.load(Ordering::Relaxed);
+    let fallback_ratio = if gpu_jobs > 0 {
+        gpu_fallbacks as f64 / gpu_jobs as f64
+    } else {
+        0.0
+    };
+
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        out.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    };
+
+    metric(
+        "dreamcodec_jobs_completed_total",
+        "counter",
+        "Jobs finished successfully since start.",
+        JOBS_COMPLETED.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+        "dreamcodec_jobs_failed_total",
+        "counter",
+        "Jobs that failed since start.",
+        JOBS_FAILED.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+        "dreamcodec_jobs_cancelled_total",
+        "counter",
+        "Jobs cancelled since start.",
+        JOBS_CANCELLED.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+        "dreamcodec_jobs_pending",
+        "gauge",
+        "Jobs waiting to start.",
+        pending.to_string(),
+    );
+    metric(
+        "dreamcodec_jobs_running",
+        "gauge",
+        "Jobs currently encoding.",
+        running.to_string(),
+    );
+    metric(
+        "dreamcodec_gpu_jobs_total",
+        "counter",
+        "Jobs started with a hardware encoder.",
+        gpu_jobs.to_string(),
+    );
+    metric(
+        "dreamcodec_gpu_fallbacks_total",
+        "counter",
+        "Hardware encoder jobs that fell back to a CPU encoder.",
+        gpu_fallbacks.to_string(),
+    );
+    metric(
+        "dreamcodec_gpu_fallback_ratio",
+        "gauge",
+        "Share of hardware encoder jobs that fell back to the CPU.",
+        format!("{:.4}", fallback_ratio),
+    );
+    metric(
+        "dreamcodec_encode_fps",
+        "gauge",
+        "Most recent encode speed reported by FFmpeg, in frames per second.",
+        format!("{:.2}", f64::from_bits(LAST_ENCODE_FPS.load(Ordering::Relaxed))),
+    );
+
+    out
+}
+
+/// Write the metrics file atomically (temp file + rename), as the node
+/// exporter textfile collector expects.
+fn write_textfile(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension("prom.tmp");
+    std::fs::write(&temp, content)?;
+    std::fs::rename(&temp, path)
+}
+
+/// Periodically export metrics to the file configured in the settings
+/// (`metrics_file`). Nothing is written while the setting is empty.
+pub fn spawn_textfile_exporter(
+    manager: Arc<Mutex<FfmpegManager>>,
+    queue: Arc<Mutex<QueueManager>>,
+    settings: Arc<Mutex<AppSettings>>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let Some(path) = settings.lock().ok().and_then(|s| s.metrics_file.clone()) else {
+                continue;
+            };
+            let (pending, running) = match manager.lock() {
+                Ok(manager) => manager.job_counts(),
+                Err(_) => continue,
+            };
+            // Queued jobs only get a manager task once they start.
+            let queued = queue.lock().map(|q| q.pending_count()).unwrap_or(0);
+
+            if let Err(e) = write_textfile(Path::new(&path), &render(pending + queued, running)) {
+                warn!("Failed to write metrics file {}: {}", path, e);
+            }
+        }
+    });
+}
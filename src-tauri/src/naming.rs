@@ -0,0 +1,261 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Season/episode information parsed from a TV rip file name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeInfo {
+    pub title: String,
+    pub season: u32,
+    pub episode: u32,
+    pub episode_end: Option<u32>,
+}
+
+/// Turn a dotted/underscored release name fragment into a readable title.
+fn clean_title(raw: &str) -> String {
+    let bracket_tags = Regex::new(r"\[[^\]]*\]|\([^)]*\)").expect("Invalid regex");
+    let without_tags = bracket_tags.replace_all(raw, " ");
+    without_tags
+        .replace(['.', '_'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| c == '-' || c.is_whitespace())
+        .to_string()
+}
+
+/// Parse `S01E02` / `S01E02E03`, `1x02`, or anime-style `Title - 01` names.
+pub fn parse_episode(file_name: &str) -> Option<EpisodeInfo> {
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_name.to_string());
+
+    let patterns = [
+        r"(?i)^(?P<title>.*?)[\s._-]*S(?P<season>\d{1,2})[\s._-]*E(?P<episode>\d{1,3})(?:[\s._-]*E(?P<end>\d{1,3}))?",
+        r"(?i)^(?P<title>.*?)[\s._-]*(?P<season>\d{1,2})x(?P<episode>\d{2,3})\b",
+        r"^(?P<title>.*?)\s+-\s+(?P<episode>\d{2,3})(?:v\d)?\b",
+    ];
+
+    for pattern in patterns {
+        let regex = Regex::new(pattern).expect("Invalid regex");
+        if let Some(caps) = regex.captures(&stem) {
+            let title = clean_title(caps.name("title").map(|m| m.as_str()).unwrap_or(""));
+            return Some(EpisodeInfo {
+                title,
+                season: caps
+                    .name("season")
+                    .and_then(|m| m.as_str().parse().ok())
+                    .unwrap_or(1),
+                episode: caps.name("episode")?.as_str().parse().ok()?,
+                episode_end: caps.name("end").and_then(|m| m.as_str().parse().ok()),
+            });
+        }
+    }
+
+    None
+}
+
+/// Strip characters that are invalid in Windows file names from a token value.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*'))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Render an output path template relative to `output_dir`.
+///
+/// Supported tokens: `{title}`, `{season}`, `{episode}` (zero-padded to two
+/// digits), `{name}` (input file stem) and `{ext}` (output extension). Episode
+/// tokens fall back to the input name when the file isn't recognized as a TV
+/// episode, so the template never produces empty path segments.
+pub fn render_output_path(template: &str, input_file: &str, output_dir: &Path, ext: &str) -> PathBuf {
+    let name = Path::new(input_file)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let episode = parse_episode(input_file);
+
+    let title = episode
+        .as_ref()
+        .map(|e| e.title.clone())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| name.clone());
+    let season = episode
+        .as_ref()
+        .map(|e| format!("{:02}", e.season))
+        .unwrap_or_else(|| "00".to_string());
+    let episode_number = episode
+        .as_ref()
+        .map(|e| match e.episode_end {
+            Some(end) => format!("{:02}-E{:02}", e.episode, end),
+            None => format!("{:02}", e.episode),
+        })
+        .unwrap_or_else(|| "00".to_string());
+
+    let rendered = template
+        .replace("{title}", &sanitize(&title))
+        .replace("{season}", &season)
+        .replace("{episode}", &episode_number)
+        .replace("{name}", &sanitize(&name))
+        .replace("{ext}", ext);
+
+    let mut path = output_dir.to_path_buf();
+    for part in rendered.split(['/', '\\']).filter(|p| !p.trim().is_empty()) {
+        path.push(part.trim());
+    }
+
+    // Appended rather than set: `{name}` may itself contain dots.
+    let suffix = format!(".{}", ext);
+    let has_ext = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().to_lowercase().ends_with(&suffix.to_lowercase()));
+    if !template.contains("{ext}") && !has_ext {
+        let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(&suffix);
+        path.set_file_name(file_name);
+    }
+    path
+}
+
+/// Whether two paths name the same file. Resolves `.`/`..`, symlinks and,
+/// on Windows, differences in letter case.
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ if cfg!(target_os = "windows") => {
+            a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+        }
+        _ => a == b,
+    }
+}
+
+/// Temporary output next to `output`, used when a job replaces its own input.
+/// Keeps the extension so FFmpeg still picks the right muxer.
+pub fn staging_path(output: &Path) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match output.extension() {
+        Some(ext) => format!("{}.dreamcodec-staging.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.dreamcodec-staging", stem),
+    };
+    output.with_file_name(name)
+}
+
+/// `output`, or `<name>-2.<ext>`, `<name>-3.<ext>`... when a file by that
+/// name exists; for outputs whose name the user didn't choose.
+pub fn unused_path(output: &Path) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = output.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    std::iter::once(output.to_path_buf())
+        .chain((2..).map(|n| output.with_file_name(format!("{}-{}{}", stem, n, ext))))
+        .find(|path| !path.exists())
+        .expect("Unbounded name sequence")
+}
+
+/// Check up front that `output` can be written, so a locked or read-only file
+/// gives a clear error instead of FFmpeg failing at its first write.
+pub fn check_output_writable(output: &Path) -> Result<(), String> {
+    match std::fs::metadata(output) {
+        Ok(meta) if meta.permissions().readonly() => {
+            return Err(format!("Output file is read-only: {}", output.display()));
+        }
+        Ok(_) => {
+            // Opening without truncating leaves the existing file intact.
+            return match std::fs::OpenOptions::new().write(true).open(output) {
+                Ok(_) => Ok(()),
+                // ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION on Windows.
+                Err(e) if matches!(e.raw_os_error(), Some(32) | Some(33)) && cfg!(target_os = "windows") => Err(
+                    format!("Output file is in use by another program (close it and retry): {}", output.display()),
+                ),
+                Err(e) => Err(format!("Output file is not writable: {} ({})", output.display(), e)),
+            };
+        }
+        Err(_) => {}
+    }
+
+    let Some(dir) = output.parent().filter(|d| !d.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    let probe = dir.join(format!(".dreamcodec-write-test-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e) => Err(format!("Output folder is not writable: {} ({})", dir.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scene_style_names() {
+        let info = parse_episode("The.Expanse.S02E05.1080p.BluRay.x264.mkv").unwrap();
+        assert_eq!(info.title, "The Expanse");
+        assert_eq!((info.season, info.episode, info.episode_end), (2, 5, None));
+    }
+
+    #[test]
+    fn parses_multi_episode_and_anime_names() {
+        let multi = parse_episode("Show Name - S01E01E02.mkv").unwrap();
+        assert_eq!((multi.episode, multi.episode_end), (1, Some(2)));
+
+        let anime = parse_episode("[Group] Some Anime - 07 [1080p].mkv").unwrap();
+        assert_eq!(anime.title, "Some Anime");
+        assert_eq!((anime.season, anime.episode), (1, 7));
+    }
+
+    #[test]
+    fn staging_keeps_directory_and_extension() {
+        assert_eq!(
+            staging_path(Path::new("videos").join("clip.mkv").as_path()),
+            Path::new("videos").join("clip.dreamcodec-staging.mkv")
+        );
+    }
+
+    #[test]
+    fn unused_path_skips_existing_files() {
+        let dir = std::env::temp_dir().join(format!("dreamcodec-unused-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("clip.mp4"), b"").unwrap();
+        assert_eq!(unused_path(&dir.join("clip.mp4")), dir.join("clip-2.mp4"));
+        assert_eq!(unused_path(&dir.join("other.mp4")), dir.join("other.mp4"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn renders_media_server_layout() {
+        let path = render_output_path(
+            "{title}/Season {season}/{title} - S{season}E{episode}",
+            "The.Expanse.S02E05.1080p.mkv",
+            Path::new("out"),
+            "mp4",
+        );
+        assert_eq!(
+            path,
+            Path::new("out")
+                .join("The Expanse")
+                .join("Season 02")
+                .join("The Expanse - S02E05.mp4")
+        );
+    }
+
+    #[test]
+    fn keeps_dots_in_the_input_name() {
+        let path = render_output_path("{name}", "The.Expanse.S02E05.1080p.mkv", Path::new("out"), "mp4");
+        assert_eq!(path, Path::new("out").join("The.Expanse.S02E05.1080p.mp4"));
+        let path = render_output_path("{name}.mp4", "clip.mkv", Path::new("out"), "mp4");
+        assert_eq!(path, Path::new("out").join("clip.mp4"));
+    }
+}
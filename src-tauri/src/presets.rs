@@ -0,0 +1,178 @@
+use crate::error::AppError;
+use crate::ffmpeg::{ConversionOptions, FfmpegDownloader, AUDIO_FORMATS, VIDEO_FORMATS};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File inside the app data directory holding the user's presets.
+const PRESETS_FILE: &str = "custom_presets.json";
+/// Version written into exported preset files.
+const EXPORT_VERSION: u32 = 1;
+
+/// A saved encode recipe: encoder, container and the job options
+/// (filters, audio, track handling) to apply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomPreset {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub encoder: String,
+    /// Encoder speed preset (e.g. `medium`, `p5`).
+    pub preset: String,
+    /// Output container extension.
+    pub container: String,
+    #[serde(default, alias = "cpuThreads")]
+    pub cpu_threads: Option<u32>,
+    #[serde(default)]
+    pub options: ConversionOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PresetExport {
+    version: u32,
+    presets: Vec<CustomPreset>,
+}
+
+fn presets_path() -> Result<PathBuf, AppError> {
+    Ok(FfmpegDownloader::get_ffmpeg_app_dir()?.join(PRESETS_FILE))
+}
+
+pub fn load() -> Vec<CustomPreset> {
+    presets_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(presets: &[CustomPreset]) -> Result<(), AppError> {
+    let path = presets_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(presets)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize presets: {}", e)))?;
+    std::fs::write(&path, content)?;
+    Ok(())
+}
+
+/// Check a preset and drop the parts of its options that belong to one job
+/// (inputs, IDs) rather than to the recipe.
+fn sanitize(mut preset: CustomPreset) -> Result<CustomPreset, AppError> {
+    preset.name = preset.name.trim().to_string();
+    if preset.name.is_empty() {
+        return Err(AppError::Internal("Preset name must not be empty".to_string()));
+    }
+    preset.container = preset.container.trim().trim_start_matches('.').to_lowercase();
+    if !VIDEO_FORMATS.contains(&preset.container.as_str()) && !AUDIO_FORMATS.contains(&preset.container.as_str()) {
+        return Err(AppError::Internal(format!("Unsupported container: {}", preset.container)));
+    }
+    let options = &mut preset.options;
+    options.input_segments.clear();
+    options.disc_title = None;
+    options.external_id = None;
+    options.idempotency_key = None;
+    Ok(preset)
+}
+
+/// Add `incoming` presets to `existing`. Presets with a name already taken
+/// (case-insensitive) replace it when `overwrite` is set and are skipped
+/// otherwise. Returns the names added or replaced.
+fn merge(existing: &mut Vec<CustomPreset>, incoming: Vec<CustomPreset>, overwrite: bool) -> Vec<String> {
+    let mut merged = Vec::new();
+    for preset in incoming {
+        match existing.iter().position(|p| p.name.eq_ignore_ascii_case(&preset.name)) {
+            Some(_) if !overwrite => continue,
+            Some(index) => existing[index] = preset.clone(),
+            None => existing.push(preset.clone()),
+        }
+        merged.push(preset.name);
+    }
+    merged
+}
+
+/// Save a preset, replacing one with the same name.
+pub fn save_preset(preset: CustomPreset) -> Result<CustomPreset, AppError> {
+    let preset = sanitize(preset)?;
+    let mut presets = load();
+    merge(&mut presets, vec![preset.clone()], true);
+    save(&presets)?;
+    Ok(preset)
+}
+
+pub fn delete_preset(name: &str) -> Result<(), AppError> {
+    let mut presets = load();
+    let before = presets.len();
+    presets.retain(|p| !p.name.eq_ignore_ascii_case(name));
+    if presets.len() == before {
+        return Err(AppError::Internal(format!("Preset not found: {}", name)));
+    }
+    save(&presets)
+}
+
+/// Write the named presets (all when `names` is empty) to a JSON file.
+pub fn export_presets(path: &Path, names: &[String]) -> Result<usize, AppError> {
+    let presets: Vec<CustomPreset> = load()
+        .into_iter()
+        .filter(|p| names.is_empty() || names.iter().any(|n| n.eq_ignore_ascii_case(&p.name)))
+        .collect();
+    let export = PresetExport {
+        version: EXPORT_VERSION,
+        presets,
+    };
+    let content = serde_json::to_string_pretty(&export)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize presets: {}", e)))?;
+    std::fs::write(path, content)?;
+    Ok(export.presets.len())
+}
+
+/// Read presets exported by `export_presets` (or a bare JSON array of them).
+fn parse_export(content: &str) -> Result<Vec<CustomPreset>, AppError> {
+    serde_json::from_str::<PresetExport>(content)
+        .map(|export| export.presets)
+        .or_else(|_| serde_json::from_str::<Vec<CustomPreset>>(content))
+        .map_err(|e| AppError::Internal(format!("Not a preset file: {}", e)))
+}
+
+/// Import presets from a JSON file. Returns the names imported.
+pub fn import_presets(path: &Path, overwrite: bool) -> Result<Vec<String>, AppError> {
+    let content = std::fs::read_to_string(path)?;
+    let incoming = parse_export(&content)?
+        .into_iter()
+        .map(sanitize)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut presets = load();
+    let imported = merge(&mut presets, incoming, overwrite);
+    save(&presets)?;
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_skip_or_replace_existing_names() {
+        let content = r#"{"version": 1, "presets": [
+            {"name": "YouTube 1080p", "encoder": "libx264", "preset": "slow", "container": "mp4",
+             "options": {"fadeIn": 1.0}},
+            {"name": "Archive", "encoder": "libx265", "preset": "medium", "container": "mkv"}
+        ]}"#;
+        let incoming = parse_export(content).unwrap();
+        assert_eq!(incoming[0].options.fade_in, Some(1.0));
+
+        let mut existing = vec![CustomPreset {
+            name: "youtube 1080P".to_string(),
+            description: None,
+            encoder: "h264_nvenc".to_string(),
+            preset: "p5".to_string(),
+            container: "mp4".to_string(),
+            cpu_threads: None,
+            options: ConversionOptions::default(),
+        }];
+        assert_eq!(merge(&mut existing, incoming.clone(), false), vec!["Archive"]);
+        assert_eq!(existing[0].encoder, "h264_nvenc");
+        assert_eq!(merge(&mut existing, incoming, true), vec!["YouTube 1080p", "Archive"]);
+        assert_eq!(existing[0].encoder, "libx264");
+        assert_eq!(existing.len(), 2);
+    }
+}
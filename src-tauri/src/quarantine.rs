@@ -0,0 +1,131 @@
+use crate::naming;
+use log::{info, warn};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Report appended for every quarantined input.
+const REPORT_FILE: &str = "failed_jobs.csv";
+const REPORT_HEADER: &str = "timestamp,file,error_class,attempts,error\n";
+
+/// Coarse failure category for the report, so a night of failures can be
+/// sorted at a glance.
+pub fn classify_error(message: &str) -> &'static str {
+    let lower = message.to_lowercase();
+    if lower.contains("not found") || lower.contains("no such file") {
+        "missing-input"
+    } else if lower.contains("corrupt output") || lower.contains("validation") {
+        "corrupt-output"
+    } else if lower.contains("invalid data") || lower.contains("decod") {
+        "decode"
+    } else if lower.contains("encoder") || lower.contains("nvenc") || lower.contains("amf") || lower.contains("qsv") {
+        "encode"
+    } else if lower.contains("failed to start") || lower.contains("failed to wait") {
+        "process"
+    } else if lower.contains("exited with code") {
+        "ffmpeg"
+    } else {
+        "other"
+    }
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\"").replace('\n', " "))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Link `input` into `dir` (symlink, or a hard link where symlinks need
+/// extra privileges), or move it there when `move_input` is set. A
+/// same-named file from another folder gets a numbered name.
+fn place_input(input: &Path, dir: &Path, move_input: bool) -> std::io::Result<PathBuf> {
+    let target = dir.join(input.file_name().unwrap_or(input.as_os_str()));
+    if naming::is_same_file(&target, input) {
+        return Ok(target);
+    }
+    let target = naming::unused_path(&target);
+    if move_input {
+        std::fs::rename(input, &target)?;
+        return Ok(target);
+    }
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(input, &target);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_file(input, &target);
+    linked.or_else(|_| std::fs::hard_link(input, &target))?;
+    Ok(target)
+}
+
+/// Put a failed input into the quarantine folder and append it to the report.
+pub fn quarantine(dir: &Path, input: &Path, error: &str, attempts: u32, move_input: bool) {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        warn!("Failed to create quarantine folder {:?}: {}", dir, e);
+        return;
+    }
+    match place_input(input, dir, move_input) {
+        Ok(target) => info!("Quarantined failed input {:?} as {:?}", input, target),
+        Err(e) => warn!("Failed to quarantine {:?}: {}", input, e),
+    }
+
+    let report = dir.join(REPORT_FILE);
+    let is_new = !report.exists();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let line = format!(
+        "{},{},{},{},{}\n",
+        timestamp,
+        csv_field(&input.to_string_lossy()),
+        classify_error(error),
+        attempts,
+        csv_field(error)
+    );
+    let written = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&report)
+        .and_then(|mut file| {
+            if is_new {
+                file.write_all(REPORT_HEADER.as_bytes())?;
+            }
+            file.write_all(line.as_bytes())
+        });
+    if let Err(e) = written {
+        warn!("Failed to write quarantine report {:?}: {}", report, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_common_failures() {
+        assert_eq!(classify_error("Conversion produced corrupt output: Output file is empty"), "corrupt-output");
+        assert_eq!(classify_error("FFmpeg exited with code: 1"), "ffmpeg");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+
+    #[test]
+    fn same_named_inputs_get_their_own_entries() {
+        let root = std::env::temp_dir().join(format!("dreamcodec-quarantine-{}", std::process::id()));
+        let dir = root.join("quarantine");
+        for folder in ["a", "b"] {
+            std::fs::create_dir_all(root.join(folder)).unwrap();
+            std::fs::write(root.join(folder).join("clip.mp4"), folder).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let first = place_input(&root.join("a").join("clip.mp4"), &dir, false).unwrap();
+        let second = place_input(&root.join("b").join("clip.mp4"), &dir, false).unwrap();
+        assert_eq!(first, dir.join("clip.mp4"));
+        assert_eq!(second, dir.join("clip-2.mp4"));
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "b");
+        // Quarantining the same input again reuses its entry.
+        assert_eq!(place_input(&root.join("a").join("clip.mp4"), &dir, false).unwrap(), first);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
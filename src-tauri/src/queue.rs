@@ -0,0 +1,330 @@
+use crate::ffmpeg::{ConversionStatus, FfmpegDownloader, FfmpegManager};
+use crate::history::has_tag;
+use crate::StartConversionArgs;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// File inside the app data directory holding the job queue.
+const QUEUE_FILE: &str = "queue.json";
+
+/// A conversion waiting in (or run by) the queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedJob {
+    pub id: String,
+    /// Conversion task running the job, once started.
+    pub task_id: Option<String>,
+    pub status: ConversionStatus,
+    pub request: StartConversionArgs,
+}
+
+/// Conversions accepted through `enqueue_conversion`, started in order as
+/// slots free up. Persisted after every change so pending work survives a
+/// crash or restart.
+#[derive(Debug, Default)]
+pub struct QueueManager {
+    jobs: Vec<QueuedJob>,
+    /// Where the queue is persisted; `None` keeps it in memory only.
+    path: Option<PathBuf>,
+}
+
+fn queue_path() -> Option<PathBuf> {
+    FfmpegDownloader::get_ffmpeg_app_dir()
+        .ok()
+        .map(|dir| dir.join(QUEUE_FILE))
+}
+
+impl QueueManager {
+    /// Load the persisted queue. Jobs that were running when the app exited
+    /// start over.
+    pub fn load() -> Self {
+        let path = queue_path();
+        let jobs: Vec<QueuedJob> = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let mut queue = Self { jobs, path };
+        queue.reset_interrupted();
+        queue
+    }
+
+    fn reset_interrupted(&mut self) {
+        for job in &mut self.jobs {
+            if matches!(job.status, ConversionStatus::Running) {
+                job.status = ConversionStatus::Pending;
+                job.task_id = None;
+            }
+        }
+    }
+
+    fn save(&self) {
+        let Some(ref path) = self.path else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(&self.jobs)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(path, content).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Failed to save job queue {:?}: {}", path, e);
+        }
+    }
+
+    pub fn enqueue(&mut self, request: StartConversionArgs) -> String {
+        let id = Uuid::new_v4().to_string();
+        self.jobs.push(QueuedJob {
+            id: id.clone(),
+            task_id: None,
+            status: ConversionStatus::Pending,
+            request,
+        });
+        self.save();
+        id
+    }
+
+    /// Job queued with `key`, unless it failed or was cancelled (a retry
+    /// with the same key then queues a new job).
+    pub fn job_for_idempotency_key(&self, key: &str) -> Option<String> {
+        self.jobs
+            .iter()
+            .filter(|job| !matches!(job.status, ConversionStatus::Failed(_) | ConversionStatus::Cancelled))
+            .find(|job| job.request.options.idempotency_key.as_deref() == Some(key))
+            .map(|job| job.id.clone())
+    }
+
+    pub fn jobs(&self) -> Vec<QueuedJob> {
+        self.jobs.clone()
+    }
+
+    /// Jobs carrying `tag`.
+    pub fn tagged(&self, tag: &str) -> Vec<QueuedJob> {
+        self.jobs
+            .iter()
+            .filter(|job| has_tag(&job.request.options.tags, tag))
+            .cloned()
+            .collect()
+    }
+
+    /// Every tag used in the queue.
+    pub fn tags(&self) -> impl Iterator<Item = &String> {
+        self.jobs.iter().flat_map(|job| &job.request.options.tags)
+    }
+
+    /// Conversion task ID -> queue job ID for jobs that have started.
+    pub fn task_jobs(&self) -> HashMap<String, String> {
+        self.jobs
+            .iter()
+            .filter_map(|job| job.task_id.clone().map(|task_id| (task_id, job.id.clone())))
+            .collect()
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<QueuedJob> {
+        let position = self.jobs.iter().position(|job| job.id == id)?;
+        let job = self.jobs.remove(position);
+        self.save();
+        Some(job)
+    }
+
+    /// Drop completed, failed and cancelled jobs.
+    pub fn clear_finished(&mut self) {
+        self.jobs
+            .retain(|job| matches!(job.status, ConversionStatus::Pending | ConversionStatus::Running));
+        self.save();
+    }
+
+    /// The next pending job if fewer than `limit` jobs are running. It is
+    /// marked running right away so it isn't handed out twice.
+    pub fn take_next(&mut self, limit: usize) -> Option<(String, StartConversionArgs)> {
+        let running = self
+            .jobs
+            .iter()
+            .filter(|job| matches!(job.status, ConversionStatus::Running))
+            .count();
+        if running >= limit {
+            return None;
+        }
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| matches!(job.status, ConversionStatus::Pending))?;
+        job.status = ConversionStatus::Running;
+        let next = (job.id.clone(), job.request.clone());
+        self.save();
+        Some(next)
+    }
+
+    /// Place of a pending job among the pending jobs, from 1.
+    pub fn waiting_position(&self, id: &str) -> Option<usize> {
+        self.jobs
+            .iter()
+            .filter(|job| matches!(job.status, ConversionStatus::Pending))
+            .position(|job| job.id == id)
+            .map(|index| index + 1)
+    }
+
+    /// Jobs still waiting for a free slot.
+    pub fn pending_count(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| matches!(job.status, ConversionStatus::Pending))
+            .count()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&QueuedJob> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// Tasks of the jobs the queue has started and not seen finish.
+    pub fn running_task_ids(&self) -> Vec<String> {
+        self.jobs
+            .iter()
+            .filter(|job| matches!(job.status, ConversionStatus::Running))
+            .filter_map(|job| job.task_id.clone())
+            .collect()
+    }
+
+    pub fn mark_started(&mut self, id: &str, task_id: String) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.task_id = Some(task_id);
+            self.save();
+        }
+    }
+
+    pub fn mark_failed(&mut self, id: &str, message: String) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.status = ConversionStatus::Failed(message);
+            self.save();
+        }
+    }
+
+    /// Copy the final status of finished conversions onto their jobs.
+    pub fn sync(&mut self, manager: &FfmpegManager) {
+        let mut changed = false;
+        for job in &mut self.jobs {
+            let Some(ref task_id) = job.task_id else {
+                continue;
+            };
+            if !matches!(job.status, ConversionStatus::Running) {
+                continue;
+            }
+            match manager.get_progress(task_id).map(|p| p.status) {
+                Some(status @ (ConversionStatus::Completed | ConversionStatus::Failed(_) | ConversionStatus::Cancelled)) => {
+                    job.status = status;
+                    changed = true;
+                }
+                Some(_) => {}
+                None => {
+                    job.status = ConversionStatus::Failed("Conversion task disappeared".to_string());
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.save();
+        }
+    }
+}
+
+/// Seconds until the waiting job at `position` (1 = next) can start, with
+/// `limit` slots, the queue's `running` tasks as (seconds running, percent
+/// done) and `job_secs` as the typical job length. Running tasks project
+/// their own finish; without a typical length from either source there is
+/// no estimate.
+pub fn estimate_start(running: &[(f64, f64)], position: usize, limit: usize, job_secs: Option<f64>) -> Option<f64> {
+    let projected: Vec<f64> = running
+        .iter()
+        .filter(|(_, percentage)| *percentage > 0.0)
+        .map(|(elapsed, percentage)| elapsed * 100.0 / percentage)
+        .collect();
+    let job_secs = job_secs
+        .or_else(|| (!projected.is_empty()).then(|| projected.iter().sum::<f64>() / projected.len() as f64))?;
+
+    // When each slot frees up; free slots are available now.
+    let mut slots: Vec<f64> = running
+        .iter()
+        .map(|(elapsed, percentage)| {
+            let total = if *percentage > 0.0 { elapsed * 100.0 / percentage } else { job_secs };
+            (total - elapsed).max(0.0)
+        })
+        .collect();
+    slots.resize(limit.max(slots.len()).max(1), 0.0);
+    for _ in 1..position {
+        let earliest = slots
+            .iter_mut()
+            .min_by(|a, b| a.total_cmp(b))
+            .expect("at least one slot");
+        *earliest += job_secs;
+    }
+    slots.into_iter().min_by(|a, b| a.total_cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffmpeg::ConversionOptions;
+
+    fn request(input: &str) -> StartConversionArgs {
+        StartConversionArgs {
+            input_file: input.to_string(),
+            output_file: format!("{}.mp4", input),
+            encoder: "libx264".to_string(),
+            gpu_index: None,
+            cpu_threads: None,
+            preset: "fast".to_string(),
+            is_adobe_preset: None,
+            options: ConversionOptions::default(),
+        }
+    }
+
+    #[test]
+    fn hands_out_jobs_up_to_the_concurrency_limit() {
+        let mut queue = QueueManager::default();
+        for input in ["a", "b", "c"] {
+            queue.jobs.push(QueuedJob {
+                id: input.to_string(),
+                task_id: None,
+                status: ConversionStatus::Pending,
+                request: request(input),
+            });
+        }
+        assert_eq!(queue.take_next(2).map(|(id, _)| id).as_deref(), Some("a"));
+        assert_eq!(queue.take_next(2).map(|(id, _)| id).as_deref(), Some("b"));
+        assert!(queue.take_next(2).is_none());
+        assert_eq!(queue.pending_count(), 1);
+
+        queue.reset_interrupted();
+        assert!(queue.jobs.iter().all(|job| matches!(job.status, ConversionStatus::Pending)));
+        assert_eq!(queue.waiting_position("c"), Some(3));
+    }
+
+    #[test]
+    fn finds_live_jobs_by_idempotency_key() {
+        let mut queue = QueueManager::default();
+        let mut keyed = request("a");
+        keyed.options.idempotency_key = Some("upload-1".to_string());
+        let id = queue.enqueue(keyed);
+        assert_eq!(queue.job_for_idempotency_key("upload-1"), Some(id.clone()));
+        assert_eq!(queue.job_for_idempotency_key("upload-2"), None);
+
+        queue.mark_failed(&id, "boom".to_string());
+        assert_eq!(queue.job_for_idempotency_key("upload-1"), None);
+    }
+
+    #[test]
+    fn estimates_start_from_running_jobs_and_typical_length() {
+        // One slot, a job 25% done after 100s: it needs 300s more.
+        assert_eq!(estimate_start(&[(100.0, 25.0)], 1, 1, None), Some(300.0));
+        // Third in line waits for the running job plus two 400s jobs.
+        assert_eq!(estimate_start(&[(100.0, 25.0)], 3, 1, None), Some(1100.0));
+        // A free second slot takes the first waiting job right away.
+        assert_eq!(estimate_start(&[(100.0, 25.0)], 1, 2, Some(60.0)), Some(0.0));
+        assert_eq!(estimate_start(&[(10.0, 0.0)], 1, 1, None), None);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::gpu::{EncoderTestResult, GpuType};
+use crate::storage::StorageBenchmark;
+use serde::Serialize;
+
+/// Below this output write speed parallel jobs mostly wait on the disk.
+const SLOW_STORAGE_MB_PER_SEC: f64 = 60.0;
+/// Hardware encoders run this many sessions side by side without slowing
+/// each other down much.
+const GPU_CONCURRENCY: usize = 2;
+
+/// Starting settings for this machine, shown until the user picks their own.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecommendedDefaults {
+    pub encoder: String,
+    pub preset: String,
+    pub max_concurrent_jobs: usize,
+    /// Why these were picked, for display.
+    pub reasons: Vec<String>,
+}
+
+/// What the recommendation is based on.
+pub struct MachineFacts<'a> {
+    pub logical_cores: usize,
+    pub gpu_type: GpuType,
+    /// Encoder names the FFmpeg build lists.
+    pub encoders: &'a [String],
+    pub self_test: &'a [EncoderTestResult],
+    pub storage: Option<&'a StorageBenchmark>,
+}
+
+fn gpu_h264_encoder(gpu_type: GpuType) -> Option<&'static str> {
+    match gpu_type {
+        GpuType::Nvidia => Some("h264_nvenc"),
+        GpuType::Amd => Some("h264_amf"),
+        GpuType::Intel => Some("h264_qsv"),
+        GpuType::Unknown | GpuType::None => None,
+    }
+}
+
+/// x264 preset that keeps a 1080p encode around real time on this many
+/// threads.
+fn cpu_preset(logical_cores: usize) -> &'static str {
+    match logical_cores {
+        0..=4 => "veryfast",
+        5..=8 => "faster",
+        9..=16 => "fast",
+        _ => "medium",
+    }
+}
+
+pub fn recommend(facts: &MachineFacts) -> RecommendedDefaults {
+    let mut reasons = Vec::new();
+    let gpu_encoder = gpu_h264_encoder(facts.gpu_type);
+    let failed_test = gpu_encoder.and_then(|name| facts.self_test.iter().find(|r| r.encoder == name && !r.works));
+
+    let hardware = match gpu_encoder {
+        Some(name) if failed_test.is_some() => {
+            let error = failed_test.and_then(|r| r.error.as_deref()).unwrap_or("no details");
+            reasons.push(format!("{} is compiled in but failed the self-test: {}", name, error));
+            None
+        }
+        Some(name) if facts.encoders.iter().any(|e| e == name) => {
+            let tested = facts.self_test.iter().any(|r| r.encoder == name);
+            reasons.push(if tested {
+                format!("{} passed the encoder self-test", name)
+            } else {
+                format!("{} is available (not self-tested yet)", name)
+            });
+            Some(name)
+        }
+        Some(name) => {
+            reasons.push(format!("This FFmpeg build has no {}", name));
+            None
+        }
+        None => {
+            reasons.push("No supported GPU detected".to_string());
+            None
+        }
+    };
+
+    let (encoder, preset, mut max_concurrent_jobs) = match hardware {
+        Some(name) => (name, "fast", GPU_CONCURRENCY),
+        None => {
+            let preset = cpu_preset(facts.logical_cores);
+            reasons.push(format!("libx264 {} for {} logical cores", preset, facts.logical_cores));
+            // x264 already uses every core; a second job only helps on big machines.
+            ("libx264", preset, if facts.logical_cores >= 16 { 2 } else { 1 })
+        }
+    };
+
+    if let Some(storage) = facts.storage.filter(|s| s.write_mb_per_sec < SLOW_STORAGE_MB_PER_SEC) {
+        if max_concurrent_jobs > 1 {
+            reasons.push(format!(
+                "Output storage writes {:.0} MB/s; running one job at a time",
+                storage.write_mb_per_sec
+            ));
+            max_concurrent_jobs = 1;
+        }
+    }
+
+    RecommendedDefaults {
+        encoder: encoder.to_string(),
+        preset: preset.to_string(),
+        max_concurrent_jobs,
+        reasons,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_a_working_gpu_encoder_over_the_cpu() {
+        let encoders = vec!["libx264".to_string(), "h264_nvenc".to_string()];
+        let mut facts = MachineFacts {
+            logical_cores: 8,
+            gpu_type: GpuType::Nvidia,
+            encoders: &encoders,
+            self_test: &[],
+            storage: None,
+        };
+        let defaults = recommend(&facts);
+        assert_eq!((defaults.encoder.as_str(), defaults.max_concurrent_jobs), ("h264_nvenc", 2));
+
+        let failed = [EncoderTestResult {
+            encoder: "h264_nvenc".to_string(),
+            works: false,
+            error: Some("Cannot load nvcuda.dll".to_string()),
+            elapsed_ms: 40,
+        }];
+        facts.self_test = &failed;
+        let defaults = recommend(&facts);
+        assert_eq!((defaults.encoder.as_str(), defaults.preset.as_str()), ("libx264", "faster"));
+        assert_eq!(defaults.max_concurrent_jobs, 1);
+        assert!(defaults.reasons[0].contains("nvcuda.dll"));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::AppError;
+use crate::ffmpeg::ocr::OcrTool;
+use crate::ffmpeg::ratecontrol::RateControl;
+use crate::ffmpeg::{ConversionOptions, FfmpegDownloader};
+use crate::watch::WatchFolder;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// File name of the backend settings inside the app data directory.
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Backend settings applied to every job unless the job overrides them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Extra environment variables for every FFmpeg process (e.g. `CUDA_VISIBLE_DEVICES`).
+    #[serde(alias = "envVars")]
+    pub env_vars: HashMap<String, String>,
+    /// Working directory FFmpeg is started in.
+    #[serde(alias = "workingDir")]
+    pub working_dir: Option<String>,
+    /// Extra font directory for subtitle burn-in (fonts not installed system-wide).
+    #[serde(alias = "fontDir")]
+    pub font_dir: Option<String>,
+    /// Prometheus textfile (`*.prom`) to export job metrics to, for headless
+    /// render nodes scraped by the node exporter.
+    #[serde(alias = "metricsFile")]
+    pub metrics_file: Option<String>,
+    /// Folder collecting inputs of failed jobs plus a CSV report, for
+    /// unattended batch runs.
+    #[serde(alias = "quarantineDir")]
+    pub quarantine_dir: Option<String>,
+    /// Queued jobs run at the same time (1 runs the queue sequentially).
+    #[serde(alias = "maxConcurrentJobs")]
+    pub max_concurrent_jobs: Option<usize>,
+    /// FFmpeg archive (zip, tar, tar.gz or tar.xz) to download instead of the
+    /// built-in mirror list.
+    #[serde(alias = "ffmpegDownloadUrl")]
+    pub ffmpeg_download_url: Option<String>,
+    /// Named FFmpeg binaries presets can pin (name -> path to ffmpeg).
+    #[serde(alias = "ffmpegInstallations")]
+    pub ffmpeg_installations: HashMap<String, String>,
+    /// Tuned defaults per encoder name (e.g. `hevc_nvenc`).
+    #[serde(alias = "encoderDefaults")]
+    pub encoder_defaults: HashMap<String, EncoderDefaults>,
+    /// Seconds a probe, validation or thumbnail run may take before it is
+    /// killed; unset uses `watchdog::DEFAULT_TIMEOUT_SECS`.
+    #[serde(alias = "auxTimeoutSecs")]
+    pub aux_timeout_secs: Option<u64>,
+    /// Drop folders with the rules deciding which preset each new file gets.
+    #[serde(alias = "watchFolders")]
+    pub watch_folders: Vec<WatchFolder>,
+    /// External tool turning PGS/VobSub tracks into SRT for jobs with
+    /// subtitle OCR enabled.
+    #[serde(alias = "subtitleOcr")]
+    pub subtitle_ocr: Option<OcrTool>,
+}
+
+/// Settings a job with this encoder inherits unless it sets its own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncoderDefaults {
+    /// Encoder speed preset (`slow`, `p5`, ...).
+    pub preset: Option<String>,
+    #[serde(alias = "rateControl")]
+    pub rate_control: Option<RateControl>,
+}
+
+impl EncoderDefaults {
+    /// Fill in what the job left unset. Rate control is taken as a whole,
+    /// and only when the job has no quality, bitrate or size target, so the
+    /// default can't conflict with the job's own choice.
+    pub fn apply(&self, preset: &mut String, options: &mut ConversionOptions) {
+        if preset.trim().is_empty() {
+            if let Some(ref default) = self.preset {
+                *preset = default.clone();
+            }
+        }
+        let job_rate_set = options
+            .rate_control
+            .as_ref()
+            .is_some_and(|rc| rc.quality.is_some() || rc.bitrate.is_some());
+        if !job_rate_set && options.target_size_mb.is_none() {
+            if let Some(ref default) = self.rate_control {
+                let mut merged = default.clone();
+                // Peak limits the job did set still apply.
+                if let Some(ref job) = options.rate_control {
+                    merged.maxrate = job.maxrate.or(merged.maxrate);
+                    merged.bufsize = job.bufsize.or(merged.bufsize);
+                }
+                options.rate_control = Some(merged);
+            }
+        }
+    }
+}
+
+pub fn settings_path() -> Result<PathBuf, AppError> {
+    Ok(FfmpegDownloader::get_ffmpeg_app_dir()?.join(SETTINGS_FILE))
+}
+
+/// Load settings from disk, falling back to defaults if missing or unreadable.
+pub fn load() -> AppSettings {
+    let path = match settings_path() {
+        Ok(path) => path,
+        Err(_) => return AppSettings::default(),
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable settings file {:?}: {}", path, e);
+            AppSettings::default()
+        }),
+        Err(_) => AppSettings::default(),
+    }
+}
+
+pub fn save(settings: &AppSettings) -> Result<(), AppError> {
+    let path = settings_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize settings: {}", e)))?;
+    std::fs::write(&path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoder_defaults_fill_only_unset_values() {
+        let defaults = EncoderDefaults {
+            preset: Some("p5".to_string()),
+            rate_control: Some(RateControl {
+                quality: Some(23),
+                ..Default::default()
+            }),
+        };
+
+        let mut preset = String::new();
+        let mut options = ConversionOptions::default();
+        defaults.apply(&mut preset, &mut options);
+        assert_eq!(preset, "p5");
+        assert_eq!(options.rate_control.as_ref().and_then(|rc| rc.quality), Some(23));
+
+        let mut preset = "p7".to_string();
+        let mut options = ConversionOptions {
+            rate_control: Some(RateControl {
+                bitrate: Some(8000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        defaults.apply(&mut preset, &mut options);
+        assert_eq!(preset, "p7");
+        assert_eq!(options.rate_control.as_ref().and_then(|rc| rc.quality), None);
+    }
+}
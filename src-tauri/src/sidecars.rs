@@ -0,0 +1,82 @@
+use log::warn;
+use std::path::{Path, PathBuf};
+
+use crate::naming;
+
+/// Extensions of per-file sidecars (subtitles, metadata and artwork) that
+/// media servers match to a video by file name.
+const SIDECAR_EXTENSIONS: &[&str] = &[
+    "srt", "ass", "ssa", "vtt", "sub", "idx", "sup", "nfo", "jpg", "jpeg", "png", "tbn",
+];
+
+/// Folder-level artwork used by Plex/Jellyfin/Kodi. Copied as-is, and only
+/// when the output folder doesn't have one yet.
+const FOLDER_ARTWORK: &[&str] = &[
+    "poster.jpg", "poster.png", "folder.jpg", "folder.png", "fanart.jpg", "fanart.png", "banner.jpg",
+];
+
+/// The part of `file_name` after the video's stem (e.g. `.en.forced.srt`,
+/// `-poster.jpg`), if the file is a sidecar of a video named `video_stem`.
+pub fn sidecar_suffix(video_stem: &str, file_name: &str) -> Option<String> {
+    let suffix = file_name.strip_prefix(video_stem)?;
+    if !suffix.starts_with(['.', '-']) {
+        return None;
+    }
+    let ext = Path::new(suffix).extension()?.to_string_lossy().to_lowercase();
+    SIDECAR_EXTENSIONS.contains(&ext.as_str()).then(|| suffix.to_string())
+}
+
+/// Copy the sidecars of `input` next to `output`, renamed to the output's
+/// stem. Returns the files written.
+pub fn copy_sidecars(input: &Path, output: &Path) -> Vec<PathBuf> {
+    let (Some(input_dir), Some(output_dir)) = (input.parent(), output.parent()) else {
+        return Vec::new();
+    };
+    let input_stem = input.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let output_stem = output.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let Ok(entries) = std::fs::read_dir(input_dir) else {
+        return Vec::new();
+    };
+
+    let mut copied = Vec::new();
+    for entry in entries.flatten() {
+        let source = entry.path();
+        if !source.is_file() || naming::is_same_file(&source, input) {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let target = if let Some(suffix) = sidecar_suffix(&input_stem, &file_name) {
+            output_dir.join(format!("{}{}", output_stem, suffix))
+        } else if FOLDER_ARTWORK.contains(&file_name.to_lowercase().as_str()) {
+            let target = output_dir.join(&file_name);
+            if target.exists() {
+                continue;
+            }
+            target
+        } else {
+            continue;
+        };
+
+        if naming::is_same_file(&source, &target) {
+            continue;
+        }
+        match std::fs::copy(&source, &target) {
+            Ok(_) => copied.push(target),
+            Err(e) => warn!("Failed to copy sidecar {:?} to {:?}: {}", source, target, e),
+        }
+    }
+    copied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_sidecars_of_the_same_stem() {
+        assert_eq!(sidecar_suffix("Movie (2020)", "Movie (2020).en.forced.srt").as_deref(), Some(".en.forced.srt"));
+        assert_eq!(sidecar_suffix("Movie (2020)", "Movie (2020)-poster.jpg").as_deref(), Some("-poster.jpg"));
+        assert_eq!(sidecar_suffix("Movie", "Movie 2.srt"), None);
+        assert_eq!(sidecar_suffix("Movie", "Movie.mkv"), None);
+    }
+}
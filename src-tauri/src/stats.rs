@@ -0,0 +1,97 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A conversion that finished successfully this session.
+#[derive(Debug, Clone)]
+pub struct CompletedJob {
+    /// Length of the output, in seconds.
+    pub media_seconds: f64,
+    pub input_bytes: Option<u64>,
+    pub output_bytes: Option<u64>,
+    pub elapsed: Duration,
+    /// Encoded by a hardware encoder rather than its CPU fallback.
+    pub hardware: bool,
+}
+
+/// Totals since launch for the stats panel.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SessionStats {
+    pub files_converted: usize,
+    pub minutes_processed: f64,
+    /// Input minus output size; negative when the outputs are larger.
+    pub bytes_saved: i64,
+    /// Media time encoded per second of wall time (2.0 = twice real time).
+    pub average_speed: Option<f64>,
+    pub gpu_jobs: usize,
+    pub cpu_jobs: usize,
+    /// Share of converted files encoded on the GPU, from 0 to 1.
+    pub gpu_share: Option<f64>,
+}
+
+static HISTORY: Mutex<Vec<CompletedJob>> = Mutex::new(Vec::new());
+
+pub fn record(job: CompletedJob) {
+    if let Ok(mut history) = HISTORY.lock() {
+        history.push(job);
+    }
+}
+
+pub fn summarize(jobs: &[CompletedJob]) -> SessionStats {
+    let media_seconds: f64 = jobs.iter().map(|j| j.media_seconds).sum();
+    let elapsed: f64 = jobs.iter().map(|j| j.elapsed.as_secs_f64()).sum();
+    // Only jobs with both sizes known count towards the savings.
+    let bytes_saved = jobs
+        .iter()
+        .filter_map(|j| Some(j.input_bytes? as i64 - j.output_bytes? as i64))
+        .sum();
+    let gpu_jobs = jobs.iter().filter(|j| j.hardware).count();
+    SessionStats {
+        files_converted: jobs.len(),
+        minutes_processed: media_seconds / 60.0,
+        bytes_saved,
+        average_speed: (elapsed > 0.0 && media_seconds > 0.0).then(|| media_seconds / elapsed),
+        gpu_jobs,
+        cpu_jobs: jobs.len() - gpu_jobs,
+        gpu_share: (!jobs.is_empty()).then(|| gpu_jobs as f64 / jobs.len() as f64),
+    }
+}
+
+/// Mean wall time of the jobs finished this session.
+pub fn average_job_secs() -> Option<f64> {
+    let history = HISTORY.lock().ok()?;
+    let total: f64 = history.iter().map(|j| j.elapsed.as_secs_f64()).sum();
+    (!history.is_empty()).then(|| total / history.len() as f64)
+}
+
+pub fn session_stats() -> SessionStats {
+    HISTORY.lock().map(|history| summarize(&history)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_sizes_and_weights_speed_by_media_time() {
+        let job = |media_seconds: f64, secs: u64, sizes: Option<(u64, u64)>, hardware: bool| CompletedJob {
+            media_seconds,
+            input_bytes: sizes.map(|s| s.0),
+            output_bytes: sizes.map(|s| s.1),
+            elapsed: Duration::from_secs(secs),
+            hardware,
+        };
+        let stats = summarize(&[
+            job(600.0, 100, Some((1000, 400)), true),
+            job(120.0, 140, Some((100, 300)), false),
+            job(60.0, 60, None, false),
+        ]);
+        assert_eq!(stats.files_converted, 3);
+        assert_eq!(stats.minutes_processed, 13.0);
+        assert_eq!(stats.bytes_saved, 400);
+        assert_eq!(stats.average_speed, Some(2.6));
+        assert_eq!((stats.gpu_jobs, stats.cpu_jobs), (1, 2));
+
+        assert_eq!(summarize(&[]), SessionStats::default());
+    }
+}
@@ -0,0 +1,140 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// Upper bounds per measurement, so a fast disk doesn't read gigabytes and a
+/// slow share doesn't block for minutes.
+const MAX_READ_BYTES: u64 = 256 * 1024 * 1024;
+const MAX_WRITE_BYTES: u64 = 128 * 1024 * 1024;
+const MAX_DURATION: Duration = Duration::from_secs(10);
+
+static LAST_BENCHMARK: Mutex<Option<StorageBenchmark>> = Mutex::new(None);
+
+/// Sequential throughput of the input and output volumes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageBenchmark {
+    pub input_file: String,
+    pub output_dir: String,
+    /// MB/s (10^6 bytes). Recently read files may be served from the OS
+    /// cache and show higher figures than the volume can sustain.
+    pub read_mb_per_sec: f64,
+    pub write_mb_per_sec: f64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Time needed just to read the input and write an output of the same
+    /// size at these speeds: the floor for any conversion ETA.
+    pub io_floor_secs: f64,
+}
+
+impl StorageBenchmark {
+    /// Seconds needed to read `input_bytes` and write `output_bytes`.
+    pub fn io_seconds(&self, input_bytes: u64, output_bytes: u64) -> f64 {
+        let seconds = |bytes: u64, mb_per_sec: f64| {
+            if mb_per_sec > 0.0 {
+                bytes as f64 / 1_000_000.0 / mb_per_sec
+            } else {
+                0.0
+            }
+        };
+        seconds(input_bytes, self.read_mb_per_sec) + seconds(output_bytes, self.write_mb_per_sec)
+    }
+}
+
+fn mb_per_sec(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64().max(0.001)
+}
+
+fn measure_read(input: &Path) -> std::io::Result<(u64, Duration)> {
+    let mut file = std::fs::File::open(input)?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let start = Instant::now();
+    let mut total = 0u64;
+    while total < MAX_READ_BYTES && start.elapsed() < MAX_DURATION {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+    }
+    Ok((total, start.elapsed()))
+}
+
+fn measure_write(dir: &Path) -> std::io::Result<(u64, Duration)> {
+    let path = dir.join(format!(".dreamcodec-benchmark-{}.tmp", uuid::Uuid::new_v4()));
+    // Incompressible data, so compressing filesystems and shares don't flatter the result.
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let buffer: Vec<u8> = (0..CHUNK_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&path)?;
+        let start = Instant::now();
+        let mut total = 0u64;
+        while total < MAX_WRITE_BYTES && start.elapsed() < MAX_DURATION {
+            file.write_all(&buffer)?;
+            total += buffer.len() as u64;
+        }
+        file.sync_all()?;
+        Ok((total, start.elapsed()))
+    })();
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Measure sequential read of `input` and write into `output_dir`. Blocking.
+pub fn benchmark(input: &Path, output_dir: &Path) -> std::io::Result<StorageBenchmark> {
+    let input_size = std::fs::metadata(input)?.len();
+    let (bytes_read, read_time) = measure_read(input)?;
+    let (bytes_written, write_time) = measure_write(output_dir)?;
+
+    let mut report = StorageBenchmark {
+        input_file: input.to_string_lossy().to_string(),
+        output_dir: output_dir.to_string_lossy().to_string(),
+        read_mb_per_sec: mb_per_sec(bytes_read, read_time),
+        write_mb_per_sec: mb_per_sec(bytes_written, write_time),
+        bytes_read,
+        bytes_written,
+        io_floor_secs: 0.0,
+    };
+    report.io_floor_secs = report.io_seconds(input_size, input_size);
+    info!(
+        "Storage benchmark: read {:.1} MB/s ({}), write {:.1} MB/s ({})",
+        report.read_mb_per_sec, report.input_file, report.write_mb_per_sec, report.output_dir
+    );
+    *LAST_BENCHMARK.lock().unwrap_or_else(|e| e.into_inner()) = Some(report.clone());
+    Ok(report)
+}
+
+/// The most recent benchmark of this session, for ETA estimates.
+pub fn last_benchmark() -> Option<StorageBenchmark> {
+    LAST_BENCHMARK.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_floor_adds_read_and_write_time() {
+        let report = StorageBenchmark {
+            input_file: String::new(),
+            output_dir: String::new(),
+            read_mb_per_sec: 2.5,
+            write_mb_per_sec: 100.0,
+            bytes_read: 0,
+            bytes_written: 0,
+            io_floor_secs: 0.0,
+        };
+        assert_eq!(report.io_seconds(250_000_000, 100_000_000), 101.0);
+    }
+}
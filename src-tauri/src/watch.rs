@@ -0,0 +1,232 @@
+use crate::ffmpeg::probe_info;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Rules for one watched drop folder. The folder watcher asks the backend
+/// what to do with each file that appears.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchFolder {
+    pub path: String,
+    /// Checked in order; the first matching rule picks the preset.
+    pub rules: Vec<WatchRule>,
+    /// Custom preset for files no rule matches; `None` leaves them alone.
+    #[serde(alias = "fallbackPreset")]
+    pub fallback_preset: Option<String>,
+    /// File name patterns to skip (`*` and `?` wildcards, case-insensitive),
+    /// e.g. `*.part` or `~*`.
+    pub ignore: Vec<String>,
+    /// Files modified more recently than this are still being copied in.
+    #[serde(alias = "minAgeSecs")]
+    pub min_age_secs: Option<u64>,
+}
+
+/// Conditions a file must all meet for `preset` to apply; empty lists and
+/// unset bounds match anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchRule {
+    /// Lowercase extensions without the dot.
+    pub extensions: Vec<String>,
+    /// Video codec names as FFmpeg reports them (`h264`, `prores`).
+    pub codecs: Vec<String>,
+    #[serde(alias = "minHeight")]
+    pub min_height: Option<u32>,
+    #[serde(alias = "maxHeight")]
+    pub max_height: Option<u32>,
+    /// Name of the custom preset to convert with.
+    pub preset: String,
+}
+
+/// What the file looked like when probed.
+#[derive(Debug, Clone, Default)]
+pub struct FileFacts {
+    pub extension: String,
+    pub codec: Option<String>,
+    pub height: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum WatchDecision {
+    /// Matches an ignore pattern or is outside every watched folder.
+    #[serde(rename_all = "camelCase")]
+    Ignore { reason: String },
+    /// Too new; ask again after this long.
+    #[serde(rename_all = "camelCase")]
+    Wait { retry_after_secs: u64 },
+    /// `rule` is the index of the matching rule, `None` for the fallback.
+    Convert { preset: String, rule: Option<usize> },
+    /// No rule matched and the folder has no fallback.
+    NoMatch,
+}
+
+/// `*` matches any run of characters, `?` exactly one.
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| wildcard_match(rest, &name[i..])),
+        Some((p, rest)) => name
+            .split_first()
+            .is_some_and(|(n, name)| (*p == '?' || p == n) && wildcard_match(rest, name)),
+    }
+}
+
+impl WatchRule {
+    fn needs_probe(&self) -> bool {
+        !self.codecs.is_empty() || self.min_height.is_some() || self.max_height.is_some()
+    }
+
+    fn matches(&self, facts: &FileFacts) -> bool {
+        let extension_ok = self.extensions.is_empty()
+            || self.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&facts.extension));
+        let codec_ok = self.codecs.is_empty()
+            || facts
+                .codec
+                .as_ref()
+                .is_some_and(|codec| self.codecs.iter().any(|c| c.eq_ignore_ascii_case(codec)));
+        // A file without a known height can't satisfy a resolution bound.
+        let height_ok = match (self.min_height, self.max_height) {
+            (None, None) => true,
+            (min, max) => facts
+                .height
+                .is_some_and(|h| min.is_none_or(|min| h >= min) && max.is_none_or(|max| h <= max)),
+        };
+        extension_ok && codec_ok && height_ok
+    }
+}
+
+impl WatchFolder {
+    /// Whether `file` is inside this folder (at any depth).
+    pub fn contains(&self, file: &Path) -> bool {
+        !self.path.is_empty() && file.starts_with(&self.path)
+    }
+
+    /// Ignore patterns and the age check, which don't need a probe.
+    pub fn precheck(&self, file_name: &str, age: Duration) -> Option<WatchDecision> {
+        let name: Vec<char> = file_name.to_lowercase().chars().collect();
+        if let Some(pattern) = self.ignore.iter().find(|p| {
+            let pattern: Vec<char> = p.to_lowercase().chars().collect();
+            wildcard_match(&pattern, &name)
+        }) {
+            return Some(WatchDecision::Ignore {
+                reason: format!("Matches ignore pattern {}", pattern),
+            });
+        }
+        let min_age = self.min_age_secs.unwrap_or(0);
+        (age.as_secs() < min_age).then(|| WatchDecision::Wait {
+            retry_after_secs: min_age - age.as_secs(),
+        })
+    }
+
+    pub fn needs_probe(&self) -> bool {
+        self.rules.iter().any(WatchRule::needs_probe)
+    }
+
+    /// Pick the preset for a file that passed `precheck`.
+    pub fn choose(&self, facts: &FileFacts) -> WatchDecision {
+        if let Some((index, rule)) = self.rules.iter().enumerate().find(|(_, rule)| rule.matches(facts)) {
+            return WatchDecision::Convert {
+                preset: rule.preset.clone(),
+                rule: Some(index),
+            };
+        }
+        match self.fallback_preset {
+            Some(ref preset) => WatchDecision::Convert {
+                preset: preset.clone(),
+                rule: None,
+            },
+            None => WatchDecision::NoMatch,
+        }
+    }
+}
+
+/// Decide what to do with `file_path`, using the innermost watched folder
+/// that contains it.
+pub async fn evaluate(ffmpeg_path: &str, folders: &[WatchFolder], file_path: &str) -> WatchDecision {
+    let path = Path::new(file_path);
+    let Some(folder) = folders
+        .iter()
+        .filter(|f| f.contains(path))
+        .max_by_key(|f| f.path.len())
+    else {
+        return WatchDecision::Ignore {
+            reason: "Not inside a watched folder".to_string(),
+        };
+    };
+
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let age = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .unwrap_or_default();
+    if let Some(decision) = folder.precheck(&file_name, age) {
+        return decision;
+    }
+
+    let mut facts = FileFacts {
+        extension: path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    if folder.needs_probe() {
+        if let Some(info) = probe_info(ffmpeg_path, file_path).await {
+            facts.codec = info.video_streams.first().map(|s| s.codec.clone());
+            facts.height = info.height;
+        }
+    }
+    folder.choose(&facts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_rule_wins_after_ignores_and_age() {
+        let folder = WatchFolder {
+            path: "/drop".to_string(),
+            rules: vec![
+                WatchRule {
+                    codecs: vec!["prores".to_string()],
+                    min_height: Some(2160),
+                    preset: "UHD Proxy".to_string(),
+                    ..Default::default()
+                },
+                WatchRule {
+                    extensions: vec![".MOV".to_string(), "mxf".to_string()],
+                    preset: "Edit Proxy".to_string(),
+                    ..Default::default()
+                },
+            ],
+            fallback_preset: Some("Web".to_string()),
+            ignore: vec!["*.part".to_string(), "~*".to_string()],
+            min_age_secs: Some(30),
+        };
+
+        assert!(matches!(folder.precheck("clip.MP4.PART", Duration::ZERO), Some(WatchDecision::Ignore { .. })));
+        assert_eq!(
+            folder.precheck("clip.mov", Duration::from_secs(10)),
+            Some(WatchDecision::Wait { retry_after_secs: 20 })
+        );
+        assert_eq!(folder.precheck("clip.mov", Duration::from_secs(60)), None);
+
+        let facts = |extension: &str, codec: &str, height: u32| FileFacts {
+            extension: extension.to_string(),
+            codec: Some(codec.to_string()),
+            height: Some(height),
+        };
+        let convert = |preset: &str, rule| WatchDecision::Convert {
+            preset: preset.to_string(),
+            rule,
+        };
+        assert_eq!(folder.choose(&facts("mov", "prores", 2160)), convert("UHD Proxy", Some(0)));
+        assert_eq!(folder.choose(&facts("mov", "prores", 1080)), convert("Edit Proxy", Some(1)));
+        assert_eq!(folder.choose(&facts("mp4", "h264", 1080)), convert("Web", None));
+        assert!(folder.contains(Path::new("/drop/sub/clip.mov")));
+    }
+}
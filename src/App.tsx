@@ -85,6 +85,13 @@ const PREFERENCE_CACHE_KEY = "dreamcodec.preferences.v1";
 const HARDWARE_CACHE_KEY = "dreamcodec.hardware.v1";
 const HARDWARE_CACHE_VERSION = 1;
 
+interface RecommendedDefaults {
+  encoder: string;
+  preset: string;
+  max_concurrent_jobs: number;
+  reasons: string[];
+}
+
 interface PreferenceCache {
   encoder: string;
   gpuPreference: string;
@@ -128,6 +135,17 @@ const readPreferenceCache = (): PreferenceCache => {
   }
 };
 
+const hasCachedPreference = (key: keyof PreferenceCache): boolean => {
+  if (typeof window === "undefined") return false;
+
+  try {
+    const raw = window.localStorage.getItem(PREFERENCE_CACHE_KEY);
+    return Boolean(raw) && key in (JSON.parse(raw as string) as Partial<PreferenceCache>);
+  } catch {
+    return false;
+  }
+};
+
 const writePreferenceCache = (update: Partial<PreferenceCache>) => {
   if (typeof window === "undefined") return;
 
@@ -205,7 +223,7 @@ export default function App() {
   const [activeTab, setActiveTab] = useState("queue");
   const [outputDir, setOutputDir] = useState(() => readPreferenceCache().outputDir);
   const [encoder, setEncoder] = useState(() => readPreferenceCache().encoder);
-  const [preset, setPreset] = useState("fast");
+  const [preset, setPreset] = useState("");
   const [outputFormat, setOutputFormat] = useState("mp4");
   const [queue, setQueue] = useState<QueueFile[]>([]);
   const [encoders, setEncoders] = useState<Encoder[]>([]);
@@ -227,6 +245,8 @@ export default function App() {
   const startingRef = useRef<Set<string>>(new Set());
   const pollerRef = useRef<number | null>(null);
   const [panicInfo, setPanicInfo] = useState<{ payload: string, location: string } | null>(null);
+  const hadEncoderPreference = useRef(Boolean(readPreferenceCache().encoder));
+  const hadConcurrencyPreference = useRef(hasCachedPreference("maxConcurrent"));
 
   useEffect(() => {
     conversionsRef.current = conversions;
@@ -744,6 +764,25 @@ export default function App() {
         if (info.available_encoders.length === 0) {
           setHardwareInitError("No encoders were detected.");
         }
+
+        try {
+          const defaults = await invoke<RecommendedDefaults>("get_recommended_defaults");
+          // The preset belongs to the recommended encoder, so it only
+          // applies when that encoder is taken too.
+          if (!hadEncoderPreference.current) {
+            setEncoder(defaults.encoder);
+            setPreset(defaults.preset);
+          }
+          if (!hadConcurrencyPreference.current) {
+            setMaxConcurrent(Math.min(5, Math.max(1, defaults.max_concurrent_jobs)));
+          }
+          addLog(
+            "info",
+            `Recommended defaults: ${defaults.encoder} ${defaults.preset}, ${defaults.max_concurrent_jobs} job(s) (${defaults.reasons.join("; ")})`
+          );
+        } catch (err) {
+          addLog("warn", `Could not get recommended defaults: ${String(err)}`);
+        }
       } catch (err) {
         console.error("Failed to get GPU info:", err);
         if (!cached) {
@@ -872,7 +911,7 @@ export default function App() {
   const startPendingConversion = async (item: ConversionItem): Promise<string | null> => {
     if (!item.params) return null;
     try {
-      const taskId = await invoke<string>("start_conversion", {
+      const handle = await invoke<{ task_id: string }>("start_conversion", {
         args: {
           inputFile: item.inputFile,
           outputFile: item.outputFile,
@@ -883,7 +922,7 @@ export default function App() {
           isAdobePreset: false,
         },
       });
-      return taskId;
+      return handle.task_id;
     } catch (err) {
       addLog("error", `Failed to start: ${getFileName(item.inputFile)} (${String(err)})`);
       return null;
@@ -1174,6 +1213,7 @@ export default function App() {
             <div className="form-group">
               <label><i className="ri-speed-fill"></i> Preset</label>
               <select className="select" value={preset} onChange={(e) => setPreset(e.target.value)}>
+                <option value="">Encoder Default</option>
                 <option value="ultrafast">Ultra Fast</option>
                 <option value="superfast">Super Fast</option>
                 <option value="veryfast">Very Fast</option>